@@ -1,320 +1,2902 @@
 //! Command dispatcher - routes parsed CLI arguments to the corresponding action.
 use std::collections::HashSet;
-use std::fs;
-use std::io::{self, Write};
-use std::path::PathBuf;
+use std::fs::{self, File};
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::sync::mpsc;
+use std::time::{Duration, UNIX_EPOCH};
 
 use clap::CommandFactory;
 
-use crate::cli::{self, Args, Commands, LayoutCommands};
-use crate::config::Config;
+use crate::audit;
+use crate::cli::{self, Args, Commands, DaemonCommands, LayoutCommands};
+use crate::config::{Compact, Config, HistoryConfig, LintConfig, SortOrder};
+use crate::daemon;
+use crate::diff;
+use crate::editor::Editor;
+use crate::error::TsmanError;
+use crate::glob;
+use crate::logging;
 use crate::menu::Menu;
 use crate::menu::action_dispatcher::DefaultActionDispacher;
 use crate::menu::event_handler::DefaultEventHandler;
 use crate::menu::item::MenuItem;
+use crate::menu::preview::PREVIEW_FIELDS;
 use crate::menu::renderer::DefaultMenuRenderer;
+use crate::menu::theme;
 use crate::menu::ui_flags::UiFlags;
-use crate::persistence::{Persistence, StorageKind};
+use crate::metadata::{self, SessionMetadata};
+use crate::naming;
+use crate::patch;
+use crate::persistence::{
+    Persistence, SnapshotRetention, StorageKind, read_session_file,
+    write_session_file,
+};
+use crate::projects;
+use crate::template;
 use crate::terminal_utils;
+use crate::tmux::charset::Charset;
+use crate::tmux::client::{self, SystemTmux, TmuxClient};
+use crate::tmux::control::{ControlModeTmux, EventWatcher};
+use crate::tmux::import::{self, Format};
 use crate::tmux::interface::*;
 use crate::tmux::layout::Layout;
-use crate::tmux::session::{Pane, Session, Window};
+use crate::tmux::layout_parser;
+use crate::tmux::plan::{Progress, restore_script};
+use crate::tmux::session::{Pane, Session, Window, combine_previews};
+use crate::util::{expand_home, is_session_file, validate_session_name};
+use crate::watch;
 use dirs::home_dir;
 
 use anyhow::{Context, Result};
-use shell_escape::escape;
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use regex::Regex;
+
+const ENV_PROFILE: &str = "TSMAN_PROFILE";
 
 /// Dispatches parsed CLI arguments to the matching subcommand handler.
 pub fn handle(args: Args) -> Result<()> {
-    let config = Config::load()?;
-    let persistence = Persistence::new(&config.storage)?;
+    let profile = args
+        .profile
+        .or_else(|| std::env::var(ENV_PROFILE).ok())
+        .filter(|p| !p.is_empty());
+    let config = Config::load(profile.as_deref())?;
+    let persistence = Persistence::new(&config.storage)?
+        .with_snapshot_retention(config.snapshots.retention());
+    if let Err(err) = persistence
+        .ensure_data_dir()
+        .and_then(|dir| logging::init(args.verbose, &dir))
+    {
+        eprintln!("Warning: logging disabled: {err:#}");
+    }
+    log::debug!("Running {:?}", args.command);
+    client::set_command_policy(config.tmux.command_policy());
+    set_on_step_failure(match args.command {
+        Commands::Open {
+            allow_partial: true,
+            ..
+        }
+        | Commands::Reload {
+            allow_partial: true,
+            ..
+        } => OnStepFailure::Continue,
+        _ => config.restore.on_failure,
+    });
+    // A bad pattern mustn't stop the commands that don't restore anything,
+    // like `edit --config` to fix it; `validate` reports it as an error.
+    let patterns = config.lint.patterns().unwrap_or_else(|err| {
+        eprintln!("Warning: using the default lint patterns. {err:#}");
+        LintConfig::default_patterns()
+    });
+    set_restore_lint(RestoreLint {
+        patterns,
+        fail: config.lint.fail,
+    });
+    let client = match &args.command {
+        Commands::Open {
+            client,
+            dry_run: false,
+            ..
+        }
+        | Commands::Menu { client, .. } => choose_client(
+            client.as_deref(),
+            config.tmux.pick_client,
+            &SystemTmux::default(),
+        )?,
+        _ => None,
+    };
+    let tmux = SystemTmux::default().attaching_client(client);
+    let editor = Editor::resolve(config.editor.command.as_deref());
 
     match args.command {
-        Commands::Save { session_name } => {
-            save(session_name.as_deref(), &persistence)
+        Commands::Save {
+            session_name,
+            to,
+            append,
+            stdout,
+            ..
+        } if stdout || to.as_deref() == Some(Path::new("-")) => {
+            if append {
+                anyhow::bail!("--append needs a file to add the session to");
+            }
+            print_save(session_name.as_deref(), &tmux, &persistence)
+        }
+        Commands::Save {
+            session_name,
+            to: Some(path),
+            append,
+            ..
+        } => save_to_file(
+            session_name.as_deref(),
+            &path,
+            append,
+            &tmux,
+            &persistence,
+        ),
+        Commands::Save {
+            session_name,
+            with_history,
+            ..
+        } => save(
+            session_name.as_deref(),
+            with_history.then_some(&config.history),
+            &tmux,
+            &persistence,
+        ),
+        Commands::Open {
+            confirm_commands: true,
+            ..
+        } if !io::stdin().is_terminal() => {
+            anyhow::bail!("--confirm-commands needs an interactive terminal")
+        }
+        Commands::Open {
+            file: Some(file),
+            dry_run,
+            here,
+            into,
+            confirm_commands,
+            ..
+        } => {
+            if here || into.is_some() {
+                anyhow::bail!("--here and --into take a session name");
+            }
+            open_file(&file, dry_run, confirm_commands, &tmux, &persistence)
+                .map(print_report)
+        }
+        Commands::Open {
+            session_name: Some(file),
+            dry_run,
+            create,
+            here,
+            into,
+            confirm_commands,
+            ..
+        } if is_session_file(&file) => {
+            if create.is_some() || here || into.is_some() {
+                anyhow::bail!(
+                    "--create, --here and --into take a session name, not a \
+                     file"
+                );
+            }
+            open_file(
+                Path::new(&file),
+                dry_run,
+                confirm_commands,
+                &tmux,
+                &persistence,
+            )
+            .map(print_report)
+        }
+        Commands::Open {
+            session_name,
+            create: Some(dir),
+            snapshot,
+            ..
+        } => {
+            let template = config
+                .projects
+                .name_template
+                .as_deref()
+                .unwrap_or(naming::DEFAULT_TEMPLATE);
+            open_dir(
+                &dir,
+                session_name.as_deref(),
+                template,
+                snapshot || config.snapshots.on_open,
+                &tmux,
+                &persistence,
+            )
+            .map(print_report)
+        }
+        Commands::Open {
+            session_name,
+            here,
+            into,
+            ..
+        } if here || into.is_some() => {
+            let session_name = match session_name {
+                Some(name) => config.resolve_alias(&name).to_string(),
+                None => default_session(&config)?,
+            };
+            open_into(&session_name, into.as_deref(), &tmux, &persistence)
+                .map(print_report)
+        }
+        Commands::Open {
+            session_name,
+            dry_run,
+            snapshot,
+            confirm_commands,
+            ..
+        } => {
+            let session_name = match session_name {
+                Some(name) => config.resolve_alias(&name).to_string(),
+                None => default_session(&config)?,
+            };
+            if dry_run {
+                print_restore_plan(&session_name, &persistence)
+            } else {
+                if io::stdin().is_terminal() {
+                    resolve_dead_panes(&session_name, &tmux, &persistence)?;
+                    resolve_divergence(&session_name, &tmux, &persistence)?;
+                }
+                let snapshot = snapshot || config.snapshots.on_open;
+                if confirm_commands {
+                    open_confirmed(&session_name, snapshot, &tmux, &persistence)
+                } else {
+                    open(
+                        &session_name,
+                        snapshot,
+                        &tmux,
+                        &persistence,
+                        &mut print_progress(),
+                    )
+                }
+                .map(print_report)
+            }
+        }
+        Commands::Edit {
+            file: Some(path), ..
+        } => {
+            if path == Path::new("-") {
+                anyhow::bail!("Can't edit standard input");
+            }
+            editor.open(&path)
+        }
+        Commands::Edit {
+            session_name,
+            set,
+            patch,
+            ..
+        } if !set.is_empty() || patch.is_some() => patch_session(
+            session_name
+                .as_deref()
+                .map(|name| config.resolve_alias(name)),
+            &set,
+            patch.as_deref(),
+            &tmux,
+            &persistence,
+        ),
+        Commands::Edit { session_name, .. } => edit(
+            session_name
+                .as_deref()
+                .map(|name| config.resolve_alias(name)),
+            &tmux,
+            &persistence,
+            &editor,
+        ),
+        Commands::Reload { session_name, .. } => reload(
+            session_name
+                .as_deref()
+                .map(|name| config.resolve_alias(name)),
+            &tmux,
+            &persistence,
+            &mut print_progress(),
+        )
+        .map(print_report),
+        Commands::Delete {
+            session_name,
+            regex,
+            yes,
+            dry_run,
+        } => {
+            if regex || glob::is_glob(&session_name) {
+                delete_matching(
+                    &session_name,
+                    regex,
+                    yes,
+                    dry_run,
+                    &persistence,
+                )
+            } else if dry_run {
+                let name = validate_session_name(&session_name)?;
+                persistence
+                    .existing_config_path(StorageKind::Session, &name)?;
+                println!("{name}");
+                Ok(())
+            } else {
+                delete(&validate_session_name(&session_name)?, &persistence)
+            }
+        }
+        Commands::List {
+            pattern,
+            regex,
+            tree: true,
+            active,
+            ..
+        } => list_tree(
+            pattern.as_deref(),
+            regex,
+            active,
+            charset(false, &config),
+            config.menu.use_icons,
+            &tmux,
+            &persistence,
+        ),
+        Commands::List {
+            pattern,
+            regex,
+            format,
+            plain,
+            ..
+        } => {
+            let format = if plain {
+                None
+            } else {
+                format.or_else(|| config.list.format.clone())
+            };
+            list(
+                pattern.as_deref(),
+                regex,
+                format.as_deref(),
+                &tmux,
+                &persistence,
+            )
+        }
+        Commands::Exec {
+            session_name,
+            command,
+            split,
+            all_panes,
+            shell_only,
+        } => {
+            let target = if all_panes {
+                ExecTarget::AllPanes { shell_only }
+            } else if split {
+                ExecTarget::Split
+            } else {
+                ExecTarget::Window
+            };
+            exec(
+                config.resolve_alias(&session_name),
+                &command.join(" "),
+                target,
+                &tmux,
+                &persistence,
+            )
+        }
+        Commands::Kill {
+            unsaved: true, yes, ..
+        } => kill_unsaved(yes, &tmux, &persistence),
+        Commands::Kill {
+            session_name,
+            detach_only,
+            ..
+        } => kill(session_name.as_deref(), detach_only, &tmux, &persistence),
+        Commands::Path { name, layout, .. } => {
+            let name = match name.as_deref() {
+                Some(name) if !layout => Some(config.resolve_alias(name)),
+                name => name,
+            };
+            print_path(name, layout, &persistence)
+        }
+        Commands::Prune {
+            older_than,
+            yes,
+            archive,
+        } => prune(older_than, yes, archive, &persistence),
+        Commands::Gc {
+            session_name,
+            keep,
+            max_age,
+            dry_run,
+        } => gc(
+            session_name.as_deref(),
+            SnapshotRetention {
+                keep: keep.or(config.snapshots.keep),
+                max_age_days: max_age.or(config.snapshots.max_age_days),
+            },
+            dry_run,
+            &persistence,
+        ),
+        Commands::Diff {
+            session_name,
+            side_by_side,
+            no_color,
+            quiet,
+        } => diff(
+            session_name.as_deref(),
+            DiffStyle {
+                side_by_side,
+                color: !no_color
+                    && std::env::var_os("NO_COLOR").is_none()
+                    && io::stdout().is_terminal(),
+                quiet,
+            },
+            &tmux,
+            &persistence,
+        ),
+        Commands::Validate { session_name } => {
+            validate(session_name.as_deref(), &config.lint, &persistence)
+        }
+        Commands::Doctor { cleanup, accept } => {
+            doctor(cleanup, accept, &tmux, &persistence)
+        }
+        Commands::Daemon {
+            command:
+                Some(DaemonCommands::Install {
+                    autosave,
+                    socket,
+                    no_enable,
+                }),
+            ..
+        } => {
+            let path = daemon::install(
+                autosave,
+                socket.as_deref(),
+                profile.as_deref(),
+                !no_enable,
+            )?;
+            println!("Installed {}", path.display());
+            Ok(())
         }
-        Commands::Open { session_name } => open(&session_name, &persistence),
-        Commands::Edit { session_name } => {
-            edit(session_name.as_deref(), &persistence)
+        Commands::Daemon {
+            socket, autosave, ..
+        } => {
+            let socket = match socket {
+                Some(socket) => socket,
+                None => daemon::default_socket(&persistence)?,
+            };
+            let autosave =
+                autosave.map(|minutes| Duration::from_secs(minutes * 60));
+            daemon::serve(&socket, autosave, &persistence)
+        }
+        Commands::Watch {
+            session_name,
+            debounce,
+        } => {
+            let session_name = match session_name {
+                Some(name) => config.resolve_alias(&name).to_string(),
+                None => get_session_name(&tmux)?,
+            };
+            watch::watch(
+                &session_name,
+                Duration::from_secs(debounce),
+                &tmux,
+                &persistence,
+            )
+        }
+        Commands::Remap {
+            session_name,
+            from,
+            to,
+            ..
+        } => remap(session_name.as_deref(), &from, &to, &persistence),
+        Commands::Import {
+            path: Some(path),
+            format,
+            name,
+            force,
+            ..
+        } => import_file(
+            &path,
+            format.map(Into::into),
+            name.as_deref(),
+            force,
+            &persistence,
+        ),
+        Commands::Import {
+            url,
+            name,
+            layout,
+            force,
+            ..
+        } => {
+            let kind = if layout {
+                StorageKind::Layout
+            } else {
+                StorageKind::Session
+            };
+            let url = url.context("Pass a file or --url to import")?;
+            import_url(
+                &url,
+                name.as_deref(),
+                kind,
+                force,
+                &config.lint,
+                &persistence,
+            )
         }
-        Commands::Reload { session_name } => {
-            reload(session_name.as_deref(), &persistence)
+        Commands::Projects => {
+            for project in projects::discover(&config.projects)? {
+                println!("{}", project.display());
+            }
+            Ok(())
         }
-        Commands::Delete { session_name } => {
-            delete(&session_name, &persistence)
+        Commands::Log { name, limit } => {
+            print_log(name.as_deref(), limit, &persistence)
         }
+        Commands::Stats { sort } => stats(sort, &persistence),
+        Commands::Meta {
+            session_name,
+            tags,
+            untags,
+            pin,
+            unpin,
+            description,
+        } => meta(
+            &session_name,
+            &tags,
+            &untags,
+            pin.then_some(true).or(unpin.then_some(false)),
+            description.as_deref(),
+            &persistence,
+        ),
         Commands::Menu {
             preview,
             ask_for_confirmation,
+            filter,
+            group_by,
+            ascii,
+            keymap,
+            compact,
+            preview_position,
+            theme,
+            ..
         } => {
+            theme::select(theme.unwrap_or(config.menu.theme));
+            if let Some(preview_template) = &config.menu.preview_template {
+                template::parse(preview_template, PREVIEW_FIELDS)
+                    .context("Invalid preview_template under [menu]")?;
+            }
             let show_preview = preview || config.menu.preview;
             let confirm =
                 ask_for_confirmation || config.menu.ask_for_confirmation;
             menu(
-                show_preview,
-                confirm,
-                config.menu.show_key_presses,
+                UiFlags {
+                    ask_for_confirmation: confirm,
+                    show_preview,
+                    preview_position: preview_position
+                        .unwrap_or(config.menu.preview_position),
+                    preview_height: config.menu.preview_height(),
+                    prompt_save_on_exit: config.menu.prompt_save_on_exit,
+                    snapshot_on_open: config.snapshots.on_open,
+                    show_key_presses: config.menu.show_key_presses,
+                    sort_order: config.menu.sort,
+                    list_filter: filter.unwrap_or(config.menu.filter),
+                    group_by: group_by.unwrap_or(config.menu.group_by),
+                    charset: charset(ascii, &config),
+                    use_icons: config.menu.use_icons,
+                    preview_cmd: config.menu.preview_cmd.clone(),
+                    preview_template: config.menu.preview_template.clone(),
+                    keymap: keymap.unwrap_or(config.menu.keymap),
+                    wrap_around: config.menu.wrap_around,
+                    tick_rate: config.menu.tick_rate(),
+                    compact: if compact {
+                        Compact::Always
+                    } else {
+                        config.menu.compact
+                    },
+                },
+                menu_client(tmux, config.tmux.control_mode),
+                config
+                    .tmux
+                    .servers
+                    .iter()
+                    .map(|s| {
+                        menu_client(
+                            SystemTmux::with_server(s.clone()),
+                            config.tmux.control_mode,
+                        )
+                    })
+                    .collect(),
                 persistence,
+                editor,
+                config,
             )
         }
-        Commands::Completions { shell } => {
-            completions(shell);
-            Ok(())
+        Commands::Completions { shell } => {
+            completions(shell);
+            Ok(())
+        }
+        Commands::Setup => setup(),
+        Commands::Layout { command } => {
+            handle_layout(command, &tmux, &persistence, &editor)
+        }
+    }
+}
+
+/// Wraps `tmux` in a control-mode client if enabled. The menu issues many
+/// small queries, which is where a persistent connection pays off.
+fn menu_client(tmux: SystemTmux, control_mode: bool) -> Box<dyn TmuxClient> {
+    if control_mode {
+        Box::new(ControlModeTmux::new(tmux))
+    } else {
+        Box::new(tmux)
+    }
+}
+
+fn save(
+    session_name: Option<&str>,
+    history: Option<&HistoryConfig>,
+    tmux: &dyn TmuxClient,
+    persistence: &Persistence,
+) -> Result<()> {
+    let mut current_session =
+        get_session(tmux, None).context("Failed to get current session")?;
+    let live_name = current_session.name.clone();
+
+    if let Some(name) = session_name {
+        current_session.name = name.to_string();
+    }
+
+    let _lock = persistence.lock(StorageKind::Session)?;
+    keep_saved_env(&mut current_session, persistence);
+    if let Some(history) = history {
+        save_history(
+            &live_name,
+            &mut current_session,
+            history,
+            tmux,
+            persistence,
+        )
+        .context("Failed to save pane history")?;
+    }
+
+    let yaml = serde_yaml::to_string(&current_session).with_context(|| {
+        format!("Failed to serialize session {current_session:#?} to yaml")
+    })?;
+
+    persistence
+        .save_config(StorageKind::Session, &current_session.name, yaml)
+        .context("Failed to save yaml config to disk")?;
+    audit::record(persistence, "save", &current_session.name);
+
+    Ok(())
+}
+
+/// Prints the config [`save`] would write for the current session, as
+/// `session_name` if given, without writing anything.
+fn print_save(
+    session_name: Option<&str>,
+    tmux: &dyn TmuxClient,
+    persistence: &Persistence,
+) -> Result<()> {
+    let mut session =
+        get_session(tmux, None).context("Failed to get current session")?;
+    if let Some(name) = session_name {
+        session.name = name.to_string();
+    }
+    keep_saved_env(&mut session, persistence);
+
+    let yaml = serde_yaml::to_string(&session)
+        .context("Failed to serialize session to yaml")?;
+    print!("{yaml}");
+    Ok(())
+}
+
+/// How `tsman diff` prints the differences.
+struct DiffStyle {
+    side_by_side: bool,
+    color: bool,
+    /// Print nothing, fail with [`TsmanError::SessionDiffers`] if there are
+    /// any.
+    quiet: bool,
+}
+
+/// Prints how the running session `session_name` (the current one if not
+/// given) differs from its saved config: the YAML of both, as
+/// [`print_save`] would print the running one.
+fn diff(
+    session_name: Option<&str>,
+    style: DiffStyle,
+    tmux: &dyn TmuxClient,
+    persistence: &Persistence,
+) -> Result<()> {
+    let mut live = get_session(tmux, session_name)
+        .context("Failed to get the running session")?;
+    // The config as written, without its included windows, which
+    // `keep_saved_env` drops from the running session too.
+    let saved: Session = persistence.load(StorageKind::Session, &live.name)?;
+    persistence.warn_if_modified(&live.name);
+    keep_saved_env(&mut live, persistence);
+
+    let saved_yaml = serde_yaml::to_string(&saved)
+        .context("Failed to serialize session to yaml")?;
+    let live_yaml = serde_yaml::to_string(&live)
+        .context("Failed to serialize session to yaml")?;
+    let lines = diff::diff_lines(&saved_yaml, &live_yaml);
+
+    if style.quiet {
+        if diff::has_changes(&lines) {
+            return Err(TsmanError::SessionDiffers(live.name).into());
+        }
+        return Ok(());
+    }
+    let out = if style.side_by_side {
+        let width = crossterm::terminal::size()
+            .map_or(80, |(columns, _)| usize::from(columns));
+        diff::side_by_side(&lines, width, style.color)
+    } else {
+        diff::unified(
+            &lines,
+            &format!("{} (saved)", live.name),
+            &format!("{} (running)", live.name),
+            style.color,
+        )
+    };
+    print!("{out}");
+    Ok(())
+}
+
+/// Saves the current session, as `session_name` if given, into the file of
+/// several sessions `path` - on its own, or with `append` in place of the
+/// session with the same name or after the others.
+fn save_to_file(
+    session_name: Option<&str>,
+    path: &Path,
+    append: bool,
+    tmux: &dyn TmuxClient,
+    persistence: &Persistence,
+) -> Result<()> {
+    let mut session =
+        get_session(tmux, None).context("Failed to get current session")?;
+    if let Some(name) = session_name {
+        session.name = name.to_string();
+    }
+
+    // Another save appending to the same file between the read and the
+    // write would be lost.
+    let _lock = persistence.lock(StorageKind::Session)?;
+    let mut sessions = if append && path.exists() {
+        read_session_file(path)?
+    } else {
+        Vec::new()
+    };
+    let name = session.name.clone();
+    match sessions.iter_mut().find(|saved| saved.name == name) {
+        Some(saved) => {
+            session.keep_env_from(saved);
+            *saved = session;
+        }
+        None => sessions.push(session),
+    }
+
+    write_session_file(path, &sessions)?;
+    audit::record(
+        persistence,
+        "save",
+        &format!("{name} -> {}", path.display()),
+    );
+    Ok(())
+}
+
+/// Carries the hand-written settings of the config `session` is about to
+/// overwrite over to it, since tmux can't report them: its `env` maps and
+/// its `include` list, leaving the included windows out of `session`.
+fn keep_saved_env(session: &mut Session, persistence: &Persistence) {
+    if let Ok(saved) =
+        persistence.load::<Session>(StorageKind::Session, &session.name)
+    {
+        session.keep_env_from(&saved);
+        let fragments =
+            persistence.load_fragments(&saved).unwrap_or_else(|err| {
+                log::warn!("Couldn't read the included files: {err:#}");
+                Vec::new()
+            });
+        session.keep_includes_from(&saved, &fragments);
+    }
+}
+
+/// Captures the scrollback of every pane of the running session `live_name`
+/// into gzipped files in the history directory of `session`, replacing any
+/// saved before, and points the panes at them.
+fn save_history(
+    live_name: &str,
+    session: &mut Session,
+    config: &HistoryConfig,
+    tmux: &dyn TmuxClient,
+    persistence: &Persistence,
+) -> Result<()> {
+    persistence.remove_history(&session.name)?;
+    let dir = persistence.history_dir(&session.name);
+    fs::create_dir_all(&dir).with_context(|| {
+        format!("Failed to create directory {}", dir.display())
+    })?;
+
+    for window in &mut session.windows {
+        for pane in &mut window.panes {
+            let target = format!("{live_name}:{}.{}", window.index, pane.index);
+            let text = capture_pane(tmux, &target, config.lines())?;
+            let text = keep_last_bytes(&text, config.max_bytes());
+
+            let path = dir.join(format!("{}.{}.gz", window.index, pane.index));
+            let mut encoder = GzEncoder::new(
+                File::create(&path).with_context(|| {
+                    format!("Failed to create {}", path.display())
+                })?,
+                Compression::default(),
+            );
+            encoder.write_all(text.as_bytes())?;
+            encoder.finish()?;
+            pane.history = Some(path.to_string_lossy().into_owned());
+        }
+    }
+    Ok(())
+}
+
+/// The end of `text`, at most `max` bytes of it, starting at a line.
+fn keep_last_bytes(text: &str, max: usize) -> &str {
+    if text.len() <= max {
+        return text;
+    }
+    let cut = text.len() - max;
+    // Searching bytes, since `cut` may fall inside a character.
+    match text.as_bytes()[cut..].iter().position(|&b| b == b'\n') {
+        Some(pos) => &text[cut + pos + 1..],
+        None => "",
+    }
+}
+
+/// Saves the tmux session with the given name to disk.
+pub fn save_target(
+    session_name: &str,
+    tmux: &dyn TmuxClient,
+    persistence: &Persistence,
+) -> Result<()> {
+    let mut current_session = get_session(tmux, Some(session_name))
+        .context("Failed to get current session")?;
+
+    let _lock = persistence.lock(StorageKind::Session)?;
+    keep_saved_env(&mut current_session, persistence);
+    let yaml = serde_yaml::to_string(&current_session).with_context(|| {
+        format!("Failed to serialize session {current_session:#?} to yaml")
+    })?;
+
+    persistence
+        .save_config(StorageKind::Session, &current_session.name, yaml)
+        .context("Failed to save yaml config to disk")?;
+    audit::record(persistence, "save", &current_session.name);
+
+    Ok(())
+}
+
+/// Restores a saved session, or attaches if it's already active. With
+/// `snapshot`, a running session that no longer matches its config is
+/// snapshotted first.
+pub fn open(
+    session_name: &str,
+    snapshot: bool,
+    tmux: &dyn TmuxClient,
+    persistence: &Persistence,
+    on_progress: &mut dyn FnMut(Progress),
+) -> Result<RestoreReport> {
+    open_at_window(session_name, None, snapshot, tmux, persistence, on_progress)
+}
+
+/// Like [`open`], but selects `window` of the saved config before
+/// attaching. A running session's windows may be numbered differently from
+/// the config, so there the window is found by name.
+pub fn open_at_window(
+    session_name: &str,
+    window: Option<&Window>,
+    snapshot: bool,
+    tmux: &dyn TmuxClient,
+    persistence: &Persistence,
+    on_progress: &mut dyn FnMut(Progress),
+) -> Result<RestoreReport> {
+    // Not finding the window is no reason not to open the session.
+    let select = |target: &str, window: &Window| {
+        if let Err(err) = select_window(tmux, session_name, target) {
+            log::warn!("Couldn't select window '{}': {err}", window.name);
+        }
+    };
+
+    // A concurrent `open` of the same session finishes its restore first,
+    // then this one finds the session running and attaches to it. The lock
+    // is released before attaching, which blocks outside tmux.
+    let restore_lock = persistence.lock_restore(session_name)?;
+
+    if is_active_session(tmux, session_name)? {
+        log::info!("Attaching to running session '{session_name}'");
+        if snapshot {
+            snapshot_if_diverged(session_name, tmux, persistence)
+                .context("Failed to snapshot the running session")?;
+        }
+        drop(restore_lock);
+        record_open(session_name, persistence);
+        if let Some(window) = window {
+            select(&format!("={}", window.name), window);
+        }
+        attach_to_session(tmux, session_name)?;
+        return Ok(RestoreReport::default());
+    }
+
+    let session: Session = persistence
+        .load_session(session_name)
+        .context("Failed to read session from config file")?;
+
+    record_open(session_name, persistence);
+    audit::record(persistence, "restore", session_name);
+    let report = restore_session_detached(tmux, &session, on_progress)
+        .context("Failed to restore session")?;
+    drop(restore_lock);
+    if let Some(window) = window {
+        select(&window.index, window);
+    }
+    attach_to_session(tmux, session_name)?;
+    Ok(report)
+}
+
+/// Like [`open`], but a session that isn't running yet has its commands
+/// reviewed with [`confirm_commands`] before it is restored.
+fn open_confirmed(
+    session_name: &str,
+    snapshot: bool,
+    tmux: &dyn TmuxClient,
+    persistence: &Persistence,
+) -> Result<RestoreReport> {
+    let restore_lock = persistence.lock_restore(session_name)?;
+    if is_active_session(tmux, session_name)? {
+        drop(restore_lock);
+        return open(
+            session_name,
+            snapshot,
+            tmux,
+            persistence,
+            &mut print_progress(),
+        );
+    }
+
+    let mut session: Session = persistence
+        .load_session(session_name)
+        .context("Failed to read session from config file")?;
+    confirm_commands(&mut session)?;
+
+    record_open(session_name, persistence);
+    audit::record(persistence, "restore", session_name);
+    let report =
+        restore_session_detached(tmux, &session, &mut print_progress())
+            .context("Failed to restore session")?;
+    drop(restore_lock);
+    attach_to_session(tmux, session_name)?;
+    Ok(report)
+}
+
+/// Walks through the commands restoring `session` would type - `pre`,
+/// `pre_window` and each pane's command - asking whether to run, skip or
+/// replace each one. Only `session` is changed, never its config.
+fn confirm_commands(session: &mut Session) -> Result<()> {
+    println!("Commands of '{}':", session.name);
+    for command in std::mem::take(&mut session.pre) {
+        if let Some(command) = confirm_command("pre", command)? {
+            session.pre.push(command);
+        }
+    }
+    if let Some(command) = session.pre_window.take() {
+        session.pre_window =
+            confirm_command("pre_window, in every pane", command)?;
+    }
+    for window in &mut session.windows {
+        for pane in &mut window.panes {
+            let Some(command) = pane.current_command.take() else {
+                continue;
+            };
+            let label = format!(
+                "Window {} '{}', pane {}",
+                window.index, window.name, pane.index
+            );
+            pane.current_command = confirm_command(&label, command)?;
+        }
+    }
+    Ok(())
+}
+
+/// Asks what to do with one command: `None` skips it. An empty replacement
+/// skips it too.
+fn confirm_command(label: &str, command: String) -> Result<Option<String>> {
+    println!("\n{label}:\n  {command}");
+    loop {
+        match prompt_line("[r]un, [s]kip, [e]dit or [q]uit? [R/s/e/q]: ")?
+            .as_str()
+        {
+            "" | "r" => return Ok(Some(command)),
+            "s" => return Ok(None),
+            "e" => {
+                let edited = prompt_text("Run instead: ")?;
+                return Ok((!edited.is_empty()).then_some(edited));
+            }
+            "q" => return Err(TsmanError::Aborted.into()),
+            _ => {}
+        }
+    }
+}
+
+/// Restores the saved session `session_name` without attaching, unless it is
+/// already running, and returns the report of the restore if there was one.
+pub fn restore_detached(
+    session_name: &str,
+    tmux: &dyn TmuxClient,
+    persistence: &Persistence,
+) -> Result<Option<RestoreReport>> {
+    let _restore_lock = persistence.lock_restore(session_name)?;
+    if is_active_session(tmux, session_name)? {
+        return Ok(None);
+    }
+
+    let session: Session = persistence
+        .load_session(session_name)
+        .context("Failed to read session from config file")?;
+
+    record_open(session_name, persistence);
+    audit::record(persistence, "restore", session_name);
+    restore_session_detached(tmux, &session, &mut |_| {})
+        .context("Failed to restore session")
+        .map(Some)
+}
+
+/// Restores every session defined in the file `path` that isn't running yet
+/// and attaches to the first, or with `dry_run` prints their restore plans.
+/// With `confirm`, each session's commands are reviewed before its restore.
+fn open_file(
+    path: &Path,
+    dry_run: bool,
+    confirm: bool,
+    tmux: &dyn TmuxClient,
+    persistence: &Persistence,
+) -> Result<RestoreReport> {
+    let mut sessions = read_session_file(path)?;
+    let Some(first) = sessions.first().map(|session| session.name.clone())
+    else {
+        anyhow::bail!("{} defines no sessions", path.display());
+    };
+    for session in &mut sessions {
+        validate_session_name(&session.name)?;
+        for fragment in persistence.load_fragments(session)? {
+            session.add_fragment(fragment)?;
+        }
+    }
+
+    if dry_run {
+        for session in &sessions {
+            print!("{}", restore_script(session, &session.name));
+        }
+        return Ok(RestoreReport::default());
+    }
+
+    let mut report = RestoreReport::default();
+    for session in &mut sessions {
+        let _restore_lock = persistence.lock_restore(&session.name)?;
+        if is_active_session(tmux, &session.name)? {
+            log::info!("'{}' is already running", session.name);
+            continue;
+        }
+        if confirm {
+            confirm_commands(session)?;
+        }
+        audit::record(persistence, "restore", &session.name);
+        let restored =
+            restore_session_detached(tmux, session, &mut print_progress())
+                .with_context(|| {
+                    format!("Failed to restore session '{}'", session.name)
+                })?;
+        report.failed_steps.extend(restored.failed_steps);
+        report.mismatches.extend(restored.mismatches);
+    }
+
+    attach_to_session(tmux, &first)?;
+    Ok(report)
+}
+
+/// Adds the windows of the saved session `session_name` to the running
+/// session `into`, or the current one, after its last window. They are
+/// built in the background, leaving the window and pane the session shows
+/// selected.
+fn open_into(
+    session_name: &str,
+    into: Option<&str>,
+    tmux: &dyn TmuxClient,
+    persistence: &Persistence,
+) -> Result<RestoreReport> {
+    let current = match into {
+        Some(target) => {
+            if !is_active_session(tmux, target)? {
+                anyhow::bail!("Session '{target}' is not running");
+            }
+            target.to_string()
+        }
+        None => get_session_name(tmux)?,
+    };
+    if current == session_name {
+        anyhow::bail!("Already in session '{session_name}'");
+    }
+    let session: Session = persistence
+        .load_session(session_name)
+        .context("Failed to read session from config file")?;
+
+    record_open(session_name, persistence);
+    audit::record(
+        persistence,
+        if into.is_some() {
+            "open-into"
+        } else {
+            "open-here"
+        },
+        &format!("{session_name} -> {current}"),
+    );
+    let windows: Vec<&Window> = session.windows.iter().collect();
+    let (_, failed_steps) = append_windows(
+        tmux,
+        &session,
+        &current,
+        &windows,
+        &mut print_progress(),
+    )
+    .context("Failed to add the session's windows")?;
+
+    Ok(RestoreReport {
+        failed_steps,
+        mismatches: Vec::new(),
+        warnings: Vec::new(),
+    })
+}
+
+/// Snapshots the running session `session_name` if it no longer matches its
+/// saved config, so opening it never loses track of the live state. Returns
+/// the snapshot's path, if one was taken.
+fn snapshot_if_diverged(
+    session_name: &str,
+    tmux: &dyn TmuxClient,
+    persistence: &Persistence,
+) -> Result<Option<PathBuf>> {
+    // Without a saved config there is nothing the live state differs from.
+    let Ok(saved) = persistence.load_session(session_name) else {
+        return Ok(None);
+    };
+    let diverged = list_window_shapes(tmux)?
+        .get(session_name)
+        .is_some_and(|windows| saved.differs_from(windows));
+    if !diverged {
+        return Ok(None);
+    }
+
+    let live = get_session(tmux, Some(session_name))?;
+    let yaml = serde_yaml::to_string(&live)?;
+    let path = persistence.save_snapshot(session_name, yaml)?;
+    audit::record(
+        persistence,
+        "snapshot",
+        &format!("{session_name} ({})", path.display()),
+    );
+    Ok(Some(path))
+}
+
+/// Asks what to do when the running session `session_name` no longer
+/// matches its saved config: attach to it as it is, add the saved windows it
+/// is missing, or save it over the config.
+fn resolve_divergence(
+    session_name: &str,
+    tmux: &dyn TmuxClient,
+    persistence: &Persistence,
+) -> Result<()> {
+    if !is_active_session(tmux, session_name)? {
+        return Ok(());
+    }
+    let Ok(saved) = persistence.load_session(session_name) else {
+        return Ok(());
+    };
+    let Some(live) = list_window_shapes(tmux)?.remove(session_name) else {
+        return Ok(());
+    };
+    if !saved.differs_from(&live) {
+        return Ok(());
+    }
+
+    let missing: Vec<&Window> = saved
+        .windows
+        .iter()
+        .filter(|window| !live.iter().any(|(name, _)| *name == window.name))
+        .collect();
+
+    println!("'{session_name}' is running but no longer matches its config.");
+    let reapply = if missing.is_empty() {
+        String::new()
+    } else {
+        format!(
+            " [r]e-apply the config ({} missing windows),",
+            missing.len()
+        )
+    };
+    let choice = prompt_line(&format!(
+        "[a]ttach as is,{reapply} [s]ave it over the config or [q]uit? [A/{}s/q]: ",
+        if missing.is_empty() { "" } else { "r/" }
+    ))?;
+
+    match choice.as_str() {
+        "r" if !missing.is_empty() => {
+            audit::record(persistence, "reapply", session_name);
+            let (_, failed) = append_windows(
+                tmux,
+                &saved,
+                session_name,
+                &missing,
+                &mut print_progress(),
+            )
+            .context("Failed to add the missing windows")?;
+            for failure in failed {
+                eprintln!("Warning: {failure}");
+            }
+        }
+        "s" => save_target(session_name, tmux, persistence)?,
+        "q" => return Err(TsmanError::Aborted.into()),
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Offers to restart the panes of the running session `session_name` whose
+/// processes have exited (kept open by `remain-on-exit`) from its saved
+/// config.
+fn resolve_dead_panes(
+    session_name: &str,
+    tmux: &dyn TmuxClient,
+    persistence: &Persistence,
+) -> Result<()> {
+    if !is_active_session(tmux, session_name)? {
+        return Ok(());
+    }
+    let Ok(saved) = persistence.load_session(session_name) else {
+        return Ok(());
+    };
+    let dead = list_dead_panes(tmux, session_name)?;
+    if dead.is_empty() {
+        return Ok(());
+    }
+
+    println!("'{session_name}' has {} dead panes:", dead.len());
+    for pane in &dead {
+        println!(
+            "  Window {} '{}', pane {}",
+            pane.window_index, pane.window_name, pane.index
+        );
+    }
+    if !prompt_bool("Respawn them from the config? [Y/n]: ", true)? {
+        return Ok(());
+    }
+
+    audit::record(persistence, "respawn", session_name);
+    for pane in &dead {
+        if let Err(err) = respawn_pane(tmux, &saved, pane) {
+            eprintln!("Warning: couldn't respawn pane {}: {err}", pane.id);
+        }
+    }
+    Ok(())
+}
+
+/// The tmux client `open` and the menu switch to the session: the one named
+/// `client`, the current one for `current` or the user's pick for `ask` -
+/// or with `pick`, whenever several clients are attached. `None` attaches
+/// as usual.
+fn choose_client(
+    client: Option<&str>,
+    pick: bool,
+    tmux: &dyn TmuxClient,
+) -> Result<Option<String>> {
+    match client {
+        Some("current") => match current_client(tmux)? {
+            Some(name) => Ok(Some(name)),
+            None => anyhow::bail!("--client current only works inside tmux"),
+        },
+        Some("ask") => pick_client(tmux, true),
+        Some(name) => {
+            if !list_clients(tmux)?.iter().any(|c| c.name == name) {
+                anyhow::bail!("No tmux client '{name}' is attached");
+            }
+            Ok(Some(name.to_string()))
+        }
+        None if pick && io::stdin().is_terminal() => pick_client(tmux, false),
+        None => Ok(None),
+    }
+}
+
+/// Lists the attached clients and asks which one to switch. A single client
+/// is only offered when `always` is set.
+fn pick_client(tmux: &dyn TmuxClient, always: bool) -> Result<Option<String>> {
+    let clients = list_clients(tmux)?;
+    if clients.is_empty() || (clients.len() == 1 && !always) {
+        return Ok(None);
+    }
+
+    println!("Attached tmux clients:");
+    for (i, client) in clients.iter().enumerate() {
+        println!(
+            "  {}) {}  {}x{}  showing '{}'",
+            i + 1,
+            client.name,
+            client.width,
+            client.height,
+            client.session
+        );
+    }
+    loop {
+        let choice = prompt_line(&format!(
+            "Switch which client? [1-{}, Enter for this terminal]: ",
+            clients.len()
+        ))?;
+        if choice.is_empty() {
+            return Ok(None);
+        }
+        if let Some(client) = choice
+            .parse::<usize>()
+            .ok()
+            .and_then(|n| clients.get(n.wrapping_sub(1)))
+        {
+            return Ok(Some(client.name.clone()));
+        }
+    }
+}
+
+/// Returns the configured `default_session`, for `open` without a name.
+fn default_session(config: &Config) -> Result<String> {
+    let name = config.default_session.as_deref().context(
+        "No session name given and no default_session set in the config file",
+    )?;
+    validate_session_name(name)
+        .with_context(|| format!("Invalid default_session '{name}'"))
+}
+
+/// Opens the session for the project in `dir`, named `session_name` or by
+/// `template`. Without a saved config, a new session with one window in
+/// `dir` is created.
+fn open_dir(
+    dir: &Path,
+    session_name: Option<&str>,
+    template: &str,
+    snapshot: bool,
+    tmux: &dyn TmuxClient,
+    persistence: &Persistence,
+) -> Result<RestoreReport> {
+    let dir = fs::canonicalize(dir).with_context(|| {
+        format!("Invalid project directory: {}", dir.display())
+    })?;
+    let name = match session_name {
+        Some(name) => name.to_string(),
+        None => naming::session_name(template, &dir)?,
+    };
+
+    let restore_lock = persistence.lock_restore(&name)?;
+    if is_active_session(tmux, &name)?
+        || persistence
+            .existing_config_path(StorageKind::Session, &name)
+            .is_ok()
+    {
+        drop(restore_lock);
+        return open(&name, snapshot, tmux, persistence, &mut print_progress());
+    }
+
+    let session = Session::single_window(
+        &name,
+        &dir.to_string_lossy(),
+        base_index(tmux)?,
+    );
+    record_open(&name, persistence);
+    audit::record(
+        persistence,
+        "create",
+        &format!("{name} ({})", dir.display()),
+    );
+    let report =
+        restore_session_detached(tmux, &session, &mut print_progress())
+            .context("Failed to create session")?;
+    drop(restore_lock);
+    attach_to_session(tmux, &name)?;
+    Ok(report)
+}
+
+/// Reports restore progress on a single stderr line, cleared once the
+/// restore is done. Prints nothing when stderr isn't a terminal.
+fn print_progress() -> impl FnMut(Progress) {
+    let tty = io::stderr().is_terminal();
+    move |progress| {
+        if !tty {
+            return;
+        }
+        let mut stderr = io::stderr();
+        if progress.is_done() {
+            let _ = write!(stderr, "\r\x1b[K");
+        } else {
+            let _ = write!(stderr, "\r\x1b[KRestoring {progress}");
+        }
+        let _ = stderr.flush();
+    }
+}
+
+/// Lists what went wrong during a restore on stderr.
+fn print_report(report: RestoreReport) {
+    for warning in &report.warnings {
+        eprintln!("Warning: {warning}");
+    }
+    if report.is_clean() {
+        return;
+    }
+    eprintln!("Warning: session restored with problems:");
+    for problem in report.problems() {
+        eprintln!("  - {problem}");
+    }
+}
+
+/// Prints the restore plan of a saved session as a shell script, without
+/// touching tmux.
+fn print_restore_plan(
+    session_name: &str,
+    persistence: &Persistence,
+) -> Result<()> {
+    let session: Session = persistence
+        .load_session(session_name)
+        .context("Failed to read session from config file")?;
+
+    print!("{}", restore_script(&session, &session.name));
+
+    Ok(())
+}
+
+/// Bumps the usage stats of a saved session. Stats are best-effort and must
+/// never block opening a session.
+fn record_open(session_name: &str, persistence: &Persistence) {
+    if persistence
+        .get_config_file_path(StorageKind::Session, session_name)
+        .is_ok_and(|path| path.exists())
+    {
+        let _ = persistence
+            .open_metadata()
+            .and_then(|metadata| metadata.record_open(session_name));
+    }
+}
+
+/// Opens a session's YAML config in the editor. Falls back to the current
+/// session.
+fn edit(
+    session_name: Option<&str>,
+    tmux: &dyn TmuxClient,
+    persistence: &Persistence,
+    editor: &Editor,
+) -> Result<()> {
+    let name = match session_name {
+        Some(name) => name.to_string(),
+        None => get_session_name(tmux)?,
+    };
+    edit_config(persistence, editor, StorageKind::Session, &name)
+}
+
+/// Applies `--set` assignments, then the merge patch read from `patch_path`,
+/// to the saved session `session_name` (the current one if not given).
+/// Nothing is written unless the result is a valid session config.
+/// Comments in the config are dropped.
+fn patch_session(
+    session_name: Option<&str>,
+    assignments: &[String],
+    patch_path: Option<&Path>,
+    tmux: &dyn TmuxClient,
+    persistence: &Persistence,
+) -> Result<()> {
+    let name = match session_name {
+        Some(name) => name.to_string(),
+        None => get_session_name(tmux)?,
+    };
+    let patch_value = patch_path
+        .map(|path| -> Result<serde_yaml::Value> {
+            let text = if path == Path::new("-") {
+                io::read_to_string(io::stdin())?
+            } else {
+                fs::read_to_string(path).with_context(|| {
+                    format!("Failed to read {}", path.display())
+                })?
+            };
+            serde_yaml::from_str(&text).map_err(|err| {
+                TsmanError::InvalidConfig {
+                    path: path.to_path_buf(),
+                    source: err.into(),
+                }
+                .into()
+            })
+        })
+        .transpose()?;
+
+    let _lock = persistence.lock(StorageKind::Session)?;
+    let path = persistence.existing_config_path(StorageKind::Session, &name)?;
+    persistence.warn_if_modified(&name);
+    let mut doc: serde_yaml::Value = serde_yaml::from_str(
+        &persistence.load_config(StorageKind::Session, &name)?,
+    )
+    .map_err(|err| TsmanError::InvalidConfig {
+        path: path.clone(),
+        source: err.into(),
+    })?;
+
+    let old_name = doc["name"].clone();
+
+    for assignment in assignments {
+        let (field, value) = patch::parse_assignment(assignment)?;
+        patch::set(&mut doc, &field, value)?;
+    }
+    if let Some(patch_value) = patch_value {
+        patch::merge(&mut doc, patch_value);
+    }
+
+    if doc["name"] != old_name {
+        anyhow::bail!("The patch renames the session, use `tsman rename`");
+    }
+    serde_yaml::from_value::<Session>(doc.clone()).map_err(|err| {
+        TsmanError::InvalidConfig {
+            path,
+            source: format!("after patching: {err}").into(),
+        }
+    })?;
+
+    // The patched document rather than the session, so keys keep their
+    // order and fields left out stay out. Comments are lost either way.
+    let yaml = serde_yaml::to_string(&doc)?;
+    persistence.save_config(StorageKind::Session, &name, yaml)?;
+    audit::record(persistence, "patch", &name);
+    println!("Updated '{name}'");
+    Ok(())
+}
+
+/// Opens a config file (session or layout) in the editor.
+pub fn edit_config(
+    persistence: &Persistence,
+    editor: &Editor,
+    kind: StorageKind,
+    name: &str,
+) -> Result<()> {
+    let path = persistence.get_config_file_path(kind, name)?;
+    editor.open(&path)?;
+    if kind == StorageKind::Session && path.exists() {
+        persistence.record_config_hash(name)?;
+    }
+    Ok(())
+}
+
+/// Kills (or with `detach_only`, detaches) a running session, or every
+/// session without an attached client if `session_name` is `None`.
+fn kill(
+    session_name: Option<&str>,
+    detach_only: bool,
+    tmux: &dyn TmuxClient,
+    persistence: &Persistence,
+) -> Result<()> {
+    let Some(session_name) = session_name else {
+        for name in list_detached_sessions(tmux)? {
+            close_session(tmux, &name)?;
+            audit::record(persistence, "kill", &name);
+            log::info!("Killed session '{name}'");
+            println!("{name}");
+        }
+        return Ok(());
+    };
+
+    if !is_active_session(tmux, session_name)? {
+        return Err(
+            TsmanError::SessionNotFound(session_name.to_string()).into()
+        );
+    }
+
+    if detach_only {
+        detach_session(tmux, session_name)?;
+    } else {
+        close_session(tmux, session_name)?;
+        audit::record(persistence, "kill", session_name);
+        log::info!("Killed session '{session_name}'");
+    }
+    Ok(())
+}
+
+/// Kills every running session without a saved config, after listing them
+/// and asking unless `yes` is set.
+fn kill_unsaved(
+    yes: bool,
+    tmux: &dyn TmuxClient,
+    persistence: &Persistence,
+) -> Result<()> {
+    let unsaved = list_unsaved_sessions(tmux, persistence)?;
+    if unsaved.is_empty() {
+        println!("No unsaved sessions running.");
+        return Ok(());
+    }
+
+    if !yes {
+        for name in &unsaved {
+            println!("  {name}");
+        }
+        let prompt = format!("Kill these {} sessions? [y/N]: ", unsaved.len());
+        if !prompt_bool(&prompt, false)? {
+            return Ok(());
+        }
+    }
+
+    for name in &unsaved {
+        close_session(tmux, name)?;
+        audit::record(persistence, "kill", name);
+        log::info!("Killed session '{name}'");
+    }
+    println!("Killed {} sessions.", unsaved.len());
+    Ok(())
+}
+
+/// Running sessions that have no saved config, except the one tsman runs
+/// in.
+pub fn list_unsaved_sessions(
+    tmux: &dyn TmuxClient,
+    persistence: &Persistence,
+) -> Result<Vec<String>> {
+    let saved = persistence.list_saved_configs(StorageKind::Session)?;
+    let current = get_session_name(tmux).ok();
+    Ok(list_active_sessions(tmux)?
+        .into_iter()
+        .filter(|name| !saved.contains(name) && current.as_ref() != Some(name))
+        .collect())
+}
+
+/// Where `tsman exec` runs its command.
+enum ExecTarget {
+    Window,
+    Split,
+    AllPanes { shell_only: bool },
+}
+
+/// Runs `command` in the `target` panes of a session, restoring the session
+/// first if it isn't running.
+fn exec(
+    session_name: &str,
+    command: &str,
+    target: ExecTarget,
+    tmux: &dyn TmuxClient,
+    persistence: &Persistence,
+) -> Result<()> {
+    if !is_active_session(tmux, session_name)? {
+        let session: Session = persistence
+            .load_session(session_name)
+            .context("Failed to read session from config file")?;
+        audit::record(persistence, "restore", session_name);
+        let report =
+            restore_session_detached(tmux, &session, &mut print_progress())
+                .context("Failed to restore session")?;
+        print_report(report);
+    }
+
+    let panes = match target {
+        ExecTarget::Window => {
+            vec![exec_in_session(tmux, session_name, command, false)?]
+        }
+        ExecTarget::Split => {
+            vec![exec_in_session(tmux, session_name, command, true)?]
+        }
+        ExecTarget::AllPanes { shell_only } => {
+            broadcast_to_session(tmux, session_name, command, shell_only)?
+        }
+    };
+    for pane_id in panes {
+        println!("{pane_id}");
+    }
+    Ok(())
+}
+
+/// Prints the path of a saved config, or of the storage directory when
+/// `name` is `None`.
+fn print_path(
+    name: Option<&str>,
+    layout: bool,
+    persistence: &Persistence,
+) -> Result<()> {
+    let kind = if layout {
+        StorageKind::Layout
+    } else {
+        StorageKind::Session
+    };
+
+    let path = match name {
+        Some(name) => persistence.existing_config_path(kind, name)?,
+        None => persistence.dir(kind).clone(),
+    };
+    println!("{}", path.display());
+    Ok(())
+}
+
+/// Reloads a session from its saved config.
+///
+/// - If the session is active and we are currently attached to it, uses a
+///   temp-session switch to avoid disconnecting the client.
+/// - If the session is active but we are not attached, kills and recreates
+///   it directly, then attaches.
+/// - If the session is not active, opens it fresh (equivalent to `open`).
+pub fn reload(
+    session_name: Option<&str>,
+    tmux: &dyn TmuxClient,
+    persistence: &Persistence,
+    on_progress: &mut dyn FnMut(Progress),
+) -> Result<RestoreReport> {
+    let name = match session_name {
+        Some(n) => n.to_string(),
+        None => {
+            anyhow::ensure!(
+                std::env::var("TMUX").is_ok(),
+                "Reload requires a session name or being inside a tmux \
+                 session"
+            );
+            get_session_name(tmux)?
+        }
+    };
+
+    let session: Session = persistence
+        .load_session(&name)
+        .context("No saved config found for this session")?;
+
+    audit::record(persistence, "reload", &name);
+    if is_active_session(tmux, &name)? {
+        let currently_attached =
+            get_session_name(tmux).ok().as_deref() == Some(&name);
+        reload_session(tmux, &session, currently_attached, on_progress)
+            .context("Failed to reload session")
+    } else {
+        restore_session(tmux, &session, on_progress)
+            .context("Failed to restore session")
+    }
+}
+
+/// Deletes a saved session's YAML config from disk.
+pub fn delete(session_name: &str, persistence: &Persistence) -> Result<()> {
+    let _lock = persistence.lock(StorageKind::Session)?;
+    let path =
+        persistence.existing_config_path(StorageKind::Session, session_name)?;
+    fs::remove_file(path)?;
+    persistence.remove_history(session_name)?;
+    persistence.open_metadata()?.remove(session_name)?;
+    audit::record(persistence, "delete", session_name);
+    log::info!("Deleted session config '{session_name}'");
+    Ok(())
+}
+
+/// Saved session names matching a glob `pattern`, or a regular expression
+/// if `regex` is set.
+fn matching_sessions(
+    pattern: &str,
+    regex: bool,
+    persistence: &Persistence,
+) -> Result<Vec<String>> {
+    let re = if regex {
+        Regex::new(pattern)
+    } else {
+        glob::to_regex(pattern)
+    }
+    .with_context(|| format!("Invalid pattern '{pattern}'"))?;
+
+    let mut names: Vec<String> = persistence
+        .list_saved_configs(StorageKind::Session)?
+        .into_iter()
+        .filter(|name| re.is_match(name))
+        .collect();
+    names.sort();
+    Ok(names)
+}
+
+/// Deletes every saved session matching `pattern`, after listing them and
+/// asking unless `yes` is set. With `dry_run`, only lists them.
+fn delete_matching(
+    pattern: &str,
+    regex: bool,
+    yes: bool,
+    dry_run: bool,
+    persistence: &Persistence,
+) -> Result<()> {
+    let names = matching_sessions(pattern, regex, persistence)?;
+    if names.is_empty() {
+        anyhow::bail!("No saved sessions match '{pattern}'");
+    }
+
+    for name in &names {
+        println!("{name}");
+    }
+    if dry_run {
+        return Ok(());
+    }
+    if !yes {
+        let prompt = format!("Delete these {} sessions? [y/N]: ", names.len());
+        if !prompt_bool(&prompt, false)? {
+            return Ok(());
+        }
+    }
+
+    for name in &names {
+        delete(name, persistence)?;
+    }
+    Ok(())
+}
+
+/// Prints the saved sessions matching `pattern`, one per line: the name, or
+/// `format` filled in for it.
+fn list(
+    pattern: Option<&str>,
+    regex: bool,
+    format: Option<&str>,
+    tmux: &dyn TmuxClient,
+    persistence: &Persistence,
+) -> Result<()> {
+    let names = matching_sessions(pattern.unwrap_or("*"), regex, persistence)?;
+    let Some(format) = format else {
+        for name in names {
+            println!("{name}");
+        }
+        return Ok(());
+    };
+
+    let parts =
+        template::parse(format, LIST_FIELDS).context("Invalid list format")?;
+    let field_used = |field: &str| template::uses(&parts, field);
+    let all_metadata = persistence.open_metadata()?.all()?;
+    let active = if field_used("active") {
+        list_active_sessions(tmux).unwrap_or_default()
+    } else {
+        Vec::new()
+    };
+    let now = metadata::now();
+
+    for name in names {
+        // Only read the config when a field needs it.
+        let session = if ["work_dir", "windows", "panes"]
+            .iter()
+            .any(|field| field_used(field))
+        {
+            match persistence.load_session(&name) {
+                Ok(session) => Some(session),
+                Err(err) => {
+                    eprintln!("Warning: skipping '{name}': {err:#}");
+                    continue;
+                }
+            }
+        } else {
+            None
+        };
+        let md = all_metadata.get(&name).cloned().unwrap_or_default();
+
+        let line = template::fill(&parts, |field| match field {
+            "name" => name.clone(),
+            "path" => persistence
+                .get_config_file_path(StorageKind::Session, &name)
+                .map(|path| path.display().to_string())
+                .unwrap_or_default(),
+            "work_dir" => session
+                .as_ref()
+                .map(|s| s.work_dir.clone())
+                .unwrap_or_default(),
+            "windows" => {
+                session.as_ref().map_or(0, |s| s.windows.len()).to_string()
+            }
+            "panes" => session
+                .as_ref()
+                .map_or(0, |s| s.windows.iter().map(|w| w.panes.len()).sum())
+                .to_string(),
+            "tags" => md.tags.join(","),
+            "description" => md.description.clone().unwrap_or_default(),
+            "pinned" => md.pinned.to_string(),
+            "open_count" => md.open_count.to_string(),
+            "last_opened" => md
+                .last_opened
+                .map(|at| metadata::format_age(at, now))
+                .unwrap_or_else(|| "never".to_string()),
+            "last_opened_unix" => md.last_opened.unwrap_or(0).to_string(),
+            "active" => active.contains(&name).to_string(),
+            _ => unreachable!("checked by template::parse"),
+        });
+        println!("{line}");
+    }
+    Ok(())
+}
+
+/// Tree glyphs to draw with: ASCII if asked for by the flag or the config.
+fn charset(ascii: bool, config: &Config) -> Charset {
+    if ascii || config.menu.ascii {
+        Charset::Ascii
+    } else {
+        Charset::Unicode
+    }
+}
+
+/// Prints the saved sessions matching `pattern` as one tree of their
+/// windows and panes under the sessions directory. With `active`, the
+/// running parts of each are marked.
+fn list_tree(
+    pattern: Option<&str>,
+    regex: bool,
+    active: bool,
+    charset: Charset,
+    icons: bool,
+    tmux: &dyn TmuxClient,
+    persistence: &Persistence,
+) -> Result<()> {
+    let names = matching_sessions(pattern.unwrap_or("*"), regex, persistence)?;
+    let live = if active {
+        list_window_shapes(tmux)?
+    } else {
+        Default::default()
+    };
+
+    let mut previews = Vec::new();
+    for name in names {
+        match persistence.load_session(&name) {
+            Ok(session) => previews.push(session.get_live_preview(
+                charset,
+                icons,
+                live.get(&name).map(Vec::as_slice),
+            )),
+            Err(err) => eprintln!("Warning: skipping '{name}': {err:#}"),
+        }
+    }
+
+    let root = persistence.dir(StorageKind::Session).display().to_string();
+    print!("{}", combine_previews(&root, &previews, charset));
+    Ok(())
+}
+
+/// Fields `list --format` fills in.
+const LIST_FIELDS: &[&str] = &[
+    "name",
+    "path",
+    "work_dir",
+    "windows",
+    "panes",
+    "tags",
+    "description",
+    "pinned",
+    "open_count",
+    "last_opened",
+    "last_opened_unix",
+    "active",
+];
+
+/// Renames a saved config file and updates the name inside the YAML,
+/// refusing to replace another config. A session's saved scrollback moves
+/// along, with the paths to it.
+pub fn rename(
+    persistence: &Persistence,
+    kind: StorageKind,
+    old_name: &str,
+    new_name: &str,
+) -> Result<()> {
+    let _lock = persistence.lock(kind)?;
+    let path = persistence.existing_config_path(kind, old_name)?;
+    if persistence.existing_config_path(kind, new_name).is_ok() {
+        return Err(TsmanError::ConfigExists {
+            kind: kind.noun(),
+            name: new_name.to_string(),
+        }
+        .into());
+    }
+    let mut new_path = path.clone();
+    new_path.set_file_name(new_name);
+    new_path.set_extension("yaml");
+    fs::rename(path, new_path)?;
+    log::info!("Renamed config '{old_name}' to '{new_name}'");
+    if let StorageKind::Session = kind {
+        persistence.open_metadata()?.rename(old_name, new_name)?;
+        persistence.rename_history(old_name, new_name)?;
+    }
+
+    let raw_yaml = persistence
+        .load_config(kind, new_name)
+        .context("Failed to read config file")?;
+    let mut value: serde_yaml::Value = serde_yaml::from_str(&raw_yaml)
+        .with_context(|| format!("Failed to deserialize yaml: {raw_yaml}"))?;
+    value["name"] = serde_yaml::Value::String(new_name.to_owned());
+    if let StorageKind::Session = kind {
+        let (from, to) = (
+            persistence.history_dir(old_name),
+            persistence.history_dir(new_name),
+        );
+        let histories =
+            config_panes(&mut value).filter_map(|pane| pane.get_mut("history"));
+        for history in histories {
+            if let Some(file) = history
+                .as_str()
+                .and_then(|path| Path::new(path).strip_prefix(&from).ok())
+            {
+                *history = serde_yaml::Value::String(
+                    to.join(file).to_string_lossy().into_owned(),
+                );
+            }
+        }
+    }
+
+    let updated_yaml =
+        serde_yaml::to_string(&value).context("Failed to serialize yaml")?;
+    persistence
+        .save_config(kind, new_name, updated_yaml)
+        .context("Failed to save yaml config to disk")?;
+
+    let action = match kind {
+        StorageKind::Session => "rename",
+        StorageKind::Layout => "layout-rename",
+    };
+    audit::record(persistence, action, &format!("{old_name} -> {new_name}"));
+
+    Ok(())
+}
+
+/// The panes of every window of a session config.
+fn config_panes(
+    value: &mut serde_yaml::Value,
+) -> impl Iterator<Item = &mut serde_yaml::Value> {
+    value
+        .get_mut("windows")
+        .and_then(serde_yaml::Value::as_sequence_mut)
+        .into_iter()
+        .flatten()
+        .filter_map(|window| window.get_mut("panes")?.as_sequence_mut())
+        .flatten()
+}
+
+/// Saves a copy of the config `name` as `new_name`, which must not be taken.
+/// The copy starts without metadata or saved scrollback.
+pub fn duplicate(
+    persistence: &Persistence,
+    kind: StorageKind,
+    name: &str,
+    new_name: &str,
+) -> Result<()> {
+    let _lock = persistence.lock(kind)?;
+    if persistence.existing_config_path(kind, new_name).is_ok() {
+        return Err(TsmanError::ConfigExists {
+            kind: kind.noun(),
+            name: new_name.to_string(),
+        }
+        .into());
+    }
+
+    let raw_yaml = persistence
+        .load_config(kind, name)
+        .context("Failed to read config file")?;
+    let mut value: serde_yaml::Value = serde_yaml::from_str(&raw_yaml)
+        .with_context(|| format!("Failed to deserialize yaml: {raw_yaml}"))?;
+    value["name"] = serde_yaml::Value::String(new_name.to_owned());
+    // The scrollback stays with the original, which may save over it.
+    for pane in config_panes(&mut value) {
+        if let Some(pane) = pane.as_mapping_mut() {
+            pane.remove("history");
+        }
+    }
+
+    let yaml =
+        serde_yaml::to_string(&value).context("Failed to serialize yaml")?;
+    persistence
+        .save_config(kind, new_name, yaml)
+        .context("Failed to save yaml config to disk")?;
+    log::info!("Duplicated config '{name}' as '{new_name}'");
+
+    let action = match kind {
+        StorageKind::Session => "duplicate",
+        StorageKind::Layout => "layout-duplicate",
+    };
+    audit::record(persistence, action, &format!("{name} -> {new_name}"));
+
+    Ok(())
+}
+
+/// Reads the saved session `session_name`, or every saved session, and lists
+/// the ones that can't be read and the risky commands in the others.
+fn validate(
+    session_name: Option<&str>,
+    lint: &LintConfig,
+    persistence: &Persistence,
+) -> Result<()> {
+    let patterns = lint.patterns()?;
+    let names = match session_name {
+        Some(name) => vec![name.to_string()],
+        None => {
+            let mut names =
+                persistence.list_saved_configs(StorageKind::Session)?;
+            names.sort();
+            names
+        }
+    };
+
+    let (mut invalid, mut risky) = (0, 0);
+    for name in &names {
+        let session: Session = match persistence.load_session(name) {
+            Ok(session) => session,
+            Err(err) => {
+                println!("{name}: {err:#}");
+                invalid += 1;
+                continue;
+            }
+        };
+        let commands = session.commands_matching(&patterns);
+        if !commands.is_empty() {
+            risky += 1;
+        }
+        for command in commands {
+            println!("{name}: risky command - {command}");
+        }
+    }
+
+    if invalid > 0 {
+        anyhow::bail!("{invalid} of {} configs can't be read", names.len());
+    }
+    if risky > 0 && lint.fail {
+        anyhow::bail!("{risky} configs contain risky commands");
+    }
+    if invalid + risky == 0 {
+        println!("{} configs checked, no problems found.", names.len());
+    }
+    Ok(())
+}
+
+/// Lists the saved sessions changed outside tsman since it last wrote them,
+/// recording their current content as trusted with `accept`, and the temp
+/// sessions whose tsman process is gone - restores that were interrupted
+/// before they could roll back - killing them with `cleanup`.
+fn doctor(
+    cleanup: bool,
+    accept: bool,
+    tmux: &dyn TmuxClient,
+    persistence: &Persistence,
+) -> Result<()> {
+    // Changed configs are listed below rather than warned about.
+    let persistence = &persistence.clone().with_modified_warnings(false);
+    // Before looking for changed configs, which records unknown ones.
+    let changes = persistence.reconcile_configs()?;
+    for line in changes.describe() {
+        println!("{line}");
+    }
+
+    let mut modified = Vec::new();
+    for name in persistence.list_saved_configs(StorageKind::Session)? {
+        if persistence.config_modified(&name)? {
+            modified.push(name);
+        }
+    }
+    modified.sort();
+    for name in &modified {
+        if accept {
+            persistence.record_config_hash(name)?;
+            audit::record(persistence, "accept", name);
+            println!("Accepted the changes to '{name}'");
+        } else {
+            println!("Config '{name}' was changed outside tsman");
         }
-        Commands::Init => init(),
-        Commands::Layout { command } => handle_layout(command, &persistence),
     }
-}
 
-fn save(session_name: Option<&str>, persistence: &Persistence) -> Result<()> {
-    let mut current_session =
-        get_session(None).context("Failed to get current session")?;
+    let leftovers: Vec<String> = list_active_sessions(tmux)?
+        .into_iter()
+        .filter(|name| temp_session_pid(name).is_some_and(|pid| !is_alive(pid)))
+        .collect();
 
-    if let Some(name) = session_name {
-        current_session.name = name.to_string();
+    if leftovers.is_empty() && modified.is_empty() && changes.is_empty() {
+        println!("No problems found.");
+        return Ok(());
     }
 
-    let yaml = serde_yaml::to_string(&current_session).with_context(|| {
-        format!("Failed to serialize session {current_session:#?} to yaml")
-    })?;
-
-    persistence
-        .save_config(StorageKind::Session, &current_session.name, yaml)
-        .context("Failed to save yaml config to disk")?;
+    for name in leftovers {
+        if !cleanup {
+            println!("Leftover temp session '{name}'");
+            continue;
+        }
+        close_session(tmux, &name)
+            .with_context(|| format!("Failed to kill '{name}'"))?;
+        audit::record(persistence, "cleanup", &name);
+        println!("Killed leftover temp session '{name}'");
+    }
 
     Ok(())
 }
 
-/// Saves the tmux session with the given name to disk.
-pub fn save_target(
-    session_name: &str,
+/// Whether a process with `pid` is running. One owned by another user
+/// counts, since signalling it is only refused, not impossible.
+fn is_alive(pid: u32) -> bool {
+    // 0 and out-of-range values would signal process groups instead.
+    let Some(pid) = libc::pid_t::try_from(pid).ok().filter(|&pid| pid > 0)
+    else {
+        return false;
+    };
+    // SAFETY: signal 0 only checks that the process can be signalled.
+    let signalled = unsafe { libc::kill(pid, 0) } == 0;
+    signalled || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+/// Removes the snapshots of `session_name`, or of every session, that
+/// `retention` doesn't keep, or with `dry_run` only lists them.
+fn gc(
+    session_name: Option<&str>,
+    retention: SnapshotRetention,
+    dry_run: bool,
     persistence: &Persistence,
 ) -> Result<()> {
-    let current_session = get_session(Some(session_name))
-        .context("Failed to get current session")?;
-
-    let yaml = serde_yaml::to_string(&current_session).with_context(|| {
-        format!("Failed to serialize session {current_session:#?} to yaml")
-    })?;
+    if retention.is_unlimited() {
+        anyhow::bail!(
+            "No retention policy: pass --keep or --max-age, or set keep or \
+             max_age_days under [snapshots] in the config file"
+        );
+    }
 
-    persistence
-        .save_config(StorageKind::Session, &current_session.name, yaml)
-        .context("Failed to save yaml config to disk")?;
+    let expired = persistence.expired_snapshots(session_name, retention)?;
+    if expired.is_empty() {
+        println!("No snapshots to remove.");
+        return Ok(());
+    }
+    let bytes: u64 = expired
+        .iter()
+        .filter_map(|path| fs::metadata(path).ok())
+        .map(|md| md.len())
+        .sum();
+
+    if dry_run {
+        for path in &expired {
+            println!("{}", path.display());
+        }
+        println!(
+            "Would remove {} snapshots ({} KB).",
+            expired.len(),
+            bytes.div_ceil(1024)
+        );
+        return Ok(());
+    }
 
+    persistence.remove_snapshots(&expired)?;
+    audit::record(persistence, "gc", &format!("{} snapshots", expired.len()));
+    println!(
+        "Removed {} snapshots ({} KB).",
+        expired.len(),
+        bytes.div_ceil(1024)
+    );
     Ok(())
 }
 
-/// Restores a saved session, or attaches if it's already active.
-pub fn open(session_name: &str, persistence: &Persistence) -> Result<()> {
-    if is_active_session(session_name)? {
-        attach_to_session(session_name)?;
-        return Ok(());
+/// Deletes (or archives) saved sessions whose work_dir is gone or, with
+/// `older_than`, that haven't been opened for that many days.
+fn prune(
+    older_than: Option<u64>,
+    yes: bool,
+    archive: bool,
+    persistence: &Persistence,
+) -> Result<()> {
+    let all_metadata = persistence.open_metadata()?.all()?;
+    let now = metadata::now();
+
+    let mut stale = Vec::new();
+    for name in persistence.list_saved_configs(StorageKind::Session)? {
+        let session: Session =
+            match persistence.load(StorageKind::Session, &name) {
+                Ok(session) => session,
+                Err(err) => {
+                    eprintln!("Warning: skipping '{name}': {err:#}");
+                    continue;
+                }
+            };
+
+        if !Path::new(&session.work_dir).is_dir() {
+            stale.push((name, format!("{} doesn't exist", session.work_dir)));
+            continue;
+        }
+
+        let Some(days) = older_than else {
+            continue;
+        };
+        let last_used =
+            match all_metadata.get(&name).and_then(|md| md.last_opened) {
+                Some(last_opened) => Some(last_opened),
+                None => persistence
+                    .get_config_file_path(StorageKind::Session, &name)
+                    .ok()
+                    .and_then(|path| fs::metadata(path).ok())
+                    .and_then(|md| md.modified().ok())
+                    .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                    .map(|age| age.as_secs()),
+            };
+        if let Some(last_used) = last_used
+            && now.saturating_sub(last_used) > days * 24 * 60 * 60
+        {
+            stale.push((
+                name,
+                format!("last used {}", metadata::format_age(last_used, now)),
+            ));
+        }
     }
 
-    let yaml = persistence
-        .load_config(StorageKind::Session, session_name)
-        .context("Failed to read session from config file")?;
+    if stale.is_empty() {
+        println!("Nothing to prune.");
+        return Ok(());
+    }
 
-    let session: Session = serde_yaml::from_str(&yaml).with_context(|| {
-        format!("Failed to deserialize session from yaml {yaml}")
-    })?;
+    let verb = if archive { "Archive" } else { "Delete" };
+    for (name, reason) in stale {
+        if !yes
+            && !prompt_bool(
+                &format!("{verb} '{name}' ({reason})? [y/N]: "),
+                false,
+            )?
+        {
+            continue;
+        }
 
-    restore_session(&session).context("Failed to restore session")?;
+        if archive {
+            let _lock = persistence.lock(StorageKind::Session)?;
+            let path =
+                persistence.archive_config(StorageKind::Session, &name)?;
+            audit::record(persistence, "archive", &name);
+            println!("Archived '{name}' to {}", path.display());
+        } else {
+            delete(&name, persistence)?;
+            println!("Deleted '{name}'");
+        }
+    }
 
     Ok(())
 }
 
-/// Opens a session's YAML config in `$EDITOR`. Falls back to the current session.
-pub fn edit(
+/// Moves the paths under `from` to under `to` in a saved session, or in all
+/// of them if `session_name` is `None`.
+fn remap(
     session_name: Option<&str>,
+    from: &str,
+    to: &str,
     persistence: &Persistence,
 ) -> Result<()> {
-    let path = if let Some(name) = session_name {
-        persistence.get_config_file_path(StorageKind::Session, name)?
-    } else {
-        let name = get_session_name()?;
-        persistence.get_config_file_path(StorageKind::Session, &name)?
+    let from = expand_home(from);
+    let to = expand_home(to);
+    if from.trim_end_matches('/').is_empty() {
+        anyhow::bail!("--from must not be the root directory");
+    }
+
+    let _lock = persistence.lock(StorageKind::Session)?;
+    let names = match session_name {
+        Some(name) => vec![name.to_string()],
+        None => persistence.list_saved_configs(StorageKind::Session)?,
     };
 
-    let path_str = escape(path.as_os_str().to_string_lossy());
-    let editor = std::env::var("EDITOR").unwrap_or_else(|_| "vi".to_string());
+    for name in names {
+        let mut session: Session =
+            persistence.load(StorageKind::Session, &name)?;
+        let changed = session.remap_paths(&from, &to);
+        if changed == 0 {
+            continue;
+        }
 
-    Command::new("sh")
-        .arg("-c")
-        .arg(format!("{editor} {path_str}"))
-        .status()?;
+        let yaml = serde_yaml::to_string(&session)?;
+        persistence.save_config(StorageKind::Session, &name, yaml)?;
+        audit::record(
+            persistence,
+            "remap",
+            &format!("{name} ({from} -> {to})"),
+        );
+        println!("{name}: {changed} path(s) updated");
+    }
 
     Ok(())
 }
 
-/// Opens a config file (session or layout) in `$EDITOR`.
-pub fn edit_config(
-    persistence: &Persistence,
+/// Downloads a config of the given kind from `url` and saves it, renamed to
+/// `name` if given. A session's risky commands are listed first, and with
+/// `[lint] fail` it isn't saved at all.
+fn import_url(
+    url: &str,
+    name: Option<&str>,
     kind: StorageKind,
-    name: &str,
+    force: bool,
+    lint: &LintConfig,
+    persistence: &Persistence,
 ) -> Result<()> {
-    let path = persistence.get_config_file_path(kind, name)?;
-    let path_str = escape(path.as_os_str().to_string_lossy());
-    let editor = std::env::var("EDITOR").unwrap_or_else(|_| "vi".to_string());
+    if !url.starts_with("https://") && !url.starts_with("http://") {
+        anyhow::bail!("Only http(s) URLs can be imported: {url}");
+    }
+
+    let output = Command::new("curl")
+        .args(["--fail", "--silent", "--show-error", "--location"])
+        .args(["--max-time", "30", "--", url])
+        .output()
+        .context("Failed to run curl, is it installed?")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "Failed to download {url}: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    let yaml = String::from_utf8(output.stdout)
+        .with_context(|| format!("{url} is not a text file"))?;
+
+    let (config_name, yaml) = match kind {
+        StorageKind::Session => {
+            let mut session: Session = serde_yaml::from_str(&yaml)
+                .with_context(|| format!("{url} is not a session config"))?;
+            if let Some(name) = name {
+                session.name = name.to_string();
+            }
+            for window in &session.windows {
+                if !window.layout.is_empty() {
+                    layout_parser::parse(&window.layout)?;
+                }
+            }
+            lint_import(&session, url, lint)?;
+            (session.name.clone(), serde_yaml::to_string(&session)?)
+        }
+        StorageKind::Layout => {
+            let mut layout: Layout = serde_yaml::from_str(&yaml)
+                .with_context(|| format!("{url} is not a layout config"))?;
+            if let Some(name) = name {
+                layout.name = name.to_string();
+            }
+            for window in &layout.windows {
+                layout_parser::parse(&window.layout)?;
+            }
+            (layout.name.clone(), serde_yaml::to_string(&layout)?)
+        }
+    };
+    validate_session_name(&config_name).with_context(|| {
+        format!("Invalid name '{config_name}' in {url}, pass --name")
+    })?;
+
+    save_imported(kind, &[(config_name, yaml)], url, force, persistence)
+}
 
-    Command::new("sh")
-        .arg("-c")
-        .arg(format!("{editor} {path_str}"))
-        .status()?;
+/// Warns about the risky commands `session`, downloaded from `url`, would
+/// replay once restored, or with `[lint] fail` refuses to import it.
+fn lint_import(session: &Session, url: &str, lint: &LintConfig) -> Result<()> {
+    let commands = session.commands_matching(&lint.patterns()?);
+    if commands.is_empty() {
+        return Ok(());
+    }
 
+    let verdict = if lint.fail { "Error" } else { "Warning" };
+    eprintln!(
+        "{verdict}: '{}' from {url} replays risky commands:",
+        session.name
+    );
+    for command in &commands {
+        eprintln!("  - {command}");
+    }
+    if lint.fail {
+        anyhow::bail!(
+            "Refusing to import '{}' ([lint] fail is set)",
+            session.name
+        );
+    }
     Ok(())
 }
 
-/// Reloads a session from its saved config.
-///
-/// - If the session is active and we are currently attached to it, uses a
-///   temp-session switch to avoid disconnecting the client.
-/// - If the session is active but we are not attached, kills and recreates
-///   it directly, then attaches.
-/// - If the session is not active, opens it fresh (equivalent to `open`).
-pub fn reload(
-    session_name: Option<&str>,
+/// Converts the file at `path` from `format`, or the format detected from
+/// its contents, and saves the sessions it defines - the only one under
+/// `name` if given.
+fn import_file(
+    path: &Path,
+    format: Option<Format>,
+    name: Option<&str>,
+    force: bool,
     persistence: &Persistence,
 ) -> Result<()> {
-    let name = match session_name {
-        Some(n) => n.to_string(),
-        None => {
-            anyhow::ensure!(
-                std::env::var("TMUX").is_ok(),
-                "Reload requires a session name or being inside a tmux \
-                 session"
-            );
-            get_session_name()?
-        }
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let format = match format {
+        Some(format) => format,
+        None => pick_format(path, import::detect(&text))?,
     };
+    let mut sessions = import::convert(path, &text, format)?;
+
+    if let Some(name) = name {
+        let [session] = sessions.as_mut_slice() else {
+            anyhow::bail!(
+                "{} defines {} sessions, --name needs exactly one",
+                path.display(),
+                sessions.len()
+            );
+        };
+        session.name = name.to_string();
+    }
+    if sessions.is_empty() {
+        anyhow::bail!("{} defines no sessions", path.display());
+    }
 
-    let yaml = persistence
-        .load_config(StorageKind::Session, &name)
-        .context("No saved config found for this session")?;
+    let mut configs = Vec::new();
+    for session in &sessions {
+        validate_session_name(&session.name).with_context(|| {
+            format!("Invalid name '{}' in {}", session.name, path.display())
+        })?;
+        configs.push((session.name.clone(), serde_yaml::to_string(session)?));
+    }
+    let source = format!("{} {}", format.name(), path.display());
+    save_imported(StorageKind::Session, &configs, &source, force, persistence)
+}
 
-    let session: Session = serde_yaml::from_str(&yaml).with_context(|| {
-        format!("Failed to deserialize session from yaml {yaml}")
-    })?;
+/// The one of `candidates` a file is in, asking which if there are several.
+fn pick_format(path: &Path, candidates: Vec<Format>) -> Result<Format> {
+    match candidates.as_slice() {
+        [] => anyhow::bail!(
+            "Can't tell the format of {}, pass --format",
+            path.display()
+        ),
+        [format] => Ok(*format),
+        _ if !io::stdin().is_terminal() => anyhow::bail!(
+            "{} could be a {} file, pass --format",
+            path.display(),
+            candidates
+                .iter()
+                .map(|format| format.name())
+                .collect::<Vec<_>>()
+                .join(" or ")
+        ),
+        _ => {
+            println!("{} could be:", path.display());
+            for (i, format) in candidates.iter().enumerate() {
+                println!("  {}) {}", i + 1, format.name());
+            }
+            let choice = prompt_line("Which one? [1]: ")?;
+            let i = match choice.as_str() {
+                "" => 0,
+                choice => choice
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|i| i.checked_sub(1))
+                    .filter(|&i| i < candidates.len())
+                    .context("No such format")?,
+            };
+            Ok(candidates[i])
+        }
+    }
+}
 
-    if is_active_session(&name)? {
-        let currently_attached =
-            get_session_name().ok().as_deref() == Some(&name);
-        reload_session(&session, currently_attached)
-            .context("Failed to reload session")?;
-    } else {
-        restore_session(&session).context("Failed to restore session")?;
+/// Saves imported `configs` (name and YAML pairs) of `kind` from `source`,
+/// refusing to overwrite any existing one unless `force` is set.
+fn save_imported(
+    kind: StorageKind,
+    configs: &[(String, String)],
+    source: &str,
+    force: bool,
+    persistence: &Persistence,
+) -> Result<()> {
+    let _lock = persistence.lock(kind)?;
+    if !force
+        && let Some((name, _)) = configs.iter().find(|(name, _)| {
+            persistence.existing_config_path(kind, name).is_ok()
+        })
+    {
+        anyhow::bail!(
+            "A config named '{name}' already exists, pass --force to \
+             overwrite it"
+        );
+    }
+    for (name, yaml) in configs {
+        persistence
+            .save_config(kind, name, yaml.clone())
+            .context("Failed to save yaml config to disk")?;
+        audit::record(persistence, "import", &format!("{name} ({source})"));
+        println!("Imported '{name}'");
     }
 
     Ok(())
 }
 
-/// Deletes a saved session's YAML config from disk.
-pub fn delete(session_name: &str, persistence: &Persistence) -> Result<()> {
-    let path =
-        persistence.get_config_file_path(StorageKind::Session, session_name)?;
-    fs::remove_file(path)?;
+/// Prints the most recent `limit` audit log entries (all if 0), optionally
+/// only those about `name`.
+fn print_log(
+    name: Option<&str>,
+    limit: usize,
+    persistence: &Persistence,
+) -> Result<()> {
+    let entries: Vec<_> = audit::read(persistence)?
+        .into_iter()
+        .filter(|entry| name.is_none_or(|name| entry.is_about(name)))
+        .collect();
+    let skip = match limit {
+        0 => 0,
+        limit => entries.len().saturating_sub(limit),
+    };
+
+    let width = entries
+        .iter()
+        .map(|entry| entry.action.len())
+        .max()
+        .unwrap_or(0);
+    for entry in &entries[skip..] {
+        println!("{}  {:<width$}  {}", entry.time, entry.action, entry.target);
+    }
     Ok(())
 }
 
-/// Renames a saved config file and updates the name inside the YAML.
-pub fn rename(
+fn stats(sort: SortOrder, persistence: &Persistence) -> Result<()> {
+    let names = persistence.list_saved_configs(StorageKind::Session)?;
+    if names.is_empty() {
+        println!("No saved sessions.");
+        return Ok(());
+    }
+
+    let mut all_metadata = persistence.open_metadata()?.all()?;
+    let now = metadata::now();
+
+    let mut rows: Vec<(String, SessionMetadata)> = names
+        .into_iter()
+        .map(|name| {
+            let md = all_metadata.remove(&name).unwrap_or_default();
+            (name, md)
+        })
+        .collect();
+
+    rows.sort_by(|(a_name, a), (b_name, b)| {
+        let by_sort = match sort {
+            SortOrder::Name => std::cmp::Ordering::Equal,
+            SortOrder::Recent => b.last_opened.cmp(&a.last_opened),
+            SortOrder::Frecency => b.frecency(now).total_cmp(&a.frecency(now)),
+        };
+        by_sort.then(a_name.cmp(b_name))
+    });
+
+    let name_width = rows
+        .iter()
+        .map(|(name, _)| name.len())
+        .max()
+        .unwrap_or(0)
+        .max("NAME".len());
+
+    println!(
+        "{:<name_width$}  {:>5}  {:<12}  {:>8}  TAGS",
+        "NAME", "OPENS", "LAST OPENED", "FRECENCY"
+    );
+    for (name, md) in rows {
+        let last_opened = md
+            .last_opened
+            .map(|t| metadata::format_age(t, now))
+            .unwrap_or_else(|| "never".to_string());
+        let pin = if md.pinned { " (pinned)" } else { "" };
+        println!(
+            "{:<name_width$}  {:>5}  {:<12}  {:>8.2}  {}{}",
+            name,
+            md.open_count,
+            last_opened,
+            md.frecency(now),
+            md.tags.join(","),
+            pin
+        );
+    }
+
+    Ok(())
+}
+
+fn meta(
+    session_name: &str,
+    tags: &[String],
+    untags: &[String],
+    pinned: Option<bool>,
+    description: Option<&str>,
     persistence: &Persistence,
-    kind: StorageKind,
-    old_name: &str,
-    new_name: &str,
 ) -> Result<()> {
-    let path = persistence.get_config_file_path(kind, old_name)?;
-    let mut new_path = path.clone();
-    new_path.set_file_name(new_name);
-    new_path.set_extension("yaml");
-    fs::rename(path, new_path)?;
+    persistence.existing_config_path(StorageKind::Session, session_name)?;
 
-    let raw_yaml = persistence
-        .load_config(kind, new_name)
-        .context("Failed to read config file")?;
-    let mut value: serde_yaml::Value = serde_yaml::from_str(&raw_yaml)
-        .with_context(|| format!("Failed to deserialize yaml: {raw_yaml}"))?;
-    value["name"] = serde_yaml::Value::String(new_name.to_owned());
+    let store = persistence.open_metadata()?;
 
-    let updated_yaml =
-        serde_yaml::to_string(&value).context("Failed to serialize yaml")?;
-    persistence
-        .save_config(kind, new_name, updated_yaml)
-        .context("Failed to save yaml config to disk")?;
+    for tag in tags {
+        store.add_tag(session_name, tag)?;
+    }
+    for tag in untags {
+        store.remove_tag(session_name, tag)?;
+    }
+    if let Some(pinned) = pinned {
+        store.set_pinned(session_name, pinned)?;
+    }
+    if let Some(description) = description {
+        store.set_description(
+            session_name,
+            Some(description).filter(|d| !d.is_empty()),
+        )?;
+    }
+
+    let md = store.get(session_name)?;
+    println!("name:        {session_name}");
+    println!("description: {}", md.description.as_deref().unwrap_or("-"));
+    println!("tags:        {}", md.tags.join(", "));
+    println!("pinned:      {}", md.pinned);
+    println!("opened:      {} times", md.open_count);
 
     Ok(())
 }
 
 fn completions(shell: clap_complete::Shell) {
-    clap_complete::generate(
-        shell,
-        &mut cli::Args::command(),
-        "tsman",
-        &mut std::io::stdout(),
-    );
+    write_completions(shell, &mut io::stdout());
+}
+
+fn write_completions(shell: clap_complete::Shell, out: &mut dyn Write) {
+    clap_complete::generate(shell, &mut cli::Args::command(), "tsman", out);
 }
 
 fn menu(
-    show_preview: bool,
-    ask_for_confirmation: bool,
-    show_key_presses: bool,
+    ui_flags: UiFlags,
+    tmux: Box<dyn TmuxClient>,
+    servers: Vec<Box<dyn TmuxClient>>,
     persistence: Persistence,
+    editor: Editor,
+    config: Config,
 ) -> Result<()> {
-    let mut terminal = terminal_utils::init()?;
-
-    let current_session = get_session_name().ok();
+    // Warnings printed under the menu would garble it.
+    let persistence = persistence.with_modified_warnings(false);
+    let current_session = get_session_name(tmux.as_ref()).ok();
+    // Configs renamed outside tsman keep their metadata only if picked up
+    // before the list is built.
+    let changes = persistence.reconcile_configs().unwrap_or_else(|err| {
+        log::warn!("Failed to check the sessions directory: {err:#}");
+        Default::default()
+    });
+
+    // One watcher per server; they stop when dropped at the end of the menu.
+    let (events_tx, events_rx) = mpsc::channel();
+    let _watchers: Vec<EventWatcher> = if config.tmux.control_mode {
+        std::iter::once(&tmux)
+            .chain(&servers)
+            .map(|client| {
+                let system = match client.server() {
+                    Some(server) => SystemTmux::with_server(server),
+                    None => SystemTmux::default(),
+                };
+                EventWatcher::spawn(system, events_tx.clone())
+            })
+            .collect()
+    } else {
+        Vec::new()
+    };
 
     let mut menu = Menu::new(
-        get_all_sessions(&persistence)?,
-        UiFlags::new(ask_for_confirmation, show_preview, show_key_presses),
+        get_all_sessions(
+            tmux.as_ref(),
+            &servers,
+            &persistence,
+            ui_flags.sort_order,
+        )?,
+        ui_flags,
         current_session.as_deref(),
+        tmux,
+        servers,
         persistence,
         Box::new(DefaultMenuRenderer),
         Box::new(DefaultEventHandler),
         Box::new(DefaultActionDispacher),
-    );
-
-    menu.run(&mut terminal)?;
+    )
+    .with_tmux_events(events_rx)
+    .with_editor(editor)
+    .with_projects(config.projects)
+    .with_aliases(config.aliases);
+    if !changes.is_empty() {
+        menu = menu.with_info(changes.describe().join("\n"));
+    }
 
-    terminal_utils::restore(terminal)?;
+    terminal_utils::run(|terminal| menu.run(terminal))
+}
 
-    Ok(())
+/// Whether the saved session `name` has working directories that don't
+/// exist. Unreadable configs count as fine; they fail loudly on open.
+pub fn has_missing_dirs(persistence: &Persistence, name: &str) -> bool {
+    persistence
+        .load_session(name)
+        .is_ok_and(|session| !session.missing_dirs().is_empty())
 }
 
-fn get_all_sessions(persistence: &Persistence) -> Result<Vec<MenuItem>> {
+/// Builds the session list for the menu: the union of saved configs and
+/// sessions on the `tmux` server, followed by the sessions on each of the
+/// extra `servers`. Saved sessions are ranked by `sort` using the metadata
+/// store.
+pub fn get_all_sessions(
+    tmux: &dyn TmuxClient,
+    servers: &[Box<dyn TmuxClient>],
+    persistence: &Persistence,
+    sort: SortOrder,
+) -> Result<Vec<MenuItem>> {
     let saved_sessions: HashSet<String> = persistence
         .list_saved_configs(StorageKind::Session)?
         .into_iter()
         .collect();
 
     let active_sessions: HashSet<String> =
-        list_active_sessions()?.into_iter().collect();
+        list_active_sessions(tmux)?.into_iter().collect();
+    // Only used to mark modified sessions and dead panes, so a failure
+    // isn't fatal.
+    let window_shapes = list_window_shapes(tmux).unwrap_or_default();
+    let dead_panes = count_dead_panes(tmux).unwrap_or_default();
+    // Likewise only used to tell apart unrelated sessions sharing a name.
+    let session_paths = list_session_paths(tmux).unwrap_or_default();
 
     let union: HashSet<_> =
         saved_sessions.union(&active_sessions).cloned().collect();
 
-    let all_sessions: Vec<MenuItem> = union
+    // A broken metadata database shouldn't keep the menu from opening.
+    let all_metadata = persistence
+        .open_metadata()
+        .and_then(|store| store.all())
+        .unwrap_or_default();
+    let now = metadata::now();
+
+    let mut unrelated = Vec::new();
+    let mut all_sessions: Vec<MenuItem> = union
         .into_iter()
         .map(|name| {
-            MenuItem::new(
+            let mut item = MenuItem::new(
                 name.clone(),
                 saved_sessions.contains(&name),
                 active_sessions.contains(&name),
-            )
+            );
+            if item.saved
+                && let Ok(session) = persistence.load_session(&name)
+            {
+                item.missing_dirs = !session.missing_dirs().is_empty();
+                // A running session in another directory is a different
+                // project that happens to share the name, listed apart.
+                if let Some(path) = session_paths.get(&name)
+                    && item.active
+                    && !same_dir(path, &session.work_dir)
+                {
+                    item.active = false;
+                    item.work_dir = Some(session.work_dir.clone());
+                    unrelated
+                        .push(MenuItem::unrelated(name.clone(), path.clone()));
+                }
+                item.dirty = item.active
+                    && window_shapes
+                        .get(&name)
+                        .is_some_and(|windows| session.differs_from(windows));
+            }
+            if item.active {
+                item.dead_panes = dead_panes.get(&name).copied().unwrap_or(0);
+            }
+            if let Some(md) = all_metadata.get(&name) {
+                item.pinned = md.pinned;
+                item.tags = md.tags.clone();
+                item.score = match sort {
+                    SortOrder::Name => 0.0,
+                    SortOrder::Recent => md.last_opened.unwrap_or(0) as f64,
+                    SortOrder::Frecency => md.frecency(now),
+                };
+            }
+            item
         })
         .collect();
+    all_sessions.extend(unrelated);
+
+    for server in servers {
+        let server_name = server.server().unwrap_or_default();
+        all_sessions.extend(
+            list_active_sessions(server.as_ref())?
+                .into_iter()
+                .map(|name| MenuItem::on_server(name, server_name.to_string())),
+        );
+    }
 
     Ok(all_sessions)
 }
 
+/// Whether the paths `a` and `b` name the same directory, ignoring a
+/// leading `~` and trailing slashes.
+fn same_dir(a: &str, b: &str) -> bool {
+    let normalize = |path: &str| {
+        let path = expand_home(path);
+        match path.trim_end_matches('/') {
+            "" => "/".to_string(),
+            trimmed => trimmed.to_string(),
+        }
+    };
+    normalize(a) == normalize(b)
+}
+
 fn handle_layout(
     command: LayoutCommands,
+    tmux: &dyn TmuxClient,
     persistence: &Persistence,
+    editor: &Editor,
 ) -> Result<()> {
     match command {
         LayoutCommands::Save { layout_name } => {
-            layout_save(layout_name.as_deref(), persistence)
+            layout_save(layout_name.as_deref(), tmux, persistence)
         }
         LayoutCommands::Create {
             layout_name,
@@ -324,24 +2906,28 @@ fn handle_layout(
             &layout_name,
             &work_dir,
             session_name.as_deref(),
+            tmux,
             persistence,
-        ),
+            &mut print_progress(),
+        )
+        .map(print_report),
         LayoutCommands::List => layout_list(persistence),
         LayoutCommands::Delete { layout_name } => {
             layout_delete(&layout_name, persistence)
         }
         LayoutCommands::Edit { layout_name } => {
-            layout_edit(&layout_name, persistence)
+            layout_edit(&layout_name, persistence, editor)
         }
     }
 }
 
 fn layout_save(
     layout_name: Option<&str>,
+    tmux: &dyn TmuxClient,
     persistence: &Persistence,
 ) -> Result<()> {
     let current_session =
-        get_session(None).context("Failed to get current session")?;
+        get_session(tmux, None).context("Failed to get current session")?;
 
     let mut layout = Layout::from(&current_session);
 
@@ -353,9 +2939,11 @@ fn layout_save(
         format!("Failed to serialize layout {layout:#?} to yaml")
     })?;
 
+    let _lock = persistence.lock(StorageKind::Layout)?;
     persistence
         .save_config(StorageKind::Layout, &layout.name, yaml)
         .context("Failed to save layout config to disk")?;
+    audit::record(persistence, "layout-save", &layout.name);
 
     Ok(())
 }
@@ -365,24 +2953,22 @@ pub fn layout_create(
     layout_name: &str,
     work_dir: &str,
     session_name: Option<&str>,
+    tmux: &dyn TmuxClient,
     persistence: &Persistence,
-) -> Result<()> {
+    on_progress: &mut dyn FnMut(Progress),
+) -> Result<RestoreReport> {
     let work_dir = std::fs::canonicalize(work_dir)
         .with_context(|| format!("Invalid working directory: {work_dir}"))?
         .to_string_lossy()
         .to_string();
 
-    let yaml = persistence
-        .load_config(StorageKind::Layout, layout_name)
+    let layout: Layout = persistence
+        .load(StorageKind::Layout, layout_name)
         .context("Failed to read layout from config file")?;
 
-    let layout: Layout = serde_yaml::from_str(&yaml).with_context(|| {
-        format!("Failed to deserialize layout from yaml {yaml}")
-    })?;
-
     let name = session_name.unwrap_or(layout_name).to_string();
 
-    if is_active_session(&name)? {
+    if is_active_session(tmux, &name)? {
         anyhow::bail!("Session '{name}' already exists");
     }
 
@@ -399,18 +2985,23 @@ pub fn layout_create(
                 panes: (0..lw.pane_count)
                     .map(|i| Pane {
                         index: i.to_string(),
-                        current_command: None,
                         work_dir: work_dir.clone(),
+                        ..Default::default()
                     })
                     .collect(),
+                ..Default::default()
             })
             .collect(),
+        ..Default::default()
     };
 
-    restore_session(&session)
-        .context("Failed to create session from layout")?;
-
-    Ok(())
+    audit::record(
+        persistence,
+        "restore",
+        &format!("{} (layout {layout_name})", session.name),
+    );
+    restore_session(tmux, &session, on_progress)
+        .context("Failed to create session from layout")
 }
 
 fn layout_list(persistence: &Persistence) -> Result<()> {
@@ -426,35 +3017,35 @@ fn layout_list(persistence: &Persistence) -> Result<()> {
 }
 
 fn layout_delete(layout_name: &str, persistence: &Persistence) -> Result<()> {
+    let _lock = persistence.lock(StorageKind::Layout)?;
     let path =
-        persistence.get_config_file_path(StorageKind::Layout, layout_name)?;
+        persistence.existing_config_path(StorageKind::Layout, layout_name)?;
     fs::remove_file(path)?;
+    audit::record(persistence, "layout-delete", layout_name);
     Ok(())
 }
 
-fn layout_edit(layout_name: &str, persistence: &Persistence) -> Result<()> {
+fn layout_edit(
+    layout_name: &str,
+    persistence: &Persistence,
+    editor: &Editor,
+) -> Result<()> {
     let path =
         persistence.get_config_file_path(StorageKind::Layout, layout_name)?;
-
-    let path_str = escape(path.as_os_str().to_string_lossy());
-    let editor = std::env::var("EDITOR").unwrap_or_else(|_| "vi".to_string());
-
-    Command::new("sh")
-        .arg("-c")
-        .arg(format!("{editor} {path_str}"))
-        .status()?;
-
-    Ok(())
+    editor.open(&path)
 }
 
-fn init() -> Result<()> {
+fn setup() -> Result<()> {
+    if !io::stdin().is_terminal() {
+        anyhow::bail!("Setup asks questions, run it in a terminal");
+    }
     let home = home_dir()
         .ok_or_else(|| anyhow::anyhow!("Failed to determine HOME directory"))?;
 
     let default_sessions = home.join(".config").join(".tsessions");
     let default_layouts = home.join(".config").join(".tlayouts");
 
-    println!("Initializing tsman — press Enter to accept defaults.\n");
+    println!("Setting up tsman - press Enter to accept defaults.\n");
 
     let sessions_dir = prompt_path(
         &format!("Sessions directory [{}]: ", default_sessions.display()),
@@ -466,24 +3057,34 @@ fn init() -> Result<()> {
     )?;
 
     println!();
-    let preview = prompt_bool("Enable preview pane by default? [Y/n]: ")?;
+    let preview = prompt_bool("Enable preview pane by default? [Y/n]: ", true)?;
     let ask_for_confirmation =
-        prompt_bool("Prompt for confirmation before deleting? [Y/n]: ")?;
+        prompt_bool("Prompt for confirmation before deleting? [Y/n]: ", true)?;
     let show_key_presses =
-        prompt_bool("Show key press hints in menu? [Y/n]: ")?;
+        prompt_bool("Show key press hints in menu? [Y/n]: ", true)?;
+    let keymap = prompt_keymap()?;
 
     // Check for existing config before writing anything.
     let config_dir = home.join(".config").join("tsman");
     let config_path = config_dir.join("config.toml");
+    let backup_path = config_dir.join("config.toml.bak");
     if config_path.exists() {
-        let overwrite = prompt_bool(&format!(
-            "\nConfig already exists at {}. Overwrite? [y/N]: ",
-            config_path.display()
-        ))?;
+        let overwrite = prompt_bool(
+            &format!(
+                "\nConfig already exists at {}. Overwrite (the old one is \
+                 kept as {})? [y/N]: ",
+                config_path.display(),
+                backup_path.display()
+            ),
+            false,
+        )?;
         if !overwrite {
-            println!("Aborted.");
-            return Ok(());
+            return Err(TsmanError::Aborted)
+                .context("Kept the existing config");
         }
+        fs::copy(&config_path, &backup_path).with_context(|| {
+            format!("Failed to back up {}", config_path.display())
+        })?;
     }
 
     fs::create_dir_all(&sessions_dir).with_context(|| {
@@ -503,6 +3104,7 @@ fn init() -> Result<()> {
          preview = {preview}\n\
          ask_for_confirmation = {ask_for_confirmation}\n\
          show_key_presses = {show_key_presses}\n\
+         keymap = \"{keymap}\"\n\
          \n\
          [storage]\n\
          sessions_dir = \"{sessions_str}\"\n\
@@ -510,8 +3112,141 @@ fn init() -> Result<()> {
     );
 
     fs::write(&config_path, toml)?;
-    println!("\nDone! Config written to {}", config_path.display());
+    println!("\nConfig written to {}", config_path.display());
+
+    println!();
+    setup_completions(&home)?;
+    setup_tmux_bindings(&home)?;
+
+    println!("\nDone! Run `tsman menu` to get started.");
+    Ok(())
+}
+
+/// Asks for the menu's key bindings until given `emacs` or `vim`.
+fn prompt_keymap() -> Result<&'static str> {
+    loop {
+        match prompt_line("Key bindings, emacs or vim? [emacs]: ")?.as_str() {
+            "" | "emacs" | "e" => return Ok("emacs"),
+            "vim" | "v" => return Ok("vim"),
+            _ => println!("Please answer emacs or vim."),
+        }
+    }
+}
+
+/// Offers to write completions for the shell in `$SHELL` to where that
+/// shell looks for them.
+fn setup_completions(home: &Path) -> Result<()> {
+    let shell = std::env::var_os("SHELL").and_then(|shell| {
+        clap_complete::Shell::from_shell_path(PathBuf::from(shell))
+    });
+    let (shell, path) = match shell {
+        Some(shell @ clap_complete::Shell::Bash) => (
+            shell,
+            dirs::data_dir()
+                .unwrap_or_else(|| home.join(".local").join("share"))
+                .join("bash-completion/completions/tsman"),
+        ),
+        Some(shell @ clap_complete::Shell::Zsh) => {
+            (shell, home.join(".zfunc").join("_tsman"))
+        }
+        Some(shell @ clap_complete::Shell::Fish) => {
+            (shell, home.join(".config/fish/completions/tsman.fish"))
+        }
+        _ => {
+            println!(
+                "Couldn't tell your shell, see `tsman completions --help` \
+                 to install completions."
+            );
+            return Ok(());
+        }
+    };
+
+    if !prompt_bool(
+        &format!("Install {shell} completions to {}? [Y/n]: ", path.display()),
+        true,
+    )? {
+        return Ok(());
+    }
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    let mut file = File::create(&path)
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    write_completions(shell, &mut file);
+    println!("Completions written to {}", path.display());
+    if shell == clap_complete::Shell::Zsh {
+        println!(
+            "Add `fpath+=~/.zfunc` before `compinit` in your .zshrc if it \
+             isn't there yet."
+        );
+    }
+    Ok(())
+}
+
+/// Offers to append bindings opening the menu in a popup and saving the
+/// current session to the tmux config.
+fn setup_tmux_bindings(home: &Path) -> Result<()> {
+    // tmux reads ~/.tmux.conf first and the XDG location only without it.
+    let xdg_conf = home.join(".config/tmux/tmux.conf");
+    let path = if !home.join(".tmux.conf").exists() && xdg_conf.exists() {
+        xdg_conf
+    } else {
+        home.join(".tmux.conf")
+    };
+
+    let existing = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => {
+            return Err(e)
+                .with_context(|| format!("Failed to read {}", path.display()));
+        }
+    };
+    if existing.contains("tsman ") {
+        println!("{} already runs tsman, leaving it as is.", path.display());
+        return Ok(());
+    }
+
+    if !prompt_bool(
+        &format!("Add tsman key bindings to {}? [y/N]: ", path.display()),
+        false,
+    )? {
+        return Ok(());
+    }
+    let menu_key = match prompt_text("Prefix key to open the menu [f]: ")? {
+        key if key.is_empty() => "f".to_string(),
+        key => key,
+    };
+    let save_key = match prompt_text("Prefix key to save the session [C-s]: ")?
+    {
+        key if key.is_empty() => "C-s".to_string(),
+        key => key,
+    };
 
+    let mut bindings = String::new();
+    if !existing.is_empty() && !existing.ends_with('\n') {
+        bindings.push('\n');
+    }
+    bindings.push_str(&format!(
+        "\n# tsman: open the menu in a popup (tmux 3.2+) and save the session\n\
+         bind {menu_key} display-popup -E -w 80% -h 80% \"tsman menu\"\n\
+         bind {save_key} run-shell \"tsman save\"\n"
+    ));
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .and_then(|mut file| file.write_all(bindings.as_bytes()))
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    println!(
+        "Bindings added, run `tmux source-file {}` to load them.",
+        path.display()
+    );
     Ok(())
 }
 
@@ -533,10 +3268,26 @@ fn prompt_path(prompt: &str, default: &std::path::Path) -> Result<PathBuf> {
     Ok(PathBuf::from(trimmed))
 }
 
-fn prompt_bool(prompt: &str) -> Result<bool> {
+/// Prints `prompt` and reads a line of input, trimmed and lowercased.
+fn prompt_line(prompt: &str) -> Result<String> {
+    Ok(prompt_text(prompt)?.to_lowercase())
+}
+
+/// Prints `prompt` and reads a line of input, trimmed but otherwise as
+/// typed.
+fn prompt_text(prompt: &str) -> Result<String> {
     print!("{prompt}");
     io::stdout().flush()?;
     let mut input = String::new();
     io::stdin().read_line(&mut input)?;
-    Ok(!matches!(input.trim().to_lowercase().as_str(), "n" | "no"))
+    Ok(input.trim().to_string())
+}
+
+/// Asks a yes/no question; an empty answer means `default`.
+fn prompt_bool(prompt: &str, default: bool) -> Result<bool> {
+    Ok(match prompt_line(prompt)?.as_str() {
+        "y" | "yes" => true,
+        "n" | "no" => false,
+        _ => default,
+    })
 }
@@ -0,0 +1,89 @@
+//! Audit log - an append-only record of the actions that change sessions
+//! or configs, kept in the data dir so `tsman log` can show what happened
+//! and when.
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+
+use anyhow::{Context, Result};
+
+use crate::logging;
+use crate::persistence::Persistence;
+
+const AUDIT_FILE: &str = "audit.log";
+
+/// One recorded action.
+pub struct Entry {
+    /// UTC time, as written by [`logging::timestamp`].
+    pub time: String,
+    pub action: String,
+    /// What the action applied to, usually a session or layout name.
+    pub target: String,
+}
+
+/// Appends `action` on `target` to the audit log.
+///
+/// Never fails: a broken data dir shouldn't stop the action itself, so
+/// problems are only logged.
+pub fn record(persistence: &Persistence, action: &str, target: &str) {
+    let line = format!(
+        "{}\t{}\t{}\n",
+        logging::timestamp(),
+        action,
+        target.replace(['\t', '\n'], " ")
+    );
+
+    let result = persistence.ensure_data_dir().and_then(|dir| {
+        let path = dir.join(AUDIT_FILE);
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+        // A single write, so concurrent tsman processes can't interleave
+        // their lines.
+        file.write_all(line.as_bytes())?;
+        Ok(())
+    });
+
+    if let Err(err) = result {
+        log::warn!(
+            "Failed to record '{action} {target}' in the audit log: {err:#}"
+        );
+    }
+}
+
+impl Entry {
+    /// Returns whether the entry is about the session or layout `name`,
+    /// including renames to or from it.
+    pub fn is_about(&self, name: &str) -> bool {
+        self.target == name
+            || self.target.starts_with(&format!("{name} "))
+            || self.target.ends_with(&format!(" -> {name}"))
+    }
+}
+
+/// Reads every entry of the audit log, oldest first. Malformed lines are
+/// skipped.
+pub fn read(persistence: &Persistence) -> Result<Vec<Entry>> {
+    let path = persistence.ensure_data_dir()?.join(AUDIT_FILE);
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => {
+            return Err(err)
+                .with_context(|| format!("Failed to read {}", path.display()));
+        }
+    };
+
+    Ok(content
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(3, '\t');
+            Some(Entry {
+                time: fields.next()?.to_string(),
+                action: fields.next()?.to_string(),
+                target: fields.next()?.to_string(),
+            })
+        })
+        .collect())
+}
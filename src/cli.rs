@@ -1,6 +1,13 @@
 //! CLI argument parser - defines all commands and subcommands via `clap`.
-use crate::util::validate_session_name;
-use clap::{Parser, Subcommand};
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+
+use crate::config::{
+    GroupBy, Keymap, ListFilter, MenuTheme, PreviewPosition, SortOrder,
+};
+use crate::tmux::import::Format;
+use crate::util::{validate_session_name, validate_session_or_file};
+use clap::{Parser, Subcommand, ValueEnum};
 use clap_complete::Shell;
 
 /// Command-line argument parser for `tsman`.
@@ -25,6 +32,14 @@ Examples:
 Use `tsman <COMMAND> --help` for more details."
 )]
 pub struct Args {
+    /// Profile from the config file to use (also read from $TSMAN_PROFILE)
+    #[arg(long, global = true)]
+    pub profile: Option<String>,
+
+    /// Log more to the log file in the data dir (-v: info, -vv: debug)
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    pub verbose: u8,
+
     #[command(subcommand)]
     pub command: Commands,
 }
@@ -36,37 +51,192 @@ pub enum Commands {
         about = "Save the current session",
         long_about = "Save the currently attached tmux session. Stores the 
 <session_name>.yaml config file in $TSMAN_CONFIG_STORAGE_DIR if set, or 
-~/.config/.tsessions.",
+~/.config/.tsessions.
+
+With --to, save into a file that can define several sessions instead -
+handy for a team environment checked into a repository. --append adds the
+session to the file rather than overwriting it.
+
+With --stdout (or -o -), print the config instead of writing it anywhere,
+to inspect what would be saved or pipe it into other tools.
+
+Examples:
+ tsman save
+ tsman save --to team.yaml --append
+ tsman save --stdout > api.yaml",
         alias = "s"
     )]
     Save {
         /// Name of the session (default: name of current session)
         #[arg(value_parser = validate_session_name)]
         session_name: Option<String>,
+
+        /// Also save each pane's scrollback, printed again on restore
+        #[arg(long)]
+        with_history: bool,
+
+        /// Save into this file of several sessions instead of the sessions
+        /// directory, or print the config if it is -
+        #[arg(
+            short = 'o',
+            long,
+            value_name = "FILE",
+            conflicts_with = "with_history"
+        )]
+        to: Option<PathBuf>,
+
+        /// Print the config instead of saving it
+        #[arg(long, conflicts_with_all = ["with_history", "to", "append"])]
+        stdout: bool,
+
+        /// Add the session to the file, replacing the one with the same name,
+        /// instead of overwriting the file
+        #[arg(long, requires = "to")]
+        append: bool,
     },
 
     #[command(
         about = "Open the specified session",
-        long_about = "Restore the selected session and then attach to it.",
-        arg_required_else_help = true,
+        long_about = "Restore the selected session and then attach to it.
+
+Without <session_name>, or with --default, open the default_session of the
+config file.
+
+With --create, open the session for a project directory instead: the
+session is named by the [projects] name_template of the config file (or
+<session_name> if given) and, if it has no saved config, created with a
+single window in that directory.
+
+Given a .yaml file instead of a name, restore every session the file
+defines (a list of sessions, as written by `save --to`) and attach to the
+first. --file does the same for a file of any name, or - for standard
+input.
+
+Examples:
+ tsman open work
+ tsman open team.yaml
+ tsman open --file .tsman
+ tsman open --file - < ide.yaml
+ tsman open --default
+ tsman open --create ~/src/api
+ tsman open tools --into work  # add the windows of `tools` to `work`",
         alias = "o"
     )]
     Open {
-        /// Name of the session
-        #[arg(value_parser = validate_session_name)]
-        session_name: String,
+        /// Name of the session (default: default_session from the config),
+        /// or a .yaml file of several sessions
+        #[arg(value_parser = validate_session_or_file)]
+        session_name: Option<String>,
+
+        /// Open the default_session from the config file
+        #[arg(long, conflicts_with_all = ["session_name", "create"])]
+        default: bool,
+
+        /// Restore the sessions of this config file, or of standard input
+        /// if it is -
+        #[arg(
+            short,
+            long,
+            value_name = "FILE",
+            conflicts_with_all = ["session_name", "default", "create"]
+        )]
+        file: Option<PathBuf>,
+
+        /// Open (or create) the session for this project directory
+        #[arg(long, value_name = "DIR", conflicts_with = "dry_run")]
+        create: Option<PathBuf>,
+
+        /// Print the tmux commands that would restore the session as a shell
+        /// script instead of running them
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Snapshot a running session that no longer matches its config
+        /// before attaching (default: [snapshots] on_open)
+        #[arg(long, conflicts_with = "dry_run")]
+        snapshot: bool,
+
+        /// Keep a session whose restore had failing steps instead of rolling
+        /// it back (default: [restore] on_failure)
+        #[arg(long, conflicts_with = "dry_run")]
+        allow_partial: bool,
+
+        /// tmux client to switch to the session (e.g. /dev/pts/3), `current`
+        /// for the one tsman runs in, or `ask` to pick one
+        #[arg(long, value_name = "CLIENT", conflicts_with = "dry_run")]
+        client: Option<String>,
+
+        /// Review each command the restore would run, to run, skip or edit
+        /// it before it is sent
+        #[arg(
+            long,
+            conflicts_with_all = ["create", "dry_run", "here", "into"]
+        )]
+        confirm_commands: bool,
+
+        /// Add the session's windows to the current tmux session instead of
+        /// switching to it
+        #[arg(long, conflicts_with_all = ["create", "dry_run", "snapshot"])]
+        here: bool,
+
+        /// Add the session's windows to this running session instead of
+        /// switching to it
+        #[arg(
+            long,
+            value_name = "SESSION",
+            conflicts_with_all = ["create", "dry_run", "snapshot", "here"]
+        )]
+        into: Option<String>,
     },
 
     #[command(
         about = "Edit the specified session",
-        long_about = "Open the config file of the specified session in $EDITOR
-for manual editing.",
+        long_about = "Open the config file of the specified session in your editor
+for manual editing. With --file, open a config file anywhere on disk
+instead, such as one checked into a project repository.
+
+The editor is the `[editor] command` from the config file, else $VISUAL,
+else $EDITOR, else nano or vi.
+
+--set and --patch change the config without an editor, for scripts. --set
+assigns a value, read as YAML, to a dot-separated path of keys and list
+indices; `null` removes the key. --patch merges a YAML file (`-` for stdin) into the config:
+mappings are merged, `null` removes a key and other values replace. The
+result must still be a valid config, or nothing is written. Keys keep their
+order, but comments in the config are dropped.
+
+Examples:
+ tsman edit work --set windows.0.panes.1.current_command='npm run dev'
+ tsman edit work --set work_dir=/srv/work --set pre_window=null
+ tsman edit work --patch patch.yaml",
         alias = "e"
     )]
     Edit {
         /// Name of the session (default: name of current session)
         #[arg(value_parser = validate_session_name)]
         session_name: Option<String>,
+
+        /// Edit this config file instead of a saved session's
+        #[arg(
+            short,
+            long,
+            value_name = "FILE",
+            conflicts_with = "session_name"
+        )]
+        file: Option<PathBuf>,
+
+        /// Set a field without opening the editor (repeatable)
+        #[arg(long, value_name = "PATH=VALUE", conflicts_with = "file")]
+        set: Vec<String>,
+
+        /// Merge a YAML file into the config without opening the editor
+        #[arg(
+            long,
+            alias = "apply",
+            value_name = "FILE",
+            conflicts_with = "file"
+        )]
+        patch: Option<PathBuf>,
     },
 
     #[command(
@@ -79,19 +249,540 @@ YAML config. Must be run from inside tmux.",
         /// Name of the session (default: name of current session)
         #[arg(value_parser = validate_session_name)]
         session_name: Option<String>,
+
+        /// Keep the new session even if some of its steps failed, instead of
+        /// keeping the old one (default: [restore] on_failure)
+        #[arg(long)]
+        allow_partial: bool,
     },
 
     #[command(
         about = "Delete specified session",
         long_about = "Remove the config file of the specified session from the
-config storage directory.",
+config storage directory. A glob pattern (or a regular expression with
+--regex) deletes every saved session whose name matches, after listing them
+and asking.
+
+Examples:
+ tsman delete work             # delete `work`
+ tsman delete 'exp-*' --dry-run # list what would be deleted
+ tsman delete 'exp-*' --yes     # delete without asking
+ tsman delete --regex '^tmp[0-9]+$'",
         arg_required_else_help = true,
         alias = "d"
     )]
     Delete {
+        /// Name of the session, or a glob pattern like 'exp-*'
+        session_name: String,
+
+        /// Match the name as a regular expression instead
+        #[arg(long)]
+        regex: bool,
+
+        /// Don't ask before deleting the sessions a pattern matches
+        #[arg(long, short)]
+        yes: bool,
+
+        /// Only list the sessions that would be deleted
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    #[command(
+        about = "List saved sessions",
+        long_about = "Print the names of the saved sessions, or only of those
+matching a glob pattern (or a regular expression with --regex).
+
+--format prints a line per session with these fields filled in: {name},
+{work_dir}, {path}, {windows}, {panes}, {tags}, {description}, {pinned},
+{open_count}, {last_opened} (e.g. `3d ago`), {last_opened_unix} and {active}
+(`true` while it is running). `\\t` and `\\n` are a tab and a newline.
+`[list] format` in the config file sets the default; --plain prints only the
+names regardless.
+
+--tree prints every matching session with its windows and panes as one tree
+under the sessions directory. With --active the running sessions, and the
+windows and panes they still have, are marked with `*`.
+
+Examples:
+ tsman list           # every saved session
+ tsman list 'work-*'  # sessions starting with `work-`
+ tsman list --format '{name}\\t{windows}\\t{last_opened}'
+ tsman list --tree --active",
+        alias = "ls"
+    )]
+    List {
+        /// Glob pattern the names must match
+        pattern: Option<String>,
+
+        /// Match the pattern as a regular expression instead
+        #[arg(long, requires = "pattern")]
+        regex: bool,
+
+        /// Line to print per session, with {field} placeholders
+        #[arg(long, value_name = "TEMPLATE")]
+        format: Option<String>,
+
+        /// Print only the names, ignoring [list] format
+        #[arg(long, conflicts_with = "format")]
+        plain: bool,
+
+        /// Print the sessions with their windows and panes as one tree
+        #[arg(long, conflicts_with_all = ["format", "plain"])]
+        tree: bool,
+
+        /// Mark the running sessions, windows and panes in the tree
+        #[arg(long, requires = "tree")]
+        active: bool,
+    },
+
+    #[command(
+        about = "Run a command in a session",
+        long_about = "Open a new window in the specified session (or a new pane
+with --split) and run the command in it, or with --all-panes type it into
+every pane the session already has. The session is restored from its saved
+config first, without attaching, if it isn't running. Prints the ids of the
+panes the command was sent to.
+
+Examples:
+ tsman exec work cargo test
+ tsman exec work --split -- tail -f log/dev.log
+ tsman exec work --all-panes --shell-only source .env",
+        arg_required_else_help = true
+    )]
+    Exec {
         /// Name of the session
         #[arg(value_parser = validate_session_name)]
         session_name: String,
+
+        /// Command to run; several words are joined with spaces
+        #[arg(required = true, trailing_var_arg = true)]
+        command: Vec<String>,
+
+        /// Split the session's current window instead of opening a new one
+        #[arg(long, conflicts_with = "all_panes")]
+        split: bool,
+
+        /// Type the command into every existing pane of the session instead
+        #[arg(long)]
+        all_panes: bool,
+
+        /// With --all-panes, skip panes that aren't at a shell prompt
+        #[arg(long, requires = "all_panes")]
+        shell_only: bool,
+    },
+
+    #[command(
+        about = "Print the path of a saved config",
+        long_about = "Print the path of the specified session's config file, or
+with --dir the directory configs are stored in. Fails if the config doesn't
+exist.
+
+Examples:
+ git -C \"$(tsman path --dir)\" add \"$(tsman path work)\"
+ cat \"$(tsman path --layout dev)\"",
+        arg_required_else_help = true
+    )]
+    Path {
+        /// Name of the session (or layout with --layout)
+        #[arg(
+            value_parser = validate_session_name,
+            required_unless_present = "dir"
+        )]
+        name: Option<String>,
+
+        /// Print the storage directory instead of a config's path
+        #[arg(long, conflicts_with = "name")]
+        dir: bool,
+
+        /// Resolve a layout instead of a session
+        #[arg(long)]
+        layout: bool,
+    },
+
+    #[command(
+        about = "Kill a running session",
+        long_about = "Kill the specified running tmux session. Its saved config,
+if any, is left untouched.
+
+Examples:
+ tsman kill work                   # kill `work`
+ tsman kill work --detach-only     # detach its clients, keep it running
+ tsman kill --all-inactive-clients # kill every session nobody is attached to
+ tsman kill --unsaved              # kill every session without a saved config",
+        arg_required_else_help = true,
+        alias = "k"
+    )]
+    Kill {
+        /// Name of the session
+        #[arg(
+            value_parser = validate_session_name,
+            required_unless_present_any = ["all_inactive_clients", "unsaved"]
+        )]
+        session_name: Option<String>,
+
+        /// Kill every session that has no client attached instead
+        #[arg(long, conflicts_with_all = ["session_name", "detach_only"])]
+        all_inactive_clients: bool,
+
+        /// Kill every running session that has no saved config instead,
+        /// except the current one, after listing them and asking
+        #[arg(
+            long,
+            conflicts_with_all = ["session_name", "detach_only", "all_inactive_clients"]
+        )]
+        unsaved: bool,
+
+        /// Don't ask before killing the unsaved sessions
+        #[arg(long, short, requires = "unsaved")]
+        yes: bool,
+
+        /// Only detach the session's clients, leaving it running
+        #[arg(long)]
+        detach_only: bool,
+    },
+
+    #[command(
+        about = "Delete or archive stale session configs",
+        long_about = "Find saved sessions whose working directory no longer exists,
+and with --older-than those that haven't been opened for that many days, then
+delete them after asking about each one.
+
+Examples:
+ tsman prune                        # configs whose work_dir is gone
+ tsman prune --older-than 90 --yes  # also unused for 90 days, don't ask
+ tsman prune --archive              # move to <sessions dir>/archive instead"
+    )]
+    Prune {
+        /// Also prune sessions not opened for this many days (falls back to
+        /// the config's modification time for sessions never opened)
+        #[arg(long, value_name = "DAYS")]
+        older_than: Option<u64>,
+
+        /// Don't ask before pruning each session
+        #[arg(long, short)]
+        yes: bool,
+
+        /// Move configs to the archive subdirectory instead of deleting them
+        #[arg(long)]
+        archive: bool,
+    },
+
+    #[command(
+        about = "Remove old session snapshots",
+        long_about = "Remove the snapshots taken by `open --snapshot` and `daemon --autosave`
+that the retention policy no longer keeps: beyond the newest --keep of each
+session, or older than --max-age days. Both default to keep and max_age_days
+under [snapshots] in the config file, which are also applied each time a
+snapshot is taken.
+
+Examples:
+ tsman gc --keep 10             # the 10 newest of each session
+ tsman gc api --max-age 30      # api's snapshots from the last 30 days
+ tsman gc --dry-run             # list what the configured policy removes"
+    )]
+    Gc {
+        /// Name of the session (default: every session with snapshots)
+        #[arg(value_parser = validate_session_name)]
+        session_name: Option<String>,
+
+        /// Most snapshots to keep per session (at least 1)
+        #[arg(long, value_name = "N")]
+        keep: Option<NonZeroUsize>,
+
+        /// Remove snapshots older than this many days
+        #[arg(long, value_name = "DAYS")]
+        max_age: Option<u64>,
+
+        /// List the snapshots that would be removed instead of removing them
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    #[command(
+        about = "Compare a running session with its saved config",
+        long_about = "Show how the running session (the current one by default)
+differs from its saved config, as the lines `tsman save` would change. The
+diff is unified by default, or two columns with --side-by-side, and colored
+when printed to a terminal unless --no-color or NO_COLOR is set. With
+--quiet nothing is printed and the exit status is 6 if they differ, 0 if
+they don't.
+
+Examples:
+ tsman diff                 # the current session
+ tsman diff api --side-by-side
+ tsman diff api --quiet || tsman save api"
+    )]
+    Diff {
+        /// Name of the running session
+        #[arg(value_parser = validate_session_name)]
+        session_name: Option<String>,
+
+        /// Print the saved and running config in two columns
+        #[arg(short = 'y', long)]
+        side_by_side: bool,
+
+        /// Print without colors
+        #[arg(long)]
+        no_color: bool,
+
+        /// Print nothing, only set the exit status
+        #[arg(
+            short,
+            long,
+            conflicts_with_all = ["side_by_side", "no_color"]
+        )]
+        quiet: bool,
+    },
+
+    #[command(
+        about = "Check saved session configs",
+        long_about = "Check that the specified saved session config (or every one) can be
+read, and warn about pane commands matching the [lint] patterns - by default
+risky ones like `rm -rf`, `shutdown` or `git push --force`, which a restore
+would replay. Fails if a config can't be read, or with [lint] fail = true if
+a risky command is found.
+
+Examples:
+ tsman validate       # every saved session
+ tsman validate api   # only api"
+    )]
+    Validate {
+        /// Name of the session (default: every saved session)
+        #[arg(value_parser = validate_session_name)]
+        session_name: Option<String>,
+    },
+
+    #[command(
+        about = "Find changed configs and leftovers of interrupted restores",
+        long_about = "List the saved sessions whose config was changed outside tsman
+(by hand, or truncated by a sync tool) since tsman last wrote it, and the
+temp sessions (tsman-temp-<pid>) left running by tsman processes that were
+killed while restoring a session. Configs renamed outside tsman are found by
+their content and keep their metadata; they are listed with the copied and
+new ones. With --accept, trust the changed configs as they are now; with
+--cleanup, kill the temp sessions.
+
+Examples:
+ tsman doctor            # list problems
+ tsman doctor --accept   # trust the changed configs
+ tsman doctor --cleanup  # kill the leftovers"
+    )]
+    Doctor {
+        /// Kill the leftover temp sessions
+        #[arg(long)]
+        cleanup: bool,
+
+        /// Record the changed configs as they are now
+        #[arg(long)]
+        accept: bool,
+    },
+
+    #[command(
+        about = "Serve a JSON-RPC API for editor plugins",
+        long_about = "Listen on a unix socket for JSON-RPC 2.0 requests, one per line, and
+answer each with a response line. The methods are list, show {name},
+open {name} (restore without attaching), save {name} (save a running
+session) and rename {name, new_name}.
+
+The socket is $XDG_RUNTIME_DIR/tsman.sock, or tsman.sock in the data dir.
+
+With --autosave, also snapshot the running sessions that changed every
+MINUTES minutes. `tsman daemon install` sets the daemon up as a service.
+
+Examples:
+ tsman daemon
+ tsman daemon --socket /tmp/tsman.sock
+ tsman daemon --autosave 15
+ tsman daemon install",
+        args_conflicts_with_subcommands = true
+    )]
+    Daemon {
+        #[command(subcommand)]
+        command: Option<DaemonCommands>,
+
+        /// Path of the socket to listen on
+        #[arg(long)]
+        socket: Option<PathBuf>,
+
+        /// Snapshot the running sessions that changed every MINUTES minutes
+        #[arg(long, value_name = "MINUTES", value_parser = clap::value_parser!(u64).range(1..))]
+        autosave: Option<u64>,
+    },
+
+    #[command(
+        about = "Re-save a session's config whenever it changes",
+        long_about = "Watch a running session and save it over its config whenever its windows,
+panes or layout change, once they have stayed the same for --debounce
+seconds - handy while rearranging a workspace. Runs until the session is
+closed or Ctrl-C is pressed.
+
+A lighter alternative to `tsman daemon --autosave`, which snapshots every
+running session instead.
+
+Examples:
+ tsman watch
+ tsman watch api --debounce 5"
+    )]
+    Watch {
+        /// Name of the session (default: name of current session)
+        #[arg(value_parser = validate_session_name)]
+        session_name: Option<String>,
+
+        /// Seconds the session must stay unchanged before it is saved
+        #[arg(long, value_name = "SECONDS", default_value_t = 2)]
+        debounce: u64,
+    },
+
+    #[command(
+        about = "Rewrite paths in session configs after moving a project",
+        long_about = "Replace the path prefix --from with --to in the working
+directories and pane commands of the specified session's config, or of every
+saved session with --all. Only whole path components match, so --from ~/src
+leaves ~/src2 alone.
+
+Examples:
+ tsman remap api --from ~/work/api --to ~/src/api
+ tsman remap --all --from /home/old --to /home/new",
+        arg_required_else_help = true
+    )]
+    Remap {
+        /// Name of the session
+        #[arg(
+            value_parser = validate_session_name,
+            required_unless_present = "all"
+        )]
+        session_name: Option<String>,
+
+        /// Rewrite every saved session instead
+        #[arg(long, conflicts_with = "session_name")]
+        all: bool,
+
+        /// Old path prefix
+        #[arg(long, value_name = "PATH")]
+        from: String,
+
+        /// New path prefix
+        #[arg(long, value_name = "PATH")]
+        to: String,
+    },
+
+    #[command(
+        about = "Import a session from a file or URL",
+        long_about = "Convert a tmuxinator or tmuxp project, a tmux-resurrect save file
+or a tsman config (or a file of several, as written by `save --to`) and
+save the sessions it defines to the storage directory, under their own
+names or --name. The format is detected from the file's contents; if it
+could be more than one, tsman asks, or --format picks it.
+
+With --url, download a session config (or, with --layout, a layout) with
+curl instead and check that it parses and, like validate, for risky
+commands. Refuses to overwrite an existing config unless --force is given.
+
+Examples:
+ tsman import ~/.config/tmuxinator/blog.yml
+ tsman import ~/.local/share/tmux/resurrect/last
+ tsman import --url https://example.com/team-dev.yaml
+ tsman import --url https://example.com/ide.yaml --layout --name ide",
+        arg_required_else_help = true
+    )]
+    Import {
+        /// File to convert and import
+        #[arg(conflicts_with = "url")]
+        path: Option<PathBuf>,
+
+        /// http(s) URL of the YAML config
+        #[arg(long, required_unless_present = "path")]
+        url: Option<String>,
+
+        /// Format of the file, instead of detecting it
+        #[arg(long, value_enum, conflicts_with = "url")]
+        format: Option<ImportFormat>,
+
+        /// Save under this name instead of the one in the config
+        #[arg(long, value_parser = validate_session_name)]
+        name: Option<String>,
+
+        /// Install the config as a layout instead of a session
+        #[arg(long, requires = "url")]
+        layout: bool,
+
+        /// Overwrite an existing config with the same name
+        #[arg(long, short)]
+        force: bool,
+    },
+
+    #[command(
+        about = "List the project repositories under the configured roots",
+        long_about = "Print the path of every git repository found under the
+[projects] roots of the config file, one per line. Handy as input to a fuzzy
+finder, e.g. to create a session from a layout in the chosen project.
+
+Examples:
+ tsman projects
+ tsman layout create dev \"$(tsman projects | fzf)\""
+    )]
+    Projects,
+
+    #[command(
+        about = "Show the log of actions that changed sessions or configs",
+        long_about = "Print the audit log: every save, delete, rename, kill,
+restore and similar action tsman performed, with its UTC time, oldest first.
+
+Examples:
+ tsman log            # the last 20 actions
+ tsman log work -n 0  # every action on `work`"
+    )]
+    Log {
+        /// Only show actions on this session or layout
+        name: Option<String>,
+
+        /// Number of most recent entries to show (0 for all)
+        #[arg(long, short = 'n', default_value_t = 20)]
+        limit: usize,
+    },
+
+    #[command(
+        about = "Show usage statistics of saved sessions",
+        long_about = "Print how often and how recently each saved session was
+opened, along with its pin and tags."
+    )]
+    Stats {
+        /// Order of the listed sessions
+        #[arg(long, short, value_enum, default_value_t = SortOrder::Frecency)]
+        sort: SortOrder,
+    },
+
+    #[command(
+        about = "Show or edit a session's metadata",
+        long_about = "Manage the tags, pin and description of a saved session.
+Without any flags, prints the session's current metadata.
+
+Examples:
+ tsman meta work --tag rust --tag infra # add tags
+ tsman meta work --pin                  # always list first in the menu
+ tsman meta work --description \"Main API workspace\"",
+        arg_required_else_help = true
+    )]
+    Meta {
+        /// Name of the session
+        #[arg(value_parser = validate_session_name)]
+        session_name: String,
+        /// Add a tag (repeatable)
+        #[arg(long = "tag", value_name = "TAG")]
+        tags: Vec<String>,
+        /// Remove a tag (repeatable)
+        #[arg(long = "untag", value_name = "TAG")]
+        untags: Vec<String>,
+        /// Pin the session to the top of the menu
+        #[arg(long, conflicts_with = "unpin")]
+        pin: bool,
+        /// Unpin the session
+        #[arg(long)]
+        unpin: bool,
+        /// Set the description (an empty string clears it)
+        #[arg(long)]
+        description: Option<String>,
     },
 
     #[command(
@@ -109,6 +800,33 @@ currently active sessions.",
             help = "Prompt for confirmation before deleting a session"
         )]
         ask_for_confirmation: bool,
+        /// Only list these sessions (cycle with C-f in the menu)
+        #[arg(long, value_enum)]
+        filter: Option<ListFilter>,
+        /// Split the session list into collapsible sections (toggle with Tab)
+        #[arg(long, value_enum)]
+        group_by: Option<GroupBy>,
+        /// Draw borders and previews with ASCII instead of box-drawing
+        /// characters
+        #[arg(long)]
+        ascii: bool,
+        /// Key bindings: emacs-style or modal vim-style
+        #[arg(long, value_enum)]
+        keymap: Option<Keymap>,
+        /// Use the compact layout, without borders or preview, regardless
+        /// of the terminal size
+        #[arg(long)]
+        compact: bool,
+        /// Where to place the preview pane
+        #[arg(long, value_enum)]
+        preview_position: Option<PreviewPosition>,
+        /// Colors for a dark or light terminal background
+        #[arg(long, value_enum)]
+        theme: Option<MenuTheme>,
+        /// tmux client opened sessions are switched on (e.g. /dev/pts/3),
+        /// `current` for the one the menu runs in, or `ask` to pick one
+        #[arg(long, value_name = "CLIENT")]
+        client: Option<String>,
     },
 
     #[command(
@@ -128,13 +846,15 @@ Examples:
     },
 
     #[command(
-        about = "Initialize tsman configuration",
-        long_about = "Create default storage directories and write a \
-config file at ~/.config/tsman/config.toml. You will be prompted for \
-each setting; press Enter to accept the default.",
-        alias = "i"
+        about = "Set up tsman interactively",
+        long_about = "Guided first-run setup. Asks where to store sessions and \
+layouts, for the menu's preview and confirmation defaults and key bindings, \
+and writes ~/.config/tsman/config.toml. Then offers to install completions \
+for your shell and to add key bindings to your tmux config. Press Enter to \
+accept each default.",
+        aliases = ["init", "i"]
     )]
-    Init,
+    Setup,
 
     #[command(
         about = "Manage layout templates",
@@ -148,6 +868,34 @@ without working directories, allowing reuse across projects.",
     },
 }
 
+/// Subcommands for running the daemon as a service.
+#[derive(Debug, Subcommand)]
+pub enum DaemonCommands {
+    #[command(
+        about = "Install the daemon as a user service",
+        long_about = "Write a systemd user unit (Linux) or launchd agent (macOS) running
+`tsman daemon --autosave <MINUTES>`, then enable and start it.
+
+Examples:
+ tsman daemon install
+ tsman daemon install --autosave 5
+ tsman daemon install --no-enable  # only write the file"
+    )]
+    Install {
+        /// Snapshot the running sessions that changed every MINUTES minutes
+        #[arg(long, value_name = "MINUTES", default_value_t = 15, value_parser = clap::value_parser!(u64).range(1..))]
+        autosave: u64,
+
+        /// Path of the socket the service listens on
+        #[arg(long)]
+        socket: Option<PathBuf>,
+
+        /// Only write the service file, without enabling it
+        #[arg(long)]
+        no_enable: bool,
+    },
+}
+
 /// Subcommands for managing layout templates.
 #[derive(Debug, Subcommand)]
 pub enum LayoutCommands {
@@ -200,7 +948,7 @@ All panes will start in the specified working directory.",
 
     #[command(
         about = "Edit a layout config file",
-        long_about = "Open the config file of the specified layout in $EDITOR
+        long_about = "Open the config file of the specified layout in your editor
 for manual editing.",
         arg_required_else_help = true,
         alias = "e"
@@ -211,3 +959,25 @@ for manual editing.",
         layout_name: String,
     },
 }
+
+/// File formats `tsman import` converts.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum ImportFormat {
+    /// A tsman session config, or a list of them.
+    Tsman,
+    Tmuxinator,
+    Tmuxp,
+    /// A tmux-resurrect save file.
+    Resurrect,
+}
+
+impl From<ImportFormat> for Format {
+    fn from(format: ImportFormat) -> Self {
+        match format {
+            ImportFormat::Tsman => Format::Tsman,
+            ImportFormat::Tmuxinator => Format::Tmuxinator,
+            ImportFormat::Tmuxp => Format::Tmuxp,
+            ImportFormat::Resurrect => Format::Resurrect,
+        }
+    }
+}
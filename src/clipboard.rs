@@ -0,0 +1,56 @@
+//! System clipboard access through the OSC 52 escape sequence, which the
+//! terminal handles itself, so copying works over ssh without any helper
+//! program on the remote host.
+use std::env;
+use std::io::{self, Write};
+
+use anyhow::Result;
+
+use crate::tmux::client::{SystemTmux, TmuxClient};
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Copies `text` to the clipboard of the terminal tsman is running in.
+///
+/// Inside tmux the text goes through `set-buffer -w`, which stores it in a
+/// paste buffer and has tmux forward it to the outer terminal as OSC 52;
+/// writing the sequence directly would be swallowed unless tmux's
+/// `set-clipboard` option allows it.
+pub fn copy(text: &str) -> Result<()> {
+    if env::var_os("TMUX").is_some() {
+        let args = ["set-buffer", "-w", "--", text];
+        SystemTmux::default().run(&args)?.check(&args)?;
+        return Ok(());
+    }
+
+    let mut stdout = io::stdout();
+    write!(stdout, "\x1b]52;c;{}\x07", base64(text.as_bytes()))?;
+    stdout.flush()?;
+    Ok(())
+}
+
+/// Standard, padded base64 encoding of `data`.
+fn base64(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let group = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                let index = (group >> (18 - 6 * i)) & 0x3f;
+                out.push(BASE64_ALPHABET[index as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+
+    out
+}
@@ -1,20 +1,45 @@
 //! User configuration loaded from `~/.config/tsman/config.toml`.
 //!
 //! Precedence: CLI flag > env var > config file > default.
-use std::{fs, path::PathBuf};
+//!
+//! A `[profiles.<name>]` table mirrors the top-level sections; when a profile
+//! is selected its values are layered over the base config.
+use std::{
+    collections::HashMap, fs, num::NonZeroUsize, path::PathBuf, time::Duration,
+};
 
-use anyhow::Result;
+use anyhow::{Context, Result};
+use clap::ValueEnum;
 use dirs::home_dir;
+use regex::Regex;
 use serde::Deserialize;
 
+use crate::error::TsmanError;
+use crate::persistence::SnapshotRetention;
+use crate::tmux::client::CommandPolicy;
+use crate::tmux::interface::{OnStepFailure, RISKY_COMMANDS};
+
 const CONFIG_PATH: &str = ".config/tsman/config.toml";
+const PROFILES_KEY: &str = "profiles";
 
 /// Top-level config struct, mirroring `config.toml` sections.
 #[derive(Debug, Default, Deserialize)]
 #[serde(default)]
 pub struct Config {
+    /// Session `tsman open` opens when no name is given.
+    pub default_session: Option<String>,
     pub menu: MenuConfig,
     pub storage: StorageConfig,
+    pub tmux: TmuxConfig,
+    pub editor: EditorConfig,
+    pub projects: ProjectsConfig,
+    pub snapshots: SnapshotsConfig,
+    pub restore: RestoreConfig,
+    pub history: HistoryConfig,
+    pub lint: LintConfig,
+    pub list: ListConfig,
+    /// `[aliases]` section - short names for sessions, e.g. `w = "work-main"`.
+    pub aliases: HashMap<String, String>,
 }
 
 /// `[menu]` section - persistent UI preferences.
@@ -24,6 +49,161 @@ pub struct MenuConfig {
     pub preview: bool,
     pub ask_for_confirmation: bool,
     pub show_key_presses: bool,
+    pub sort: SortOrder,
+    pub filter: ListFilter,
+    pub group_by: GroupBy,
+    pub ascii: bool,
+    pub use_icons: bool,
+    pub preview_cmd: Option<String>,
+    /// Text of a saved session's preview, with placeholders.
+    pub preview_template: Option<String>,
+    pub keymap: Keymap,
+    pub wrap_around: bool,
+    /// Milliseconds between the menu's checks for background updates.
+    pub tick_rate_ms: Option<u64>,
+    pub compact: Compact,
+    pub preview_position: PreviewPosition,
+    /// Percent of the height the preview takes when placed at the bottom.
+    pub preview_height: Option<u16>,
+    /// Ask to save modified and unsaved running sessions when quitting.
+    pub prompt_save_on_exit: bool,
+    pub theme: MenuTheme,
+}
+
+/// How sessions are ordered in the menu and in `tsman stats`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    /// Alphabetically.
+    #[default]
+    Name,
+    /// Most recently opened first.
+    Recent,
+    /// Most frequently and recently opened first.
+    Frecency,
+}
+
+impl SortOrder {
+    pub fn label(self) -> &'static str {
+        match self {
+            SortOrder::Name => "name",
+            SortOrder::Recent => "recent",
+            SortOrder::Frecency => "frecency",
+        }
+    }
+}
+
+/// Which sessions the menu lists.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ListFilter {
+    /// Every session.
+    #[default]
+    All,
+    /// Only running sessions.
+    Active,
+    /// Only sessions with a saved config.
+    Saved,
+    /// Only running sessions without a saved config.
+    Unsaved,
+}
+
+impl ListFilter {
+    /// The filter after this one when cycling through them in the menu.
+    pub fn next(self) -> Self {
+        match self {
+            ListFilter::All => ListFilter::Active,
+            ListFilter::Active => ListFilter::Saved,
+            ListFilter::Saved => ListFilter::Unsaved,
+            ListFilter::Unsaved => ListFilter::All,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ListFilter::All => "all",
+            ListFilter::Active => "active",
+            ListFilter::Saved => "saved",
+            ListFilter::Unsaved => "unsaved",
+        }
+    }
+}
+
+/// How the menu splits the session list into sections.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum GroupBy {
+    /// A single flat list.
+    #[default]
+    None,
+    /// Active sessions, then saved ones that aren't running.
+    Status,
+    /// By each session's first tag, untagged ones last.
+    Tag,
+}
+
+impl GroupBy {
+    pub fn label(self) -> &'static str {
+        match self {
+            GroupBy::None => "none",
+            GroupBy::Status => "status",
+            GroupBy::Tag => "tag",
+        }
+    }
+}
+
+/// Key bindings of the menu's session and layout list.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Keymap {
+    /// Typing filters the list; actions are on Ctrl keys.
+    #[default]
+    Emacs,
+    /// Modal: letter keys act on the list in normal mode, `/` or `i`
+    /// switches to insert mode to type the filter.
+    Vim,
+}
+
+/// When the menu uses its compact layout, without borders or preview.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Compact {
+    /// When the terminal is smaller than 80x20, e.g. in a tmux popup.
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+/// Colors the menu is drawn with.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum MenuTheme {
+    /// Dark or light, following the terminal's background.
+    #[default]
+    Auto,
+    Dark,
+    Light,
+}
+
+/// Where the menu places the preview pane.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum PreviewPosition {
+    /// Beside the results, taking 40% of the width.
+    #[default]
+    Right,
+    /// Below the results, taking `preview_height` percent of the height.
+    Bottom,
+}
+
+impl PreviewPosition {
+    pub fn toggled(self) -> Self {
+        match self {
+            PreviewPosition::Right => PreviewPosition::Bottom,
+            PreviewPosition::Bottom => PreviewPosition::Right,
+        }
+    }
 }
 
 /// `[storage]` section - override default storage directories.
@@ -32,24 +212,288 @@ pub struct MenuConfig {
 pub struct StorageConfig {
     pub sessions_dir: Option<PathBuf>,
     pub layouts_dir: Option<PathBuf>,
+    /// Where tsman keeps its own state, e.g. the metadata database.
+    pub data_dir: Option<PathBuf>,
+}
+
+/// `[editor]` section - how configs are opened for editing.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct EditorConfig {
+    /// Editor command line, e.g. `code --wait`. Takes precedence over
+    /// `$VISUAL` and `$EDITOR`.
+    pub command: Option<String>,
+}
+
+/// `[projects]` section - where `tsman projects` and the menu's create mode
+/// look for project repositories.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct ProjectsConfig {
+    /// Directories to search, e.g. `~/src`.
+    pub roots: Vec<String>,
+    /// How many levels below a root to search (default 2).
+    pub depth: Option<usize>,
+    /// Only list projects matching one of these globs, if any are given.
+    pub include: Vec<String>,
+    /// Skip projects and directories matching any of these globs.
+    pub exclude: Vec<String>,
+    /// How sessions created for a project directory are named, e.g.
+    /// `{repo}-{branch}` (default `{dir_name}`).
+    pub name_template: Option<String>,
+}
+
+/// `[snapshots]` section - copies of live session state kept by tsman.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct SnapshotsConfig {
+    /// Snapshot a running session that no longer matches its config before
+    /// `open` attaches to it.
+    pub on_open: bool,
+    /// Most snapshots kept per session; older ones are removed. 0 is
+    /// rejected when the config is loaded, leave it unset to keep them all.
+    pub keep: Option<NonZeroUsize>,
+    /// Snapshots older than this many days are removed.
+    pub max_age_days: Option<u64>,
+}
+
+impl SnapshotsConfig {
+    pub fn retention(&self) -> SnapshotRetention {
+        SnapshotRetention {
+            keep: self.keep,
+            max_age_days: self.max_age_days,
+        }
+    }
+}
+
+/// `[restore]` section - how sessions are rebuilt from their configs.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct RestoreConfig {
+    /// Whether a failed step rolls the restore back or is only reported.
+    pub on_failure: OnStepFailure,
+}
+
+/// `[history]` section - pane scrollback saved by `save --with-history`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct HistoryConfig {
+    /// Lines of scrollback captured per pane (default 2000).
+    pub lines: Option<usize>,
+    /// Most kilobytes of history kept per pane, the newest lines first
+    /// (default 256).
+    pub max_kb: Option<usize>,
+}
+
+/// `[list]` section - output of `tsman list`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct ListConfig {
+    /// Line printed per session when `--format` isn't given.
+    pub format: Option<String>,
+}
+
+/// `[lint]` section - risky commands `validate` and `open` warn about.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct LintConfig {
+    /// Regular expressions replacing the default [`RISKY_COMMANDS`].
+    pub patterns: Option<Vec<String>>,
+    /// Refuse to restore a session with risky commands instead of warning.
+    pub fail: bool,
+}
+
+/// `[tmux]` section - tmux server settings.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct TmuxConfig {
+    /// Extra tmux socket names (`tmux -L <name>`) whose sessions are shown
+    /// in the menu alongside the default server's.
+    pub servers: Vec<String>,
+    /// Talk to tmux over a persistent control-mode (`tmux -C`) connection
+    /// in the menu instead of spawning a process per command.
+    pub control_mode: bool,
+    /// Milliseconds a tmux command may run before it is killed.
+    pub timeout_ms: Option<u64>,
+    /// Extra attempts for commands that fail because the server isn't
+    /// ready yet.
+    pub retries: Option<u32>,
+    /// Ask which client to switch when several are attached.
+    pub pick_client: bool,
+}
+
+impl LintConfig {
+    /// The configured patterns, or the defaults, compiled.
+    pub fn patterns(&self) -> Result<Vec<Regex>> {
+        match &self.patterns {
+            Some(patterns) => patterns
+                .iter()
+                .map(|p| {
+                    Regex::new(p)
+                        .with_context(|| format!("Invalid lint pattern '{p}'"))
+                })
+                .collect(),
+            None => Ok(Self::default_patterns()),
+        }
+    }
+
+    /// [`RISKY_COMMANDS`], compiled.
+    pub fn default_patterns() -> Vec<Regex> {
+        RISKY_COMMANDS
+            .iter()
+            .map(|p| Regex::new(p).expect("default patterns are valid"))
+            .collect()
+    }
+}
+
+impl HistoryConfig {
+    pub fn lines(&self) -> usize {
+        self.lines.unwrap_or(2000)
+    }
+
+    pub fn max_bytes(&self) -> usize {
+        self.max_kb.unwrap_or(256) * 1024
+    }
+}
+
+impl MenuConfig {
+    /// Tick rate of the menu, 50ms unless configured.
+    pub fn tick_rate(&self) -> Duration {
+        Duration::from_millis(self.tick_rate_ms.unwrap_or(50).max(1))
+    }
+
+    /// Height of a bottom preview in percent, 50 unless configured, kept
+    /// between 10 and 90 so the results stay visible.
+    pub fn preview_height(&self) -> u16 {
+        self.preview_height.unwrap_or(50).clamp(10, 90)
+    }
+}
+
+impl TmuxConfig {
+    /// Policy for tmux commands, with defaults for unset values.
+    pub fn command_policy(&self) -> CommandPolicy {
+        let default = CommandPolicy::default();
+        CommandPolicy {
+            timeout: self
+                .timeout_ms
+                .map_or(default.timeout, Duration::from_millis),
+            retries: self.retries.unwrap_or(default.retries),
+        }
+    }
 }
 
 impl Config {
-    /// Load config from `~/.config/tsman/config.toml`.
+    /// Returns the session `name` is an alias for, or `name` itself.
+    pub fn resolve_alias<'a>(&'a self, name: &'a str) -> &'a str {
+        self.aliases.get(name).map_or(name, String::as_str)
+    }
+
+    /// Load config from `~/.config/tsman/config.toml`, applying `profile` if
+    /// given.
     ///
-    /// Returns `Config::default()` if the file does not exist.
-    /// Returns an error only if the file exists but cannot be parsed.
-    pub fn load() -> Result<Self> {
+    /// Returns `Config::default()` if the file does not exist and no profile
+    /// is requested. Returns an error if the file can't be parsed or the
+    /// profile isn't defined.
+    pub fn load(profile: Option<&str>) -> Result<Self> {
         let path = config_path()?;
-        if !path.exists() {
-            return Ok(Self::default());
+        let mut table = if path.exists() {
+            let raw = fs::read_to_string(&path)?;
+            raw.parse::<toml::Table>().map_err(|source| {
+                TsmanError::InvalidConfig {
+                    path: path.clone(),
+                    source: source.into(),
+                }
+            })?
+        } else {
+            toml::Table::new()
+        };
+
+        let profiles = table.remove(PROFILES_KEY);
+
+        if let Some(name) = profile {
+            let overlay = profiles
+                .as_ref()
+                .and_then(|p| p.get(name))
+                .and_then(|p| p.as_table())
+                .with_context(|| {
+                    format!(
+                        "Profile '{name}' is not defined in {}",
+                        path.display()
+                    )
+                })?;
+            merge_tables(&mut table, overlay);
+            set_default_profile_dirs(&mut table, overlay, name)?;
         }
-        let raw = fs::read_to_string(&path)?;
-        let config: Self = toml::from_str(&raw)?;
+
+        let config: Self =
+            table
+                .try_into()
+                .map_err(|source| TsmanError::InvalidConfig {
+                    path,
+                    source: Box::new(source),
+                })?;
         Ok(config)
     }
 }
 
+/// Recursively layers `overlay` on top of `base`, replacing non-table values.
+fn merge_tables(base: &mut toml::Table, overlay: &toml::Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(key), value) {
+            (Some(toml::Value::Table(base_sub)), toml::Value::Table(sub)) => {
+                merge_tables(base_sub, sub)
+            }
+            _ => {
+                base.insert(key.clone(), value.clone());
+            }
+        }
+    }
+}
+
+/// Gives a profile its own storage directories (`~/.config/.tsessions-<name>`,
+/// `~/.config/.tlayouts-<name>` and `<data dir>/tsman/profiles/<name>`) unless
+/// it sets them explicitly, so profiles never share state by accident.
+fn set_default_profile_dirs(
+    table: &mut toml::Table,
+    overlay: &toml::Table,
+    name: &str,
+) -> Result<()> {
+    let home = home_dir()
+        .ok_or_else(|| anyhow::anyhow!("Failed to determine HOME directory"))?;
+    let overlay_storage = overlay.get("storage").and_then(|s| s.as_table());
+    let storage = table
+        .entry("storage")
+        .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+        .as_table_mut()
+        .context("[storage] must be a table")?;
+
+    let data = dirs::data_dir()
+        .ok_or_else(|| anyhow::anyhow!("Failed to determine data directory"))?;
+    let defaults = [
+        (
+            "sessions_dir",
+            home.join(".config").join(format!(".tsessions-{name}")),
+        ),
+        (
+            "layouts_dir",
+            home.join(".config").join(format!(".tlayouts-{name}")),
+        ),
+        ("data_dir", data.join("tsman").join("profiles").join(name)),
+    ];
+
+    for (key, dir) in defaults {
+        if overlay_storage.is_some_and(|s| s.contains_key(key)) {
+            continue;
+        }
+        storage.insert(
+            key.to_string(),
+            toml::Value::String(dir.to_string_lossy().into_owned()),
+        );
+    }
+
+    Ok(())
+}
+
 fn config_path() -> Result<PathBuf> {
     let home = home_dir()
         .ok_or_else(|| anyhow::anyhow!("Failed to determine HOME directory"))?;
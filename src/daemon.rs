@@ -0,0 +1,478 @@
+//! Daemon - serves a small JSON-RPC 2.0 API over a unix socket, one
+//! request and one response per line, so editor plugins can drive tsman
+//! without spawning it and parsing its output for every call.
+//!
+//! Methods: `list`, `show {name}`, `open {name}`, `save {name}` and
+//! `rename {name, new_name}`.
+//!
+//! It can also snapshot the running sessions periodically, and be installed
+//! as a systemd or launchd user service.
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use serde_json::{Value, json};
+
+use crate::actions;
+use crate::audit;
+use crate::error::TsmanError;
+use crate::persistence::{Persistence, StorageKind};
+use crate::tmux::client::SystemTmux;
+use crate::tmux::interface::{
+    get_session, list_active_sessions, temp_session_pid,
+};
+use crate::tmux::session::Session;
+use crate::util::validate_session_name;
+
+const SOCKET_FILE: &str = "tsman.sock";
+const SYSTEMD_UNIT: &str = "tsman.service";
+const LAUNCHD_LABEL: &str = "io.github.tecuceanugabriel.tsman";
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// Start of the range JSON-RPC leaves to the server, used for requests that
+/// were valid but failed.
+const SERVER_ERROR: i64 = -32000;
+/// A request that would replace a saved config, e.g. renaming onto a name
+/// that is taken.
+const CONFLICT: i64 = -32001;
+
+#[derive(Deserialize)]
+struct Request {
+    #[serde(default)]
+    id: Value,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Deserialize)]
+struct NameParams {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct RenameParams {
+    name: String,
+    new_name: String,
+}
+
+/// A request that couldn't be answered, with its JSON-RPC error code.
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<anyhow::Error> for RpcError {
+    fn from(err: anyhow::Error) -> Self {
+        Self::new(SERVER_ERROR, format!("{err:#}"))
+    }
+}
+
+/// `$XDG_RUNTIME_DIR/tsman.sock`, or `tsman.sock` in the data dir if there
+/// is no runtime dir.
+pub fn default_socket(persistence: &Persistence) -> Result<PathBuf> {
+    match std::env::var_os("XDG_RUNTIME_DIR").filter(|dir| !dir.is_empty()) {
+        Some(dir) => Ok(PathBuf::from(dir).join(SOCKET_FILE)),
+        None => Ok(persistence.ensure_data_dir()?.join(SOCKET_FILE)),
+    }
+}
+
+/// Listens on `socket` until killed, answering each connection on its own
+/// thread, and snapshots the running sessions every `autosave` if set.
+pub fn serve(
+    socket: &Path,
+    autosave: Option<Duration>,
+    persistence: &Persistence,
+) -> Result<()> {
+    let listener = bind(socket)?;
+    println!("Listening on {}", socket.display());
+    log::info!("Daemon listening on {}", socket.display());
+
+    if let Some(interval) = autosave {
+        let persistence = persistence.clone();
+        thread::spawn(move || autosave_every(interval, &persistence));
+    }
+
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                log::warn!("Failed to accept a connection: {err}");
+                continue;
+            }
+        };
+        let persistence = persistence.clone();
+        thread::spawn(move || {
+            if let Err(err) = handle_connection(stream, &persistence) {
+                log::warn!("Daemon connection failed: {err:#}");
+            }
+        });
+    }
+
+    Ok(())
+}
+
+/// Binds `socket`, replacing the file a killed daemon left behind but not
+/// the socket of one that is still running.
+fn bind(socket: &Path) -> Result<UnixListener> {
+    if UnixStream::connect(socket).is_ok() {
+        bail!("A daemon is already listening on {}", socket.display());
+    }
+    match fs::remove_file(socket) {
+        Err(err) if err.kind() != ErrorKind::NotFound => {
+            return Err(err).with_context(|| {
+                format!("Failed to remove stale socket {}", socket.display())
+            });
+        }
+        _ => {}
+    }
+    if let Some(dir) = socket.parent() {
+        fs::create_dir_all(dir).with_context(|| {
+            format!("Failed to create directory {}", dir.display())
+        })?;
+    }
+    UnixListener::bind(socket)
+        .with_context(|| format!("Failed to listen on {}", socket.display()))
+}
+
+fn handle_connection(
+    stream: UnixStream,
+    persistence: &Persistence,
+) -> Result<()> {
+    let mut writer = stream.try_clone()?;
+    let tmux = SystemTmux::default();
+
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = respond(&line, &tmux, persistence);
+        writeln!(writer, "{response}")?;
+        writer.flush()?;
+    }
+
+    Ok(())
+}
+
+/// The response line to the request `line`.
+fn respond(line: &str, tmux: &SystemTmux, persistence: &Persistence) -> Value {
+    let request = match serde_json::from_str::<Value>(line) {
+        Err(err) => {
+            Err((Value::Null, RpcError::new(PARSE_ERROR, err.to_string())))
+        }
+        Ok(value) => {
+            let id = value.get("id").cloned().unwrap_or(Value::Null);
+            serde_json::from_value::<Request>(value).map_err(|err| {
+                (id, RpcError::new(INVALID_REQUEST, err.to_string()))
+            })
+        }
+    };
+
+    let (id, result) = match request {
+        Ok(request) => {
+            log::debug!("Daemon request '{}'", request.method);
+            let result =
+                call(&request.method, request.params, tmux, persistence);
+            (request.id, result)
+        }
+        Err((id, err)) => (id, Err(err)),
+    };
+
+    match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(err) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": err.code, "message": err.message },
+        }),
+    }
+}
+
+fn call(
+    method: &str,
+    params: Value,
+    tmux: &SystemTmux,
+    persistence: &Persistence,
+) -> Result<Value, RpcError> {
+    match method {
+        "list" => Ok(list(tmux, persistence)?),
+        "show" => {
+            let name = parse_name(params)?;
+            let session: Session = persistence
+                .load_session(&name)
+                .with_context(|| format!("Failed to read session '{name}'"))?;
+            Ok(json!(session))
+        }
+        "open" => {
+            let name = parse_name(params)?;
+            let report = actions::restore_detached(&name, tmux, persistence)?;
+            Ok(json!({
+                "restored": report.is_some(),
+                "problems": report
+                    .iter()
+                    .flat_map(|report| report.problems())
+                    .collect::<Vec<_>>(),
+            }))
+        }
+        "save" => {
+            let name = parse_name(params)?;
+            actions::save_target(&name, tmux, persistence)?;
+            Ok(Value::Null)
+        }
+        "rename" => {
+            let RenameParams { name, new_name } = parse_params(params)?;
+            let name = session_name(name)?;
+            let new_name = session_name(new_name)?;
+            actions::rename(
+                persistence,
+                StorageKind::Session,
+                &name,
+                &new_name,
+            )
+            .map_err(|err| {
+                match err.downcast_ref::<TsmanError>() {
+                    Some(TsmanError::ConfigExists { .. }) => {
+                        RpcError::new(CONFLICT, format!("{err:#}"))
+                    }
+                    _ => err.into(),
+                }
+            })?;
+            Ok(Value::Null)
+        }
+        _ => Err(RpcError::new(
+            METHOD_NOT_FOUND,
+            format!("Unknown method '{method}'"),
+        )),
+    }
+}
+
+fn parse_params<T: DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+    serde_json::from_value(params)
+        .map_err(|err| RpcError::new(INVALID_PARAMS, err.to_string()))
+}
+
+/// The `name` of [`NameParams`], checked like [`session_name`].
+fn parse_name(params: Value) -> Result<String, RpcError> {
+    let NameParams { name } = parse_params(params)?;
+    session_name(name)
+}
+
+/// `name` if it is a valid session name. Names are joined onto the storage
+/// directory, so anything else - like `../x` - is refused before it can
+/// reach a file outside it.
+fn session_name(name: String) -> Result<String, RpcError> {
+    validate_session_name(&name)
+        .map_err(|err| RpcError::new(INVALID_PARAMS, err.to_string()))
+}
+
+/// Every saved or running session, by name, with whether it is either.
+fn list(tmux: &SystemTmux, persistence: &Persistence) -> Result<Value> {
+    let saved: BTreeSet<String> = persistence
+        .list_saved_configs(StorageKind::Session)?
+        .into_iter()
+        .collect();
+    let active: BTreeSet<String> =
+        list_active_sessions(tmux)?.into_iter().collect();
+
+    Ok(saved
+        .union(&active)
+        .map(|name| {
+            json!({
+                "name": name,
+                "saved": saved.contains(name),
+                "active": active.contains(name),
+            })
+        })
+        .collect())
+}
+
+fn autosave_every(interval: Duration, persistence: &Persistence) {
+    let tmux = SystemTmux::default();
+    let mut last_saved = HashMap::new();
+    loop {
+        thread::sleep(interval);
+        if let Err(err) = snapshot_changed(&tmux, persistence, &mut last_saved)
+        {
+            log::warn!("Autosave failed: {err:#}");
+        }
+    }
+}
+
+/// Snapshots each running session whose state isn't the one in
+/// `last_saved`, which maps session names to their last snapshot.
+fn snapshot_changed(
+    tmux: &SystemTmux,
+    persistence: &Persistence,
+    last_saved: &mut HashMap<String, String>,
+) -> Result<()> {
+    let active = list_active_sessions(tmux)?;
+    last_saved.retain(|name, _| active.contains(name));
+
+    for name in active {
+        if temp_session_pid(&name).is_some() {
+            continue;
+        }
+        let yaml = serde_yaml::to_string(&get_session(tmux, Some(&name))?)?;
+        if last_saved.get(&name) == Some(&yaml) {
+            continue;
+        }
+        persistence.save_snapshot(&name, yaml.clone())?;
+        audit::record(persistence, "autosave", &name);
+        last_saved.insert(name, yaml);
+    }
+
+    Ok(())
+}
+
+/// Writes a user service running this binary as `tsman daemon --autosave
+/// <autosave>` - a systemd unit, or a launchd agent on macOS - and, with
+/// `enable`, enables and starts it. Returns the path of the service file.
+pub fn install(
+    autosave: u64,
+    socket: Option<&Path>,
+    profile: Option<&str>,
+    enable: bool,
+) -> Result<PathBuf> {
+    let exe = std::env::current_exe()
+        .context("Failed to find the tsman executable")?;
+    let mut args = vec![
+        exe.to_string_lossy().into_owned(),
+        "daemon".to_string(),
+        "--autosave".to_string(),
+        autosave.to_string(),
+    ];
+    if let Some(socket) = socket {
+        args.extend(["--socket".to_string(), socket.display().to_string()]);
+    }
+    if let Some(profile) = profile {
+        args.extend(["--profile".to_string(), profile.to_string()]);
+    }
+
+    let home = dirs::home_dir().context("Failed to find the home directory")?;
+    let (path, contents, enable_commands) = if cfg!(target_os = "macos") {
+        let path = home
+            .join("Library/LaunchAgents")
+            .join(format!("{LAUNCHD_LABEL}.plist"));
+        let load = ["launchctl", "load", "-w", &path.display().to_string()]
+            .map(String::from);
+        (path, launchd_agent(&args), vec![load.to_vec()])
+    } else {
+        let path = home.join(".config/systemd/user").join(SYSTEMD_UNIT);
+        let reload = ["systemctl", "--user", "daemon-reload"].map(String::from);
+        let enable = ["systemctl", "--user", "enable", "--now", SYSTEMD_UNIT]
+            .map(String::from);
+        (
+            path,
+            systemd_unit(&args),
+            vec![reload.to_vec(), enable.to_vec()],
+        )
+    };
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).with_context(|| {
+            format!("Failed to create directory {}", dir.display())
+        })?;
+    }
+    fs::write(&path, contents)
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    log::info!("Wrote daemon service {}", path.display());
+
+    if enable {
+        for command in enable_commands {
+            let status = Command::new(&command[0])
+                .args(&command[1..])
+                .status()
+                .with_context(|| format!("Failed to run {}", command[0]))?;
+            if !status.success() {
+                bail!("'{}' failed with {status}", command.join(" "));
+            }
+        }
+    }
+
+    Ok(path)
+}
+
+fn systemd_unit(args: &[String]) -> String {
+    // systemd splits ExecStart on whitespace unless an argument is quoted,
+    // and expands `%` specifiers everywhere.
+    let exec_start = args
+        .iter()
+        .map(|arg| {
+            let arg = arg.replace('%', "%%");
+            if arg.contains(|c: char| c.is_whitespace() || c == '"') {
+                format!(
+                    "\"{}\"",
+                    arg.replace('\\', "\\\\").replace('"', "\\\"")
+                )
+            } else {
+                arg
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    format!(
+        "[Unit]
+Description=tsman daemon - session autosave and editor API
+
+[Service]
+ExecStart={exec_start}
+Restart=on-failure
+
+[Install]
+WantedBy=default.target
+"
+    )
+}
+
+fn launchd_agent(args: &[String]) -> String {
+    let escape = |s: &str| {
+        s.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+    };
+    let program_arguments: String = args
+        .iter()
+        .map(|arg| format!("        <string>{}</string>\n", escape(arg)))
+        .collect();
+
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>{LAUNCHD_LABEL}</string>
+    <key>ProgramArguments</key>
+    <array>
+{program_arguments}    </array>
+    <key>RunAtLoad</key>
+    <true/>
+    <key>KeepAlive</key>
+    <true/>
+</dict>
+</plist>
+"#
+    )
+}
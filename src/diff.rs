@@ -0,0 +1,194 @@
+//! Line diffs of session configs, rendered unified or side by side.
+
+/// A line of a diff between an old and a new text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Line<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const CYAN: &str = "\x1b[36m";
+const RESET: &str = "\x1b[0m";
+
+/// Lines of unchanged context around each change in [`unified`] output.
+const CONTEXT: usize = 3;
+
+/// Diffs `old` against `new` line by line, by their longest common
+/// subsequence. Removed lines come before the added ones replacing them.
+pub fn diff_lines<'a>(old: &'a str, new: &'a str) -> Vec<Line<'a>> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    // common[i][j] is the length of the longest common subsequence of
+    // old[i..] and new[j..]. Configs are short, so quadratic is fine.
+    let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            lines.push(Line::Same(old[i]));
+            i += 1;
+            j += 1;
+        } else if j == new.len()
+            || (i < old.len() && common[i + 1][j] >= common[i][j + 1])
+        {
+            lines.push(Line::Removed(old[i]));
+            i += 1;
+        } else {
+            lines.push(Line::Added(new[j]));
+            j += 1;
+        }
+    }
+    lines
+}
+
+/// Whether `lines` has any change.
+pub fn has_changes(lines: &[Line]) -> bool {
+    lines.iter().any(|line| !matches!(line, Line::Same(_)))
+}
+
+/// Renders `lines` as a unified diff of `old_label` and `new_label`, with
+/// a few lines of context around each hunk. Empty if nothing changed.
+pub fn unified(
+    lines: &[Line],
+    old_label: &str,
+    new_label: &str,
+    color: bool,
+) -> String {
+    if !has_changes(lines) {
+        return String::new();
+    }
+    let paint = |code: &str, text: String| {
+        if color {
+            format!("{code}{text}{RESET}\n")
+        } else {
+            format!("{text}\n")
+        }
+    };
+
+    let mut out = paint(RED, format!("--- {old_label}"));
+    out += &paint(GREEN, format!("+++ {new_label}"));
+
+    let changed: Vec<usize> = (0..lines.len())
+        .filter(|&idx| !matches!(lines[idx], Line::Same(_)))
+        .collect();
+    let mut idx = 0;
+    while idx < changed.len() {
+        // Grow the hunk while the next change is within its context.
+        let start = changed[idx].saturating_sub(CONTEXT);
+        let mut end = changed[idx];
+        while idx < changed.len() && changed[idx] <= end + 2 * CONTEXT {
+            end = changed[idx];
+            idx += 1;
+        }
+        let end = (end + CONTEXT + 1).min(lines.len());
+
+        let (old_start, new_start) = line_numbers(&lines[..start]);
+        let (old_len, new_len) = line_numbers(&lines[start..end]);
+        out += &paint(
+            CYAN,
+            format!(
+                "@@ -{},{old_len} +{},{new_len} @@",
+                old_start + 1,
+                new_start + 1
+            ),
+        );
+        for line in &lines[start..end] {
+            out += &match line {
+                Line::Same(text) => format!(" {text}\n"),
+                Line::Removed(text) => paint(RED, format!("-{text}")),
+                Line::Added(text) => paint(GREEN, format!("+{text}")),
+            };
+        }
+    }
+    out
+}
+
+/// Renders `lines` as two columns fitting `width`, old on the left and new
+/// on the right, marked like `diff -y`: `|` for a changed line, `<` for a
+/// removed one and `>` for an added one. Empty if nothing changed.
+pub fn side_by_side(lines: &[Line], width: usize, color: bool) -> String {
+    if !has_changes(lines) {
+        return String::new();
+    }
+    let column = width.saturating_sub(3) / 2;
+
+    let mut out = String::new();
+    let mut idx = 0;
+    while idx < lines.len() {
+        if let Line::Same(text) = lines[idx] {
+            out += &row(text, ' ', text, column, "");
+            idx += 1;
+            continue;
+        }
+        // Pair a run of removed lines with the added lines after it.
+        let removed: Vec<&str> = lines[idx..]
+            .iter()
+            .map_while(|line| match line {
+                Line::Removed(text) => Some(*text),
+                _ => None,
+            })
+            .collect();
+        idx += removed.len();
+        let added: Vec<&str> = lines[idx..]
+            .iter()
+            .map_while(|line| match line {
+                Line::Added(text) => Some(*text),
+                _ => None,
+            })
+            .collect();
+        idx += added.len();
+
+        for n in 0..removed.len().max(added.len()) {
+            let (old, new) = (removed.get(n), added.get(n));
+            let (mark, code) = match (old, new) {
+                (Some(_), Some(_)) => ('|', CYAN),
+                (Some(_), None) => ('<', RED),
+                _ => ('>', GREEN),
+            };
+            out += &row(
+                old.unwrap_or(&""),
+                mark,
+                new.unwrap_or(&""),
+                column,
+                if color { code } else { "" },
+            );
+        }
+    }
+    out
+}
+
+/// Lines of the old and new text in `lines`.
+fn line_numbers(lines: &[Line]) -> (usize, usize) {
+    lines.iter().fold((0, 0), |(old, new), line| match line {
+        Line::Same(_) => (old + 1, new + 1),
+        Line::Removed(_) => (old + 1, new),
+        Line::Added(_) => (old, new + 1),
+    })
+}
+
+/// A line of [`side_by_side`] output, each side cut to `column` chars.
+fn row(old: &str, mark: char, new: &str, column: usize, code: &str) -> String {
+    let cut = |text: &str| text.chars().take(column).collect::<String>();
+    let old = format!("{:column$}", cut(old));
+    let line = format!("{old} {mark} {}", cut(new));
+    let line = line.trim_end();
+    if code.is_empty() {
+        format!("{line}\n")
+    } else {
+        format!("{code}{line}{RESET}\n")
+    }
+}
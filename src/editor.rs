@@ -0,0 +1,137 @@
+//! Editor used to open configs - resolved from the config file, `$VISUAL`,
+//! `$EDITOR` and finally whichever of the fallbacks is installed.
+use std::env;
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::{Context, Result, bail};
+
+const ENV_VISUAL: &str = "VISUAL";
+const ENV_EDITOR: &str = "EDITOR";
+
+/// Editors tried in order when none is configured.
+const FALLBACK_EDITORS: &[&str] = &["nano", "vi"];
+
+/// A command line that edits the file appended to it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Editor {
+    command: String,
+}
+
+impl Default for Editor {
+    fn default() -> Self {
+        Self::resolve(None)
+    }
+}
+
+impl Editor {
+    /// Picks the editor: `configured` (the `[editor] command` setting), then
+    /// `$VISUAL`, then `$EDITOR`, then the first fallback found on `PATH`.
+    /// Empty values count as unset.
+    pub fn resolve(configured: Option<&str>) -> Self {
+        let from_env = |var| env::var(var).ok();
+        let command = configured
+            .map(str::to_string)
+            .into_iter()
+            .chain(
+                [from_env(ENV_VISUAL), from_env(ENV_EDITOR)]
+                    .into_iter()
+                    .flatten(),
+            )
+            .find(|cmd| !cmd.trim().is_empty())
+            .unwrap_or_else(|| {
+                FALLBACK_EDITORS
+                    .iter()
+                    .find(|editor| on_path(editor))
+                    .unwrap_or(&"vi")
+                    .to_string()
+            });
+
+        Self { command }
+    }
+
+    /// Opens `path` in the editor and waits for it to exit.
+    ///
+    /// The command is split into words like a shell would (quotes and
+    /// backslashes are honoured) but never run through one, so the path is
+    /// passed as a single argument whatever characters it contains.
+    pub fn open(&self, path: &Path) -> Result<()> {
+        let words = split_words(&self.command).with_context(|| {
+            format!("Failed to parse editor command '{}'", self.command)
+        })?;
+        let Some((program, args)) = words.split_first() else {
+            bail!("Editor command is empty");
+        };
+
+        log::debug!("Editing {} with '{}'", path.display(), self.command);
+        let status = Command::new(program)
+            .args(args)
+            .arg(path)
+            .status()
+            .with_context(|| format!("Failed to run editor '{program}'"))?;
+
+        if !status.success() {
+            bail!("Editor '{}' exited with {status}", self.command);
+        }
+        Ok(())
+    }
+}
+
+/// Whether an executable named `name` is in one of the `PATH` directories.
+fn on_path(name: &str) -> bool {
+    env::var_os("PATH").is_some_and(|path| {
+        env::split_paths(&path).any(|dir| dir.join(name).is_file())
+    })
+}
+
+/// Splits `command` into words using POSIX shell quoting rules: whitespace
+/// separates words, single quotes are literal, double quotes allow `\"`,
+/// `\\`, `` \` `` and `\$` escapes, and a backslash elsewhere escapes the
+/// next character.
+fn split_words(command: &str) -> Result<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = command.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => words.extend(word.take()),
+            '\'' => {
+                let word = word.get_or_insert_default();
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => bail!("unterminated single quote"),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_default();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\' | '`' | '$')) => word.push(c),
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => bail!("unterminated double quote"),
+                        },
+                        Some(c) => word.push(c),
+                        None => bail!("unterminated double quote"),
+                    }
+                }
+            }
+            '\\' => match chars.next() {
+                Some(c) => word.get_or_insert_default().push(c),
+                None => bail!("trailing backslash"),
+            },
+            c => word.get_or_insert_default().push(c),
+        }
+    }
+    words.extend(word);
+
+    Ok(words)
+}
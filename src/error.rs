@@ -0,0 +1,190 @@
+//! Error type - the failures tsman reports, typed so callers can match on
+//! them and so each kind maps to its own process exit code.
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+/// Result of tsman's library operations.
+pub type Result<T, E = TsmanError> = std::result::Result<T, E>;
+
+/// Everything that can go wrong in tsman's library layer.
+#[derive(Debug)]
+pub enum TsmanError {
+    /// No running tmux session has this name.
+    SessionNotFound(String),
+    /// No saved config of this kind (`session`, `layout`) has this name.
+    ConfigNotFound {
+        kind: &'static str,
+        name: String,
+    },
+    /// A saved config of this kind already has this name.
+    ConfigExists {
+        kind: &'static str,
+        name: String,
+    },
+    /// The command needs to run inside tmux.
+    NotInTmux,
+    /// The `tmux` binary couldn't be run or didn't answer in time.
+    TmuxUnavailable {
+        cmd: String,
+        source: io::Error,
+    },
+    /// tmux ran the command but rejected it.
+    TmuxFailed {
+        cmd: String,
+        stderr: String,
+    },
+    /// tmux printed something tsman couldn't make sense of.
+    UnexpectedOutput {
+        cmd: String,
+        output: String,
+    },
+    /// A control-mode client couldn't be set up or was lost.
+    ControlClient(String),
+    /// A window layout string couldn't be parsed.
+    InvalidLayout {
+        layout: String,
+        reason: String,
+    },
+    /// A config file couldn't be parsed.
+    InvalidConfig {
+        path: PathBuf,
+        source: Box<dyn Error + Send + Sync>,
+    },
+    /// A `--set` assignment or patch couldn't be applied to a config.
+    InvalidPatch(String),
+    /// Steps of a restore failed, so the half-built session was killed.
+    RestoreFailed {
+        session: String,
+        failed_steps: Vec<String>,
+    },
+    /// The session replays risky commands and `[lint] fail` is set.
+    RiskyCommands {
+        session: String,
+        commands: Vec<String>,
+    },
+    /// The user declined a prompt.
+    Aborted,
+    /// `tsman diff --quiet` found the running session differs from its
+    /// saved config. Reported only through the exit status.
+    SessionDiffers(String),
+    Io(io::Error),
+}
+
+impl TsmanError {
+    /// Process exit status reported for this error.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            TsmanError::SessionNotFound(_)
+            | TsmanError::ConfigNotFound { .. } => 2,
+            TsmanError::TmuxUnavailable { .. } => 3,
+            TsmanError::InvalidConfig { .. }
+            | TsmanError::InvalidLayout { .. } => 4,
+            TsmanError::Aborted => 5,
+            TsmanError::SessionDiffers(_) => 6,
+            _ => 1,
+        }
+    }
+}
+
+/// Exit status for an error chain: that of the first [`TsmanError`] in it,
+/// or 1 if there is none.
+pub fn exit_code(err: &anyhow::Error) -> u8 {
+    err.chain()
+        .find_map(|cause| cause.downcast_ref::<TsmanError>())
+        .map_or(1, TsmanError::exit_code)
+}
+
+/// Whether the error chain should end tsman silently, with only its exit
+/// status to tell it apart.
+pub fn is_silent(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        matches!(
+            cause.downcast_ref::<TsmanError>(),
+            Some(TsmanError::SessionDiffers(_))
+        )
+    })
+}
+
+impl fmt::Display for TsmanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TsmanError::SessionNotFound(name) => {
+                write!(f, "Session '{name}' not found")
+            }
+            TsmanError::ConfigNotFound { kind, name } => {
+                write!(f, "No saved {kind} named '{name}'")
+            }
+            TsmanError::ConfigExists { kind, name } => {
+                write!(f, "A saved {kind} named '{name}' already exists")
+            }
+            TsmanError::NotInTmux => write!(f, "Not inside a tmux session"),
+            TsmanError::TmuxUnavailable { cmd, .. } => {
+                write!(f, "Failed to execute '{cmd}'")
+            }
+            TsmanError::TmuxFailed { cmd, stderr } if stderr.is_empty() => {
+                write!(f, "'{cmd}' failed")
+            }
+            TsmanError::TmuxFailed { cmd, stderr } => {
+                write!(f, "'{cmd}' failed: {}", stderr.trim_end())
+            }
+            TsmanError::UnexpectedOutput { cmd, output } => {
+                write!(f, "Unexpected output from '{cmd}': {output:?}")
+            }
+            TsmanError::ControlClient(reason) => {
+                write!(f, "Control-mode client: {reason}")
+            }
+            TsmanError::InvalidLayout { layout, reason } => {
+                write!(f, "Invalid layout '{layout}': {reason}")
+            }
+            TsmanError::InvalidConfig { path, .. } => {
+                write!(f, "Invalid config in {}", path.display())
+            }
+            TsmanError::InvalidPatch(reason) => f.write_str(reason),
+            TsmanError::RestoreFailed {
+                session,
+                failed_steps,
+            } => {
+                write!(f, "Restoring '{session}' failed and was rolled back")?;
+                for step in failed_steps {
+                    write!(f, "\n  - {step}")?;
+                }
+                Ok(())
+            }
+            TsmanError::RiskyCommands { session, commands } => {
+                write!(
+                    f,
+                    "Refusing to restore '{session}', it replays risky \
+                     commands ([lint] fail is set)"
+                )?;
+                for command in commands {
+                    write!(f, "\n  - {command}")?;
+                }
+                Ok(())
+            }
+            TsmanError::Aborted => write!(f, "Aborted"),
+            TsmanError::SessionDiffers(session) => {
+                write!(f, "'{session}' differs from its saved config")
+            }
+            TsmanError::Io(err) => err.fmt(f),
+        }
+    }
+}
+
+impl Error for TsmanError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            TsmanError::TmuxUnavailable { source, .. } => Some(source),
+            TsmanError::InvalidConfig { source, .. } => Some(source.as_ref()),
+            TsmanError::Io(err) => err.source(),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for TsmanError {
+    fn from(err: io::Error) -> Self {
+        TsmanError::Io(err)
+    }
+}
@@ -0,0 +1,73 @@
+//! Shell-style globs - `*`, `?`, `[abc]` and `[!abc]` - compiled into
+//! regexes, for session name patterns and the `[projects]` filters.
+use regex::Regex;
+
+/// Returns whether `pattern` contains glob wildcards: `*`, `?` or `[`.
+pub fn is_glob(pattern: &str) -> bool {
+    pattern.contains(['*', '?', '['])
+}
+
+/// Compiles `glob` into a regex matching whole names. Everything but the
+/// wildcards matches itself, regex metacharacters included.
+pub fn to_regex(glob: &str) -> Result<Regex, regex::Error> {
+    compile(glob, false)
+}
+
+/// Like [`to_regex`], but for `/`-separated paths: `*` and `?` don't match
+/// a `/`, and `**` matches across them.
+pub fn path_to_regex(glob: &str) -> Result<Regex, regex::Error> {
+    compile(glob, true)
+}
+
+fn compile(glob: &str, paths: bool) -> Result<Regex, regex::Error> {
+    let any = if paths { "[^/]" } else { "." };
+    let mut re = String::from("^");
+    let mut rest = glob;
+    while let Some(c) = rest.chars().next() {
+        rest = &rest[c.len_utf8()..];
+        match c {
+            '*' if paths && rest.starts_with('*') => {
+                rest = &rest[1..];
+                re += ".*";
+            }
+            '*' => {
+                re += any;
+                re.push('*');
+            }
+            '?' => re += any,
+            '[' => match bracket(rest) {
+                Some((class, after)) => {
+                    re += &class;
+                    rest = after;
+                }
+                // Never closed, so an ordinary character as in the shell.
+                None => re += r"\[",
+            },
+            c => re += &regex::escape(c.encode_utf8(&mut [0; 4])),
+        }
+    }
+    re.push('$');
+    Regex::new(&re)
+}
+
+/// The regex class for the bracket expression whose `[` comes right before
+/// `rest`, and what follows its `]`, or `None` if it isn't closed. A `]`
+/// first in the set is part of it, as in the shell.
+fn bracket(rest: &str) -> Option<(String, &str)> {
+    let (negated, set) = match rest.strip_prefix('!') {
+        Some(set) => (true, set),
+        None => (false, rest),
+    };
+    let (end, _) = set.char_indices().skip(1).find(|&(_, c)| c == ']')?;
+
+    let mut class = String::from(if negated { "[^" } else { "[" });
+    for c in set[..end].chars() {
+        // Special inside a regex class, but not inside a glob's.
+        if matches!(c, '\\' | '[' | ']' | '^' | '&' | '~') {
+            class.push('\\');
+        }
+        class.push(c);
+    }
+    class.push(']');
+    Some((class, &set[end + 1..]))
+}
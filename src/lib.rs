@@ -1 +1,7 @@
+pub mod diff;
+pub mod error;
+pub mod glob;
+pub mod patch;
+#[cfg(feature = "test-harness")]
+pub mod test_support;
 pub mod tmux;
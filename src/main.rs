@@ -1,18 +1,47 @@
 //! Main entry point - parses CLI arguments and delegates to [`actions::handle`].
 mod actions;
+mod audit;
 mod cli;
+mod clipboard;
 mod config;
+mod daemon;
+mod diff;
+mod editor;
+mod error;
+mod glob;
+mod logging;
 mod menu;
+mod metadata;
+mod naming;
+mod patch;
 mod persistence;
+mod projects;
+mod signals;
+mod template;
 mod terminal_utils;
 mod tmux;
 mod util;
+mod watch;
 
-use anyhow::{Context, Result};
+use std::process::ExitCode;
+
+use anyhow::Context;
 use clap::Parser;
 
-fn main() -> Result<()> {
+fn main() -> ExitCode {
     let args = cli::Args::parse();
-    actions::handle(args).context("Failed to execute command")?;
-    Ok(())
+    let result = signals::install().and_then(|()| {
+        actions::handle(args).context("Failed to execute command")
+    });
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            if !error::is_silent(&err) {
+                log::error!("{err:#}");
+                eprintln!("Error: {err:?}");
+            }
+            ExitCode::from(error::exit_code(&err))
+        }
+    }
 }
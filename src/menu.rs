@@ -1,5 +1,6 @@
 //! Interactive TUI menu for managing sessions and layouts.
-use std::time::Duration;
+use std::collections::HashMap;
+use std::sync::mpsc::Receiver;
 
 use crossterm::event::{self};
 
@@ -10,17 +11,23 @@ use anyhow::Result;
 pub mod action;
 pub mod action_dispatcher;
 pub mod event_handler;
+pub mod history;
 pub mod item;
 pub mod items_state;
+pub mod preview;
 pub mod renderer;
 pub mod state;
+pub mod theme;
 pub mod ui_flags;
 
+use crate::config::ProjectsConfig;
+use crate::editor::Editor;
 use crate::menu::action_dispatcher::*;
 use crate::menu::event_handler::*;
 use crate::menu::item::MenuItem;
 use crate::menu::renderer::*;
 use crate::menu::state::MenuState;
+use crate::tmux::control::TmuxEvent;
 
 /// Top-level menu that owns state, renderer, event handler, and action dispatcher.
 pub struct Menu<'a> {
@@ -32,10 +39,13 @@ pub struct Menu<'a> {
 
 impl<'a> Menu<'a> {
     /// Creates a new [`Menu`] with the given items and configuration.
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         items: Vec<MenuItem>,
         ui_flags: crate::menu::ui_flags::UiFlags,
         current_session: Option<&str>,
+        tmux: Box<dyn crate::tmux::client::TmuxClient>,
+        servers: Vec<Box<dyn crate::tmux::client::TmuxClient>>,
         persistence: crate::persistence::Persistence,
         renderer: Box<dyn MenuRenderer>,
         event_handler: Box<dyn EventHandler>,
@@ -46,6 +56,8 @@ impl<'a> Menu<'a> {
                 items,
                 ui_flags,
                 current_session,
+                tmux,
+                servers,
                 persistence,
             ),
             renderer,
@@ -54,13 +66,49 @@ impl<'a> Menu<'a> {
         }
     }
 
-    /// Runs the render/event loop until the user exits.
+    /// Keeps the session list in sync with tmux notifications from `events`.
+    pub fn with_tmux_events(mut self, events: Receiver<TmuxEvent>) -> Self {
+        self.state.tmux_events = Some(events);
+        self
+    }
+
+    /// Opens configs in `editor` instead of the default one.
+    pub fn with_editor(mut self, editor: Editor) -> Self {
+        self.state.editor = editor;
+        self
+    }
+
+    /// Offers the repositories under the `projects` roots as working dirs
+    /// in create mode.
+    pub fn with_projects(mut self, projects: ProjectsConfig) -> Self {
+        self.state.projects = projects;
+        self
+    }
+
+    /// Lets the filter find sessions by their `[aliases]` short names.
+    pub fn with_aliases(mut self, aliases: HashMap<String, String>) -> Self {
+        self.state.items.set_aliases(aliases);
+        self
+    }
+
+    /// Opens with `message` in the info popup.
+    pub fn with_info(mut self, message: String) -> Self {
+        self.state.show_info(message);
+        self
+    }
+
+    /// Runs the render/event loop until the user exits. The menu is only
+    /// redrawn after an event or a change in state, checked every tick.
     pub fn run(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+        let mut redraw = true;
         while !self.state.should_exit {
-            terminal
-                .draw(|frame| self.renderer.draw(frame, &mut self.state))?;
+            if redraw {
+                terminal
+                    .draw(|frame| self.renderer.draw(frame, &mut self.state))?;
+                redraw = false;
+            }
 
-            if event::poll(Duration::from_millis(50))? {
+            if event::poll(self.state.ui_flags.tick_rate)? {
                 let event = event::read()?;
                 let (action, key_label) =
                     self.event_handler.handle_event(event, &self.state);
@@ -72,7 +120,19 @@ impl<'a> Menu<'a> {
                     &mut self.state,
                     terminal,
                 )?;
+                redraw = true;
             }
+
+            redraw |= self.state.handle_tmux_events();
+            redraw |= self.state.poll_preview();
+            redraw |= self.state.expire_last_key();
+        }
+
+        self.state.remember_filter();
+        if let Err(err) =
+            self.state.filter_history.save(&self.state.persistence)
+        {
+            log::warn!("Failed to save the filter history: {err:#}");
         }
 
         Ok(())
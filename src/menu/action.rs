@@ -7,27 +7,70 @@ pub enum MenuAction {
     Save,
     Rename,
     Kill,
+    /// Kill every running session without a saved config.
+    KillUnsaved,
     Reload,
+    CopyPath,
+    CopyYaml,
+    /// Focus the preview to edit the selected session's windows.
+    EditWindows,
+    ExitWindows,
+    /// Move the window selection by this many rows.
+    SelectWindow(i32),
+    /// Move the selected window this many positions.
+    MoveWindow(i32),
+    EnterWindowRenameMode,
+    ExitWindowRenameMode,
+    RenameWindow,
+    DeleteWindow,
     MoveSelection(i32),
+    /// Move the selection by this many pages.
+    MovePage(i32),
+    SelectFirst,
+    SelectLast,
     AppendToInput(char),
     DeleteFromInput,
     RemoveLastWord,
     DeleteToLineStart,
     TogglePreview,
+    TogglePreviewPosition,
     ScrollPreviewDown,
     ScrollPreviewUp,
     ToggleHelp,
+    /// Run the action the confirmation popup asked about.
+    Confirm,
     HideConfirmation,
     EnterRenameMode,
     ExitRenameMode,
-    CloseErrorPopup,
+    /// Ask for the name of a copy of the selected config.
+    EnterDuplicateMode,
+    /// Save the copy under the name typed in the input.
+    Duplicate,
+    ClosePopup,
     ToggleListMode,
+    CycleListFilter,
+    ToggleGroup,
+    EnterInsertMode,
+    ExitInsertMode,
+    /// First key of a two-key command, e.g. the `d` of `dd`.
+    PendingKey(char),
+    /// Drop the pending key of an unknown two-key command.
+    CancelPendingKey,
+    /// Save every running session that is unsaved or has changed.
+    SaveAll,
+    /// Recall an older (`true`) or newer filter query from the history.
+    RecallFilter(bool),
     ConfirmCreateName,
     CreateFromLayout,
     ExitCreateMode,
     TriggerCompletion,
     CompletionSelectPrev,
     CompletionSelectNext,
+    /// Quit, first asking to save sessions if configured.
     Exit,
+    /// Save every session listed by the save-on-exit popup, then quit.
+    SaveAllAndExit,
+    /// Quit without saving.
+    ExitWithoutSaving,
     Nop,
 }
@@ -10,17 +10,29 @@ use crossterm::{
 };
 use ratatui::DefaultTerminal;
 
-use crate::{actions, menu::state::MenuState, tmux};
 use crate::{
+    actions, audit, clipboard, menu::renderer, menu::state::MenuState, tmux,
+};
+use crate::{
+    config::{GroupBy, ListFilter, ProjectsConfig},
+    error::TsmanError,
     menu::{
         action::MenuAction,
         item::MenuItem,
-        state::{ListMode, MenuMode},
+        state::{ListMode, MenuMode, WindowEditor},
     },
     persistence::StorageKind,
+    projects,
+    tmux::{
+        charset::Charset, interface::RestoreReport, plan::Progress,
+        session::Session,
+    },
     util::validate_session_name,
 };
 
+/// Most sessions the bulk kill confirmation lists by name.
+const CONFIRM_LIST_LIMIT: usize = 10;
+
 /// Executes a [`MenuAction`] by mutating state and calling tmux/persistence APIs.
 pub trait ActionDispatcher {
     fn dispach(
@@ -41,12 +53,19 @@ impl ActionDispatcher for DefaultActionDispacher {
         state: &mut MenuState,
         terminal: &mut DefaultTerminal,
     ) -> Result<()> {
+        if !matches!(action, MenuAction::Nop) {
+            log::debug!("Menu action {action:?}");
+        }
+        if !matches!(action, MenuAction::Nop | MenuAction::PendingKey(_)) {
+            state.pending_key = None;
+        }
+
         match action {
             MenuAction::Open => {
                 if state.list_mode == ListMode::Layouts {
                     handle_enter_create_name(state)?;
                 } else {
-                    handle_open(state)?;
+                    handle_open(state, terminal)?;
                 }
             }
             MenuAction::Delete => handle_delete(state)?,
@@ -54,11 +73,46 @@ impl ActionDispatcher for DefaultActionDispacher {
             MenuAction::Save => handle_save(state)?,
             MenuAction::Rename => handle_rename(state)?,
             MenuAction::Kill => handle_kill(state)?,
-            MenuAction::Reload => handle_reload(state)?,
+            MenuAction::KillUnsaved => handle_kill_unsaved(state)?,
+            MenuAction::Reload => handle_reload(state, terminal)?,
+            MenuAction::CopyPath => handle_copy(state, false)?,
+            MenuAction::CopyYaml => handle_copy(state, true)?,
+            MenuAction::EditWindows => handle_edit_windows(state),
+            MenuAction::ExitWindows => {
+                state.window_editor = None;
+                state.mode = MenuMode::Normal;
+            }
+            MenuAction::SelectWindow(delta) => {
+                if let Some(editor) = &mut state.window_editor {
+                    editor.selected = editor
+                        .selected
+                        .saturating_add_signed(delta as isize)
+                        .min(editor.session.windows.len() - 1);
+                }
+            }
+            MenuAction::MoveWindow(delta) => handle_move_window(state, delta)?,
+            MenuAction::EnterWindowRenameMode => {
+                handle_enter_window_rename(state)
+            }
+            MenuAction::ExitWindowRenameMode => state.mode = MenuMode::Windows,
+            MenuAction::RenameWindow => handle_rename_window(state)?,
+            MenuAction::DeleteWindow => handle_delete_window(state)?,
             MenuAction::MoveSelection(delta) => {
                 state.items.move_selection(delta);
                 state.preview_scroll = 0;
             }
+            MenuAction::MovePage(pages) => {
+                state.items.move_page(pages);
+                state.preview_scroll = 0;
+            }
+            MenuAction::SelectFirst => {
+                state.items.select_first();
+                state.preview_scroll = 0;
+            }
+            MenuAction::SelectLast => {
+                state.items.select_last();
+                state.preview_scroll = 0;
+            }
             MenuAction::RemoveLastWord => {
                 state.handle_textarea_input(|t| {
                     t.delete_word();
@@ -94,6 +148,13 @@ impl ActionDispatcher for DefaultActionDispacher {
             MenuAction::TogglePreview => {
                 state.ui_flags.show_preview = !state.ui_flags.show_preview;
             }
+            MenuAction::TogglePreviewPosition => {
+                state.ui_flags.preview_position =
+                    state.ui_flags.preview_position.toggled();
+                // Shows the preview if it was off; moving a hidden pane
+                // would look like the key did nothing.
+                state.ui_flags.show_preview = true;
+            }
             MenuAction::ScrollPreviewDown => {
                 state.preview_scroll = state.preview_scroll.saturating_add(1);
             }
@@ -107,15 +168,35 @@ impl ActionDispatcher for DefaultActionDispacher {
                     state.mode = MenuMode::HelpPopup;
                 }
             }
-            MenuAction::HideConfirmation => {
-                state.mode = MenuMode::Normal;
+            MenuAction::Confirm => {
+                let action = std::mem::replace(
+                    &mut state.confirmed_action,
+                    MenuAction::Nop,
+                );
+                self.dispach(action, state, terminal)?;
             }
+            MenuAction::HideConfirmation => state.mode = state.base_mode(),
             MenuAction::EnterRenameMode => handle_enter_rename(state)?,
             MenuAction::ExitRenameMode => state.mode = MenuMode::Normal,
-            MenuAction::CloseErrorPopup => state.mode = MenuMode::Normal,
+            MenuAction::EnterDuplicateMode => handle_enter_duplicate(state),
+            MenuAction::Duplicate => handle_duplicate(state)?,
+            MenuAction::ClosePopup => state.mode = state.base_mode(),
             MenuAction::ToggleListMode => handle_toggle_list_mode(state)?,
+            MenuAction::CycleListFilter => handle_cycle_list_filter(state),
+            MenuAction::ToggleGroup => {
+                let filter = state.filter_input.lines().join("\n");
+                state.items.toggle_selected_group(&filter);
+            }
+            MenuAction::EnterInsertMode => state.insert_mode = true,
+            MenuAction::ExitInsertMode => state.insert_mode = false,
+            MenuAction::PendingKey(c) => state.pending_key = Some(c),
+            MenuAction::CancelPendingKey => {}
+            MenuAction::SaveAll => handle_save_all(state)?,
+            MenuAction::RecallFilter(back) => state.recall_filter(back),
             MenuAction::ConfirmCreateName => handle_confirm_create_name(state)?,
-            MenuAction::CreateFromLayout => handle_create_from_layout(state)?,
+            MenuAction::CreateFromLayout => {
+                handle_create_from_layout(state, terminal)?
+            }
             MenuAction::ExitCreateMode => handle_exit_create_mode(state),
             MenuAction::TriggerCompletion => handle_trigger_completion(state),
             MenuAction::CompletionSelectPrev => {
@@ -125,8 +206,16 @@ impl ActionDispatcher for DefaultActionDispacher {
                 handle_completion_select(state, 1);
             }
             MenuAction::Exit => {
-                state.should_exit = true;
+                if state.ui_flags.prompt_save_on_exit
+                    && !state.sessions_to_save().is_empty()
+                {
+                    state.mode = MenuMode::SaveOnExitPopup;
+                } else {
+                    state.should_exit = true;
+                }
             }
+            MenuAction::SaveAllAndExit => handle_save_all_and_exit(state)?,
+            MenuAction::ExitWithoutSaving => state.should_exit = true,
             MenuAction::Nop => {}
         };
 
@@ -134,17 +223,89 @@ impl ActionDispatcher for DefaultActionDispacher {
     }
 }
 
-fn handle_open(state: &mut MenuState) -> Result<()> {
+fn handle_open(
+    state: &mut MenuState,
+    terminal: &mut DefaultTerminal,
+) -> Result<()> {
     let Some((_, selection)) = state.items.get_selected_item() else {
         return Ok(());
     };
 
-    actions::open(&selection.name, &state.persistence)?;
+    if selection.saved && refuse_clash(state, &selection, "open") {
+        return Ok(());
+    }
+
+    if selection.server.is_some() || selection.unrelated {
+        tmux::interface::attach_to_session(
+            state.client_for(&selection),
+            &selection.name,
+        )?;
+    } else {
+        // Opened from the window editor, the selected window is shown.
+        let window = state
+            .window_editor
+            .as_ref()
+            .map(|editor| editor.session.windows[editor.selected].clone());
+        let report = actions::open_at_window(
+            &selection.name,
+            window.as_ref(),
+            state.ui_flags.snapshot_on_open,
+            state.tmux.as_ref(),
+            &state.persistence,
+            &mut show_progress(
+                terminal,
+                &selection.name,
+                state.ui_flags.charset,
+            ),
+        );
+        // A rolled back restore leaves nothing to attach to, but the menu
+        // can still be used.
+        let report = match report {
+            Err(err)
+                if err.chain().any(|cause| {
+                    matches!(
+                        cause.downcast_ref(),
+                        Some(TsmanError::RestoreFailed { .. })
+                    )
+                }) =>
+            {
+                state.show_error(format!("{err:#}"));
+                return Ok(());
+            }
+            report => report?,
+        };
+        if !report.is_clean() {
+            show_report(state, &report);
+            return Ok(());
+        }
+    }
     state.should_exit = true;
 
     Ok(())
 }
 
+/// Shows an error and returns `true` if the saved config `selection` can't
+/// be `verb`ed because an unrelated session holds its name.
+fn refuse_clash(
+    state: &mut MenuState,
+    selection: &MenuItem,
+    verb: &str,
+) -> bool {
+    let Some(work_dir) = state
+        .items
+        .unrelated_to(&selection.name)
+        .and_then(|item| item.work_dir.clone())
+    else {
+        return false;
+    };
+    state.show_error(format!(
+        "Can't {verb} '{}': a session of another project in {work_dir} has \
+         its name; rename one of them first",
+        selection.name
+    ));
+    true
+}
+
 fn handle_delete(state: &mut MenuState) -> Result<()> {
     if state.ui_flags.ask_for_confirmation && state.mode == MenuMode::Normal {
         if let Some((_, selection)) = state.items.get_selected_item() {
@@ -152,6 +313,7 @@ fn handle_delete(state: &mut MenuState) -> Result<()> {
             state.pending_confirmation =
                 format!("{} '{}'?", verb, selection.name);
         }
+        state.confirmed_action = MenuAction::Delete;
         state.mode = MenuMode::ConfirmationPopup;
         return Ok(());
     }
@@ -164,20 +326,24 @@ fn handle_delete(state: &mut MenuState) -> Result<()> {
 
     if selection.saved {
         actions::delete(&selection.name, &state.persistence)?;
-        state
-            .items
-            .update_item(&selection.name, Some(false), None, None);
+        state.items.update_item(&selection, Some(false), None, None);
+        state.invalidate_preview();
     } else {
-        tmux::interface::close_session(&selection.name)?;
-        state
-            .items
-            .update_item(&selection.name, None, Some(false), None);
+        tmux::interface::close_session(
+            state.client_for(&selection),
+            &selection.name,
+        )?;
+        audit::record(&state.persistence, "kill", &selection.name);
+        state.items.update_item(&selection, None, Some(false), None);
     }
 
     if (selection.saved && !selection.active)
         || (!selection.saved && selection.active)
     {
-        state.items.remove_item(idx, selection);
+        state.items.remove_item(idx, selection.clone());
+    }
+    if selection.work_dir.is_some() {
+        state.items.end_clash(&selection.name);
     }
 
     state
@@ -204,11 +370,67 @@ fn handle_edit(
         disable_raw_mode()?;
         execute!(io::stdout(), LeaveAlternateScreen)?;
 
-        actions::edit_config(&state.persistence, kind, &selection.name)?;
+        let edited = actions::edit_config(
+            &state.persistence,
+            &state.editor,
+            kind,
+            &selection.name,
+        );
 
+        // Back to the menu's terminal whatever the editor did, before the
+        // error is shown in it.
         enable_raw_mode()?;
         execute!(io::stdout(), EnterAlternateScreen)?;
         terminal.clear()?;
+
+        if let Err(err) = edited {
+            state.show_error(format!("{err:#}"));
+        }
+        if kind == StorageKind::Session {
+            let missing =
+                actions::has_missing_dirs(&state.persistence, &selection.name);
+            state.items.set_missing_dirs(&selection, missing);
+        }
+        state.invalidate_preview();
+    }
+
+    Ok(())
+}
+
+/// Copies the selected config's path, or its YAML if `yaml` is set, to the
+/// clipboard.
+fn handle_copy(state: &mut MenuState, yaml: bool) -> Result<()> {
+    let Some((_, selection)) = state.items.get_selected_item() else {
+        return Ok(());
+    };
+
+    if !selection.saved {
+        state.show_error(format!("'{}' has no saved config", selection.name));
+        return Ok(());
+    }
+
+    let kind = match state.list_mode {
+        ListMode::Sessions => StorageKind::Session,
+        ListMode::Layouts => StorageKind::Layout,
+    };
+    let (text, what) = if yaml {
+        (
+            state.persistence.load_config(kind, &selection.name)?,
+            "config",
+        )
+    } else {
+        let path = state
+            .persistence
+            .get_config_file_path(kind, &selection.name)?;
+        (path.to_string_lossy().into_owned(), "config path")
+    };
+
+    match clipboard::copy(&text) {
+        Ok(()) => state.show_info(format!(
+            "Copied the {what} of '{}' to the clipboard",
+            selection.name
+        )),
+        Err(err) => state.show_error(format!("Failed to copy: {err:#}")),
     }
 
     Ok(())
@@ -219,11 +441,30 @@ fn handle_save(state: &mut MenuState) -> Result<()> {
         return Ok(());
     };
 
+    if selection.unrelated {
+        state.show_error(format!(
+            "Saving would overwrite the config of another project named \
+             '{}'; rename the session first",
+            selection.name
+        ));
+        return Ok(());
+    }
+
     if !selection.saved {
-        actions::save_target(&selection.name, &state.persistence)?;
-        state
-            .items
-            .update_item(&selection.name, Some(true), None, None);
+        actions::save_target(
+            &selection.name,
+            state.client_for(&selection),
+            &state.persistence,
+        )?;
+        if selection.server.is_some() {
+            // The config belongs to the default server's list.
+            let saved_item = MenuItem::new(selection.name.clone(), true, false);
+            state.items.update_item(&saved_item, Some(true), None, None);
+            state.items.add_item(saved_item);
+        } else {
+            state.items.update_item(&selection, Some(true), None, None);
+        }
+        state.invalidate_preview();
         state
             .items
             .update_filter(&state.filter_input.lines().join("\n"));
@@ -232,6 +473,67 @@ fn handle_save(state: &mut MenuState) -> Result<()> {
     Ok(())
 }
 
+/// Saves the sessions the save-on-exit popup listed and quits. A session
+/// that fails to save keeps the menu open with the error shown.
+fn handle_save_all_and_exit(state: &mut MenuState) -> Result<()> {
+    let names: Vec<String> = state
+        .sessions_to_save()
+        .into_iter()
+        .map(|item| item.name.clone())
+        .collect();
+
+    for name in names {
+        if let Err(err) =
+            actions::save_target(&name, state.tmux.as_ref(), &state.persistence)
+        {
+            state.show_error(format!("Failed to save '{name}': {err:#}"));
+            return Ok(());
+        }
+    }
+    state.should_exit = true;
+
+    Ok(())
+}
+
+/// Saves every session the save-on-exit popup would list, staying in the
+/// menu.
+fn handle_save_all(state: &mut MenuState) -> Result<()> {
+    let names: Vec<String> = state
+        .sessions_to_save()
+        .into_iter()
+        .map(|item| item.name.clone())
+        .collect();
+    if names.is_empty() {
+        state.show_info("Nothing to save".to_string());
+        return Ok(());
+    }
+
+    for name in &names {
+        if let Err(err) =
+            actions::save_target(name, state.tmux.as_ref(), &state.persistence)
+        {
+            state.show_error(format!("Failed to save '{name}': {err:#}"));
+            return Ok(());
+        }
+        if let Some(item) = state
+            .items
+            .items
+            .iter_mut()
+            .find(|item| item.server.is_none() && item.name == *name)
+        {
+            item.saved = true;
+            item.dirty = false;
+        }
+    }
+    state.invalidate_preview();
+    state
+        .items
+        .update_filter(&state.filter_input.lines().join("\n"));
+    state.show_info(format!("Saved {} sessions", names.len()));
+
+    Ok(())
+}
+
 fn handle_rename(state: &mut MenuState) -> Result<()> {
     let Some((_, selection)) = state.items.get_selected_item() else {
         return Ok(());
@@ -242,16 +544,27 @@ fn handle_rename(state: &mut MenuState) -> Result<()> {
     let new_name = state.rename_input.lines().join("\n");
 
     if let Err(err) = validate_session_name(&new_name) {
-        state.mode = MenuMode::ErrorPopup(err.to_string());
+        state.show_error(err.to_string());
         return Ok(());
     }
 
     state
         .items
-        .update_item(&selection.name, None, None, Some(&new_name));
+        .update_item(&selection, None, None, Some(&new_name));
 
     if selection.active {
-        tmux::interface::rename_session(&selection.name, &new_name)?;
+        tmux::interface::rename_session(
+            state.client_for(&selection),
+            &selection.name,
+            &new_name,
+        )?;
+        if !selection.saved {
+            audit::record(
+                &state.persistence,
+                "rename",
+                &format!("{} -> {new_name}", selection.name),
+            );
+        }
     }
 
     if selection.saved {
@@ -262,6 +575,13 @@ fn handle_rename(state: &mut MenuState) -> Result<()> {
         actions::rename(&state.persistence, kind, &selection.name, &new_name)?;
     }
 
+    if selection.work_dir.is_some() {
+        state.items.end_clash(&selection.name);
+        state.items.end_clash(&new_name);
+    }
+
+    state.invalidate_preview();
+
     state.filter_input.delete_line_by_head();
     state
         .items
@@ -276,13 +596,18 @@ fn handle_kill(state: &mut MenuState) -> Result<()> {
     };
 
     if selection.active {
-        tmux::interface::close_session(&selection.name)?;
-        state
-            .items
-            .update_item(&selection.name, None, Some(false), None);
+        tmux::interface::close_session(
+            state.client_for(&selection),
+            &selection.name,
+        )?;
+        audit::record(&state.persistence, "kill", &selection.name);
+        state.items.update_item(&selection, None, Some(false), None);
 
         if !selection.saved {
-            state.items.remove_item(idx, selection);
+            state.items.remove_item(idx, selection.clone());
+        }
+        if selection.unrelated {
+            state.items.end_clash(&selection.name);
         }
 
         state.items.sort();
@@ -294,7 +619,57 @@ fn handle_kill(state: &mut MenuState) -> Result<()> {
     Ok(())
 }
 
-fn handle_reload(state: &mut MenuState) -> Result<()> {
+/// Kills the running sessions without a saved config on the default
+/// server, after listing them in the confirmation popup.
+fn handle_kill_unsaved(state: &mut MenuState) -> Result<()> {
+    if state.list_mode != ListMode::Sessions {
+        return Ok(());
+    }
+
+    let unsaved = actions::list_unsaved_sessions(
+        state.tmux.as_ref(),
+        &state.persistence,
+    )?;
+    if unsaved.is_empty() {
+        state.show_info("No unsaved sessions running".to_string());
+        return Ok(());
+    }
+
+    if state.mode == MenuMode::Normal {
+        let mut message = format!("Kill {} unsaved sessions?", unsaved.len());
+        for name in unsaved.iter().take(CONFIRM_LIST_LIMIT) {
+            message += &format!("\n{name}");
+        }
+        if unsaved.len() > CONFIRM_LIST_LIMIT {
+            message += &format!(
+                "\n... and {} more",
+                unsaved.len() - CONFIRM_LIST_LIMIT
+            );
+        }
+        state.pending_confirmation = message;
+        state.confirmed_action = MenuAction::KillUnsaved;
+        state.mode = MenuMode::ConfirmationPopup;
+        return Ok(());
+    }
+
+    state.mode = MenuMode::Normal;
+
+    for name in &unsaved {
+        tmux::interface::close_session(state.tmux.as_ref(), name)?;
+        audit::record(&state.persistence, "kill", name);
+    }
+
+    let active = tmux::interface::list_active_sessions(state.tmux.as_ref())?;
+    let filter = state.filter_input.lines().join("\n");
+    state.items.sync_active(None, &active, &filter);
+
+    Ok(())
+}
+
+fn handle_reload(
+    state: &mut MenuState,
+    terminal: &mut DefaultTerminal,
+) -> Result<()> {
     if state.list_mode != ListMode::Sessions {
         return Ok(());
     }
@@ -304,17 +679,25 @@ fn handle_reload(state: &mut MenuState) -> Result<()> {
     };
 
     if !selection.saved {
-        state.mode =
-            MenuMode::ErrorPopup("Session must be saved to reload".to_string());
+        state.show_error("Session must be saved to reload".to_string());
+        return Ok(());
+    }
+    if refuse_clash(state, &selection, "reload") {
         return Ok(());
     }
 
-    match actions::reload(Some(&selection.name), &state.persistence) {
-        Ok(()) => {
+    match actions::reload(
+        Some(&selection.name),
+        state.tmux.as_ref(),
+        &state.persistence,
+        &mut show_progress(terminal, &selection.name, state.ui_flags.charset),
+    ) {
+        Ok(report) if !report.is_clean() => show_report(state, &report),
+        Ok(_) => {
             state.should_exit = true;
         }
         Err(err) => {
-            state.mode = MenuMode::ErrorPopup(err.to_string());
+            state.show_error(err.to_string());
         }
     }
 
@@ -337,6 +720,197 @@ fn handle_enter_rename(state: &mut MenuState) -> Result<()> {
     Ok(())
 }
 
+/// Asks for the name of a copy of the selected config, starting from its
+/// own name.
+fn handle_enter_duplicate(state: &mut MenuState) {
+    let Some((_, selection)) = state.items.get_selected_item() else {
+        return;
+    };
+    if !selection.saved {
+        state.show_error(format!("'{}' has no saved config", selection.name));
+        return;
+    }
+
+    state.rename_input.delete_line_by_head();
+    state.rename_input.insert_str(selection.name);
+    state.mode = MenuMode::Duplicate;
+}
+
+/// Saves a copy of the selected config under the typed name and selects it.
+fn handle_duplicate(state: &mut MenuState) -> Result<()> {
+    let Some((_, selection)) = state.items.get_selected_item() else {
+        return Ok(());
+    };
+
+    state.mode = MenuMode::Normal;
+
+    let new_name = state.rename_input.lines().join("\n");
+
+    if let Err(err) = validate_session_name(&new_name) {
+        state.show_error(err.to_string());
+        return Ok(());
+    }
+
+    let kind = match state.list_mode {
+        ListMode::Sessions => StorageKind::Session,
+        ListMode::Layouts => StorageKind::Layout,
+    };
+    if let Err(err) =
+        actions::duplicate(&state.persistence, kind, &selection.name, &new_name)
+    {
+        state.show_error(format!("{err:#}"));
+        return Ok(());
+    }
+
+    let mut copy = MenuItem::new(new_name, true, false);
+    copy.missing_dirs = selection.missing_dirs;
+    state.items.add_item(copy.clone());
+
+    state.filter_input.delete_line_by_head();
+    state.items.update_filter_keep_selection(Some(&copy), "");
+
+    Ok(())
+}
+
+/// Loads the selected saved session to edit its windows in the preview
+/// pane.
+fn handle_edit_windows(state: &mut MenuState) {
+    if state.list_mode != ListMode::Sessions {
+        return;
+    }
+    let Some((_, selection)) = state.items.get_selected_item() else {
+        return;
+    };
+    if !selection.saved {
+        state.show_error(format!("'{}' has no saved config", selection.name));
+        return;
+    }
+
+    match state
+        .persistence
+        .load::<Session>(StorageKind::Session, &selection.name)
+    {
+        Ok(session) => {
+            // With no server running, tmux's default: off.
+            let renumber =
+                tmux::interface::renumbers_windows(state.tmux.as_ref())
+                    .unwrap_or(false);
+            state.window_editor = Some(WindowEditor {
+                name: selection.name,
+                session,
+                selected: 0,
+                renumber,
+            });
+            state.ui_flags.show_preview = true;
+            state.mode = MenuMode::Windows;
+        }
+        Err(err) => state.show_error(format!("{err:#}")),
+    }
+}
+
+/// Writes the session being edited back to its config and records `action`
+/// on `window` in the audit log.
+fn save_window_editor(
+    state: &mut MenuState,
+    action: &str,
+    window: &str,
+) -> Result<()> {
+    let Some(editor) = &state.window_editor else {
+        return Ok(());
+    };
+
+    let _lock = state.persistence.lock(StorageKind::Session)?;
+    let yaml = serde_yaml::to_string(&editor.session)?;
+    state
+        .persistence
+        .save_config(StorageKind::Session, &editor.name, yaml)?;
+    audit::record(
+        &state.persistence,
+        action,
+        &format!("{}:{window}", editor.name),
+    );
+    state.invalidate_preview();
+
+    Ok(())
+}
+
+fn handle_move_window(state: &mut MenuState, delta: i32) -> Result<()> {
+    let Some(editor) = &mut state.window_editor else {
+        return Ok(());
+    };
+
+    let from = editor.selected;
+    let Some(to) = from.checked_add_signed(delta as isize) else {
+        return Ok(());
+    };
+    editor.selected = editor.session.move_window(from, to, editor.renumber);
+    if editor.selected == from {
+        return Ok(());
+    }
+
+    let window = editor.session.windows[editor.selected].name.clone();
+    save_window_editor(state, "window-move", &window)
+}
+
+fn handle_enter_window_rename(state: &mut MenuState) {
+    let Some(editor) = &state.window_editor else {
+        return;
+    };
+
+    let name = editor.session.windows[editor.selected].name.clone();
+    state.rename_input.delete_line_by_head();
+    state.rename_input.insert_str(name);
+    state.mode = MenuMode::RenameWindow;
+}
+
+fn handle_rename_window(state: &mut MenuState) -> Result<()> {
+    state.mode = MenuMode::Windows;
+
+    let new_name = state.rename_input.lines().join("\n");
+    if new_name.trim().is_empty() {
+        state.show_error("Window name cannot be empty".to_string());
+        return Ok(());
+    }
+
+    let Some(editor) = &mut state.window_editor else {
+        return Ok(());
+    };
+    let window = &mut editor.session.windows[editor.selected];
+    if window.name == new_name {
+        return Ok(());
+    }
+    let change = format!("{} -> {new_name}", window.name);
+    window.name = new_name;
+
+    save_window_editor(state, "window-rename", &change)
+}
+
+fn handle_delete_window(state: &mut MenuState) -> Result<()> {
+    let Some(editor) = &mut state.window_editor else {
+        return Ok(());
+    };
+    if editor.session.windows.len() < 2 {
+        state.show_error("Can't delete the only window".to_string());
+        return Ok(());
+    }
+
+    let name = editor.session.windows[editor.selected].name.clone();
+    if state.ui_flags.ask_for_confirmation && state.mode == MenuMode::Windows {
+        state.pending_confirmation = format!("Delete window '{name}'?");
+        state.confirmed_action = MenuAction::DeleteWindow;
+        state.mode = MenuMode::ConfirmationPopup;
+        return Ok(());
+    }
+    state.mode = MenuMode::Windows;
+
+    editor
+        .session
+        .remove_window(editor.selected, editor.renumber);
+    editor.selected = editor.selected.min(editor.session.windows.len() - 1);
+
+    save_window_editor(state, "window-delete", &name)
+}
+
 fn handle_toggle_list_mode(state: &mut MenuState) -> Result<()> {
     state.list_mode = match state.list_mode {
         ListMode::Sessions => ListMode::Layouts,
@@ -344,29 +918,12 @@ fn handle_toggle_list_mode(state: &mut MenuState) -> Result<()> {
     };
 
     let items = match state.list_mode {
-        ListMode::Sessions => {
-            let saved: std::collections::HashSet<String> = state
-                .persistence
-                .list_saved_configs(StorageKind::Session)?
-                .into_iter()
-                .collect();
-            let active: std::collections::HashSet<String> =
-                tmux::interface::list_active_sessions()?
-                    .into_iter()
-                    .collect();
-            let union: std::collections::HashSet<_> =
-                saved.union(&active).cloned().collect();
-            union
-                .into_iter()
-                .map(|name| {
-                    MenuItem::new(
-                        name.clone(),
-                        saved.contains(&name),
-                        active.contains(&name),
-                    )
-                })
-                .collect()
-        }
+        ListMode::Sessions => actions::get_all_sessions(
+            state.tmux.as_ref(),
+            &state.servers,
+            &state.persistence,
+            state.ui_flags.sort_order,
+        )?,
         ListMode::Layouts => state
             .persistence
             .list_saved_configs(StorageKind::Layout)?
@@ -375,12 +932,34 @@ fn handle_toggle_list_mode(state: &mut MenuState) -> Result<()> {
             .collect(),
     };
 
+    state.items.set_list_filter(match state.list_mode {
+        ListMode::Sessions => state.ui_flags.list_filter,
+        ListMode::Layouts => ListFilter::All,
+    });
+    state.items.set_group_by(match state.list_mode {
+        ListMode::Sessions => state.ui_flags.group_by,
+        ListMode::Layouts => GroupBy::None,
+    });
     state.items.replace_items(items);
+    state.remember_filter();
     state.filter_input.delete_line_by_head();
 
     Ok(())
 }
 
+fn handle_cycle_list_filter(state: &mut MenuState) {
+    if state.list_mode != ListMode::Sessions {
+        return;
+    }
+
+    state.ui_flags.list_filter = state.ui_flags.list_filter.next();
+    state.items.set_list_filter(state.ui_flags.list_filter);
+    state
+        .items
+        .update_filter_and_reset(&state.filter_input.lines().join("\n"));
+    state.preview_scroll = 0;
+}
+
 fn handle_enter_create_name(state: &mut MenuState) -> Result<()> {
     if state.items.get_selected_item().is_none() {
         return Ok(());
@@ -396,7 +975,7 @@ fn handle_confirm_create_name(state: &mut MenuState) -> Result<()> {
     let name = state.rename_input.lines().join("\n");
 
     if let Err(err) = validate_session_name(&name) {
-        state.mode = MenuMode::ErrorPopup(err.to_string());
+        state.show_error(err.to_string());
         return Ok(());
     }
 
@@ -407,7 +986,10 @@ fn handle_confirm_create_name(state: &mut MenuState) -> Result<()> {
     Ok(())
 }
 
-fn handle_create_from_layout(state: &mut MenuState) -> Result<()> {
+fn handle_create_from_layout(
+    state: &mut MenuState,
+    terminal: &mut DefaultTerminal,
+) -> Result<()> {
     let work_dir_raw = state.rename_input.lines().join("\n");
     let work_dir = expand_tilde(&work_dir_raw);
 
@@ -421,19 +1003,44 @@ fn handle_create_from_layout(state: &mut MenuState) -> Result<()> {
         &selection.name,
         &work_dir,
         Some(&session_name),
+        state.tmux.as_ref(),
         &state.persistence,
+        &mut show_progress(terminal, &session_name, state.ui_flags.charset),
     ) {
-        Ok(()) => {
+        Ok(report) if !report.is_clean() => show_report(state, &report),
+        Ok(_) => {
             state.should_exit = true;
         }
         Err(err) => {
-            state.mode = MenuMode::ErrorPopup(err.to_string());
+            state.show_error(err.to_string());
         }
     }
 
     Ok(())
 }
 
+/// Redraws the menu as a progress popup after each restore step.
+fn show_progress<'a>(
+    terminal: &'a mut DefaultTerminal,
+    name: &'a str,
+    charset: Charset,
+) -> impl FnMut(Progress) + 'a {
+    move |progress| {
+        let _ = terminal.draw(|frame| {
+            renderer::draw_restore_progress(frame, name, progress, charset)
+        });
+    }
+}
+
+/// Keeps the menu open to show what went wrong during a restore.
+fn show_report(state: &mut MenuState, report: &RestoreReport) {
+    let problems: Vec<&str> = report.problems().collect();
+    state.show_error(format!(
+        "Restored with problems:\n{}",
+        problems.join("\n")
+    ));
+}
+
 fn handle_exit_create_mode(state: &mut MenuState) {
     state.mode = MenuMode::Normal;
     state.rename_input.delete_line_by_head();
@@ -481,6 +1088,37 @@ fn compute_completions(input: &str) -> Vec<String> {
     completions
 }
 
+/// Completes a project name to the paths of the matching projects, or
+/// returns `None` if there are no project roots configured.
+fn compute_project_completions(
+    input: &str,
+    config: &ProjectsConfig,
+) -> Option<Vec<String>> {
+    if config.roots.is_empty() {
+        return None;
+    }
+    let projects = match projects::discover(config) {
+        Ok(projects) => projects,
+        Err(err) => {
+            log::warn!("Failed to discover projects: {err:#}");
+            return None;
+        }
+    };
+
+    let input = input.to_lowercase();
+    Some(
+        projects
+            .into_iter()
+            .filter(|path| {
+                path.file_name().is_some_and(|name| {
+                    name.to_string_lossy().to_lowercase().contains(&input)
+                })
+            })
+            .map(|path| format!("{}/", path.display()))
+            .collect(),
+    )
+}
+
 fn apply_completion(state: &mut MenuState, completion: &str) {
     state.rename_input.delete_line_by_head();
     state.rename_input.insert_str(completion);
@@ -493,7 +1131,13 @@ fn handle_trigger_completion(state: &mut MenuState) {
     }
 
     let input = state.rename_input.lines().join("\n");
-    let completions = compute_completions(&input);
+    let completions = if input.contains('/') || input.starts_with('~') {
+        compute_completions(&input)
+    } else {
+        compute_project_completions(&input, &state.projects)
+            .filter(|completions| !completions.is_empty())
+            .unwrap_or_else(|| compute_completions(&input))
+    };
     match completions.len() {
         0 => {}
         1 => {
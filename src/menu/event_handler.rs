@@ -1,10 +1,38 @@
 use crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
 
+use crate::config::Keymap;
 use crate::menu::{
     action::MenuAction,
     state::{MenuMode, MenuState},
 };
 
+/// Starts a leader chord: Space, which can't appear in a session name,
+/// followed by one of the [`LEADER_CHORDS`] keys.
+pub const LEADER_KEY: char = ' ';
+
+/// Second keys of the leader chords and what they do, in the order the hint
+/// popup lists them.
+pub const LEADER_CHORDS: &[(char, &str)] = &[
+    ('o', "Open"),
+    ('s', "Save"),
+    ('w', "Save all"),
+    ('e', "Edit"),
+    ('r', "Rename"),
+    ('d', "Delete"),
+    ('k', "Kill"),
+    ('K', "Kill unsaved"),
+    ('R', "Reload"),
+    ('W', "Edit windows"),
+    ('D', "Duplicate"),
+    ('y', "Copy path"),
+    ('Y', "Copy YAML"),
+    ('p', "Toggle preview"),
+    ('f', "Cycle filter"),
+    ('l', "Sessions/layouts"),
+    ('?', "Help"),
+    ('q', "Quit"),
+];
+
 /// Maps terminal events to [`MenuAction`]s based on the current mode.
 pub trait EventHandler {
     fn handle_event(
@@ -32,15 +60,43 @@ impl EventHandler for DefaultEventHandler {
         }
 
         let action = match state.mode {
-            MenuMode::Normal => handle_normal_mode_key(key),
+            MenuMode::Normal if state.pending_key == Some(LEADER_KEY) => {
+                handle_leader_chord_key(key)
+            }
+            MenuMode::Normal
+                if key.code == KeyCode::Char(LEADER_KEY)
+                    && key.modifiers.is_empty() =>
+            {
+                MenuAction::PendingKey(LEADER_KEY)
+            }
+            MenuMode::Normal => match state.ui_flags.keymap {
+                Keymap::Emacs => handle_normal_mode_key(key),
+                Keymap::Vim if state.insert_mode => handle_insert_mode_key(key),
+                Keymap::Vim => {
+                    handle_vim_normal_mode_key(key, state.pending_key)
+                }
+            },
             MenuMode::Rename => handle_rename_mode_key(key),
+            MenuMode::Duplicate => match handle_rename_mode_key(key) {
+                MenuAction::Rename => MenuAction::Duplicate,
+                action => action,
+            },
             MenuMode::HelpPopup => handle_help_popup_key(key),
             MenuMode::ConfirmationPopup => handle_confirmation_popup_key(key),
-            MenuMode::ErrorPopup(_) => handle_error_popup_key(key),
+            MenuMode::ErrorPopup(_) | MenuMode::InfoPopup(_) => {
+                handle_popup_key(key)
+            }
             MenuMode::CreateFromLayoutName => handle_create_name_mode_key(key),
             MenuMode::CreateFromLayoutWorkdir => {
                 handle_create_workdir_mode_key(key)
             }
+            MenuMode::SaveOnExitPopup => handle_save_on_exit_popup_key(key),
+            MenuMode::Windows => handle_windows_mode_key(key),
+            MenuMode::RenameWindow => match handle_rename_mode_key(key) {
+                MenuAction::Rename => MenuAction::RenameWindow,
+                MenuAction::ExitRenameMode => MenuAction::ExitWindowRenameMode,
+                action => action,
+            },
         };
 
         let label = key_event_to_label(key);
@@ -52,6 +108,19 @@ fn handle_normal_mode_key(key: KeyEvent) -> MenuAction {
     let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
     let shift = key.modifiers.contains(KeyModifiers::SHIFT);
 
+    if key.modifiers.contains(KeyModifiers::ALT) {
+        return match key.code {
+            KeyCode::Char('y') => MenuAction::CopyYaml,
+            KeyCode::Char('p') => MenuAction::RecallFilter(true),
+            KeyCode::Char('n') => MenuAction::RecallFilter(false),
+            KeyCode::Char('t') => MenuAction::TogglePreviewPosition,
+            KeyCode::Char('k') => MenuAction::KillUnsaved,
+            KeyCode::Char('w') => MenuAction::EditWindows,
+            KeyCode::Char('d') => MenuAction::EnterDuplicateMode,
+            _ => MenuAction::Nop,
+        };
+    }
+
     match (ctrl, shift, key.code) {
         (true, _, KeyCode::Char('p')) => MenuAction::MoveSelection(-1),
         (true, _, KeyCode::Char('n')) => MenuAction::MoveSelection(1),
@@ -61,8 +130,10 @@ fn handle_normal_mode_key(key: KeyEvent) -> MenuAction {
         (true, _, KeyCode::Char('d')) => MenuAction::Delete,
         (true, _, KeyCode::Char('k')) => MenuAction::Kill,
         (true, _, KeyCode::Char('o')) => MenuAction::Reload,
+        (true, _, KeyCode::Char('y')) => MenuAction::CopyPath,
         (true, _, KeyCode::Char('c')) => MenuAction::Exit,
         (true, _, KeyCode::Char('l')) => MenuAction::ToggleListMode,
+        (true, _, KeyCode::Char('f')) => MenuAction::CycleListFilter,
         (true, _, KeyCode::Char('t')) => MenuAction::TogglePreview,
         (true, _, KeyCode::Char('h')) => MenuAction::ToggleHelp,
         (true, _, KeyCode::Char('w')) => MenuAction::RemoveLastWord,
@@ -75,13 +146,119 @@ fn handle_normal_mode_key(key: KeyEvent) -> MenuAction {
         (false, _, KeyCode::Backspace) => MenuAction::DeleteFromInput,
         (false, _, KeyCode::Up) => MenuAction::MoveSelection(-1),
         (false, _, KeyCode::Down) => MenuAction::MoveSelection(1),
+        (false, _, KeyCode::PageUp) => MenuAction::MovePage(-1),
+        (false, _, KeyCode::PageDown) => MenuAction::MovePage(1),
+        (false, _, KeyCode::Home) => MenuAction::SelectFirst,
+        (false, _, KeyCode::End) => MenuAction::SelectLast,
         (false, _, KeyCode::Enter) => MenuAction::Open,
+        (false, _, KeyCode::Tab) => MenuAction::ToggleGroup,
         (false, _, KeyCode::Esc) => MenuAction::Exit,
 
         _ => MenuAction::Nop,
     }
 }
 
+/// Vim keymap, insert mode: like the emacs keymap, but Esc returns to
+/// normal mode instead of closing the menu and Up/Down browse the filter
+/// history.
+fn handle_insert_mode_key(key: KeyEvent) -> MenuAction {
+    if key.modifiers.is_empty() {
+        match key.code {
+            KeyCode::Esc => return MenuAction::ExitInsertMode,
+            KeyCode::Up => return MenuAction::RecallFilter(true),
+            KeyCode::Down => return MenuAction::RecallFilter(false),
+            _ => {}
+        }
+    }
+    handle_normal_mode_key(key)
+}
+
+/// Vim keymap, normal mode: letter keys act on the selected item; Ctrl
+/// bindings work as in the emacs keymap.
+fn handle_vim_normal_mode_key(
+    key: KeyEvent,
+    pending_key: Option<char>,
+) -> MenuAction {
+    if key.modifiers.contains(KeyModifiers::CONTROL)
+        || key.modifiers.contains(KeyModifiers::ALT)
+    {
+        return handle_normal_mode_key(key);
+    }
+
+    match (pending_key, key.code) {
+        (Some('d'), KeyCode::Char('d')) => MenuAction::Delete,
+        (_, KeyCode::Char('d')) => MenuAction::PendingKey('d'),
+        (Some('g'), KeyCode::Char('g')) => MenuAction::SelectFirst,
+        (_, KeyCode::Char('g')) => MenuAction::PendingKey('g'),
+        (_, KeyCode::Char('G')) => MenuAction::SelectLast,
+
+        (_, KeyCode::Char('j')) => MenuAction::MoveSelection(1),
+        (_, KeyCode::Char('k')) => MenuAction::MoveSelection(-1),
+        (_, KeyCode::Char('J')) => MenuAction::ScrollPreviewDown,
+        (_, KeyCode::Char('K')) => MenuAction::ScrollPreviewUp,
+        (_, KeyCode::Char('o')) => MenuAction::Open,
+        (_, KeyCode::Char('r')) => MenuAction::EnterRenameMode,
+        (_, KeyCode::Char('e')) => MenuAction::Edit,
+        (_, KeyCode::Char('s')) => MenuAction::Save,
+        (_, KeyCode::Char('x')) => MenuAction::Kill,
+        (_, KeyCode::Char('X')) => MenuAction::KillUnsaved,
+        (_, KeyCode::Char('w')) => MenuAction::EditWindows,
+        (_, KeyCode::Char('D')) => MenuAction::EnterDuplicateMode,
+        (_, KeyCode::Char('R')) => MenuAction::Reload,
+        (_, KeyCode::Char('y')) => MenuAction::CopyPath,
+        (_, KeyCode::Char('Y')) => MenuAction::CopyYaml,
+        (_, KeyCode::Char('p')) => MenuAction::TogglePreview,
+        (_, KeyCode::Char('P')) => MenuAction::TogglePreviewPosition,
+        (_, KeyCode::Char('f')) => MenuAction::CycleListFilter,
+        (_, KeyCode::Char('L')) => MenuAction::ToggleListMode,
+        (_, KeyCode::Char('?')) => MenuAction::ToggleHelp,
+        (_, KeyCode::Char('/' | 'i' | 'a')) => MenuAction::EnterInsertMode,
+        (_, KeyCode::Char('q')) => MenuAction::Exit,
+
+        _ => match handle_normal_mode_key(key) {
+            // Letters are commands here, not filter input.
+            MenuAction::AppendToInput(_) => MenuAction::Nop,
+            action => action,
+        },
+    }
+}
+
+/// The key after the leader, with either keymap; any other key drops the
+/// chord.
+fn handle_leader_chord_key(key: KeyEvent) -> MenuAction {
+    let KeyCode::Char(c) = key.code else {
+        return MenuAction::CancelPendingKey;
+    };
+    if key
+        .modifiers
+        .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT)
+    {
+        return MenuAction::CancelPendingKey;
+    }
+
+    match c {
+        'o' => MenuAction::Open,
+        's' => MenuAction::Save,
+        'w' => MenuAction::SaveAll,
+        'e' => MenuAction::Edit,
+        'r' => MenuAction::EnterRenameMode,
+        'd' => MenuAction::Delete,
+        'k' => MenuAction::Kill,
+        'K' => MenuAction::KillUnsaved,
+        'R' => MenuAction::Reload,
+        'W' => MenuAction::EditWindows,
+        'D' => MenuAction::EnterDuplicateMode,
+        'y' => MenuAction::CopyPath,
+        'Y' => MenuAction::CopyYaml,
+        'p' => MenuAction::TogglePreview,
+        'f' => MenuAction::CycleListFilter,
+        'l' => MenuAction::ToggleListMode,
+        '?' => MenuAction::ToggleHelp,
+        'q' => MenuAction::Exit,
+        _ => MenuAction::CancelPendingKey,
+    }
+}
+
 fn handle_rename_mode_key(key: KeyEvent) -> MenuAction {
     match (key.modifiers.contains(KeyModifiers::CONTROL), key.code) {
         (true, KeyCode::Char('c')) => MenuAction::ExitRenameMode,
@@ -97,9 +274,43 @@ fn handle_rename_mode_key(key: KeyEvent) -> MenuAction {
     }
 }
 
+/// Editing the windows of a saved session in the preview pane; the same
+/// keys with either keymap.
+fn handle_windows_mode_key(key: KeyEvent) -> MenuAction {
+    let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+    let shift = key.modifiers.contains(KeyModifiers::SHIFT);
+
+    match (ctrl, shift, key.code) {
+        (true, _, KeyCode::Char('p')) => MenuAction::SelectWindow(-1),
+        (true, _, KeyCode::Char('n')) => MenuAction::SelectWindow(1),
+        (true, _, KeyCode::Char('c')) => MenuAction::ExitWindows,
+
+        (false, true, KeyCode::Up) | (false, _, KeyCode::Char('K')) => {
+            MenuAction::MoveWindow(-1)
+        }
+        (false, true, KeyCode::Down) | (false, _, KeyCode::Char('J')) => {
+            MenuAction::MoveWindow(1)
+        }
+        (false, _, KeyCode::Up | KeyCode::Char('k')) => {
+            MenuAction::SelectWindow(-1)
+        }
+        (false, _, KeyCode::Down | KeyCode::Char('j')) => {
+            MenuAction::SelectWindow(1)
+        }
+        (false, _, KeyCode::Char('r')) => MenuAction::EnterWindowRenameMode,
+        (false, _, KeyCode::Char('d')) => MenuAction::DeleteWindow,
+        (false, _, KeyCode::Enter) => MenuAction::Open,
+        (false, _, KeyCode::Esc | KeyCode::Char('q')) => {
+            MenuAction::ExitWindows
+        }
+
+        _ => MenuAction::Nop,
+    }
+}
+
 fn handle_confirmation_popup_key(key: KeyEvent) -> MenuAction {
     match key.code {
-        KeyCode::Char('y' | 'Y') | KeyCode::Enter => MenuAction::Delete,
+        KeyCode::Char('y' | 'Y') | KeyCode::Enter => MenuAction::Confirm,
         KeyCode::Char('n' | 'N' | 'q') | KeyCode::Esc => {
             MenuAction::HideConfirmation
         }
@@ -107,6 +318,17 @@ fn handle_confirmation_popup_key(key: KeyEvent) -> MenuAction {
     }
 }
 
+fn handle_save_on_exit_popup_key(key: KeyEvent) -> MenuAction {
+    match key.code {
+        KeyCode::Char('a' | 'A' | 'y' | 'Y') | KeyCode::Enter => {
+            MenuAction::SaveAllAndExit
+        }
+        KeyCode::Char('s' | 'S' | 'n' | 'N') => MenuAction::ExitWithoutSaving,
+        KeyCode::Char('q') | KeyCode::Esc => MenuAction::ClosePopup,
+        _ => MenuAction::Nop,
+    }
+}
+
 fn handle_help_popup_key(key: KeyEvent) -> MenuAction {
     match (key.modifiers.contains(KeyModifiers::CONTROL), key.code) {
         (true, KeyCode::Char('h' | 'c')) => MenuAction::ToggleHelp,
@@ -117,8 +339,8 @@ fn handle_help_popup_key(key: KeyEvent) -> MenuAction {
     }
 }
 
-fn handle_error_popup_key(_key: KeyEvent) -> MenuAction {
-    MenuAction::CloseErrorPopup
+fn handle_popup_key(_key: KeyEvent) -> MenuAction {
+    MenuAction::ClosePopup
 }
 
 fn handle_create_name_mode_key(key: KeyEvent) -> MenuAction {
@@ -163,6 +385,12 @@ fn key_event_to_label(key: KeyEvent) -> Option<String> {
     let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
     let shift = key.modifiers.contains(KeyModifiers::SHIFT);
 
+    if let (true, KeyCode::Char(c)) =
+        (key.modifiers.contains(KeyModifiers::ALT), key.code)
+    {
+        return Some(format!("M-{c}"));
+    }
+
     match (ctrl, shift, key.code) {
         (true, _, KeyCode::Char(c)) => Some(format!("C-{c}")),
         (_, true, KeyCode::Up) => Some("S-Up".into()),
@@ -173,6 +401,10 @@ fn key_event_to_label(key: KeyEvent) -> Option<String> {
         (false, false, KeyCode::Up) => Some("Up".into()),
         (false, false, KeyCode::Down) => Some("Down".into()),
         (false, false, KeyCode::Tab) => Some("Tab".into()),
+        (false, false, KeyCode::PageUp) => Some("PgUp".into()),
+        (false, false, KeyCode::PageDown) => Some("PgDn".into()),
+        (false, false, KeyCode::Home) => Some("Home".into()),
+        (false, false, KeyCode::End) => Some("End".into()),
         _ => None,
     }
 }
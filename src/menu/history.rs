@@ -0,0 +1,109 @@
+//! Filter history - past filter queries, kept in the data dir across runs
+//! and recalled with Up/Down like a shell's history.
+use std::fs;
+use std::io::ErrorKind;
+
+use anyhow::{Context, Result};
+
+use crate::persistence::Persistence;
+
+const HISTORY_FILE: &str = "filter_history";
+
+/// Most queries kept; older ones are dropped.
+const MAX_ENTRIES: usize = 100;
+
+/// Past queries, oldest first, and the position while browsing them.
+#[derive(Default)]
+pub struct FilterHistory {
+    entries: Vec<String>,
+    /// Entry currently recalled, `None` while editing a new query.
+    cursor: Option<usize>,
+    /// The query being typed before browsing started.
+    draft: String,
+}
+
+impl FilterHistory {
+    /// Reads the history from the data dir. A missing or unreadable file
+    /// gives an empty history.
+    pub fn load(persistence: &Persistence) -> Self {
+        let path = match persistence.ensure_data_dir() {
+            Ok(dir) => dir.join(HISTORY_FILE),
+            Err(err) => {
+                log::warn!("Failed to load the filter history: {err:#}");
+                return Self::default();
+            }
+        };
+        let entries = match fs::read_to_string(&path) {
+            Ok(content) => content.lines().map(str::to_string).collect(),
+            Err(err) => {
+                if err.kind() != ErrorKind::NotFound {
+                    log::warn!("Failed to read {}: {err}", path.display());
+                }
+                Vec::new()
+            }
+        };
+
+        Self {
+            entries,
+            ..Self::default()
+        }
+    }
+
+    /// Writes the history to the data dir.
+    pub fn save(&self, persistence: &Persistence) -> Result<()> {
+        let path = persistence.ensure_data_dir()?.join(HISTORY_FILE);
+        let mut content = self.entries.join("\n");
+        content.push('\n');
+        fs::write(&path, content)
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// Adds `query` as the newest entry, moving it there if it was already
+    /// recorded. Blank queries are ignored.
+    pub fn push(&mut self, query: &str) {
+        let query = query.trim();
+        if query.is_empty() {
+            return;
+        }
+        self.entries.retain(|entry| entry != query);
+        self.entries.push(query.to_string());
+        if self.entries.len() > MAX_ENTRIES {
+            self.entries.drain(..self.entries.len() - MAX_ENTRIES);
+        }
+        self.reset();
+    }
+
+    /// Steps back to an older query. `current` is kept as the draft when
+    /// browsing starts. Returns `None` if there is nothing older.
+    pub fn prev(&mut self, current: &str) -> Option<&str> {
+        let idx = match self.cursor {
+            None if self.entries.is_empty() => return None,
+            None => {
+                self.draft = current.to_string();
+                self.entries.len() - 1
+            }
+            Some(0) => return None,
+            Some(idx) => idx - 1,
+        };
+        self.cursor = Some(idx);
+        Some(&self.entries[idx])
+    }
+
+    /// Steps forward to a newer query, and past the newest back to the
+    /// draft. Returns `None` if not browsing.
+    pub fn next(&mut self) -> Option<&str> {
+        let idx = self.cursor?;
+        if idx + 1 < self.entries.len() {
+            self.cursor = Some(idx + 1);
+            Some(&self.entries[idx + 1])
+        } else {
+            self.cursor = None;
+            Some(&self.draft)
+        }
+    }
+
+    /// Stops browsing, e.g. once the recalled query is edited.
+    pub fn reset(&mut self) {
+        self.cursor = None;
+    }
+}
@@ -8,24 +8,102 @@ pub struct MenuItem {
     pub saved: bool,
     /// Whether this item corresponds to a currently running tmux session.
     pub active: bool,
+    /// Socket name of the tmux server the session lives on, `None` for the
+    /// default server.
+    pub server: Option<String>,
+    /// Whether the session is pinned to the top of the list.
+    pub pinned: bool,
+    /// Whether the saved config has working directories that don't exist
+    /// on this machine.
+    pub missing_dirs: bool,
+    /// Whether the running session's windows no longer match its saved
+    /// config.
+    pub dirty: bool,
+    /// How many of the running session's panes are dead, kept open by
+    /// `remain-on-exit` after their process exited.
+    pub dead_panes: usize,
+    /// Tags from the session's metadata.
+    pub tags: Vec<String>,
+    /// Rank from the usage stats under the configured sort order; higher
+    /// sorts first.
+    pub score: f64,
+    /// Working directory shown next to the name when a saved config and an
+    /// unrelated running session share it, to tell the two apart.
+    pub work_dir: Option<String>,
+    /// Whether this is a running session that only shares its name with a
+    /// saved config of another project, listed apart from the config.
+    pub unrelated: bool,
 }
 
 impl MenuItem {
-    /// Creates a new menu item.
+    /// Creates a new menu item on the default tmux server.
     pub fn new(name: String, saved: bool, active: bool) -> Self {
         Self {
             name,
             saved,
             active,
+            server: None,
+            pinned: false,
+            missing_dirs: false,
+            dirty: false,
+            dead_panes: 0,
+            tags: Vec::new(),
+            score: 0.0,
+            work_dir: None,
+            unrelated: false,
         }
     }
+
+    /// Creates a menu item for an active session on a non-default server.
+    pub fn on_server(name: String, server: String) -> Self {
+        Self {
+            name,
+            saved: false,
+            active: true,
+            server: Some(server),
+            pinned: false,
+            missing_dirs: false,
+            dirty: false,
+            dead_panes: 0,
+            tags: Vec::new(),
+            score: 0.0,
+            work_dir: None,
+            unrelated: false,
+        }
+    }
+
+    /// Creates the item for a running session whose name is taken by the
+    /// saved config of another project, running in `work_dir`.
+    pub fn unrelated(name: String, work_dir: String) -> Self {
+        Self {
+            work_dir: Some(work_dir),
+            unrelated: true,
+            ..Self::new(name, false, true)
+        }
+    }
+
+    /// Returns whether `other` refers to the same session (name and server,
+    /// and which side of a name clash it is).
+    pub fn is_same(&self, other: &MenuItem) -> bool {
+        self.name == other.name
+            && self.server == other.server
+            && self.unrelated == other.unrelated
+    }
 }
 
 impl fmt::Display for MenuItem {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let saved_indicator = if !self.saved { "* " } else { "" };
         let active_indicator = if self.active { " (active)" } else { "" };
+        let server_prefix = match &self.server {
+            Some(server) => format!("[{server}] "),
+            None => String::new(),
+        };
 
-        write!(f, "{}{}{}", saved_indicator, self.name, active_indicator)
+        write!(
+            f,
+            "{}{}{}{}",
+            saved_indicator, server_prefix, self.name, active_indicator
+        )
     }
 }
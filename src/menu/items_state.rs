@@ -1,15 +1,57 @@
+use std::collections::{HashMap, HashSet};
+
 use fuzzy_matcher::{FuzzyMatcher, skim::SkimMatcherV2};
 use ratatui::widgets::ListState;
 
+use crate::config::{GroupBy, ListFilter};
 use crate::menu::item::MenuItem;
 
+/// A row of the results list.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Row {
+    /// Header of the section at this index in [`ItemsState::groups`].
+    Header(usize),
+    /// The item at this index in [`ItemsState::filtered_items_idx`].
+    Item(usize),
+}
+
+/// A section of the results list when items are grouped.
+#[derive(Debug)]
+pub struct Group {
+    pub title: String,
+    /// Number of filtered items in the section, shown even when collapsed.
+    pub len: usize,
+    pub collapsed: bool,
+}
+
+/// What a row displays, as returned by [`ItemsState::get_rows`].
+pub enum RowView<'a> {
+    Header(&'a Group),
+    Item(&'a MenuItem, &'a [usize]),
+}
+
 /// Manages the item list, fuzzy filtering, and selection cursor.
 pub struct ItemsState {
     pub items: Vec<MenuItem>,
     pub filtered_items_idx: Vec<(usize, Vec<usize>)>,
+    /// Section headers, only when grouped.
+    pub groups: Vec<Group>,
+    /// What is listed, top to bottom; the selection indexes this.
+    pub rows: Vec<Row>,
     pub list_state: ListState,
+    /// Rows that fit in the list, updated on each draw; a page jump moves
+    /// by this many.
+    pub page_size: usize,
 
     matcher: SkimMatcherV2,
+    /// Alias -> session name; typing an alias lists its session first.
+    aliases: HashMap<String, String>,
+    list_filter: ListFilter,
+    group_by: GroupBy,
+    /// Titles of the collapsed sections.
+    collapsed: HashSet<String>,
+    /// Whether stepping past either end continues at the other.
+    wrap_around: bool,
 }
 
 impl ItemsState {
@@ -22,7 +64,9 @@ impl ItemsState {
         sort_items(&mut items);
 
         if let Some(name) = current_name
-            && let Some(idx) = items.iter().position(|i| i.name == name)
+            && let Some(idx) = items
+                .iter()
+                .position(|i| i.name == name && i.server.is_none())
         {
             list_state.select(Some(idx));
         }
@@ -31,9 +75,17 @@ impl ItemsState {
             filtered_items_idx: (0..items.len())
                 .map(|i| (i, Vec::new()))
                 .collect(),
+            groups: Vec::new(),
+            rows: Vec::new(),
             items,
             list_state,
+            page_size: 1,
             matcher: fuzzy_matcher::skim::SkimMatcherV2::default(),
+            aliases: HashMap::new(),
+            list_filter: ListFilter::All,
+            group_by: GroupBy::None,
+            collapsed: HashSet::new(),
+            wrap_around: false,
         };
 
         state.update_filter("");
@@ -41,33 +93,44 @@ impl ItemsState {
         state
     }
 
-    /// Returns the selected item's filtered index and a clone of it.
+    /// Returns the selected row's index and a clone of its item, or `None`
+    /// if a section header is selected.
     pub fn get_selected_item(&self) -> Option<(usize, MenuItem)> {
         let idx = self.list_state.selected()?;
-        let &(item_idx, _) = self.filtered_items_idx.get(idx)?;
+        let Row::Item(pos) = *self.rows.get(idx)? else {
+            return None;
+        };
+        let &(item_idx, _) = self.filtered_items_idx.get(pos)?;
         let item = self.items.get(item_idx)?.clone();
         Some((idx, item))
     }
 
-    /// Returns references to items and their fuzzy match indices.
-    pub fn get_filtered_items(&self) -> Vec<(&MenuItem, &[usize])> {
-        self.filtered_items_idx
+    /// Returns up to `len` rows starting at `start`, with the fuzzy match
+    /// indices of their items.
+    pub fn get_rows(&self, start: usize, len: usize) -> Vec<RowView<'_>> {
+        self.rows
             .iter()
-            .map(|(idx, indices)| {
-                (self.items.get(*idx).unwrap(), indices.as_slice())
+            .skip(start)
+            .take(len)
+            .map(|row| match *row {
+                Row::Header(group) => RowView::Header(&self.groups[group]),
+                Row::Item(pos) => {
+                    let (idx, indices) = &self.filtered_items_idx[pos];
+                    RowView::Item(&self.items[*idx], indices.as_slice())
+                }
             })
             .collect()
     }
 
-    /// Updates fields of the item matching `name`. `None` fields are left unchanged.
+    /// Updates fields of the item matching `target`. `None` fields are left unchanged.
     pub fn update_item(
         &mut self,
-        name: &str,
+        target: &MenuItem,
         saved: Option<bool>,
         active: Option<bool>,
         new_name: Option<&str>,
     ) {
-        if let Some(item) = self.items.iter_mut().find(|i| i.name == name) {
+        if let Some(item) = self.items.iter_mut().find(|i| i.is_same(target)) {
             if let Some(saved_val) = saved {
                 item.saved = saved_val;
             }
@@ -80,39 +143,177 @@ impl ItemsState {
         }
     }
 
-    /// Re-sorts items by active status and name.
+    /// Sets whether `target`'s config has missing working directories.
+    pub fn set_missing_dirs(&mut self, target: &MenuItem, missing: bool) {
+        if let Some(item) = self.items.iter_mut().find(|i| i.is_same(target)) {
+            item.missing_dirs = missing;
+        }
+    }
+
+    /// Re-sorts items by pin, active status, usage score and name.
     pub fn sort(&mut self) {
         sort_items(&mut self.items);
     }
 
     /// Moves the selection cursor by `delta`, clamped to list bounds.
     pub fn move_selection(&mut self, delta: i32) {
-        if let Some(selection_idx) = self.list_state.selected() {
-            let new_selected =
-                usize::try_from((selection_idx as i32 + delta).max(0))
-                    .unwrap_or(0);
-            self.list_state.select(Some(
-                new_selected
-                    .min(self.filtered_items_idx.len().saturating_sub(1)),
-            ));
+        let Some(selection_idx) = self.list_state.selected() else {
+            return;
+        };
+        let last = self.rows.len().saturating_sub(1) as i32;
+        let target = selection_idx as i32 + delta;
+
+        // Only single steps wrap, so a page jump stops at the ends.
+        let new_selected = match target {
+            t if t < 0 && self.wrap_around && delta == -1 => last,
+            t if t > last && self.wrap_around && delta == 1 => 0,
+            t => t.clamp(0, last),
+        };
+        self.list_state.select(Some(new_selected as usize));
+    }
+
+    /// Moves the selection a page up (`pages < 0`) or down.
+    pub fn move_page(&mut self, pages: i32) {
+        self.move_selection(pages.saturating_mul(self.page_size.max(1) as i32));
+    }
+
+    /// Selects the first row.
+    pub fn select_first(&mut self) {
+        if !self.rows.is_empty() {
+            self.list_state.select(Some(0));
+        }
+    }
+
+    /// Selects the last row.
+    pub fn select_last(&mut self) {
+        if let Some(last) = self.rows.len().checked_sub(1) {
+            self.list_state.select(Some(last));
         }
     }
 
-    /// Removes an item by name and adjusts the selection.
+    /// Makes single steps past either end continue at the other.
+    pub fn set_wrap_around(&mut self, wrap_around: bool) {
+        self.wrap_around = wrap_around;
+    }
+
+    /// Adds an item unless one with the same name and server already exists.
+    pub fn add_item(&mut self, item: MenuItem) {
+        if !self.items.iter().any(|i| i.is_same(&item)) {
+            self.items.push(item);
+            sort_items(&mut self.items);
+        }
+    }
+
+    /// Removes an item by name and server and adjusts the selection.
     pub fn remove_item(&mut self, idx: usize, item: MenuItem) {
-        self.items.retain(|i| i.name != item.name);
-        let new_len = self.filtered_items_idx.len().saturating_sub(1);
+        self.items.retain(|i| !i.is_same(&item));
+        let new_len = self.rows.len().saturating_sub(1);
         self.list_state
             .select(Some(idx.min(new_len.saturating_sub(1))));
     }
 
+    /// Reconciles the items of `server` with the sessions running on it:
+    /// marks running ones active, drops unsaved ones that are gone and adds
+    /// new ones. The selected item stays selected if it still exists.
+    pub fn sync_active(
+        &mut self,
+        server: Option<&str>,
+        active: &[String],
+        filter: &str,
+    ) {
+        let selected = self.get_selected_item().map(|(_, item)| item);
+
+        self.items.retain_mut(|item| {
+            if item.server.as_deref() != server {
+                return true;
+            }
+            item.active = active.contains(&item.name);
+            item.saved || item.active
+        });
+        // A config stays apart from the unrelated session sharing its name
+        // while that session runs.
+        let clashes: Vec<String> = self
+            .items
+            .iter()
+            .filter(|item| item.unrelated)
+            .map(|item| item.name.clone())
+            .collect();
+        for item in &mut self.items {
+            if item.server.is_none() && item.work_dir.is_some() {
+                if clashes.contains(&item.name) {
+                    item.active = item.unrelated;
+                } else {
+                    item.work_dir = None;
+                }
+            }
+        }
+
+        for name in active {
+            if !self
+                .items
+                .iter()
+                .any(|i| i.server.as_deref() == server && &i.name == name)
+            {
+                self.items.push(match server {
+                    Some(server) => {
+                        MenuItem::on_server(name.clone(), server.to_string())
+                    }
+                    None => MenuItem::new(name.clone(), false, true),
+                });
+            }
+        }
+
+        sort_items(&mut self.items);
+        self.update_filter_keep_selection(selected.as_ref(), filter);
+    }
+
+    /// The running session listed apart from the saved config `name`
+    /// because it belongs to another project, if there is one.
+    pub fn unrelated_to(&self, name: &str) -> Option<&MenuItem> {
+        self.items
+            .iter()
+            .find(|item| item.unrelated && item.name == name)
+    }
+
+    /// Lists the items named `name` as ordinary ones again, once a rename,
+    /// kill or delete has ended their name clash.
+    pub fn end_clash(&mut self, name: &str) {
+        for item in &mut self.items {
+            if item.server.is_none() && item.name == name {
+                item.work_dir = None;
+                item.unrelated = false;
+            }
+        }
+    }
+
+    /// Re-filters items, then selects `selected` again if it is still
+    /// listed.
+    pub fn update_filter_keep_selection(
+        &mut self,
+        selected: Option<&MenuItem>,
+        input: &str,
+    ) {
+        self.update_filter(input);
+
+        let position = selected.and_then(|selected| {
+            self.rows.iter().position(|row| match *row {
+                Row::Item(pos) => {
+                    self.items[self.filtered_items_idx[pos].0].is_same(selected)
+                }
+                Row::Header(_) => false,
+            })
+        });
+        match position {
+            Some(idx) => self.list_state.select(Some(idx)),
+            None => self.move_selection(0),
+        }
+    }
+
     /// Replaces the entire item list, resetting filter and selection.
     pub fn replace_items(&mut self, mut items: Vec<MenuItem>) {
         sort_items(&mut items);
         self.items = items;
-        self.filtered_items_idx =
-            (0..self.items.len()).map(|i| (i, Vec::new())).collect();
-        self.reset_position();
+        self.update_filter_and_reset("");
     }
 
     /// Re-filters items and resets the selection to the top.
@@ -121,34 +322,196 @@ impl ItemsState {
         self.reset_position();
     }
 
+    /// Restricts which items are listed. Call [`Self::update_filter`]
+    /// afterwards to apply it.
+    pub fn set_list_filter(&mut self, filter: ListFilter) {
+        self.list_filter = filter;
+    }
+
+    /// Splits the list into sections. Call [`Self::update_filter`]
+    /// afterwards to apply it.
+    pub fn set_group_by(&mut self, group_by: GroupBy) {
+        self.group_by = group_by;
+    }
+
+    /// Collapses the section the selected row belongs to, or expands it if
+    /// it is collapsed, and selects its header.
+    pub fn toggle_selected_group(&mut self, input: &str) {
+        let Some(selected) = self.list_state.selected() else {
+            return;
+        };
+        if self.rows.is_empty() {
+            return;
+        }
+        let Some(group) = self.rows[..=selected.min(self.rows.len() - 1)]
+            .iter()
+            .rev()
+            .find_map(|row| match *row {
+                Row::Header(group) => Some(group),
+                Row::Item(_) => None,
+            })
+        else {
+            return;
+        };
+
+        let title = self.groups[group].title.clone();
+        if !self.collapsed.remove(&title) {
+            self.collapsed.insert(title);
+        }
+        self.update_filter(input);
+        let header =
+            self.rows.iter().position(|row| *row == Row::Header(group));
+        self.list_state.select(header);
+    }
+
+    /// Sets the aliases the filter resolves.
+    pub fn set_aliases(&mut self, aliases: HashMap<String, String>) {
+        self.aliases = aliases;
+    }
+
     /// Re-filters items by fuzzy-matching against `input`, keeping the current selection.
+    ///
+    /// If `input` is an alias, its session is listed first.
     pub fn update_filter(&mut self, input: &str) {
+        let list_filter = self.list_filter;
+        let listed = |item: &MenuItem| match list_filter {
+            ListFilter::All => true,
+            ListFilter::Active => item.active,
+            ListFilter::Saved => item.saved,
+            ListFilter::Unsaved => item.active && !item.saved,
+        };
+
         if input.is_empty() {
-            self.filtered_items_idx =
-                (0..self.items.len()).map(|i| (i, Vec::new())).collect();
-        } else {
             self.filtered_items_idx = self
                 .items
                 .iter()
                 .enumerate()
-                .filter_map(|(idx, item)| {
-                    self.matcher
-                        .fuzzy_indices(&item.name, input)
-                        .map(|(_, indices)| (idx, indices))
+                .filter(|(_, item)| listed(item))
+                .map(|(i, _)| (i, Vec::new()))
+                .collect();
+        } else {
+            let aliased = self.aliases.get(input).and_then(|name| {
+                self.items.iter().position(|item| {
+                    item.server.is_none() && &item.name == name && listed(item)
                 })
+            });
+            self.filtered_items_idx = aliased
+                .map(|idx| (idx, Vec::new()))
+                .into_iter()
+                .chain(
+                    self.items
+                        .iter()
+                        .enumerate()
+                        .filter(|(idx, item)| {
+                            Some(*idx) != aliased && listed(item)
+                        })
+                        .filter_map(|(idx, item)| {
+                            self.matcher
+                                .fuzzy_indices(&item.name, input)
+                                .map(|(_, indices)| (idx, indices))
+                        }),
+                )
                 .collect();
         }
+
+        self.build_rows();
+    }
+
+    /// Orders the filtered items by section and lays out the rows.
+    fn build_rows(&mut self) {
+        self.groups.clear();
+        if self.group_by == GroupBy::None {
+            self.rows =
+                (0..self.filtered_items_idx.len()).map(Row::Item).collect();
+            return;
+        }
+
+        let titles: Vec<String> = self
+            .filtered_items_idx
+            .iter()
+            .map(|(idx, _)| group_title(&self.items[*idx], self.group_by))
+            .collect();
+        let mut order: Vec<&String> = titles.iter().collect();
+        order.sort_by(|a, b| group_rank(a).cmp(&group_rank(b)).then(a.cmp(b)));
+        order.dedup();
+
+        let mut filtered = Vec::with_capacity(self.filtered_items_idx.len());
+        let mut entries: Vec<_> = std::mem::take(&mut self.filtered_items_idx)
+            .into_iter()
+            .zip(&titles)
+            .map(Some)
+            .collect();
+        self.rows.clear();
+        for title in order {
+            let collapsed = self.collapsed.contains(title);
+            self.rows.push(Row::Header(self.groups.len()));
+            let start = filtered.len();
+            for entry in entries.iter_mut() {
+                if entry.as_ref().is_some_and(|(_, t)| *t == title) {
+                    let (item, _) = entry.take().unwrap();
+                    if !collapsed {
+                        self.rows.push(Row::Item(filtered.len()));
+                    }
+                    filtered.push(item);
+                }
+            }
+            self.groups.push(Group {
+                title: title.clone(),
+                len: filtered.len() - start,
+                collapsed,
+            });
+        }
+        self.filtered_items_idx = filtered;
     }
 
+    /// Selects the first item, or the first row if every section is
+    /// collapsed.
     fn reset_position(&mut self) {
-        if self.filtered_items_idx.is_empty() {
+        let first_item =
+            self.rows.iter().position(|row| matches!(row, Row::Item(_)));
+        if self.rows.is_empty() {
             self.list_state.select(None);
         } else {
-            self.list_state.select(Some(0));
+            self.list_state.select(Some(first_item.unwrap_or(0)));
         }
     }
 }
 
+/// Title of the section `item` is listed under.
+fn group_title(item: &MenuItem, group_by: GroupBy) -> String {
+    match group_by {
+        GroupBy::Status if item.active => "Active".to_string(),
+        GroupBy::Status => "Saved".to_string(),
+        GroupBy::Tag => item
+            .tags
+            .first()
+            .cloned()
+            .unwrap_or_else(|| UNTAGGED.to_string()),
+        GroupBy::None => String::new(),
+    }
+}
+
+const UNTAGGED: &str = "Untagged";
+
+/// Sorts the "Active" section first and "Untagged" last; others go
+/// alphabetically in between.
+fn group_rank(title: &str) -> u8 {
+    match title {
+        "Active" => 0,
+        UNTAGGED => 2,
+        _ => 1,
+    }
+}
+
+/// Groups items by server (default server first), then sorts by pin,
+/// active status, usage score and name.
 fn sort_items(items: &mut [MenuItem]) {
-    items.sort_by(|a, b| b.active.cmp(&a.active).then(a.name.cmp(&b.name)))
+    items.sort_by(|a, b| {
+        a.server
+            .cmp(&b.server)
+            .then(b.pinned.cmp(&a.pinned))
+            .then(b.active.cmp(&a.active))
+            .then(b.score.total_cmp(&a.score))
+            .then(a.name.cmp(&b.name))
+    })
 }
@@ -0,0 +1,345 @@
+//! Background preview generation - previews are built on a worker thread so
+//! slow disks or big configs don't stall drawing.
+use std::io::Read;
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use regex::Regex;
+use shell_escape::escape;
+
+use crate::{
+    menu::item::MenuItem,
+    metadata::{self, SessionMetadata},
+    persistence::{Persistence, StorageKind},
+    template,
+    tmux::{
+        self, charset::Charset, client::SystemTmux,
+        layout::Layout as TmuxLayout, session::Session,
+    },
+};
+
+/// Placeholder shown while a preview is being generated.
+pub const PREVIEW_LOADING: &str = "loading…";
+
+/// How long a `preview_cmd` may run before it is killed.
+const PREVIEW_CMD_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How previews are generated.
+#[derive(Clone, Default)]
+pub struct PreviewOptions {
+    pub charset: Charset,
+    pub icons: bool,
+    /// Shell command replacing the built-in preview, with `{name}`,
+    /// `{config_path}`, `{kind}` and `{server}` placeholders.
+    pub command: Option<String>,
+    /// Layout of a session's built-in preview; see [`render_template`].
+    pub template: Option<String>,
+}
+
+/// Identifies a preview slot in the cache: item name, server, whether it is
+/// an unrelated session and whether it is a layout.
+pub type PreviewKey = (String, Option<String>, bool, bool);
+
+/// What to preview: an item, in which list mode, at which width, and the
+/// modification time of its config when requested.
+#[derive(Clone)]
+pub struct PreviewRequest {
+    pub item: MenuItem,
+    pub is_layout: bool,
+    pub width: usize,
+    pub mtime: Option<SystemTime>,
+}
+
+impl PreviewRequest {
+    pub fn key(&self) -> PreviewKey {
+        (
+            self.item.name.clone(),
+            self.item.server.clone(),
+            self.item.unrelated,
+            self.is_layout,
+        )
+    }
+
+    /// Returns whether both requests would produce the same preview.
+    pub fn matches(&self, other: &PreviewRequest) -> bool {
+        self.item.is_same(&other.item)
+            && self.is_layout == other.is_layout
+            && self.width == other.width
+            && self.mtime == other.mtime
+    }
+}
+
+/// Handle to the preview worker thread. The thread exits once the handle is
+/// dropped.
+pub struct PreviewWorker {
+    requests: Sender<PreviewRequest>,
+    results: Receiver<(PreviewRequest, String)>,
+}
+
+impl PreviewWorker {
+    pub fn spawn(persistence: Persistence, options: PreviewOptions) -> Self {
+        let (requests, request_rx) = mpsc::channel::<PreviewRequest>();
+        let (result_tx, results) = mpsc::channel();
+
+        thread::spawn(move || {
+            while let Ok(mut request) = request_rx.recv() {
+                // Only the latest selection matters; skip superseded ones.
+                if let Some(latest) = request_rx.try_iter().last() {
+                    request = latest;
+                }
+
+                let content = match &options.command {
+                    Some(command) => {
+                        run_preview_cmd(command, &persistence, &request)
+                    }
+                    None => generate(&persistence, &request, &options),
+                };
+                if result_tx.send((request, content)).is_err() {
+                    return;
+                }
+            }
+        });
+
+        Self { requests, results }
+    }
+
+    /// Queues a preview to be generated.
+    pub fn request(&self, request: PreviewRequest) {
+        let _ = self.requests.send(request);
+    }
+
+    /// Returns the previews finished since the last call.
+    pub fn finished(&self) -> Vec<(PreviewRequest, String)> {
+        self.results.try_iter().collect()
+    }
+}
+
+fn generate(
+    persistence: &Persistence,
+    request: &PreviewRequest,
+    options: &PreviewOptions,
+) -> String {
+    let (charset, icons) = (options.charset, options.icons);
+    let name = &request.item.name;
+
+    if request.item.server.is_some() || request.item.unrelated {
+        // Sessions on other servers have no config, and an unrelated one
+        // isn't the config sharing its name; preview them live.
+        let tmux = match &request.item.server {
+            Some(server) => SystemTmux::with_server(server),
+            None => SystemTmux::default(),
+        };
+        tmux::interface::get_session(&tmux, Some(name))
+            .map(|session| match &options.template {
+                Some(template) => {
+                    render_template(template, &session, None, charset, icons)
+                }
+                None => session.get_preview(charset, icons),
+            })
+            .unwrap_or_default()
+    } else if request.is_layout {
+        persistence
+            .load_config(StorageKind::Layout, name)
+            .ok()
+            .and_then(|yaml| serde_yaml::from_str::<TmuxLayout>(&yaml).ok())
+            .map(|layout| layout.get_preview(request.width, charset))
+            .unwrap_or_default()
+    } else if let Some(template) = &options.template {
+        let Ok(session) = persistence.load_session(name) else {
+            return String::new();
+        };
+        let metadata = persistence
+            .open_metadata()
+            .and_then(|store| store.get(name))
+            .ok();
+        render_template(template, &session, metadata.as_ref(), charset, icons)
+    } else {
+        let session = persistence.load_session(name).ok();
+        let tree = session
+            .as_ref()
+            .map(|session| session.get_preview(charset, icons))
+            .unwrap_or_default();
+        let warning = session
+            .as_ref()
+            .map(get_missing_dirs_preview)
+            .unwrap_or_default();
+        format!(
+            "{}{}{}",
+            warning,
+            get_metadata_preview(persistence, name),
+            tree
+        )
+    }
+}
+
+/// Runs the user's `preview_cmd` for the request and returns its output,
+/// both stdout and stderr, with terminal escape sequences removed.
+fn run_preview_cmd(
+    template: &str,
+    persistence: &Persistence,
+    request: &PreviewRequest,
+) -> String {
+    let kind = if request.is_layout {
+        StorageKind::Layout
+    } else {
+        StorageKind::Session
+    };
+    let config_path = request
+        .item
+        .saved
+        .then(|| persistence.get_config_file_path(kind, &request.item.name))
+        .and_then(|path| path.ok())
+        .map(|path| path.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let command = template
+        .replace("{name}", &escape(request.item.name.as_str().into()))
+        .replace("{config_path}", &escape(config_path.into()))
+        .replace("{kind}", kind.noun())
+        .replace(
+            "{server}",
+            &escape(request.item.server.as_deref().unwrap_or("").into()),
+        );
+
+    let child = Command::new("sh")
+        .arg("-c")
+        .arg(format!("exec 2>&1\n{command}"))
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn();
+    let mut child = match child {
+        Ok(child) => child,
+        Err(err) => return format!("Failed to run preview_cmd: {err}"),
+    };
+    let Some(mut stdout) = child.stdout.take() else {
+        return String::new();
+    };
+
+    // Read on another thread so a command that never exits can be killed.
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let mut buf = Vec::new();
+        let _ = stdout.read_to_end(&mut buf);
+        let _ = tx.send(buf);
+    });
+    let output = match rx.recv_timeout(PREVIEW_CMD_TIMEOUT) {
+        Ok(output) => output,
+        Err(_) => {
+            let _ = child.kill();
+            let _ = child.wait();
+            return format!(
+                "preview_cmd gave no output within {}s",
+                PREVIEW_CMD_TIMEOUT.as_secs()
+            );
+        }
+    };
+    let _ = child.wait();
+
+    let escapes = Regex::new(r"\x1b(\[[0-9;?]*[ -/]*[@-~]|\][^\x07]*\x07|.)")
+        .expect("valid regex");
+    escapes
+        .replace_all(&String::from_utf8_lossy(&output), "")
+        .into_owned()
+}
+
+/// Fields a session preview template fills in:
+///
+/// - `{name}`, `{work_dir}`, `{description}`, `{tags}` (comma-separated)
+/// - `{windows}` and `{panes}`: how many the session has
+/// - `{last_opened}`: e.g. `3d ago`, or `never`
+/// - `{missing_dirs}`: the missing working directories note
+/// - `{tree}`: the window and pane tree of the default preview
+///
+/// Sessions on other servers have no metadata, so those fields are empty
+/// for them.
+pub const PREVIEW_FIELDS: &[&str] = &[
+    "name",
+    "work_dir",
+    "description",
+    "tags",
+    "windows",
+    "panes",
+    "last_opened",
+    "missing_dirs",
+    "tree",
+];
+
+/// Fills in a session preview template, see [`PREVIEW_FIELDS`]. The menu
+/// checks the template before it starts; an error is shown as the preview.
+fn render_template(
+    preview_template: &str,
+    session: &Session,
+    metadata: Option<&SessionMetadata>,
+    charset: Charset,
+    icons: bool,
+) -> String {
+    let parts = match template::parse(preview_template, PREVIEW_FIELDS) {
+        Ok(parts) => parts,
+        Err(err) => return format!("Invalid preview_template: {err}"),
+    };
+    template::fill(&parts, |field| match field {
+        "name" => session.name.clone(),
+        "work_dir" => session.work_dir.clone(),
+        "description" => metadata
+            .and_then(|md| md.description.clone())
+            .unwrap_or_default(),
+        "tags" => metadata.map(|md| md.tags.join(", ")).unwrap_or_default(),
+        "windows" => session.windows.len().to_string(),
+        "panes" => session
+            .windows
+            .iter()
+            .map(|w| w.panes.len())
+            .sum::<usize>()
+            .to_string(),
+        "last_opened" => match metadata.and_then(|md| md.last_opened) {
+            Some(at) => metadata::format_age(at, metadata::now()),
+            None => "never".to_string(),
+        },
+        "missing_dirs" => get_missing_dirs_preview(session),
+        "tree" => session.get_preview(charset, icons),
+        _ => unreachable!("checked by template::parse"),
+    })
+}
+
+/// Returns the note about missing working directories shown above a
+/// session's preview, or an empty string if they all exist.
+fn get_missing_dirs_preview(session: &Session) -> String {
+    let missing = session.missing_dirs();
+    if missing.is_empty() {
+        return String::new();
+    }
+
+    let mut note = "\u{26a0} Missing dirs (panes open in $HOME):\n".to_string();
+    for dir in missing {
+        note += &format!("  {dir}\n");
+    }
+    note.push('\n');
+    note
+}
+
+/// Returns the description/tags header shown above a session's preview, or
+/// an empty string if it has neither.
+fn get_metadata_preview(persistence: &Persistence, name: &str) -> String {
+    let Some(md) = persistence
+        .open_metadata()
+        .and_then(|store| store.get(name))
+        .ok()
+    else {
+        return String::new();
+    };
+
+    let mut header = String::new();
+    if let Some(description) = &md.description {
+        header += &format!("{description}\n");
+    }
+    if !md.tags.is_empty() {
+        header += &format!("tags: {}\n", md.tags.join(", "));
+    }
+    if !header.is_empty() {
+        header.push('\n');
+    }
+    header
+}
@@ -2,28 +2,26 @@ use std::rc::Rc;
 
 use ratatui::{
     Frame,
+    buffer::Buffer,
     layout::{Alignment, Constraint, Direction, Flex, Layout, Margin, Rect},
     style::{Color, Modifier, Style},
     text::{Line, Span},
     widgets::{
-        Block, BorderType, Borders, Clear, List, ListItem, ListState,
+        Block, BorderType, Borders, Clear, Gauge, List, ListItem, ListState,
         Paragraph, Scrollbar, ScrollbarOrientation, ScrollbarState, Wrap,
     },
 };
 
+use crate::config::{Compact, GroupBy, Keymap, ListFilter, PreviewPosition};
 use crate::menu::{
-    items_state::ItemsState,
-    state::{ListMode, MenuMode, MenuState},
+    event_handler::{LEADER_CHORDS, LEADER_KEY},
+    item::MenuItem,
+    items_state::{Group, ItemsState, RowView},
+    state::{ListMode, MenuMode, MenuState, WindowEditor},
+    theme::palette,
+    ui_flags::UiFlags,
 };
-
-// Monokai color palette
-const MONOKAI_RED: Color = Color::Rgb(249, 38, 114);
-const MONOKAI_ORANGE: Color = Color::Rgb(253, 151, 31);
-const MONOKAI_GREEN: Color = Color::Rgb(166, 226, 46);
-const MONOKAI_CYAN: Color = Color::Rgb(102, 217, 239);
-const MONOKAI_PURPLE: Color = Color::Rgb(174, 129, 255);
-const MONOKAI_COMMENT: Color = Color::Rgb(117, 113, 94);
-const MONOKAI_FG: Color = Color::Rgb(248, 248, 242);
+use crate::tmux::{charset::Charset, plan::Progress};
 
 struct Theme {
     accent: Color,
@@ -32,41 +30,61 @@ struct Theme {
     prompt: Style,
 }
 
-const SESSIONS_THEME: Theme = Theme {
-    accent: MONOKAI_CYAN,
-    highlight: Style::new().bg(Color::Rgb(26, 74, 90)),
-    border: Style::new().fg(MONOKAI_CYAN),
-    prompt: Style::new().fg(MONOKAI_CYAN),
-};
+/// Sessions are drawn in cyan, layouts in purple.
+fn theme_for(list_mode: &ListMode) -> Theme {
+    let palette = palette();
+    let (accent, highlight) = match list_mode {
+        ListMode::Sessions => (palette.cyan, palette.sessions_highlight),
+        ListMode::Layouts => (palette.purple, palette.layouts_highlight),
+    };
+    Theme {
+        accent,
+        highlight: Style::new().bg(highlight),
+        border: Style::new().fg(accent),
+        prompt: Style::new().fg(accent),
+    }
+}
 
-const LAYOUTS_THEME: Theme = Theme {
-    accent: MONOKAI_PURPLE,
-    highlight: Style::new().bg(Color::Rgb(58, 42, 90)),
-    border: Style::new().fg(MONOKAI_PURPLE),
-    prompt: Style::new().fg(MONOKAI_PURPLE),
-};
+// Nerd Font icons, used with `[menu] use_icons`
+const ICON_ACTIVE: &str = "\u{f111}";
+const ICON_SAVED: &str = "\u{f0c7}";
+const ICON_REMOTE: &str = "\u{f233}";
+const ICON_DIRTY: &str = "\u{f040}";
+const ICON_DEAD: &str = "\u{f057}";
 
-fn theme_for(list_mode: &ListMode) -> &'static Theme {
-    match list_mode {
-        ListMode::Sessions => &SESSIONS_THEME,
-        ListMode::Layouts => &LAYOUTS_THEME,
-    }
+fn subtle_style() -> Style {
+    Style::new().fg(palette().comment)
 }
 
-const SUBTLE_STYLE: Style = Style::new().fg(MONOKAI_COMMENT);
-const POPUP_STYLE: Style =
-    Style::new().fg(MONOKAI_CYAN).bg(Color::Rgb(39, 40, 34));
-const ERROR_POPUP_STYLE: Style =
-    Style::new().fg(MONOKAI_RED).bg(Color::Rgb(39, 40, 34));
-const RENAME_PROMPT_STYLE: Style = Style::new().fg(MONOKAI_ORANGE);
+fn popup_style() -> Style {
+    Style::new().fg(palette().cyan).bg(palette().popup_bg)
+}
+
+fn error_popup_style() -> Style {
+    Style::new().fg(palette().red).bg(palette().popup_bg)
+}
+
+fn rename_prompt_style() -> Style {
+    Style::new().fg(palette().orange)
+}
 
 const PREVIEW_WIDTH_RATIO: u16 = 40;
+
+/// Most sessions the save-on-exit popup lists by name.
+const SAVE_ON_EXIT_LIST_LIMIT: usize = 10;
+
+/// Below this width or height the compact layout is used automatically.
+const COMPACT_WIDTH: u16 = 80;
+const COMPACT_HEIGHT: u16 = 20;
 const MAX_COMPLETION_ROWS: u16 = 8;
 
 const CONFIRMATION_POPUP_WIDTH: u16 = 15;
 
 const HELP_POPUP_WIDTH: u16 = 60;
-const HELP_POPUP_HEIGHT: u16 = 22;
+const HELP_POPUP_HEIGHT: u16 = 30;
+
+/// Width of one column of the leader chord hints.
+const LEADER_HINT_COLUMN_WIDTH: usize = 22;
 
 /// Draws the menu UI to a ratatui [`Frame`].
 pub trait MenuRenderer {
@@ -79,60 +97,121 @@ pub struct DefaultMenuRenderer;
 impl MenuRenderer for DefaultMenuRenderer {
     fn draw(&self, frame: &mut Frame, state: &mut MenuState) {
         let theme = theme_for(&state.list_mode);
-        let chunks = crate_main_layout(frame.area());
-        let content_chunks =
-            create_content_layout(chunks[0], state.ui_flags.show_preview);
+        let area = frame.area();
+        let compact = match state.ui_flags.compact {
+            Compact::Auto => {
+                area.width < COMPACT_WIDTH || area.height < COMPACT_HEIGHT
+            }
+            Compact::Always => true,
+            Compact::Never => false,
+        };
 
-        let left_content_chunks = Layout::default()
-            .direction(Direction::Vertical)
-            .constraints([Constraint::Min(3), Constraint::Length(3)])
-            .split(content_chunks[0]);
+        if compact {
+            draw_compact(frame, state, &theme);
+        } else {
+            draw_full(frame, state, &theme);
+        }
 
-        render_results_list(
+        match &state.mode {
+            MenuMode::ConfirmationPopup => {
+                draw_confirmation_popup(frame, &state.pending_confirmation)
+            }
+            MenuMode::HelpPopup => {
+                draw_help_popup(frame, state.ui_flags.wrap_around)
+            }
+            MenuMode::SaveOnExitPopup => {
+                draw_save_on_exit_popup(frame, &state.sessions_to_save())
+            }
+            MenuMode::ErrorPopup(message) => {
+                draw_message(frame, "Error", message, error_popup_style())
+            }
+            MenuMode::InfoPopup(message) => {
+                draw_message(frame, "Info", message, popup_style())
+            }
+            MenuMode::Normal if state.pending_key == Some(LEADER_KEY) => {
+                draw_leader_hints(frame)
+            }
+            _ => {}
+        }
+
+        if state.ui_flags.charset == Charset::Ascii {
+            asciify(frame.buffer_mut());
+        }
+    }
+}
+
+/// The regular layout: bordered results and filter input, the preview to
+/// the right and a status line at the bottom.
+fn draw_full(frame: &mut Frame, state: &mut MenuState, theme: &Theme) {
+    let chunks = crate_main_layout(frame.area());
+    let content_chunks = create_content_layout(chunks[0], &state.ui_flags);
+
+    let left_content_chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(3), Constraint::Length(3)])
+        .split(content_chunks[0]);
+
+    render_results_list(
+        frame,
+        left_content_chunks[0],
+        &mut state.items,
+        &state.list_mode,
+        &state.ui_flags,
+        theme,
+        false,
+    );
+
+    render_input_field(frame, left_content_chunks[1], state, theme, false);
+    draw_completion_dropdown(frame, left_content_chunks[1], state);
+
+    render_status_bar(frame, chunks[1], state, theme);
+
+    if let Some(editor) = &state.window_editor {
+        draw_window_list(frame, content_chunks[1], editor, theme, false);
+    } else if state.ui_flags.show_preview {
+        let available_width =
+            content_chunks[1].width.saturating_sub(2) as usize;
+        let preview_content = state.get_cached_preview(available_width);
+        draw_preview_pane(
             frame,
-            left_content_chunks[0],
-            &mut state.items,
-            &state.list_mode,
+            content_chunks[1],
+            preview_content,
+            state.preview_scroll,
             theme,
         );
+    }
+}
 
-        render_input_field(frame, left_content_chunks[1], state, theme);
-        draw_completion_dropdown(frame, left_content_chunks[1], state);
+/// The layout for small terminals and popups: no borders or preview, and
+/// the filter input shares the last line with a short status.
+fn draw_compact(frame: &mut Frame, state: &mut MenuState, theme: &Theme) {
+    let [list_area, input_area] =
+        Layout::vertical([Constraint::Min(1), Constraint::Length(1)])
+            .areas(frame.area());
 
-        render_help_hint(
+    if let Some(editor) = &state.window_editor {
+        draw_window_list(frame, list_area, editor, theme, true);
+    } else {
+        render_results_list(
             frame,
-            chunks[1],
+            list_area,
+            &mut state.items,
             &state.list_mode,
-            state
-                .ui_flags
-                .show_key_presses
-                .then(|| state.visible_last_key())
-                .flatten(),
+            &state.ui_flags,
             theme,
+            true,
         );
-
-        if state.ui_flags.show_preview {
-            let available_width =
-                content_chunks[1].width.saturating_sub(2) as usize;
-            let preview_content = state.get_cached_preview(available_width);
-            draw_preview_pane(
-                frame,
-                content_chunks[1],
-                preview_content,
-                state.preview_scroll,
-                theme,
-            );
-        }
-
-        match &state.mode {
-            MenuMode::ConfirmationPopup => {
-                draw_confirmation_popup(frame, &state.pending_confirmation)
-            }
-            MenuMode::HelpPopup => draw_help_popup(frame),
-            MenuMode::ErrorPopup(message) => draw_error(frame, message),
-            _ => {}
-        }
     }
+
+    let status = compact_status(state, theme);
+    let [input_area, status_area] = Layout::horizontal([
+        Constraint::Min(1),
+        Constraint::Length(status.width() as u16),
+    ])
+    .areas(input_area);
+    render_input_field(frame, input_area, state, theme, true);
+    frame.render_widget(Paragraph::new(status), status_area);
+    draw_completion_dropdown(frame, input_area, state);
 }
 
 fn crate_main_layout(area: Rect) -> Rc<[Rect]> {
@@ -142,18 +221,26 @@ fn crate_main_layout(area: Rect) -> Rc<[Rect]> {
         .split(area)
 }
 
-fn create_content_layout(area: Rect, show_preview: bool) -> Rc<[Rect]> {
-    let constrains = if show_preview {
+/// Splits the content area into the results and, if shown, the preview -
+/// to the right or below, as configured.
+fn create_content_layout(area: Rect, ui_flags: &UiFlags) -> Rc<[Rect]> {
+    let (direction, ratio) = match ui_flags.preview_position {
+        PreviewPosition::Right => (Direction::Horizontal, PREVIEW_WIDTH_RATIO),
+        PreviewPosition::Bottom => {
+            (Direction::Vertical, ui_flags.preview_height)
+        }
+    };
+    let constrains = if ui_flags.show_preview {
         vec![
-            Constraint::Percentage(100 - PREVIEW_WIDTH_RATIO),
-            Constraint::Percentage(PREVIEW_WIDTH_RATIO),
+            Constraint::Percentage(100 - ratio),
+            Constraint::Percentage(ratio),
         ]
     } else {
         vec![Constraint::Percentage(100)]
     };
 
     Layout::default()
-        .direction(Direction::Horizontal)
+        .direction(direction)
         .constraints(constrains)
         .split(area)
 }
@@ -163,84 +250,166 @@ fn render_results_list(
     area: Rect,
     items_state: &mut ItemsState,
     list_mode: &ListMode,
+    ui_flags: &UiFlags,
     theme: &Theme,
+    compact: bool,
 ) {
-    let results_block = Block::default()
-        .borders(Borders::ALL)
-        .border_type(BorderType::Rounded)
-        .border_style(theme.border)
-        .title("Results");
+    let title = match (list_mode, ui_flags.list_filter) {
+        (ListMode::Sessions, ListFilter::All) | (ListMode::Layouts, _) => {
+            "Results".to_string()
+        }
+        (ListMode::Sessions, filter) => format!("Results ({})", filter.label()),
+    };
+    let results_block = if compact {
+        Block::default()
+    } else {
+        Block::default()
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .border_style(theme.border)
+            .title(title)
+    };
+    let list_area = results_block.inner(area);
 
-    let filtered = items_state.get_filtered_items();
+    let item_count = items_state.rows.len();
 
-    if filtered.is_empty() {
+    if item_count == 0 {
         frame.render_widget(
             Paragraph::new("No results...")
                 .block(results_block)
-                .style(SUBTLE_STYLE),
+                .style(subtle_style()),
             area,
         );
         return;
     }
 
-    let items: Vec<ListItem> = filtered
-        .iter()
-        .map(|(item, match_indices)| {
-            styled_list_item(item, list_mode, match_indices)
+    // Only build the rows that fit, so drawing cost doesn't grow with the
+    // number of items.
+    let visible_height = list_area.height as usize;
+    items_state.page_size = visible_height;
+    let selected = items_state.list_state.selected().unwrap_or(0);
+    let offset = visible_offset(
+        items_state.list_state.offset(),
+        selected,
+        visible_height,
+        item_count,
+    );
+    *items_state.list_state.offset_mut() = offset;
+
+    let items: Vec<ListItem> = items_state
+        .get_rows(offset, visible_height)
+        .into_iter()
+        .map(|row| match row {
+            RowView::Header(group) => group_header(group),
+            RowView::Item(item, match_indices) => styled_list_item(
+                item,
+                list_mode,
+                match_indices,
+                ui_flags.use_icons,
+            ),
         })
         .collect();
 
-    let item_count = filtered.len();
-
     let list = List::new(items)
         .block(results_block)
         .highlight_style(theme.highlight);
 
-    frame.render_stateful_widget(list, area, &mut items_state.list_state);
+    let mut visible_state =
+        ListState::default().with_selected(Some(selected - offset));
+    frame.render_stateful_widget(list, area, &mut visible_state);
 
-    let visible_height = area.height.saturating_sub(2) as usize;
     if item_count > visible_height {
         let mut scrollbar_state = ScrollbarState::new(item_count)
             .position(items_state.list_state.selected().unwrap_or(0));
         let scrollbar = Scrollbar::new(ScrollbarOrientation::VerticalRight)
-            .style(Style::new().fg(MONOKAI_COMMENT));
+            .style(Style::new().fg(palette().comment));
+        // Drawn over the right border, or the last column without one.
+        let scrollbar_area = Rect {
+            x: area.x,
+            width: area.width,
+            ..list_area
+        };
         frame.render_stateful_widget(
             scrollbar,
-            area.inner(Margin {
-                vertical: 1,
-                horizontal: 0,
-            }),
+            scrollbar_area,
             &mut scrollbar_state,
         );
     }
 }
 
+/// Returns the index of the first visible row: the previous offset, moved
+/// just enough to keep `selected` on screen.
+fn visible_offset(
+    offset: usize,
+    selected: usize,
+    height: usize,
+    count: usize,
+) -> usize {
+    let max_offset = count.saturating_sub(height);
+    let offset = if selected < offset {
+        selected
+    } else if height > 0 && selected >= offset + height {
+        selected + 1 - height
+    } else {
+        offset
+    };
+    offset.min(max_offset)
+}
+
+/// Section header row, e.g. `▾ Active (3)`.
+fn group_header<'a>(group: &Group) -> ListItem<'a> {
+    let marker = if group.collapsed {
+        '\u{25b8}'
+    } else {
+        '\u{25be}'
+    };
+    ListItem::new(Line::from(Span::styled(
+        format!("{marker} {} ({})", group.title, group.len),
+        Style::new()
+            .fg(palette().purple)
+            .add_modifier(Modifier::BOLD),
+    )))
+}
+
 fn styled_list_item<'a>(
-    item: &crate::menu::item::MenuItem,
+    item: &MenuItem,
     list_mode: &ListMode,
     match_indices: &[usize],
+    use_icons: bool,
 ) -> ListItem<'a> {
     let mut spans = Vec::new();
 
     if *list_mode == ListMode::Sessions {
+        let active_marker = if use_icons { ICON_ACTIVE } else { "\u{25cf}" };
         if item.active && item.saved {
             spans.push(Span::styled(
-                "\u{25cf} ",
-                Style::new().fg(MONOKAI_GREEN),
+                format!("{active_marker} "),
+                Style::new().fg(palette().green),
             ));
         } else if item.active {
             spans.push(Span::styled(
-                "\u{25cf} ",
-                Style::new().fg(MONOKAI_ORANGE),
+                format!("{active_marker} "),
+                Style::new().fg(palette().orange),
             ));
+        } else if use_icons && item.saved {
+            spans.push(Span::styled(format!("{ICON_SAVED} "), subtle_style()));
         } else {
             spans.push(Span::raw("  "));
         }
     }
 
+    if let Some(server) = &item.server {
+        let label = if use_icons {
+            format!("{ICON_REMOTE} {server} ")
+        } else {
+            format!("[{server}] ")
+        };
+        spans.push(Span::styled(label, subtle_style()));
+    }
+
     let is_inactive = *list_mode == ListMode::Sessions && !item.active;
     let default_style = if is_inactive {
-        SUBTLE_STYLE
+        subtle_style()
     } else {
         Style::default()
     };
@@ -249,7 +418,7 @@ fn styled_list_item<'a>(
         spans.push(Span::styled(item.name.clone(), default_style));
     } else {
         let match_style =
-            Style::new().fg(MONOKAI_RED).add_modifier(Modifier::BOLD);
+            Style::new().fg(palette().red).add_modifier(Modifier::BOLD);
         for (i, ch) in item.name.chars().enumerate() {
             let s = ch.to_string();
             if match_indices.contains(&i) {
@@ -260,6 +429,35 @@ fn styled_list_item<'a>(
         }
     }
 
+    if let Some(work_dir) = &item.work_dir {
+        spans.push(Span::styled(format!(" ({work_dir})"), subtle_style()));
+    }
+
+    if item.dirty {
+        let marker = if use_icons { ICON_DIRTY } else { "[+]" };
+        spans.push(Span::styled(
+            format!(" {marker}"),
+            Style::new().fg(palette().orange),
+        ));
+    }
+
+    if item.dead_panes > 0 {
+        let marker = if use_icons {
+            format!("{ICON_DEAD} {}", item.dead_panes)
+        } else {
+            format!("[{} dead]", item.dead_panes)
+        };
+        spans.push(Span::styled(
+            format!(" {marker}"),
+            Style::new().fg(palette().red),
+        ));
+    }
+
+    if item.missing_dirs {
+        spans
+            .push(Span::styled(" \u{26a0}", Style::new().fg(palette().orange)));
+    }
+
     ListItem::new(Line::from(spans))
 }
 
@@ -268,6 +466,7 @@ fn render_input_field(
     area: Rect,
     state: &mut MenuState,
     theme: &Theme,
+    compact: bool,
 ) {
     let title;
     let prompt_style;
@@ -276,110 +475,239 @@ fn render_input_field(
     match state.mode {
         MenuMode::Rename => {
             title = "Rename";
-            prompt_style = RENAME_PROMPT_STYLE;
+            prompt_style = rename_prompt_style();
+            input = &state.rename_input;
+        }
+        MenuMode::Duplicate => {
+            title = "Duplicate as";
+            prompt_style = rename_prompt_style();
+            input = &state.rename_input;
+        }
+        MenuMode::RenameWindow => {
+            title = "Rename window";
+            prompt_style = rename_prompt_style();
             input = &state.rename_input;
         }
         MenuMode::CreateFromLayoutName => {
             title = "Session name";
-            prompt_style = RENAME_PROMPT_STYLE;
+            prompt_style = rename_prompt_style();
             input = &state.rename_input;
         }
         MenuMode::CreateFromLayoutWorkdir => {
             title = "Working directory";
-            prompt_style = RENAME_PROMPT_STYLE;
+            prompt_style = rename_prompt_style();
             input = &state.rename_input;
         }
         _ => {
-            title = "Search";
+            title = match state.ui_flags.keymap {
+                Keymap::Vim if state.insert_mode => "Search [insert]",
+                Keymap::Vim => "Search [normal]",
+                Keymap::Emacs => "Search",
+            };
             prompt_style = theme.prompt;
             input = &state.filter_input;
         }
     }
 
-    let input_block = Block::default()
-        .borders(Borders::ALL)
-        .border_type(BorderType::Rounded)
-        .border_style(prompt_style)
-        .title(title);
-
-    frame.render_widget(input_block, area);
-
-    let input_area = area.inner(Margin {
-        horizontal: 1,
-        vertical: 1,
-    });
+    // Without a border to hold the title, other inputs name themselves in
+    // the prompt.
+    let prompt = match state.mode {
+        MenuMode::Rename
+        | MenuMode::Duplicate
+        | MenuMode::RenameWindow
+        | MenuMode::CreateFromLayoutName
+        | MenuMode::CreateFromLayoutWorkdir
+            if compact =>
+        {
+            format!("{title}> ")
+        }
+        _ => "> ".to_string(),
+    };
+    let input_area = if compact {
+        area
+    } else {
+        let input_block = Block::default()
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .border_style(prompt_style)
+            .title(title);
+        let inner = input_block.inner(area);
+        frame.render_widget(input_block, area);
+        inner
+    };
 
     let chunks = Layout::default()
         .direction(Direction::Horizontal)
-        .constraints([Constraint::Length(2), Constraint::Min(1)].as_ref())
+        .constraints([
+            Constraint::Length(prompt.chars().count() as u16),
+            Constraint::Min(1),
+        ])
         .split(input_area);
 
-    let prompt = Paragraph::new("> ").style(prompt_style);
-    frame.render_widget(prompt, chunks[0]);
+    frame.render_widget(Paragraph::new(prompt).style(prompt_style), chunks[0]);
 
     frame.render_widget(input, chunks[1]);
 }
 
-fn render_help_hint(
+/// Result counts, vim mode and pending key, for the compact layout's input
+/// line.
+fn compact_status<'a>(state: &MenuState, theme: &Theme) -> Line<'a> {
+    let mut spans = Vec::new();
+    if let Some(key) = state.pending_key {
+        spans.push(Span::styled(
+            format!("{}- ", pending_key_label(key)),
+            Style::new()
+                .fg(palette().orange)
+                .add_modifier(Modifier::BOLD),
+        ));
+    }
+    if state.ui_flags.keymap == Keymap::Vim && state.mode == MenuMode::Normal {
+        let vim_mode = if state.insert_mode { "I " } else { "N " };
+        spans.push(Span::styled(
+            vim_mode,
+            Style::new().fg(theme.accent).add_modifier(Modifier::BOLD),
+        ));
+    }
+    spans.push(Span::styled(
+        format!(
+            "{}/{} ",
+            state.items.filtered_items_idx.len(),
+            state.items.items.len()
+        ),
+        subtle_style(),
+    ));
+    Line::from(spans)
+}
+
+/// Bottom line: list mode, result counts and list settings on the left,
+/// key hints and the last key pressed on the right.
+fn render_status_bar(
     frame: &mut Frame,
     area: Rect,
-    list_mode: &ListMode,
-    last_key: Option<&str>,
+    state: &MenuState,
     theme: &Theme,
 ) {
     let accent_bold =
         Style::new().fg(theme.accent).add_modifier(Modifier::BOLD);
-    let dim = SUBTLE_STYLE;
-    let key_style = Style::new().fg(MONOKAI_FG);
+    let dim = subtle_style();
+    let key_style = Style::new().fg(palette().fg);
 
-    let mode_label = match list_mode {
-        ListMode::Sessions => "[Sessions]",
-        ListMode::Layouts => "[Layouts]",
-    };
-    let toggle_target = match list_mode {
-        ListMode::Sessions => "Layouts",
-        ListMode::Layouts => "Sessions",
+    let (mode_label, noun, toggle_target) = match state.list_mode {
+        ListMode::Sessions => ("[Sessions]", "sessions", "Layouts"),
+        ListMode::Layouts => ("[Layouts]", "layouts", "Sessions"),
     };
 
-    // Left side: mode + hints
-    let left_spans = vec![
+    let mut left_spans = vec![
         Span::styled(mode_label, accent_bold),
-        Span::styled(" C-l", key_style),
-        Span::styled(format!(": {toggle_target} | "), dim),
-        Span::styled("C-h", key_style),
-        Span::styled(": Help | ", dim),
-        Span::styled("Esc", key_style),
-        Span::styled(": Quit", dim),
+        Span::styled(
+            format!(
+                " {}/{} ",
+                state.items.filtered_items_idx.len(),
+                state.items.items.len()
+            ),
+            key_style,
+        ),
+        Span::styled(noun, dim),
     ];
+    if state.list_mode == ListMode::Sessions {
+        let flags = &state.ui_flags;
+        let mut settings = vec![format!("sort: {}", flags.sort_order.label())];
+        if flags.list_filter != ListFilter::All {
+            settings.push(format!("filter: {}", flags.list_filter.label()));
+        }
+        if flags.group_by != GroupBy::None {
+            settings.push(format!("group: {}", flags.group_by.label()));
+        }
+        left_spans
+            .push(Span::styled(format!(" | {}", settings.join(" | ")), dim));
+    }
+    if state.ui_flags.keymap == Keymap::Vim && state.mode == MenuMode::Normal {
+        let vim_mode = if state.insert_mode {
+            " INSERT"
+        } else {
+            " NORMAL"
+        };
+        left_spans.push(Span::styled(" |", dim));
+        left_spans.push(Span::styled(vim_mode, accent_bold));
+    }
+    if let Some(key) = state.pending_key {
+        left_spans.push(Span::styled(
+            format!(" {}-", pending_key_label(key)),
+            Style::new()
+                .fg(palette().orange)
+                .add_modifier(Modifier::BOLD),
+        ));
+    }
 
-    let hint_line = Line::from(left_spans);
-    let hint = Paragraph::new(hint_line).alignment(Alignment::Center);
-
+    let esc_label = if state.ui_flags.keymap == Keymap::Vim
+        && state.insert_mode
+        && state.mode == MenuMode::Normal
+    {
+        ": Normal "
+    } else {
+        ": Quit "
+    };
+    let mut right_spans = if state.window_editor.is_some() {
+        vec![
+            Span::styled("Enter", key_style),
+            Span::styled(": Open | ", dim),
+            Span::styled("r", key_style),
+            Span::styled(": Rename | ", dim),
+            Span::styled("d", key_style),
+            Span::styled(": Delete | ", dim),
+            Span::styled("S-↑/↓", key_style),
+            Span::styled(": Move | ", dim),
+            Span::styled("Esc", key_style),
+            Span::styled(": Back ", dim),
+        ]
+    } else {
+        vec![
+            Span::styled("C-l", key_style),
+            Span::styled(format!(": {toggle_target} | "), dim),
+            Span::styled("C-h", key_style),
+            Span::styled(": Help | ", dim),
+            Span::styled("Esc", key_style),
+            Span::styled(esc_label, dim),
+        ]
+    };
+    let last_key = state
+        .ui_flags
+        .show_key_presses
+        .then(|| state.visible_last_key())
+        .flatten();
     if let Some(label) = last_key {
-        let chunks = Layout::default()
-            .direction(Direction::Horizontal)
-            .constraints([
-                Constraint::Min(0),
-                Constraint::Length(label.len() as u16 + 5),
-            ])
-            .split(area);
-
-        frame.render_widget(hint, chunks[0]);
-
-        let key_indicator = Paragraph::new(Line::from(vec![
+        right_spans.extend([
             Span::styled("[", dim),
             Span::styled(
-                label,
-                Style::new().fg(MONOKAI_ORANGE).add_modifier(Modifier::BOLD),
+                label.to_string(),
+                Style::new()
+                    .fg(palette().orange)
+                    .add_modifier(Modifier::BOLD),
             ),
             Span::styled("] ", dim),
-        ]))
-        .alignment(Alignment::Right);
-
-        frame.render_widget(key_indicator, chunks[1]);
-    } else {
-        frame.render_widget(hint, area);
+        ]);
     }
+
+    let right_line = Line::from(right_spans);
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Min(0),
+            Constraint::Length(right_line.width() as u16),
+        ])
+        .split(area);
+
+    frame.render_widget(
+        Paragraph::new(Line::from(left_spans)),
+        chunks[0].inner(Margin {
+            horizontal: 1,
+            vertical: 0,
+        }),
+    );
+    frame.render_widget(
+        Paragraph::new(right_line).alignment(Alignment::Right),
+        chunks[1],
+    );
 }
 
 fn draw_preview_pane(
@@ -402,10 +730,61 @@ fn draw_preview_pane(
     frame.render_widget(preview, chunk);
 }
 
+/// Draws the windows of the session being edited in place of the preview,
+/// with the selected one highlighted.
+fn draw_window_list(
+    frame: &mut Frame,
+    area: Rect,
+    editor: &WindowEditor,
+    theme: &Theme,
+    compact: bool,
+) {
+    let block = if compact {
+        Block::default()
+    } else {
+        Block::default()
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .border_style(
+                Style::new().fg(theme.accent).add_modifier(Modifier::BOLD),
+            )
+            .title(format!("Windows of {}", editor.name))
+    };
+
+    let items: Vec<ListItem> = editor
+        .session
+        .windows
+        .iter()
+        .map(|window| {
+            let panes = window.panes.len();
+            ListItem::new(Line::from(vec![
+                Span::styled(format!("{}: ", window.index), subtle_style()),
+                Span::raw(window.name.clone()),
+                Span::styled(
+                    format!(
+                        " ({panes} pane{})",
+                        if panes == 1 { "" } else { "s" }
+                    ),
+                    subtle_style(),
+                ),
+            ]))
+        })
+        .collect();
+
+    let list = List::new(items)
+        .block(block)
+        .highlight_style(theme.highlight);
+    let mut list_state =
+        ListState::default().with_selected(Some(editor.selected));
+    frame.render_stateful_widget(list, area, &mut list_state);
+}
+
 fn draw_confirmation_popup(f: &mut Frame, message: &str) {
     // +4 for left/right borders and one space of padding each side
-    let width = (message.len() as u16 + 4).max(CONFIRMATION_POPUP_WIDTH);
-    let popup_area = create_centered_rect(f.area(), width, 4);
+    let longest = message.lines().map(|l| l.chars().count()).max();
+    let width = (longest.unwrap_or(0) as u16 + 4).max(CONFIRMATION_POPUP_WIDTH);
+    let height = message.lines().count() as u16 + 3;
+    let popup_area = create_centered_rect(f.area(), width, height);
 
     f.render_widget(Clear, popup_area);
 
@@ -413,19 +792,74 @@ fn draw_confirmation_popup(f: &mut Frame, message: &str) {
         .title("Confirm")
         .title_alignment(Alignment::Center)
         .borders(Borders::ALL)
-        .style(POPUP_STYLE);
+        .style(popup_style());
 
-    let text = vec![
-        Line::from(message).alignment(Alignment::Center),
-        Line::from("Y/n").alignment(Alignment::Center),
-    ];
+    let mut text: Vec<Line> = message
+        .lines()
+        .map(|line| Line::from(line).alignment(Alignment::Center))
+        .collect();
+    text.push(Line::from("Y/n").alignment(Alignment::Center));
 
     let paragraph = Paragraph::new(text).block(block);
 
     f.render_widget(paragraph, popup_area);
 }
 
-fn draw_help_popup(f: &mut Frame) {
+/// Lists the sessions that would be lost on exit, with the choices to save
+/// them all, skip saving or stay in the menu.
+fn draw_save_on_exit_popup(f: &mut Frame, sessions: &[&MenuItem]) {
+    let mut text: Vec<Line> = sessions
+        .iter()
+        .take(SAVE_ON_EXIT_LIST_LIMIT)
+        .map(|item| {
+            let state = if item.saved { "modified" } else { "unsaved" };
+            Line::from(vec![
+                Span::raw(item.name.clone()),
+                Span::styled(format!(" ({state})"), subtle_style()),
+            ])
+        })
+        .collect();
+    if sessions.len() > SAVE_ON_EXIT_LIST_LIMIT {
+        text.push(Line::styled(
+            format!(
+                "... and {} more",
+                sessions.len() - SAVE_ON_EXIT_LIST_LIMIT
+            ),
+            subtle_style(),
+        ));
+    }
+    text.push(Line::from(""));
+    text.push(
+        Line::from("a: Save all | s: Skip | Esc: Cancel")
+            .alignment(Alignment::Center),
+    );
+
+    let width = text
+        .iter()
+        .map(|line| line.width() as u16 + 4)
+        .max()
+        .unwrap_or(0)
+        .max(CONFIRMATION_POPUP_WIDTH);
+    let popup_area =
+        create_centered_rect(f.area(), width, text.len() as u16 + 2);
+
+    f.render_widget(Clear, popup_area);
+
+    let block = Block::default()
+        .title("Save before exit?")
+        .title_alignment(Alignment::Center)
+        .borders(Borders::ALL)
+        .style(popup_style());
+
+    f.render_widget(
+        Paragraph::new(text)
+            .block(block)
+            .alignment(Alignment::Center),
+        popup_area,
+    );
+}
+
+fn draw_help_popup(f: &mut Frame, wrap_around: bool) {
     let popup_area =
         create_centered_rect(f.area(), HELP_POPUP_WIDTH, HELP_POPUP_HEIGHT);
 
@@ -434,40 +868,54 @@ fn draw_help_popup(f: &mut Frame) {
     let navigation_block = Block::default()
         .title("Navigation")
         .borders(Borders::ALL)
-        .style(POPUP_STYLE);
+        .style(popup_style());
 
     let session_block = Block::default()
         .title("Session Actions")
         .borders(Borders::ALL)
-        .style(POPUP_STYLE);
+        .style(popup_style());
 
     let ui_block = Block::default()
         .title("UI Controls")
         .borders(Borders::ALL)
-        .style(POPUP_STYLE);
+        .style(popup_style());
 
     let popup_block = Block::default()
         .title("Popup")
         .borders(Borders::ALL)
-        .style(POPUP_STYLE);
+        .style(popup_style());
 
-    let navigation_text = vec![
+    let mut navigation_text = vec![
         Line::from("Esc/C-c → Close"),
         Line::from("↑/C-p   → Previous item"),
         Line::from("↓/C-n   → Next item"),
+        Line::from("PgUp/PgDn → Page up/down"),
+        Line::from("Home/End  → First/last"),
     ];
+    if wrap_around {
+        navigation_text.push(Line::from("↑/↓ wrap at the ends"));
+    }
 
     let session_text = vec![
         Line::from("C-e   → Edit session"),
         Line::from("C-d   → Delete/kill"),
         Line::from("C-s   → Save session"),
         Line::from("C-k   → Kill session"),
+        Line::from("M-k   → Kill unsaved sessions"),
+        Line::from("M-w   → Edit windows"),
+        Line::from("M-d   → Duplicate config"),
         Line::from("C-o   → Reload session"),
+        Line::from("C-y   → Copy config path"),
+        Line::from("M-y   → Copy config YAML"),
         Line::from("Enter → Open session"),
+        Line::from("SPC   → Leader chords"),
     ];
 
     let ui_text = vec![
         Line::from("C-t       → Toggle preview"),
+        Line::from("M-t       → Move preview"),
+        Line::from("C-f       → Cycle session filter"),
+        Line::from("Tab       → Collapse/expand section"),
         Line::from("C-h       → Toggle help"),
         Line::from("C-w       → Delete last word"),
         Line::from("C-u       → Delete to line start"),
@@ -482,7 +930,7 @@ fn draw_help_popup(f: &mut Frame) {
     let completion_block = Block::default()
         .title("Workdir Completion")
         .borders(Borders::ALL)
-        .style(POPUP_STYLE);
+        .style(popup_style());
 
     let completion_text = vec![
         Line::from("Tab / C-n   → Open dropdown / cycle next"),
@@ -494,8 +942,8 @@ fn draw_help_popup(f: &mut Frame) {
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
-            Constraint::Length(8),
-            Constraint::Length(8),
+            Constraint::Length(14),
+            Constraint::Length(10),
             Constraint::Length(6),
         ])
         .split(popup_area);
@@ -529,15 +977,59 @@ fn draw_help_popup(f: &mut Frame) {
     );
 }
 
-fn draw_error(f: &mut Frame, message: &str) {
+/// The pending key as the status line shows it.
+fn pending_key_label(key: char) -> String {
+    if key == LEADER_KEY {
+        "SPC".to_string()
+    } else {
+        key.to_string()
+    }
+}
+
+/// Lists the leader chords in two columns while the leader is pending.
+fn draw_leader_hints(f: &mut Frame) {
+    let rows = LEADER_CHORDS.len().div_ceil(2);
+    let hint = |i: usize| {
+        LEADER_CHORDS
+            .get(i)
+            .map_or(String::new(), |(key, label)| format!("{key}  {label}"))
+    };
+    let text: Vec<Line> = (0..rows)
+        .map(|row| {
+            Line::from(format!(
+                "{:<width$}{}",
+                hint(row),
+                hint(row + rows),
+                width = LEADER_HINT_COLUMN_WIDTH
+            ))
+        })
+        .collect();
+
+    let popup_area = create_centered_rect(
+        f.area(),
+        2 * LEADER_HINT_COLUMN_WIDTH as u16 + 2,
+        rows as u16 + 2,
+    );
+
+    f.render_widget(Clear, popup_area);
+
+    let block = Block::default()
+        .title("SPC-")
+        .borders(Borders::ALL)
+        .style(popup_style());
+
+    f.render_widget(Paragraph::new(text).block(block), popup_area);
+}
+
+fn draw_message(f: &mut Frame, title: &str, message: &str, style: Style) {
     let popup_area = create_centered_rect(f.area(), 30, 10);
 
     f.render_widget(Clear, popup_area);
 
     let block = Block::default()
-        .title("Error")
+        .title(title)
         .borders(Borders::ALL)
-        .style(ERROR_POPUP_STYLE);
+        .style(style);
 
     let paragraph = Paragraph::new(message)
         .block(block)
@@ -546,6 +1038,37 @@ fn draw_error(f: &mut Frame, message: &str) {
     f.render_widget(paragraph.centered(), popup_area);
 }
 
+/// Draws the progress of a session being restored from the menu. Drawn on
+/// its own while the restore blocks the event loop.
+pub fn draw_restore_progress(
+    f: &mut Frame,
+    name: &str,
+    progress: Progress,
+    charset: Charset,
+) {
+    let popup_area = create_centered_rect(f.area(), 50, 3);
+
+    f.render_widget(Clear, popup_area);
+
+    let block = Block::default()
+        .title(format!("Restoring {name}"))
+        .title_alignment(Alignment::Center)
+        .borders(Borders::ALL)
+        .style(popup_style());
+
+    let gauge = Gauge::default()
+        .block(block)
+        .gauge_style(Style::new().fg(palette().green))
+        .ratio(progress.step as f64 / progress.steps.max(1) as f64)
+        .label(progress.to_string());
+
+    f.render_widget(gauge, popup_area);
+
+    if charset == Charset::Ascii {
+        asciify(f.buffer_mut());
+    }
+}
+
 fn draw_completion_dropdown(
     frame: &mut Frame,
     input_area: Rect,
@@ -585,9 +1108,9 @@ fn draw_completion_dropdown(
             Block::default()
                 .borders(Borders::ALL)
                 .border_type(BorderType::Rounded)
-                .border_style(Style::new().fg(MONOKAI_ORANGE)),
+                .border_style(Style::new().fg(palette().orange)),
         )
-        .highlight_style(LAYOUTS_THEME.highlight);
+        .highlight_style(theme_for(&ListMode::Layouts).highlight);
 
     let mut list_state = ListState::default();
     list_state.select(state.completion_idx);
@@ -595,6 +1118,29 @@ fn draw_completion_dropdown(
     frame.render_stateful_widget(list, dropdown_area, &mut list_state);
 }
 
+/// Replaces the box-drawing characters and symbols of a drawn frame with
+/// ASCII ones, for terminals and fonts that render them badly.
+fn asciify(buffer: &mut Buffer) {
+    for cell in &mut buffer.content {
+        let ascii = match cell.symbol() {
+            "─" | "━" | "═" => "-",
+            "│" | "┃" | "║" => "|",
+            "╭" | "╮" | "╰" | "╯" | "┌" | "┐" | "└" | "┘" | "├" | "┤" | "┬"
+            | "┴" | "┼" | "╔" | "╗" | "╚" | "╝" | "╠" | "╣" | "╦" | "╩"
+            | "╬" => "+",
+            "\u{25b8}" | "→" => ">",
+            "\u{25be}" | "↓" => "v",
+            "↑" => "^",
+            "\u{25cf}" => "*",
+            "█" => "#",
+            "…" => ".",
+            "\u{26a0}" => "!",
+            _ => continue,
+        };
+        cell.set_symbol(ascii);
+    }
+}
+
 fn create_centered_rect(area: Rect, length_x: u16, length_y: u16) -> Rect {
     let vertical =
         Layout::vertical([Constraint::Length(length_y)]).flex(Flex::Center);
@@ -1,12 +1,27 @@
+use std::collections::HashMap;
+use std::fs;
+use std::sync::mpsc::Receiver;
 use std::time::{Duration, Instant};
 
 use ratatui::style::Style;
-use tui_textarea::TextArea;
+use tui_textarea::{CursorMove, TextArea};
 
 use crate::{
-    menu::{item::MenuItem, items_state::ItemsState, ui_flags::UiFlags},
+    config::ProjectsConfig,
+    editor::Editor,
+    menu::{
+        action::MenuAction,
+        history::FilterHistory,
+        item::MenuItem,
+        items_state::ItemsState,
+        preview::{
+            PREVIEW_LOADING, PreviewKey, PreviewOptions, PreviewRequest,
+            PreviewWorker,
+        },
+        ui_flags::UiFlags,
+    },
     persistence::{Persistence, StorageKind},
-    tmux::{layout::Layout as TmuxLayout, session::Session},
+    tmux::{self, client::TmuxClient, control::TmuxEvent, session::Session},
 };
 
 /// Whether the menu is showing sessions or layouts.
@@ -21,11 +36,29 @@ pub enum ListMode {
 pub enum MenuMode {
     Normal,
     Rename,
+    /// Asks for the name of a copy of the selected config.
+    Duplicate,
     HelpPopup,
     ConfirmationPopup,
     ErrorPopup(String),
+    InfoPopup(String),
     CreateFromLayoutName,
     CreateFromLayoutWorkdir,
+    /// Asks whether to save modified and unsaved sessions before quitting.
+    SaveOnExitPopup,
+    /// The preview lists the windows of a saved session for editing.
+    Windows,
+    RenameWindow,
+}
+
+/// A saved session whose windows are edited from the preview pane. Every
+/// change is written back to its config right away.
+pub struct WindowEditor {
+    pub name: String,
+    pub session: Session,
+    pub selected: usize,
+    /// Whether tmux renumbers windows, so edits close index gaps too.
+    pub renumber: bool,
 }
 
 /// All mutable state for the menu UI.
@@ -38,10 +71,21 @@ pub struct MenuState<'a> {
     pub list_mode: ListMode,
     pub pending_create_name: String,
     pub pending_confirmation: String,
+    /// Action run when the confirmation popup is accepted.
+    pub confirmed_action: MenuAction,
     pub ui_flags: UiFlags,
     pub preview_scroll: u16,
     pub last_key: Option<String>,
     pub last_key_instant: Option<Instant>,
+    /// Whether typing goes to the filter, with the vim keymap; the emacs
+    /// keymap is always inserting.
+    pub insert_mode: bool,
+    /// First key of a two-key vim command like `dd`.
+    pub pending_key: Option<char>,
+    /// Past filter queries, recalled into the filter input.
+    pub filter_history: FilterHistory,
+    /// The session edited in [`MenuMode::Windows`].
+    pub window_editor: Option<WindowEditor>,
 
     pub should_exit: bool,
 
@@ -49,9 +93,21 @@ pub struct MenuState<'a> {
     pub completion_idx: Option<usize>,
 
     pub persistence: Persistence,
+    pub editor: Editor,
+    /// Where create mode looks for projects to offer as working dirs.
+    pub projects: ProjectsConfig,
+    /// Client for the default tmux server.
+    pub tmux: Box<dyn TmuxClient>,
+    /// Clients for the extra tmux servers whose sessions are listed.
+    pub servers: Vec<Box<dyn TmuxClient>>,
+    /// Live tmux notifications, if watched.
+    pub tmux_events: Option<Receiver<TmuxEvent>>,
 
-    /// Cached preview: (item_name, is_layout_mode, width, content)
-    preview_cache: Option<(String, bool, usize, String)>,
+    /// Previews generated by the worker, with the request they answer.
+    preview_cache: HashMap<PreviewKey, (PreviewRequest, String)>,
+    /// Preview requested from the worker and not yet received.
+    preview_pending: Option<PreviewRequest>,
+    preview_worker: PreviewWorker,
 }
 
 impl<'a> MenuState<'a> {
@@ -60,6 +116,8 @@ impl<'a> MenuState<'a> {
         items: Vec<MenuItem>,
         ui_flags: UiFlags,
         current_session: Option<&str>,
+        tmux: Box<dyn TmuxClient>,
+        servers: Vec<Box<dyn TmuxClient>>,
         persistence: Persistence,
     ) -> Self {
         let mut filter_input = TextArea::default();
@@ -68,26 +126,139 @@ impl<'a> MenuState<'a> {
         let mut rename_input = TextArea::default();
         rename_input.set_cursor_line_style(Style::default());
 
+        let preview_worker = PreviewWorker::spawn(
+            persistence.clone(),
+            PreviewOptions {
+                charset: ui_flags.charset,
+                icons: ui_flags.use_icons,
+                command: ui_flags.preview_cmd.clone(),
+                template: ui_flags.preview_template.clone(),
+            },
+        );
+
+        let mut items = ItemsState::new(items, current_session);
+        items.set_list_filter(ui_flags.list_filter);
+        items.set_group_by(ui_flags.group_by);
+        items.set_wrap_around(ui_flags.wrap_around);
+        let selected = items.get_selected_item().map(|(_, item)| item);
+        items.update_filter_keep_selection(selected.as_ref(), "");
+
         Self {
             filter_input,
             rename_input,
-            items: ItemsState::new(items, current_session),
+            items,
             mode: MenuMode::Normal,
             list_mode: ListMode::Sessions,
             pending_create_name: String::new(),
             pending_confirmation: String::new(),
+            confirmed_action: MenuAction::Nop,
+            window_editor: None,
             ui_flags,
             preview_scroll: 0,
             last_key: None,
             last_key_instant: None,
+            insert_mode: false,
+            pending_key: None,
+            filter_history: FilterHistory::load(&persistence),
             should_exit: false,
             path_completions: Vec::new(),
             completion_idx: None,
             persistence,
-            preview_cache: None,
+            editor: Editor::default(),
+            projects: ProjectsConfig::default(),
+            tmux,
+            servers,
+            tmux_events: None,
+            preview_cache: HashMap::new(),
+            preview_pending: None,
+            preview_worker,
         }
     }
 
+    /// Returns the client for the tmux server `item` lives on.
+    pub fn client_for(&self, item: &MenuItem) -> &dyn TmuxClient {
+        item.server
+            .as_deref()
+            .and_then(|name| {
+                self.servers.iter().find(|s| s.server() == Some(name))
+            })
+            .unwrap_or(&self.tmux)
+            .as_ref()
+    }
+
+    /// Applies pending tmux notifications: re-lists the sessions of servers
+    /// whose sessions changed and drops the cached preview. Returns whether
+    /// anything changed.
+    pub fn handle_tmux_events(&mut self) -> bool {
+        let Some(events) = &self.tmux_events else {
+            return false;
+        };
+        let pending: Vec<TmuxEvent> = events.try_iter().collect();
+        if pending.is_empty() {
+            return false;
+        }
+
+        self.invalidate_preview();
+
+        if self.list_mode == ListMode::Sessions {
+            let filter = self.filter_input.lines().join("\n");
+            let clients = std::iter::once(&self.tmux).chain(&self.servers);
+            for client in clients {
+                let server = client.server();
+                let changed = pending.iter().any(|event| {
+                    matches!(event, TmuxEvent::SessionsChanged { server: s }
+                        if s.as_deref() == server)
+                });
+                if changed
+                    && let Ok(active) =
+                        tmux::interface::list_active_sessions(client.as_ref())
+                {
+                    self.items.sync_active(server, &active, &filter);
+                }
+            }
+        }
+
+        true
+    }
+
+    /// Running sessions on the default server that are unsaved or whose
+    /// windows changed since they were saved.
+    pub fn sessions_to_save(&self) -> Vec<&MenuItem> {
+        if self.list_mode != ListMode::Sessions {
+            return Vec::new();
+        }
+        self.items
+            .items
+            .iter()
+            .filter(|item| {
+                item.active
+                    && item.server.is_none()
+                    && !item.unrelated
+                    && (item.dirty || !item.saved)
+            })
+            .collect()
+    }
+
+    /// Mode a closed popup returns to: the window editor if it is open.
+    pub fn base_mode(&self) -> MenuMode {
+        if self.window_editor.is_some() {
+            MenuMode::Windows
+        } else {
+            MenuMode::Normal
+        }
+    }
+
+    /// Shows `message` in the error popup and records it in the log.
+    pub fn show_error(&mut self, message: String) {
+        log::warn!("{message}");
+        self.mode = MenuMode::ErrorPopup(message);
+    }
+
+    /// Shows `message` in the info popup.
+    pub fn show_info(&mut self, message: String) {
+        self.mode = MenuMode::InfoPopup(message);
+    }
+
     /// Clears the completion dropdown state.
     pub fn clear_completions(&mut self) {
         self.path_completions.clear();
@@ -103,6 +274,19 @@ impl<'a> MenuState<'a> {
         self.last_key_instant = Some(Instant::now());
     }
 
+    /// Forgets the last key once its display window has passed. Returns
+    /// whether it did, i.e. the indicator has to be cleared from the screen.
+    pub fn expire_last_key(&mut self) -> bool {
+        let expired = self.last_key_instant.is_some_and(|instant| {
+            instant.elapsed() >= Self::KEY_DISPLAY_DURATION
+        });
+        if expired {
+            self.last_key = None;
+            self.last_key_instant = None;
+        }
+        expired
+    }
+
     /// Returns the key label if it's still within the display window.
     pub fn visible_last_key(&self) -> Option<&str> {
         match (&self.last_key, self.last_key_instant) {
@@ -119,6 +303,8 @@ impl<'a> MenuState<'a> {
     pub fn get_active_textarea(&mut self) -> &mut TextArea<'a> {
         match self.mode {
             MenuMode::Rename
+            | MenuMode::Duplicate
+            | MenuMode::RenameWindow
             | MenuMode::CreateFromLayoutName
             | MenuMode::CreateFromLayoutWorkdir => &mut self.rename_input,
             _ => &mut self.filter_input,
@@ -137,43 +323,104 @@ impl<'a> MenuState<'a> {
         let text = textarea.lines().join("\n");
         if self.mode == MenuMode::Normal {
             self.items.update_filter_and_reset(&text);
+            self.filter_history.reset();
         }
     }
 
-    /// Returns the preview content for the selected item, using a cache to
-    /// avoid re-loading and re-rendering on every frame.
+    /// Replaces the filter with an older (`back`) or newer query from the
+    /// history, if there is one.
+    pub fn recall_filter(&mut self, back: bool) {
+        let current = self.filter_input.lines().join("\n");
+        let recalled = if back {
+            self.filter_history.prev(&current)
+        } else {
+            self.filter_history.next()
+        };
+        let Some(query) = recalled.map(str::to_string) else {
+            return;
+        };
+
+        self.filter_input = TextArea::new(vec![query.clone()]);
+        self.filter_input.set_cursor_line_style(Style::default());
+        self.filter_input.move_cursor(CursorMove::End);
+        self.items.update_filter_and_reset(&query);
+    }
+
+    /// Records the current filter in the history.
+    pub fn remember_filter(&mut self) {
+        self.filter_history
+            .push(&self.filter_input.lines().join("\n"));
+    }
+
+    /// Returns the preview content for the selected item. Previews are
+    /// generated in the background and cached per item until its config
+    /// file changes; until one arrives, a placeholder is returned.
     pub fn get_cached_preview(&mut self, width: usize) -> String {
+        let Some((_, item)) = self.items.get_selected_item() else {
+            return String::new();
+        };
         let is_layout = self.list_mode == ListMode::Layouts;
-        let name = match self.items.get_selected_item() {
-            Some((_, item)) => item.name,
-            None => return String::new(),
+        let kind = if is_layout {
+            StorageKind::Layout
+        } else {
+            StorageKind::Session
+        };
+        let mtime = item
+            .server
+            .is_none()
+            .then(|| self.persistence.get_config_file_path(kind, &item.name))
+            .and_then(|path| fs::metadata(path.ok()?).ok())
+            .and_then(|metadata| metadata.modified().ok());
+        let request = PreviewRequest {
+            item,
+            is_layout,
+            width,
+            mtime,
         };
 
-        if let Some((ref cn, ci, cw, ref content)) = self.preview_cache
-            && cn == &name
-            && ci == is_layout
-            && cw == width
+        if let Some((cached, content)) = self.preview_cache.get(&request.key())
+            && cached.matches(&request)
         {
             return content.clone();
         }
 
-        let content = if is_layout {
-            self.persistence
-                .load_config(StorageKind::Layout, &name)
-                .ok()
-                .and_then(|yaml| serde_yaml::from_str::<TmuxLayout>(&yaml).ok())
-                .map(|layout| layout.get_preview(width))
-                .unwrap_or_default()
-        } else {
-            self.persistence
-                .load_config(StorageKind::Session, &name)
-                .ok()
-                .and_then(|yaml| serde_yaml::from_str::<Session>(&yaml).ok())
-                .map(|session| session.get_preview())
-                .unwrap_or_default()
-        };
+        if !self
+            .preview_pending
+            .as_ref()
+            .is_some_and(|pending| pending.matches(&request))
+        {
+            self.preview_worker.request(request.clone());
+            self.preview_pending = Some(request);
+        }
+
+        PREVIEW_LOADING.to_string()
+    }
+
+    /// Stores finished previews from the worker. Returns whether any
+    /// arrived.
+    pub fn poll_preview(&mut self) -> bool {
+        let finished = self.preview_worker.finished();
+        let arrived = !finished.is_empty();
+
+        for (request, content) in finished {
+            if self
+                .preview_pending
+                .as_ref()
+                .is_some_and(|pending| pending.matches(&request))
+            {
+                self.preview_pending = None;
+            }
+            self.preview_cache.insert(request.key(), (request, content));
+        }
+
+        arrived
+    }
 
-        self.preview_cache = Some((name, is_layout, width, content.clone()));
-        content
+    /// Drops all cached previews so they are regenerated on the next draw.
+    /// Needed after changes that may not move a config's mtime, like a save
+    /// within the same second or a metadata update.
+    pub fn invalidate_preview(&mut self) {
+        self.preview_cache.clear();
+        self.preview_pending = None;
     }
 }
@@ -0,0 +1,75 @@
+//! Color palettes the menu is drawn with, for dark and light terminals.
+use std::sync::OnceLock;
+
+use ratatui::style::Color;
+
+use crate::config::MenuTheme;
+use crate::terminal_utils;
+
+/// Colors the renderer draws with. Named after their dark-theme (Monokai)
+/// role; the light theme keeps the roles with darker, saturated shades.
+pub struct Palette {
+    pub red: Color,
+    pub orange: Color,
+    pub green: Color,
+    pub cyan: Color,
+    pub purple: Color,
+    /// Dimmed text: hints, labels, unsaved items.
+    pub comment: Color,
+    pub fg: Color,
+    /// Background of popups.
+    pub popup_bg: Color,
+    /// Background of the selected session.
+    pub sessions_highlight: Color,
+    /// Background of the selected layout.
+    pub layouts_highlight: Color,
+}
+
+/// Monokai, for dark terminal backgrounds.
+pub const DARK: Palette = Palette {
+    red: Color::Rgb(249, 38, 114),
+    orange: Color::Rgb(253, 151, 31),
+    green: Color::Rgb(166, 226, 46),
+    cyan: Color::Rgb(102, 217, 239),
+    purple: Color::Rgb(174, 129, 255),
+    comment: Color::Rgb(117, 113, 94),
+    fg: Color::Rgb(248, 248, 242),
+    popup_bg: Color::Rgb(39, 40, 34),
+    sessions_highlight: Color::Rgb(26, 74, 90),
+    layouts_highlight: Color::Rgb(58, 42, 90),
+};
+
+/// For light terminal backgrounds.
+pub const LIGHT: Palette = Palette {
+    red: Color::Rgb(196, 26, 86),
+    orange: Color::Rgb(176, 88, 0),
+    green: Color::Rgb(62, 124, 0),
+    cyan: Color::Rgb(0, 112, 150),
+    purple: Color::Rgb(112, 64, 196),
+    comment: Color::Rgb(118, 116, 104),
+    fg: Color::Rgb(40, 40, 34),
+    popup_bg: Color::Rgb(238, 237, 228),
+    sessions_highlight: Color::Rgb(198, 228, 238),
+    layouts_highlight: Color::Rgb(226, 214, 246),
+};
+
+static PALETTE: OnceLock<&'static Palette> = OnceLock::new();
+
+/// Picks the palette for this run. `Auto` asks the terminal for its
+/// background, so call this before the menu takes over the terminal. Only
+/// the first call has an effect.
+pub fn select(theme: MenuTheme) {
+    let light = match theme {
+        MenuTheme::Auto => {
+            terminal_utils::background_is_light().unwrap_or(false)
+        }
+        MenuTheme::Dark => false,
+        MenuTheme::Light => true,
+    };
+    let _ = PALETTE.set(if light { &LIGHT } else { &DARK });
+}
+
+/// The selected palette; the dark one if [`select`] wasn't called.
+pub fn palette() -> &'static Palette {
+    PALETTE.get().copied().unwrap_or(&DARK)
+}
@@ -1,20 +1,42 @@
+use std::time::Duration;
+
+use crate::config::{
+    Compact, GroupBy, Keymap, ListFilter, PreviewPosition, SortOrder,
+};
+use crate::tmux::charset::Charset;
+
 /// Toggleable UI settings derived from config.
 pub struct UiFlags {
     pub ask_for_confirmation: bool,
     pub show_preview: bool,
+    pub preview_position: PreviewPosition,
+    /// Percent of the height a bottom preview takes.
+    pub preview_height: u16,
     pub show_key_presses: bool,
-}
-
-impl UiFlags {
-    pub fn new(
-        ask_for_confirmation: bool,
-        show_preview: bool,
-        show_key_presses: bool,
-    ) -> Self {
-        Self {
-            ask_for_confirmation,
-            show_preview,
-            show_key_presses,
-        }
-    }
+    pub sort_order: SortOrder,
+    /// Which sessions are listed; layouts are never filtered.
+    pub list_filter: ListFilter,
+    /// How sessions are split into sections; layouts are never grouped.
+    pub group_by: GroupBy,
+    /// Characters the menu and its previews are drawn with.
+    pub charset: Charset,
+    /// Whether items and panes get Nerd Font icons.
+    pub use_icons: bool,
+    /// Shell command replacing the built-in preview.
+    pub preview_cmd: Option<String>,
+    /// Template the built-in session preview is rendered from.
+    pub preview_template: Option<String>,
+    pub keymap: Keymap,
+    /// Whether stepping past either end of the list continues at the other.
+    pub wrap_around: bool,
+    /// How often the menu checks for tmux notifications and finished
+    /// previews while no key is pressed.
+    pub tick_rate: Duration,
+    /// When to use the compact layout.
+    pub compact: Compact,
+    /// Whether quitting asks to save modified and unsaved sessions first.
+    pub prompt_save_on_exit: bool,
+    /// Whether opening a running session that no longer matches its config
+    /// snapshots it first.
+    pub snapshot_on_open: bool,
 }
@@ -0,0 +1,316 @@
+//! Session metadata store - usage stats, tags, pins and descriptions kept in
+//! a SQLite database so the YAML configs stay focused on session structure.
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use rusqlite::{Connection, OptionalExtension, params};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS sessions (
+    name        TEXT PRIMARY KEY,
+    last_opened INTEGER,
+    open_count  INTEGER NOT NULL DEFAULT 0,
+    pinned      INTEGER NOT NULL DEFAULT 0,
+    description TEXT
+);
+CREATE TABLE IF NOT EXISTS tags (
+    name TEXT NOT NULL,
+    tag  TEXT NOT NULL,
+    PRIMARY KEY (name, tag)
+);
+CREATE TABLE IF NOT EXISTS config_hashes (
+    name TEXT PRIMARY KEY,
+    hash TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS settings (
+    key   TEXT PRIMARY KEY,
+    value INTEGER NOT NULL
+);
+";
+
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+const HOUR: u64 = 60 * 60;
+const DAY: u64 = 24 * HOUR;
+const WEEK: u64 = 7 * DAY;
+const MONTH: u64 = 30 * DAY;
+
+/// Metadata tracked for a single saved session.
+#[derive(Debug, Default, Clone)]
+pub struct SessionMetadata {
+    /// Unix timestamp (seconds) of the last open.
+    pub last_opened: Option<u64>,
+    pub open_count: u64,
+    pub pinned: bool,
+    pub description: Option<String>,
+    pub tags: Vec<String>,
+}
+
+impl SessionMetadata {
+    /// Frecency score - open count weighted by how recently the session was
+    /// last opened.
+    pub fn frecency(&self, now: u64) -> f64 {
+        let Some(last_opened) = self.last_opened else {
+            return 0.0;
+        };
+
+        let weight = match now.saturating_sub(last_opened) {
+            age if age < HOUR => 4.0,
+            age if age < DAY => 2.0,
+            age if age < WEEK => 1.0,
+            age if age < MONTH => 0.5,
+            _ => 0.25,
+        };
+
+        self.open_count as f64 * weight
+    }
+}
+
+/// Handle to the metadata database.
+pub struct MetadataStore {
+    conn: Connection,
+}
+
+impl MetadataStore {
+    /// Opens (creating if needed) the database at `path`.
+    pub fn open(path: &Path) -> Result<Self> {
+        let conn = Connection::open(path).with_context(|| {
+            format!("Failed to open metadata database {}", path.display())
+        })?;
+        // Other tsman processes may be writing; wait for them instead of
+        // failing with SQLITE_BUSY.
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        conn.execute_batch(SCHEMA)
+            .context("Failed to initialize metadata database")?;
+        Ok(Self { conn })
+    }
+
+    /// Records that `name` was opened now.
+    pub fn record_open(&self, name: &str) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO sessions (name, last_opened, open_count)
+             VALUES (?1, ?2, 1)
+             ON CONFLICT(name) DO UPDATE SET
+                 last_opened = ?2,
+                 open_count = open_count + 1",
+            params![name, now() as i64],
+        )?;
+        Ok(())
+    }
+
+    /// Pins or unpins `name`. Pinned sessions sort first in the menu.
+    pub fn set_pinned(&self, name: &str, pinned: bool) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO sessions (name, pinned) VALUES (?1, ?2)
+             ON CONFLICT(name) DO UPDATE SET pinned = ?2",
+            params![name, pinned],
+        )?;
+        Ok(())
+    }
+
+    /// Sets (or clears, with `None`) the description of `name`.
+    pub fn set_description(
+        &self,
+        name: &str,
+        description: Option<&str>,
+    ) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO sessions (name, description) VALUES (?1, ?2)
+             ON CONFLICT(name) DO UPDATE SET description = ?2",
+            params![name, description],
+        )?;
+        Ok(())
+    }
+
+    /// Adds `tag` to `name`. Adding an existing tag is a no-op.
+    pub fn add_tag(&self, name: &str, tag: &str) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR IGNORE INTO tags (name, tag) VALUES (?1, ?2)",
+            params![name, tag],
+        )?;
+        Ok(())
+    }
+
+    /// Removes `tag` from `name`.
+    pub fn remove_tag(&self, name: &str, tag: &str) -> Result<()> {
+        self.conn.execute(
+            "DELETE FROM tags WHERE name = ?1 AND tag = ?2",
+            params![name, tag],
+        )?;
+        Ok(())
+    }
+
+    /// Returns the metadata of `name`, or the default if none is recorded.
+    pub fn get(&self, name: &str) -> Result<SessionMetadata> {
+        let mut metadata = self
+            .conn
+            .query_row(
+                "SELECT last_opened, open_count, pinned, description
+                 FROM sessions WHERE name = ?1",
+                params![name],
+                row_to_metadata,
+            )
+            .optional()?
+            .unwrap_or_default();
+
+        let mut stmt = self
+            .conn
+            .prepare("SELECT tag FROM tags WHERE name = ?1 ORDER BY tag")?;
+        metadata.tags = stmt
+            .query_map(params![name], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+
+        Ok(metadata)
+    }
+
+    /// Returns the metadata of every session that has any recorded.
+    pub fn all(&self) -> Result<HashMap<String, SessionMetadata>> {
+        let mut result = HashMap::new();
+
+        let mut stmt = self.conn.prepare(
+            "SELECT name, last_opened, open_count, pinned, description
+             FROM sessions",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row_to_metadata_at(row, 1)?))
+        })?;
+        for row in rows {
+            let (name, metadata) = row?;
+            result.insert(name, metadata);
+        }
+
+        let mut stmt = self
+            .conn
+            .prepare("SELECT name, tag FROM tags ORDER BY tag")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?;
+        for row in rows {
+            let (name, tag) = row?;
+            result.entry(name).or_default().tags.push(tag);
+        }
+
+        Ok(result)
+    }
+
+    /// Records `hash` as the content tsman last wrote to the config of
+    /// `name`.
+    pub fn set_config_hash(&self, name: &str, hash: &str) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO config_hashes (name, hash) VALUES (?1, ?2)
+             ON CONFLICT(name) DO UPDATE SET hash = ?2",
+            params![name, hash],
+        )?;
+        Ok(())
+    }
+
+    /// Returns the hash recorded by [`Self::set_config_hash`] for `name`.
+    pub fn config_hash(&self, name: &str) -> Result<Option<String>> {
+        let hash = self
+            .conn
+            .query_row(
+                "SELECT hash FROM config_hashes WHERE name = ?1",
+                params![name],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(hash)
+    }
+
+    /// Returns every recorded config hash, keyed by session name.
+    pub fn config_hashes(&self) -> Result<HashMap<String, String>> {
+        let mut stmt =
+            self.conn.prepare("SELECT name, hash FROM config_hashes")?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// Returns when tsman started looking for configs changed outside it
+    /// (Unix seconds), recording now if it hadn't yet. Configs last written
+    /// before then may have no hash without being new.
+    pub fn hashing_since(&self) -> Result<u64> {
+        self.conn.execute(
+            "INSERT OR IGNORE INTO settings (key, value)
+             VALUES ('hashing_since', ?1)",
+            params![now() as i64],
+        )?;
+        let since: i64 = self.conn.query_row(
+            "SELECT value FROM settings WHERE key = 'hashing_since'",
+            [],
+            |row| row.get(0),
+        )?;
+        Ok(since as u64)
+    }
+
+    /// Moves all metadata of `old_name` to `new_name`.
+    pub fn rename(&self, old_name: &str, new_name: &str) -> Result<()> {
+        self.remove(new_name)?;
+        self.conn.execute(
+            "UPDATE sessions SET name = ?2 WHERE name = ?1",
+            params![old_name, new_name],
+        )?;
+        self.conn.execute(
+            "UPDATE tags SET name = ?2 WHERE name = ?1",
+            params![old_name, new_name],
+        )?;
+        self.conn.execute(
+            "UPDATE config_hashes SET name = ?2 WHERE name = ?1",
+            params![old_name, new_name],
+        )?;
+        Ok(())
+    }
+
+    /// Forgets all metadata of `name`.
+    pub fn remove(&self, name: &str) -> Result<()> {
+        self.conn
+            .execute("DELETE FROM sessions WHERE name = ?1", params![name])?;
+        self.conn
+            .execute("DELETE FROM tags WHERE name = ?1", params![name])?;
+        self.conn.execute(
+            "DELETE FROM config_hashes WHERE name = ?1",
+            params![name],
+        )?;
+        Ok(())
+    }
+}
+
+/// Current time as a Unix timestamp in seconds.
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Formats the time elapsed since `timestamp` as e.g. `"3d ago"`.
+pub fn format_age(timestamp: u64, now: u64) -> String {
+    let age = now.saturating_sub(timestamp);
+    match age {
+        a if a < 60 => "just now".to_string(),
+        a if a < HOUR => format!("{}m ago", a / 60),
+        a if a < DAY => format!("{}h ago", a / HOUR),
+        a if a < MONTH => format!("{}d ago", a / DAY),
+        a => format!("{}mo ago", a / MONTH),
+    }
+}
+
+fn row_to_metadata(row: &rusqlite::Row) -> rusqlite::Result<SessionMetadata> {
+    row_to_metadata_at(row, 0)
+}
+
+fn row_to_metadata_at(
+    row: &rusqlite::Row,
+    offset: usize,
+) -> rusqlite::Result<SessionMetadata> {
+    Ok(SessionMetadata {
+        last_opened: row
+            .get::<_, Option<i64>>(offset)?
+            .map(|t| t.max(0) as u64),
+        open_count: row.get::<_, i64>(offset + 1)?.max(0) as u64,
+        pinned: row.get(offset + 2)?,
+        description: row.get(offset + 3)?,
+        tags: Vec::new(),
+    })
+}
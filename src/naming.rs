@@ -0,0 +1,97 @@
+//! Session name templates - how tsman names a session it creates for a
+//! directory, e.g. `{repo}-{branch}`.
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::{Result, bail};
+
+use crate::util::validate_session_name;
+
+/// Template used when `[projects] name_template` isn't set.
+pub const DEFAULT_TEMPLATE: &str = "{dir_name}";
+
+/// Longest name [`validate_session_name`] accepts.
+const MAX_NAME_LEN: usize = 30;
+
+/// Expands `template` for the project in `dir`.
+///
+/// Placeholders:
+/// - `{dir_name}` - the directory's name
+/// - `{parent}` - the name of the directory containing it
+/// - `{repo}` - the name of the git repository's root directory, or
+///   `{dir_name}` outside a repository
+/// - `{branch}` - the checked-out git branch, empty outside a repository
+///
+/// Characters a session name can't contain become `-` and the result is cut
+/// to 30 characters before being checked by [`validate_session_name`].
+pub fn session_name(template: &str, dir: &Path) -> Result<String> {
+    let file_name = |path: &Path| {
+        path.file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    };
+
+    let mut expanded = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        expanded += &rest[..start];
+        let Some(len) = rest[start..].find('}') else {
+            bail!("Unclosed '{{' in name template '{template}'");
+        };
+        let placeholder = &rest[start + 1..start + len];
+        expanded += &match placeholder {
+            "dir_name" => file_name(dir),
+            "parent" => dir.parent().map(file_name).unwrap_or_default(),
+            "repo" => git(dir, &["rev-parse", "--show-toplevel"])
+                .map(|root| file_name(Path::new(&root)))
+                .unwrap_or_else(|| file_name(dir)),
+            "branch" => {
+                git(dir, &["branch", "--show-current"]).unwrap_or_default()
+            }
+            _ => bail!(
+                "Unknown placeholder {{{placeholder}}} in name template \
+                 '{template}'"
+            ),
+        };
+        rest = &rest[start + len + 1..];
+    }
+    expanded += rest;
+
+    let sanitized: String = expanded
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    let name: String = sanitized
+        .trim_matches('-')
+        .chars()
+        .take(MAX_NAME_LEN)
+        .collect();
+
+    validate_session_name(&name).map_err(|err| {
+        anyhow::anyhow!(
+            "Name template '{template}' gives an invalid name '{name}' for \
+             {}: {err}",
+            dir.display()
+        )
+    })
+}
+
+/// Runs a git query in `dir`, returning its trimmed output if it succeeded
+/// and printed something.
+fn git(dir: &Path, args: &[&str]) -> Option<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .ok()?;
+    let stdout = String::from_utf8(output.stdout).ok()?;
+    let stdout = stdout.trim();
+    (output.status.success() && !stdout.is_empty()).then(|| stdout.into())
+}
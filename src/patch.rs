@@ -0,0 +1,96 @@
+//! Non-interactive edits of configs: `path=value` assignments and merge
+//! patches, applied to the YAML before it is checked as a config again.
+use serde_yaml::{Mapping, Value};
+
+use crate::error::{Result, TsmanError};
+
+/// Splits `path=value` into the dot-separated path and the value, read as
+/// YAML - so `3`, `true` and `null` aren't strings, and `'3'` is.
+pub fn parse_assignment(assignment: &str) -> Result<(String, Value)> {
+    let Some((path, value)) = assignment.split_once('=') else {
+        return Err(TsmanError::InvalidPatch(format!(
+            "'{assignment}' is not PATH=VALUE"
+        )));
+    };
+    let value = serde_yaml::from_str(value).map_err(|err| {
+        TsmanError::InvalidPatch(format!(
+            "Invalid value in '{assignment}': {err}"
+        ))
+    })?;
+    Ok((path.trim().to_string(), value))
+}
+
+/// Sets the field at `path` in `doc` to `value`. Path segments are mapping
+/// keys or list indices (`windows.0.name`); missing keys are added, and an
+/// index one past the end appends to the list. A `null` removes the key, as
+/// in [`merge`].
+pub fn set(doc: &mut Value, path: &str, value: Value) -> Result<()> {
+    let invalid = |reason: String| {
+        TsmanError::InvalidPatch(format!("Can't set '{path}': {reason}"))
+    };
+    if path.is_empty() {
+        return Err(invalid("empty path".to_string()));
+    }
+
+    let mut node = doc;
+    let mut segments = path.split('.').peekable();
+    while let Some(segment) = segments.next() {
+        if node.is_null() {
+            *node = Value::Mapping(Mapping::new());
+        }
+        node = match node {
+            Value::Mapping(mapping)
+                if value.is_null() && segments.peek().is_none() =>
+            {
+                mapping.remove(segment);
+                return Ok(());
+            }
+            Value::Mapping(mapping) => mapping
+                .entry(Value::String(segment.to_string()))
+                .or_insert(Value::Null),
+            Value::Sequence(items) => {
+                let len = items.len();
+                let idx = segment.parse::<usize>().map_err(|_| {
+                    invalid(format!("'{segment}' is not a list index"))
+                })?;
+                if idx == len {
+                    items.push(Value::Null);
+                }
+                items.get_mut(idx).ok_or_else(|| {
+                    invalid(format!("index {idx} is past the {len} items"))
+                })?
+            }
+            _ => {
+                return Err(invalid(format!(
+                    "'{segment}' is inside a value that is neither a mapping \
+                     nor a list"
+                )));
+            }
+        };
+    }
+    *node = value;
+    Ok(())
+}
+
+/// Merges `patch` into `doc`: mappings are merged key by key, a `null`
+/// removes the key, and any other value - lists included - replaces the
+/// one in `doc`.
+pub fn merge(doc: &mut Value, patch: Value) {
+    let Value::Mapping(patch) = patch else {
+        *doc = patch;
+        return;
+    };
+    if !doc.is_mapping() {
+        *doc = Value::Mapping(Mapping::new());
+    }
+    let Value::Mapping(mapping) = doc else {
+        unreachable!("doc was just made a mapping");
+    };
+    for (key, value) in patch {
+        if value.is_null() {
+            mapping.remove(&key);
+        } else {
+            merge(mapping.entry(key).or_insert(Value::Null), value);
+        }
+    }
+}
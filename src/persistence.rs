@@ -1,29 +1,134 @@
 //! Persistence layer for reading/writing session and layout YAML configs to disk.
-use std::path::PathBuf;
+use std::collections::HashSet;
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::{self, Write};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, UNIX_EPOCH};
 use std::{env, fs};
 
 use anyhow::{Context, Result};
 use dirs::home_dir;
+use serde::de::DeserializeOwned;
 
 use crate::config::StorageConfig;
+use crate::error::TsmanError;
+use crate::metadata::MetadataStore;
+use crate::tmux::session::{Fragment, Session};
+use crate::util::expand_home;
 
 const DEFAULT_SESSION_STORAGE_DIR: &str = ".tsessions";
 const DEFAULT_LAYOUT_STORAGE_DIR: &str = ".tlayouts";
 
+const DEFAULT_DATA_DIR: &str = "tsman";
+const METADATA_DB_FILE: &str = "metadata.db";
+const LOCKS_DIR: &str = "locks";
+const SNAPSHOTS_DIR: &str = "snapshots";
+const HISTORY_DIR: &str = "history";
+const ARCHIVE_DIR: &str = "archive";
+const CONFIG_EXTENSION: &str = "yaml";
+
 const ENV_SESSION_DIR: &str = "TSMAN_CONFIG_STORAGE_DIR";
 const ENV_LAYOUT_DIR: &str = "TSMAN_LAYOUT_STORAGE_DIR";
+const ENV_DATA_DIR: &str = "TSMAN_DATA_DIR";
+
+/// Session configs that appeared in the storage directory outside tsman,
+/// as found by [`Persistence::reconcile_configs`].
+#[derive(Debug, Default)]
+pub struct StorageChanges {
+    /// Old and new names of renamed configs, whose metadata moved along.
+    pub renamed: Vec<(String, String)>,
+    /// Configs and the new copies of them.
+    pub copied: Vec<(String, String)>,
+    /// New configs tsman knew nothing of.
+    pub added: Vec<String>,
+}
+
+impl StorageChanges {
+    pub fn is_empty(&self) -> bool {
+        self.renamed.is_empty()
+            && self.copied.is_empty()
+            && self.added.is_empty()
+    }
+
+    /// One line per change, e.g. `'api' was renamed to 'backend'`.
+    pub fn describe(&self) -> Vec<String> {
+        let renamed = self
+            .renamed
+            .iter()
+            .map(|(old, new)| format!("'{old}' was renamed to '{new}'"));
+        let copied = self
+            .copied
+            .iter()
+            .map(|(source, copy)| format!("'{copy}' is a copy of '{source}'"));
+        let added = self.added.iter().map(|name| format!("'{name}' is new"));
+        renamed.chain(copied).chain(added).collect()
+    }
+}
 
 /// Selects between session and layout storage directories.
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub enum StorageKind {
     Session,
     Layout,
 }
 
+impl StorageKind {
+    fn lock_name(self) -> &'static str {
+        match self {
+            StorageKind::Session => "sessions.lock",
+            StorageKind::Layout => "layouts.lock",
+        }
+    }
+
+    pub fn noun(self) -> &'static str {
+        match self {
+            StorageKind::Session => "session",
+            StorageKind::Layout => "layout",
+        }
+    }
+}
+
+/// Exclusive advisory lock on a config store or a session restore, released
+/// on drop.
+///
+/// Hold it across read-modify-write sequences (save, rename, delete) so
+/// concurrent tsman processes don't interleave them.
+pub struct StoreLock {
+    _file: File,
+}
+
+/// Which snapshots of a session are kept: at most the newest `keep`, none
+/// older than `max_age_days`. Either limit may be unset; `keep` can't be 0,
+/// which would remove every snapshot.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SnapshotRetention {
+    pub keep: Option<NonZeroUsize>,
+    pub max_age_days: Option<u64>,
+}
+
+impl SnapshotRetention {
+    pub fn is_unlimited(&self) -> bool {
+        self.keep.is_none() && self.max_age_days.is_none()
+    }
+}
+
 /// Persistence context - resolved storage directories.
+#[derive(Clone)]
 pub struct Persistence {
     sessions_dir: PathBuf,
     layouts_dir: PathBuf,
+    /// Holds tsman's own state (metadata database) rather than configs.
+    data_dir: PathBuf,
+    /// Applied to a session's snapshots each time one is taken.
+    snapshot_retention: SnapshotRetention,
+    /// Whether loading a session config warns if it was changed outside
+    /// tsman.
+    warn_modified: bool,
+    /// Sessions already warned about, so a config loaded several times is
+    /// only reported once.
+    warned: Arc<Mutex<HashSet<String>>>,
 }
 
 impl Persistence {
@@ -39,17 +144,101 @@ impl Persistence {
                 storage.layouts_dir.as_deref(),
                 DEFAULT_LAYOUT_STORAGE_DIR,
             )?,
+            data_dir: resolve_data_dir(storage.data_dir.as_deref())?,
+            snapshot_retention: SnapshotRetention::default(),
+            warn_modified: true,
+            warned: Arc::default(),
         })
     }
 
-    fn dir(&self, kind: StorageKind) -> &PathBuf {
+    /// Prunes each session's snapshots by `retention` after taking one.
+    pub fn with_snapshot_retention(
+        mut self,
+        retention: SnapshotRetention,
+    ) -> Self {
+        self.snapshot_retention = retention;
+        self
+    }
+
+    /// Whether loading a session config warns if it was changed outside
+    /// tsman since it was saved (on by default). Off where the warning
+    /// can't be shown, like the menu, or is reported anyway, like `doctor`.
+    pub fn with_modified_warnings(mut self, warn: bool) -> Self {
+        self.warn_modified = warn;
+        self
+    }
+
+    /// Opens the metadata database in the data directory, creating it if
+    /// needed.
+    pub fn open_metadata(&self) -> Result<MetadataStore> {
+        let dir = self.ensure_data_dir()?;
+        MetadataStore::open(&dir.join(METADATA_DB_FILE))
+    }
+
+    /// Returns the data directory, creating it if needed.
+    pub fn ensure_data_dir(&self) -> Result<PathBuf> {
+        fs::create_dir_all(&self.data_dir).with_context(|| {
+            format!("Failed to create directory {}", self.data_dir.display())
+        })?;
+        Ok(self.data_dir.clone())
+    }
+
+    /// Directory the `kind` configs are stored in. It may not exist yet.
+    pub fn dir(&self, kind: StorageKind) -> &PathBuf {
         match kind {
             StorageKind::Session => &self.sessions_dir,
             StorageKind::Layout => &self.layouts_dir,
         }
     }
 
+    /// Blocks until the exclusive lock on the `kind` store is acquired.
+    pub fn lock(&self, kind: StorageKind) -> Result<StoreLock> {
+        self.lock_file(kind.lock_name())
+    }
+
+    /// Blocks until the exclusive lock on restoring `session_name` is
+    /// acquired, so concurrent `open`s don't both build the session.
+    pub fn lock_restore(&self, session_name: &str) -> Result<StoreLock> {
+        self.lock_file(&format!("restore-{session_name}.lock"))
+    }
+
+    fn lock_file(&self, name: &str) -> Result<StoreLock> {
+        let dir = self.ensure_data_dir()?.join(LOCKS_DIR);
+        fs::create_dir_all(&dir).with_context(|| {
+            format!("Failed to create directory {}", dir.display())
+        })?;
+
+        let path = dir.join(name);
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)
+            .with_context(|| {
+                format!("Failed to open lock file {}", path.display())
+            })?;
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                log::info!("Waiting for the lock on {}", path.display());
+                file.lock().with_context(|| {
+                    format!("Failed to lock {}", path.display())
+                })?;
+            }
+            Err(TryLockError::Error(err)) => {
+                return Err(err).with_context(|| {
+                    format!("Failed to lock {}", path.display())
+                });
+            }
+        }
+
+        Ok(StoreLock { _file: file })
+    }
+
     /// Writes `data` as `<file_name>.yaml` in the storage directory.
+    ///
+    /// The file is replaced atomically, so readers never see a partially
+    /// written config.
     pub fn save_config(
         &self,
         kind: StorageKind,
@@ -57,21 +246,213 @@ impl Persistence {
         data: String,
     ) -> Result<()> {
         let path = self.get_config_file_path(kind, file_name)?;
-        fs::write(&path, data)?;
+        let dir = self.ensure_dir(kind)?;
+
+        let mut tmp = tempfile::Builder::new()
+            .prefix(".tsman-")
+            .tempfile_in(&dir)?;
+        tmp.write_all(data.as_bytes())?;
+        tmp.persist(&path)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        log::info!("Saved {}", path.display());
+
+        if kind == StorageKind::Session
+            && let Err(err) = self.record_hash(file_name, &data)
+        {
+            log::warn!("Failed to record the hash of '{file_name}': {err:#}");
+        }
         Ok(())
     }
 
+    /// Records the current content of the session config `file_name` as
+    /// written by tsman, e.g. after it was edited through tsman.
+    pub fn record_config_hash(&self, file_name: &str) -> Result<()> {
+        let data = self.load_config(StorageKind::Session, file_name)?;
+        self.record_hash(file_name, &data)
+    }
+
+    /// Whether the session config `file_name` was changed (or truncated)
+    /// since tsman last wrote it. A config tsman has no record of is
+    /// recorded as it is now.
+    pub fn config_modified(&self, file_name: &str) -> Result<bool> {
+        let data = self.load_config(StorageKind::Session, file_name)?;
+        match self.open_metadata()?.config_hash(file_name)? {
+            Some(hash) => Ok(hash != content_hash(&data)),
+            None => {
+                // It may be a renamed config; keep its metadata if so.
+                self.reconcile_configs()?;
+                Ok(false)
+            }
+        }
+    }
+
+    /// Picks up session configs added or renamed outside tsman (by a `git
+    /// pull` or `mv`): a new config with the content of one that is gone
+    /// takes over its metadata, and every new config is recorded as it is
+    /// now. Configs written before tsman kept hashes are recorded without
+    /// being reported.
+    pub fn reconcile_configs(&self) -> Result<StorageChanges> {
+        let metadata = self.open_metadata()?;
+        let hashing_since = metadata.hashing_since()?;
+        let mut hashes = metadata.config_hashes()?;
+        let mut saved = self.list_saved_configs(StorageKind::Session)?;
+        saved.sort();
+
+        // Nothing recorded yet, e.g. on the first run: every config would
+        // show up as new.
+        let first_run = hashes.is_empty();
+        let unknown: Vec<String> = saved
+            .iter()
+            .filter(|name| !hashes.contains_key(*name))
+            .cloned()
+            .collect();
+
+        let mut changes = StorageChanges::default();
+        for name in &unknown {
+            let hash =
+                content_hash(&self.load_config(StorageKind::Session, name)?);
+            let mut same_content = hashes
+                .iter()
+                .filter(|(_, other)| **other == hash)
+                .map(|(other, _)| other.clone())
+                .collect::<Vec<_>>();
+            same_content.sort();
+            let gone = same_content.iter().find(|other| !saved.contains(other));
+
+            if first_run {
+                metadata.set_config_hash(name, &hash)?;
+            } else if let Some(old_name) = gone.cloned() {
+                metadata.rename(&old_name, name)?;
+                log::info!("'{old_name}' was renamed to '{name}'");
+                hashes.remove(&old_name);
+                changes.renamed.push((old_name, name.clone()));
+            } else {
+                metadata.set_config_hash(name, &hash)?;
+                // Saved before tsman hashed configs, e.g. by an older
+                // version, rather than new.
+                if !self.modified_before(name, hashing_since)? {
+                    match same_content.into_iter().next() {
+                        Some(source) => {
+                            changes.copied.push((source, name.clone()))
+                        }
+                        None => changes.added.push(name.clone()),
+                    }
+                }
+            }
+            hashes.insert(name.clone(), hash);
+        }
+        Ok(changes)
+    }
+
+    /// Whether the session config `file_name` was last written before
+    /// `timestamp` (Unix seconds).
+    fn modified_before(&self, file_name: &str, timestamp: u64) -> Result<bool> {
+        let path =
+            self.get_config_file_path(StorageKind::Session, file_name)?;
+        let modified = fs::metadata(&path)
+            .and_then(|meta| meta.modified())
+            .with_context(|| format!("Failed to stat {}", path.display()))?;
+        Ok(modified < UNIX_EPOCH + Duration::from_secs(timestamp))
+    }
+
+    /// Prints a warning, once, if the session config `file_name` was
+    /// changed outside tsman since it was last saved, unless turned off
+    /// with [`Persistence::with_modified_warnings`].
+    pub fn warn_if_modified(&self, file_name: &str) {
+        if !self.warn_modified {
+            return;
+        }
+        match self.config_modified(file_name) {
+            Ok(true) => {
+                let mut warned = self.warned.lock().unwrap();
+                if warned.insert(file_name.to_string()) {
+                    log::warn!("'{file_name}' was changed outside tsman");
+                    eprintln!(
+                        "Warning: the config of '{file_name}' was changed \
+                         outside tsman since it was saved"
+                    );
+                }
+            }
+            Ok(false) => {}
+            Err(err) => {
+                log::warn!(
+                    "Failed to check the config of '{file_name}': {err:#}"
+                )
+            }
+        }
+    }
+
+    fn record_hash(&self, file_name: &str, data: &str) -> Result<()> {
+        self.open_metadata()?
+            .set_config_hash(file_name, &content_hash(data))
+    }
+
     /// Reads `<file_name>.yaml` from the storage directory.
     pub fn load_config(
         &self,
         kind: StorageKind,
         file_name: &str,
     ) -> Result<String> {
-        let path = self.get_config_file_path(kind, file_name)?;
+        let path = self.existing_config_path(kind, file_name)?;
         let data = fs::read_to_string(path)?;
         Ok(data)
     }
 
+    /// Reads `<file_name>.yaml` and deserializes it.
+    pub fn load<T: DeserializeOwned>(
+        &self,
+        kind: StorageKind,
+        file_name: &str,
+    ) -> Result<T> {
+        let path = self.existing_config_path(kind, file_name)?;
+        let yaml = fs::read_to_string(&path)?;
+        let value = serde_yaml::from_str(&yaml).map_err(|source| {
+            TsmanError::InvalidConfig {
+                path,
+                source: source.into(),
+            }
+        })?;
+        Ok(value)
+    }
+
+    /// Reads the session config `file_name` with the windows of the files it
+    /// includes added after its own, as it is restored.
+    pub fn load_session(&self, file_name: &str) -> Result<Session> {
+        let mut session: Session =
+            self.load(StorageKind::Session, file_name)?;
+        self.warn_if_modified(file_name);
+        for fragment in self.load_fragments(&session)? {
+            session.add_fragment(fragment)?;
+        }
+        Ok(session)
+    }
+
+    /// The files `session` includes, in order. A name without an extension
+    /// gets `.yaml`.
+    pub fn load_fragments(&self, session: &Session) -> Result<Vec<Fragment>> {
+        session
+            .include
+            .iter()
+            .map(|file| {
+                let mut path = self.sessions_dir.join(expand_home(file));
+                if path.extension().is_none() {
+                    path.set_extension(CONFIG_EXTENSION);
+                }
+                let yaml = fs::read_to_string(&path).with_context(|| {
+                    format!("Failed to read included file {}", path.display())
+                })?;
+                let fragment: Fragment =
+                    serde_yaml::from_str(&yaml).map_err(|source| {
+                        TsmanError::InvalidConfig {
+                            path: path.clone(),
+                            source: source.into(),
+                        }
+                    })?;
+                Ok(Fragment { path, ..fragment })
+            })
+            .collect()
+    }
+
     /// Returns the base names (without `.yaml`) of all configs in the
     /// storage directory.
     pub fn list_saved_configs(&self, kind: StorageKind) -> Result<Vec<String>> {
@@ -83,6 +464,12 @@ impl Persistence {
         for entry in paths {
             let path = entry?.path();
 
+            if path.extension().and_then(|e| e.to_str())
+                != Some(CONFIG_EXTENSION)
+            {
+                continue;
+            }
+
             let name = path
                 .file_stem()
                 .ok_or_else(|| {
@@ -106,10 +493,207 @@ impl Persistence {
         file_name: &str,
     ) -> Result<PathBuf> {
         let mut path = self.ensure_dir(kind)?;
-        path.push(format!("{file_name}.yaml"));
+        path.push(format!("{file_name}.{CONFIG_EXTENSION}"));
         Ok(path)
     }
 
+    /// Like [`Self::get_config_file_path`], but fails with
+    /// [`TsmanError::ConfigNotFound`] if no such config is saved.
+    pub fn existing_config_path(
+        &self,
+        kind: StorageKind,
+        file_name: &str,
+    ) -> Result<PathBuf> {
+        let path = self.get_config_file_path(kind, file_name)?;
+        if !path.exists() {
+            return Err(TsmanError::ConfigNotFound {
+                kind: kind.noun(),
+                name: file_name.to_string(),
+            }
+            .into());
+        }
+        Ok(path)
+    }
+
+    /// Moves `<file_name>.yaml` into the `archive` subdirectory of the
+    /// storage directory, where it is no longer listed. Returns its new
+    /// path.
+    pub fn archive_config(
+        &self,
+        kind: StorageKind,
+        file_name: &str,
+    ) -> Result<PathBuf> {
+        let path = self.existing_config_path(kind, file_name)?;
+        let archive_dir = self.dir(kind).join(ARCHIVE_DIR);
+        fs::create_dir_all(&archive_dir).with_context(|| {
+            format!("Failed to create directory {}", archive_dir.display())
+        })?;
+
+        let archived =
+            archive_dir.join(format!("{file_name}.{CONFIG_EXTENSION}"));
+        fs::rename(&path, &archived).with_context(|| {
+            format!(
+                "Failed to move {} to {}",
+                path.display(),
+                archived.display()
+            )
+        })?;
+        log::info!("Archived {} to {}", path.display(), archived.display());
+        Ok(archived)
+    }
+
+    /// Directory the saved pane history of `session_name` is kept in, next to
+    /// the session configs. It may not exist yet.
+    pub fn history_dir(&self, session_name: &str) -> PathBuf {
+        self.sessions_dir.join(HISTORY_DIR).join(session_name)
+    }
+
+    /// Deletes the saved pane history of `session_name`, if there is any.
+    pub fn remove_history(&self, session_name: &str) -> Result<()> {
+        let dir = self.history_dir(session_name);
+        if dir.exists() {
+            fs::remove_dir_all(&dir).with_context(|| {
+                format!("Failed to remove {}", dir.display())
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Moves the saved pane history of `old_name`, if there is any, to that
+    /// of `new_name`.
+    pub fn rename_history(&self, old_name: &str, new_name: &str) -> Result<()> {
+        let (from, to) =
+            (self.history_dir(old_name), self.history_dir(new_name));
+        if from.exists() {
+            fs::rename(&from, &to).with_context(|| {
+                format!("Failed to move {} to {}", from.display(), to.display())
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Writes `data` as `snapshots/<session_name>/<timestamp>.yaml` in the
+    /// data directory, or `<timestamp>-<n>.yaml` if one was already taken
+    /// that second, and returns its path. Snapshots hold the live state of
+    /// a running session, kept apart from its saved config.
+    pub fn save_snapshot(
+        &self,
+        session_name: &str,
+        data: String,
+    ) -> Result<PathBuf> {
+        let dir = self
+            .ensure_data_dir()?
+            .join(SNAPSHOTS_DIR)
+            .join(session_name);
+        fs::create_dir_all(&dir).with_context(|| {
+            format!("Failed to create directory {}", dir.display())
+        })?;
+
+        // Snapshots taken within the same second are numbered rather than
+        // written over each other.
+        let taken = crate::metadata::now();
+        let mut n = 0;
+        let (path, mut file) = loop {
+            let stem = match n {
+                0 => taken.to_string(),
+                n => format!("{taken}-{n}"),
+            };
+            let path = dir.join(format!("{stem}.{CONFIG_EXTENSION}"));
+            match File::create_new(&path) {
+                Ok(file) => break (path, file),
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {
+                    n += 1
+                }
+                Err(err) => {
+                    return Err(err).with_context(|| {
+                        format!("Failed to write {}", path.display())
+                    });
+                }
+            }
+        };
+        file.write_all(data.as_bytes())
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        log::info!("Snapshotted '{session_name}' to {}", path.display());
+
+        if !self.snapshot_retention.is_unlimited() {
+            let expired = self
+                .expired_snapshots(Some(session_name), self.snapshot_retention)
+                .and_then(|expired| self.remove_snapshots(&expired));
+            if let Err(err) = expired {
+                log::warn!(
+                    "Couldn't prune snapshots of '{session_name}': {err:#}"
+                );
+            }
+        }
+        Ok(path)
+    }
+
+    /// Snapshots of `session_name`, or of every session, that `retention`
+    /// doesn't keep, newest first. Files not named by a timestamp are left
+    /// alone.
+    pub fn expired_snapshots(
+        &self,
+        session_name: Option<&str>,
+        retention: SnapshotRetention,
+    ) -> Result<Vec<PathBuf>> {
+        let root = self.data_dir.join(SNAPSHOTS_DIR);
+        let dirs = match session_name {
+            Some(name) => vec![root.join(name)],
+            None if root.is_dir() => fs::read_dir(&root)
+                .with_context(|| format!("Failed to read {}", root.display()))?
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.is_dir())
+                .collect(),
+            None => Vec::new(),
+        };
+
+        let now = crate::metadata::now();
+        let max_age = retention
+            .max_age_days
+            .map(|days| days.saturating_mul(86_400));
+        let mut expired = Vec::new();
+        for dir in dirs {
+            let Ok(entries) = fs::read_dir(&dir) else {
+                continue;
+            };
+            let mut snapshots: Vec<((u64, u64), PathBuf)> = entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter_map(|path| {
+                    let taken = snapshot_order(path.file_stem()?.to_str()?)?;
+                    (path.extension()? == CONFIG_EXTENSION)
+                        .then_some((taken, path))
+                })
+                .collect();
+            snapshots.sort_by_key(|(taken, _)| std::cmp::Reverse(*taken));
+
+            for (i, ((taken, _), path)) in snapshots.into_iter().enumerate() {
+                let too_many =
+                    retention.keep.is_some_and(|keep| i >= keep.get());
+                let too_old =
+                    max_age.is_some_and(|age| now.saturating_sub(taken) > age);
+                if too_many || too_old {
+                    expired.push(path);
+                }
+            }
+        }
+        Ok(expired)
+    }
+
+    /// Deletes `snapshots`, then any session snapshot directory left empty.
+    pub fn remove_snapshots(&self, snapshots: &[PathBuf]) -> Result<()> {
+        for path in snapshots {
+            fs::remove_file(path).with_context(|| {
+                format!("Failed to remove {}", path.display())
+            })?;
+            log::info!("Removed snapshot {}", path.display());
+        }
+        for dir in snapshots.iter().filter_map(|path| path.parent()) {
+            // Fails while the directory still holds snapshots.
+            let _ = fs::remove_dir(dir);
+        }
+        Ok(())
+    }
+
     fn ensure_dir(&self, kind: StorageKind) -> Result<PathBuf> {
         let dir = self.dir(kind);
         fs::create_dir_all(dir).with_context(|| {
@@ -119,6 +703,51 @@ impl Persistence {
     }
 }
 
+/// Checksum and length of a config, enough to notice it was changed or cut
+/// short by a sync tool.
+fn content_hash(data: &str) -> String {
+    format!("{:08x}-{}", crc32fast::hash(data.as_bytes()), data.len())
+}
+
+/// When the snapshot named `stem` - `<timestamp>` or `<timestamp>-<n>` - was
+/// taken, as the timestamp and `n`, which orders those taken in one second.
+fn snapshot_order(stem: &str) -> Option<(u64, u64)> {
+    let (taken, n) = stem.split_once('-').unwrap_or((stem, "0"));
+    Some((taken.parse().ok()?, n.parse().ok()?))
+}
+
+/// Reads a file of several sessions: a list of them, or a single session.
+/// `-` reads standard input.
+pub fn read_session_file(path: &Path) -> Result<Vec<Session>> {
+    let yaml = if path == Path::new("-") {
+        io::read_to_string(io::stdin())
+    } else {
+        fs::read_to_string(path)
+    }
+    .with_context(|| format!("Failed to read {}", path.display()))?;
+    let invalid = |source: serde_yaml::Error| TsmanError::InvalidConfig {
+        path: path.to_path_buf(),
+        source: source.into(),
+    };
+    let value: serde_yaml::Value =
+        serde_yaml::from_str(&yaml).map_err(invalid)?;
+    let sessions = if value.is_sequence() {
+        serde_yaml::from_value(value).map_err(invalid)?
+    } else {
+        vec![serde_yaml::from_value(value).map_err(invalid)?]
+    };
+    Ok(sessions)
+}
+
+/// Writes `sessions` to `path` as a list, replacing the file.
+pub fn write_session_file(path: &Path, sessions: &[Session]) -> Result<()> {
+    let yaml = serde_yaml::to_string(sessions)?;
+    fs::write(path, yaml)
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    log::info!("Saved {}", path.display());
+    Ok(())
+}
+
 fn resolve_dir(
     env_var: &str,
     config_override: Option<&std::path::Path>,
@@ -134,3 +763,17 @@ fn resolve_dir(
         .ok_or_else(|| anyhow::anyhow!("Failed to determine HOME directory"))?;
     Ok(home.join(".config").join(default_name))
 }
+
+fn resolve_data_dir(
+    config_override: Option<&std::path::Path>,
+) -> Result<PathBuf> {
+    if let Ok(val) = env::var(ENV_DATA_DIR) {
+        return Ok(PathBuf::from(val));
+    }
+    if let Some(path) = config_override {
+        return Ok(path.to_path_buf());
+    }
+    let data = dirs::data_dir()
+        .ok_or_else(|| anyhow::anyhow!("Failed to determine data directory"))?;
+    Ok(data.join(DEFAULT_DATA_DIR))
+}
@@ -0,0 +1,126 @@
+//! Project discovery - finds the git repositories under the `[projects]`
+//! roots, for `tsman projects` and the menu's create mode.
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use regex::Regex;
+
+use crate::config::ProjectsConfig;
+use crate::glob;
+use crate::util::expand_home;
+
+/// Levels below a root searched when `depth` isn't configured.
+const DEFAULT_DEPTH: usize = 2;
+
+/// Include/exclude globs of a [`ProjectsConfig`], compiled.
+struct Filters {
+    include: Vec<Glob>,
+    exclude: Vec<Glob>,
+}
+
+/// A shell-style glob. Patterns containing a `/` match the path relative to
+/// the root, others only the directory name.
+struct Glob {
+    regex: Regex,
+    whole_path: bool,
+}
+
+impl Glob {
+    fn new(pattern: &str) -> Result<Self> {
+        Ok(Self {
+            regex: glob::path_to_regex(pattern)
+                .with_context(|| format!("Invalid glob '{pattern}'"))?,
+            whole_path: pattern.contains('/'),
+        })
+    }
+
+    fn matches(&self, relative: &Path) -> bool {
+        let subject = if self.whole_path {
+            relative.to_string_lossy()
+        } else {
+            relative
+                .file_name()
+                .map(|name| name.to_string_lossy())
+                .unwrap_or_default()
+        };
+        self.regex.is_match(&subject)
+    }
+}
+
+/// Returns the git repositories found under the configured roots, sorted.
+///
+/// Repositories aren't searched for nested ones, and hidden or excluded
+/// directories are skipped entirely. Roots that don't exist are ignored.
+pub fn discover(config: &ProjectsConfig) -> Result<Vec<PathBuf>> {
+    let compile = |globs: &[String]| -> Result<Vec<Glob>> {
+        globs.iter().map(|glob| Glob::new(glob)).collect()
+    };
+    let filters = Filters {
+        include: compile(&config.include)?,
+        exclude: compile(&config.exclude)?,
+    };
+    let depth = config.depth.unwrap_or(DEFAULT_DEPTH);
+
+    let mut projects = Vec::new();
+    for root in &config.roots {
+        let root = PathBuf::from(expand_home(root));
+        if !root.is_dir() {
+            log::warn!("Project root {} doesn't exist", root.display());
+            continue;
+        }
+
+        if is_repo(&root) {
+            projects.push(root);
+        } else {
+            scan(&root, Path::new(""), depth, &filters, &mut projects);
+        }
+    }
+
+    projects.sort();
+    projects.dedup();
+    Ok(projects)
+}
+
+fn scan(
+    dir: &Path,
+    relative: &Path,
+    depth: usize,
+    filters: &Filters,
+    projects: &mut Vec<PathBuf>,
+) {
+    if depth == 0 {
+        return;
+    }
+    let Ok(entries) = fs::read_dir(dir.join(relative)) else {
+        return;
+    };
+
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let name = entry.file_name();
+        if name.to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let relative = relative.join(&name);
+        let path = dir.join(&relative);
+        if !path.is_dir()
+            || filters.exclude.iter().any(|glob| glob.matches(&relative))
+        {
+            continue;
+        }
+
+        if !is_repo(&path) {
+            scan(dir, &relative, depth - 1, filters, projects);
+        } else if filters.include.is_empty()
+            || filters.include.iter().any(|glob| glob.matches(&relative))
+        {
+            projects.push(path);
+        }
+    }
+}
+
+/// Whether `dir` is a git work tree; `.git` is a file in linked worktrees
+/// and submodules.
+fn is_repo(dir: &Path) -> bool {
+    dir.join(".git").exists()
+}
@@ -0,0 +1,37 @@
+//! Signal handling - SIGINT, SIGTERM and SIGHUP put the terminal back and
+//! remove a half-built restore session before exiting.
+use std::process;
+use std::thread;
+
+use anyhow::{Context, Result};
+use signal_hook::consts::{SIGHUP, SIGINT, SIGTERM};
+use signal_hook::iterator::Signals;
+
+use crate::terminal_utils;
+use crate::tmux::client::{SystemTmux, TmuxClient};
+use crate::tmux::interface::temp_session_name;
+
+/// Starts a thread that cleans up and exits on the first termination
+/// signal, with the conventional `128 + signal` status.
+pub fn install() -> Result<()> {
+    let mut signals = Signals::new([SIGINT, SIGTERM, SIGHUP])
+        .context("Failed to install signal handlers")?;
+
+    thread::spawn(move || {
+        if let Some(signal) = signals.forever().next() {
+            cleanup();
+            process::exit(128 + signal);
+        }
+    });
+
+    Ok(())
+}
+
+fn cleanup() {
+    terminal_utils::reset();
+
+    // Only exists while this process is restoring a session. `=` makes tmux
+    // match the name exactly instead of as a prefix.
+    let target = format!("={}", temp_session_name());
+    let _ = SystemTmux::default().run(&["kill-session", "-t", &target]);
+}
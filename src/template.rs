@@ -0,0 +1,75 @@
+//! `{field}` templates shared by `list --format` and the menu's
+//! `preview_template`, so both read the same placeholders and escapes.
+use anyhow::{Result, bail};
+
+/// A piece of a template: literal text or a field to fill in.
+#[derive(Debug, PartialEq)]
+pub enum Part<'a> {
+    Text(String),
+    Field(&'a str),
+}
+
+/// Splits `template` into text and `{field}`s, turning `\t`, `\n` and `\\`
+/// into a tab, a newline and a backslash. Fails on an unclosed `{` or a
+/// field not in `fields`.
+pub fn parse<'a>(template: &'a str, fields: &[&str]) -> Result<Vec<Part<'a>>> {
+    let mut parts = Vec::new();
+    let mut text = String::new();
+    let mut rest = template;
+    while let Some(c) = rest.chars().next() {
+        rest = &rest[c.len_utf8()..];
+        match c {
+            '\\' => {
+                match rest.chars().next() {
+                    Some('t') => text.push('\t'),
+                    Some('n') => text.push('\n'),
+                    Some('\\') => text.push('\\'),
+                    _ => {
+                        text.push('\\');
+                        continue;
+                    }
+                }
+                rest = &rest[1..];
+            }
+            '{' => {
+                let Some(end) = rest.find('}') else {
+                    bail!("Unclosed '{{' in '{template}'");
+                };
+                let field = &rest[..end];
+                if !fields.contains(&field) {
+                    bail!(
+                        "Unknown field '{{{field}}}' in '{template}'; \
+                         available: {}",
+                        fields.join(", ")
+                    );
+                }
+                if !text.is_empty() {
+                    parts.push(Part::Text(std::mem::take(&mut text)));
+                }
+                parts.push(Part::Field(field));
+                rest = &rest[end + 1..];
+            }
+            c => text.push(c),
+        }
+    }
+    if !text.is_empty() {
+        parts.push(Part::Text(text));
+    }
+    Ok(parts)
+}
+
+/// Joins `parts`, filling in each field with `value(field)`.
+pub fn fill(parts: &[Part], mut value: impl FnMut(&str) -> String) -> String {
+    parts
+        .iter()
+        .map(|part| match part {
+            Part::Text(text) => text.clone(),
+            Part::Field(field) => value(field),
+        })
+        .collect()
+}
+
+/// Whether `parts` use `field`.
+pub fn uses(parts: &[Part], field: &str) -> bool {
+    parts.contains(&Part::Field(field))
+}
@@ -1,4 +1,11 @@
-use std::io;
+use std::env;
+use std::io::{self, IsTerminal, Write};
+use std::os::fd::AsRawFd;
+use std::panic;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Once, OnceLock};
+use std::thread::{self, ThreadId};
+use std::time::{Duration, Instant};
 
 use crossterm::{
     execute,
@@ -11,9 +18,34 @@ use ratatui::{DefaultTerminal, Terminal, prelude::CrosstermBackend};
 
 use anyhow::Result;
 
+/// Whether the terminal is currently in raw mode on the alternate screen.
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+static PANIC_HOOK: Once = Once::new();
+/// Thread drawing the UI; panics in background threads leave the terminal
+/// alone since the UI keeps running.
+static UI_THREAD: OnceLock<ThreadId> = OnceLock::new();
+
+/// How long the terminal gets to report its background color.
+const BACKGROUND_QUERY_TIMEOUT: Duration = Duration::from_millis(150);
+/// How long [`init`] waits for the rest of a background query's replies
+/// that missed [`BACKGROUND_QUERY_TIMEOUT`].
+const LATE_REPLY_TIMEOUT: Duration = Duration::from_millis(500);
+/// Whether a background query timed out with its replies still on the way,
+/// to be drained before they reach the menu as key presses.
+static LATE_REPLY: AtomicBool = AtomicBool::new(false);
+
 /// Enters raw mode and alternate screen. Must be paired with [`restore`].
+///
+/// Also installs a panic hook that puts the terminal back before the panic
+/// message is printed, so a crash doesn't leave the shell unusable.
 pub fn init() -> Result<DefaultTerminal> {
+    install_panic_hook();
+
     enable_raw_mode()?;
+    ACTIVE.store(true, Ordering::SeqCst);
+    if LATE_REPLY.swap(false, Ordering::SeqCst) {
+        read_replies(&mut Vec::new(), LATE_REPLY_TIMEOUT);
+    }
     let mut stdout = io::stdout();
     execute!(stdout, EnterAlternateScreen)?;
     let backend = CrosstermBackend::new(stdout);
@@ -23,7 +55,155 @@ pub fn init() -> Result<DefaultTerminal> {
 
 /// Leaves raw mode and alternate screen.
 pub fn restore(mut terminal: DefaultTerminal) -> Result<()> {
+    ACTIVE.store(false, Ordering::SeqCst);
     disable_raw_mode()?;
     execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
     Ok(())
 }
+
+/// Runs `f` on an initialized terminal and restores it afterwards, also when
+/// `f` returns an error.
+pub fn run<T>(f: impl FnOnce(&mut DefaultTerminal) -> Result<T>) -> Result<T> {
+    let mut terminal = init()?;
+    let result = f(&mut terminal);
+    restore(terminal)?;
+    result
+}
+
+/// Best-effort [`restore`] without the terminal handle, for teardown paths
+/// (panics, signals) that can't reach it. Does nothing if the terminal isn't
+/// in TUI mode.
+pub fn reset() {
+    if ACTIVE.swap(false, Ordering::SeqCst) {
+        let _ = disable_raw_mode();
+        let _ = execute!(io::stdout(), LeaveAlternateScreen);
+    }
+}
+
+fn install_panic_hook() {
+    PANIC_HOOK.call_once(|| {
+        let _ = UI_THREAD.set(thread::current().id());
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if UI_THREAD.get() == Some(&thread::current().id()) {
+                reset();
+            }
+            previous(info);
+        }));
+    });
+}
+
+/// Whether the terminal's background is light: from `COLORFGBG` if the
+/// terminal sets it, else by asking it with OSC 11. `None` if neither
+/// answers, e.g. when not attached to a terminal.
+pub fn background_is_light() -> Option<bool> {
+    if let Some(light) = env::var("COLORFGBG")
+        .ok()
+        .and_then(|value| colorfgbg_is_light(&value))
+    {
+        return Some(light);
+    }
+    if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+        return None;
+    }
+
+    enable_raw_mode().ok()?;
+    let reply = query_background();
+    let _ = disable_raw_mode();
+    osc11_is_light(&reply?)
+}
+
+/// `COLORFGBG` is `<fg>;<bg>` (sometimes with a middle field) in ANSI color
+/// numbers; 7 and the bright colors but 8 are light backgrounds.
+fn colorfgbg_is_light(value: &str) -> Option<bool> {
+    let bg: u8 = value.rsplit(';').next()?.parse().ok()?;
+    Some(bg == 7 || bg >= 9)
+}
+
+/// Sends the OSC 11 query followed by a DA1 (device attributes) query and
+/// reads the replies straight from stdin, giving up after
+/// [`BACKGROUND_QUERY_TIMEOUT`]. Every terminal answers DA1, and in order,
+/// so its reply marks the end of the OSC 11 one or shows there is none.
+/// Reads unbuffered so no key typed meanwhile is kept from the menu beyond
+/// the replies themselves.
+fn query_background() -> Option<String> {
+    let mut stdout = io::stdout();
+    stdout.write_all(b"\x1b]11;?\x1b\\\x1b[c").ok()?;
+    stdout.flush().ok()?;
+
+    let mut reply = Vec::new();
+    if !read_replies(&mut reply, BACKGROUND_QUERY_TIMEOUT) {
+        // A slow terminal answers after all; `init` drops the replies.
+        LATE_REPLY.store(true, Ordering::SeqCst);
+        return None;
+    }
+    Some(String::from_utf8_lossy(&reply).into_owned())
+}
+
+/// Reads stdin into `reply` until it holds the DA1 reply, returning whether
+/// it arrived within `timeout`.
+fn read_replies(reply: &mut Vec<u8>, timeout: Duration) -> bool {
+    let fd = io::stdin().as_raw_fd();
+    let deadline = Instant::now() + timeout;
+    while !has_da1_reply(reply) {
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return false;
+        }
+        let mut poll_fd = libc::pollfd {
+            fd,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        // SAFETY: `poll_fd` is a single valid pollfd.
+        let ready =
+            unsafe { libc::poll(&mut poll_fd, 1, left.as_millis() as i32) };
+        if ready <= 0 {
+            return false;
+        }
+        let mut buf = [0u8; 64];
+        // SAFETY: reads at most `buf.len()` bytes into `buf`.
+        let read =
+            unsafe { libc::read(fd, buf.as_mut_ptr().cast(), buf.len()) };
+        if read <= 0 {
+            return false;
+        }
+        reply.extend_from_slice(&buf[..read as usize]);
+    }
+    true
+}
+
+/// Whether `input` ends with a DA1 reply, `ESC [ ? <attributes> c`.
+fn has_da1_reply(input: &[u8]) -> bool {
+    let Some(attributes) = input.strip_suffix(b"c") else {
+        return false;
+    };
+    let start = attributes
+        .iter()
+        .rposition(|&b| !(b.is_ascii_digit() || b == b';'))
+        .map_or(0, |i| i + 1);
+    attributes[..start].ends_with(b"\x1b[?")
+}
+
+/// Parses an OSC 11 reply like `ESC ]11;rgb:ffff/ffff/dddd ESC \` and
+/// weighs its channels by perceived brightness.
+fn osc11_is_light(reply: &str) -> Option<bool> {
+    let rgb = reply.split("rgb:").nth(1)?;
+    let channels: Vec<f64> = rgb
+        .split('/')
+        .take(3)
+        .map(|channel| {
+            let hex: String = channel
+                .chars()
+                .take_while(|c| c.is_ascii_hexdigit())
+                .take(4)
+                .collect();
+            let max = 16f64.powi(hex.len() as i32) - 1.0;
+            u32::from_str_radix(&hex, 16).ok().map(|v| v as f64 / max)
+        })
+        .collect::<Option<_>>()?;
+    let [r, g, b] = channels[..] else {
+        return None;
+    };
+    Some(0.2126 * r + 0.7152 * g + 0.0722 * b > 0.5)
+}
@@ -0,0 +1,131 @@
+//! End-to-end test harness - a throwaway tmux server on a private socket to
+//! build, capture and restore sessions against without touching the user's
+//! own. Enabled by the `test-harness` feature.
+//!
+//! ```no_run
+//! use tsman::test_support::TestServer;
+//!
+//! let server = TestServer::start()?;
+//! server.new_session("dev", "/tmp")?;
+//! let captured = server.capture("dev")?;
+//! server.kill_session("dev")?;
+//! server.restore(&captured)?;
+//! # Ok::<(), tsman::error::TsmanError>(())
+//! ```
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::error::{Result, TsmanError};
+use crate::tmux::client::{SystemTmux, TmuxClient, TmuxOutput};
+use crate::tmux::interface::{self, RestoreReport};
+use crate::tmux::session::Session;
+
+static NEXT_SERVER: AtomicUsize = AtomicUsize::new(0);
+
+/// A tmux server of its own, killed when dropped.
+pub struct TestServer {
+    socket: String,
+    socket_path: PathBuf,
+    tmux: SystemTmux,
+}
+
+impl TestServer {
+    /// Starts a server on a socket unique to this process and call. It
+    /// reads no tmux config and stays up without sessions.
+    pub fn start() -> Result<Self> {
+        let socket = format!(
+            "tsman-test-{}-{}",
+            std::process::id(),
+            NEXT_SERVER.fetch_add(1, Ordering::Relaxed)
+        );
+        let args = [
+            "-L",
+            &socket,
+            "-f",
+            "/dev/null",
+            "start-server",
+            ";",
+            "set-option",
+            "-g",
+            "exit-empty",
+            "off",
+        ];
+        let output =
+            Command::new("tmux").args(args).output().map_err(|source| {
+                TsmanError::TmuxUnavailable {
+                    cmd: format!("tmux {}", args.join(" ")),
+                    source,
+                }
+            })?;
+        TmuxOutput {
+            success: output.status.success(),
+            stdout: String::new(),
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        }
+        .check(&args)?;
+
+        let tmux = SystemTmux::with_server(&socket);
+        let socket_path = tmux
+            .run(&["display-message", "-p", "#{socket_path}"])?
+            .stdout
+            .trim()
+            .into();
+        Ok(Self {
+            socket,
+            socket_path,
+            tmux,
+        })
+    }
+
+    /// Socket name of the server (`tmux -L <socket>`).
+    pub fn socket(&self) -> &str {
+        &self.socket
+    }
+
+    /// Client for the server, to pass to the [`interface`] functions.
+    pub fn tmux(&self) -> &SystemTmux {
+        &self.tmux
+    }
+
+    /// Runs `tmux <args>` on the server, failing if tmux rejects it.
+    pub fn run(&self, args: &[&str]) -> Result<TmuxOutput> {
+        self.tmux.run(args)?.check(args)
+    }
+
+    /// Creates a detached session with a single window in `work_dir`.
+    pub fn new_session(&self, name: &str, work_dir: &str) -> Result<()> {
+        self.run(&["new-session", "-d", "-s", name, "-c", work_dir])?;
+        Ok(())
+    }
+
+    /// Kills the session `name`.
+    pub fn kill_session(&self, name: &str) -> Result<()> {
+        interface::close_session(&self.tmux, name)
+    }
+
+    /// Names of the sessions running on the server.
+    pub fn sessions(&self) -> Result<Vec<String>> {
+        interface::list_active_sessions(&self.tmux)
+    }
+
+    /// Captures the running session `name` as it would be saved.
+    pub fn capture(&self, name: &str) -> Result<Session> {
+        interface::get_session(&self.tmux, Some(name))
+    }
+
+    /// Restores `session` on the server, leaving it detached.
+    pub fn restore(&self, session: &Session) -> Result<RestoreReport> {
+        interface::restore_session_detached(&self.tmux, session, &mut |_| {})
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        // Nothing to do if the server is already gone. tmux leaves the
+        // socket file behind.
+        let _ = self.tmux.run(&["kill-server"]);
+        let _ = fs::remove_file(&self.socket_path);
+    }
+}
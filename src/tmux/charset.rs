@@ -0,0 +1,64 @@
+//! Characters used to draw session trees and layout diagrams.
+
+/// Which characters previews are drawn with.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Charset {
+    /// Unicode box-drawing characters.
+    #[default]
+    Unicode,
+    /// Plain ASCII, for fonts and terminals (or CI logs) that render
+    /// box-drawing characters badly.
+    Ascii,
+}
+
+/// The pieces of a session tree, see [`Session::get_preview`].
+///
+/// [`Session::get_preview`]: super::session::Session::get_preview
+pub(crate) struct TreeGlyphs {
+    /// Continues a branch past a window's panes.
+    pub vertical: &'static str,
+    /// Leads to an item that has siblings below it.
+    pub branch: &'static str,
+    /// Leads to the last item of a branch.
+    pub last: &'static str,
+    /// Extends a window branch.
+    pub horizontal: &'static str,
+    /// Splits a window branch into its panes.
+    pub fork: &'static str,
+}
+
+impl Charset {
+    pub(crate) fn tree(self) -> TreeGlyphs {
+        match self {
+            Charset::Unicode => TreeGlyphs {
+                vertical: "║",
+                branch: "╠═",
+                last: "╚═",
+                horizontal: "═",
+                fork: "╦═",
+            },
+            Charset::Ascii => TreeGlyphs {
+                vertical: "|",
+                branch: "|-",
+                last: "`-",
+                horizontal: "-",
+                fork: "+-",
+            },
+        }
+    }
+
+    /// Maps a box-drawing character of a layout diagram to this charset.
+    pub(crate) fn box_char(self, c: char) -> char {
+        match self {
+            Charset::Unicode => c,
+            Charset::Ascii => match c {
+                '─' => '-',
+                '│' => '|',
+                '┌' | '┐' | '└' | '┘' | '┬' | '┴' | '├' | '┤' | '┼' => {
+                    '+'
+                }
+                c => c,
+            },
+        }
+    }
+}
@@ -0,0 +1,369 @@
+//! Tmux transport - [`TmuxClient`] abstracts how tmux commands are executed,
+//! so the interface logic can run against a real server ([`SystemTmux`]) or a
+//! fake one in tests.
+use std::io::{self, IsTerminal, Read};
+use std::os::fd::AsFd;
+use std::process::{Command, Output, Stdio};
+use std::sync::OnceLock;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use crate::error::{Result, TsmanError};
+
+/// Fragments of tmux errors that mean the server is busy starting up or
+/// shutting down rather than missing, so the command is worth retrying.
+const TRANSIENT_ERRORS: &[&str] = &[
+    "Connection refused",
+    "Resource temporarily unavailable",
+    "server exited unexpectedly",
+    "lost server",
+];
+
+const RETRY_DELAY: Duration = Duration::from_millis(50);
+
+/// How long a tmux command may take and how often it is retried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommandPolicy {
+    /// Time after which a command is killed and reported as failed, so a
+    /// wedged server can't hang tsman.
+    pub timeout: Duration,
+    /// Extra attempts for commands that fail because the server isn't
+    /// ready, with a growing delay between them.
+    pub retries: u32,
+}
+
+impl Default for CommandPolicy {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(5),
+            retries: 2,
+        }
+    }
+}
+
+static COMMAND_POLICY: OnceLock<CommandPolicy> = OnceLock::new();
+
+/// Sets the policy every [`SystemTmux`] uses. Only the first call has an
+/// effect; without one, [`CommandPolicy::default`] applies.
+pub fn set_command_policy(policy: CommandPolicy) {
+    let _ = COMMAND_POLICY.set(policy);
+}
+
+fn command_policy() -> CommandPolicy {
+    COMMAND_POLICY.get().copied().unwrap_or_default()
+}
+
+/// Captured result of a single tmux invocation.
+#[derive(Debug, Clone, Default)]
+pub struct TmuxOutput {
+    pub success: bool,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+impl TmuxOutput {
+    /// Turns a failed run of `tmux <args>` into [`TsmanError::TmuxFailed`],
+    /// carrying what tmux printed on stderr.
+    pub fn check(self, args: &[&str]) -> Result<Self> {
+        if self.success {
+            return Ok(self);
+        }
+        Err(TsmanError::TmuxFailed {
+            cmd: format!("tmux {}", args.join(" ")),
+            stderr: self.stderr,
+        })
+    }
+}
+
+/// Executes tmux commands against one tmux server.
+pub trait TmuxClient {
+    /// Runs `tmux <args>` and captures its output. A non-zero exit status is
+    /// reported through [`TmuxOutput::success`], not as an error.
+    fn run(&self, args: &[&str]) -> Result<TmuxOutput>;
+
+    /// Runs `commands` one after another in a single call to tmux, so
+    /// nothing else happens on the server in between - say, a session
+    /// nobody is attached to being destroyed. The output is that of all of
+    /// them, a failure if any failed.
+    ///
+    /// Clients that can't send several commands at once run them one by
+    /// one, stopping at the first that fails.
+    fn run_all(&self, commands: &[&[&str]]) -> Result<TmuxOutput> {
+        let mut all = TmuxOutput {
+            success: true,
+            ..Default::default()
+        };
+        for args in commands {
+            let output = self.run(args)?;
+            all.stdout += &output.stdout;
+            all.stderr += &output.stderr;
+            if !output.success {
+                all.success = false;
+                break;
+            }
+        }
+        Ok(all)
+    }
+
+    /// Runs `tmux <args>` attached to the terminal, for commands that take it
+    /// over (e.g. `attach-session`). Only stderr is captured; stdout stays
+    /// on the terminal.
+    fn run_interactive(&self, args: &[&str]) -> Result<TmuxOutput>;
+
+    /// Returns the host's process table as `pid ppid args` lines, used to
+    /// find the commands running in panes.
+    fn process_table(&self) -> Result<String>;
+
+    /// Socket name of the server (`tmux -L <name>`), `None` for the default.
+    fn server(&self) -> Option<&str>;
+
+    /// tmux client (e.g. `/dev/pts/3`) that attaching switches to the
+    /// session, instead of the one tsman runs in or a new one.
+    fn attach_client(&self) -> Option<&str> {
+        None
+    }
+
+    /// Runs `sh -c <command>` in `dir` on this host, for restore steps that
+    /// tsman runs itself rather than tmux, like a session's `pre` commands.
+    /// There is no timeout. A command that fails without printing anything
+    /// reports its exit status on stderr.
+    fn run_shell(&self, command: &str, dir: &str) -> Result<TmuxOutput> {
+        let output = Command::new("sh")
+            .args(["-c", command])
+            .current_dir(dir)
+            .stdin(Stdio::null())
+            .output();
+        Ok(match output {
+            Ok(output) => {
+                let mut stderr =
+                    String::from_utf8_lossy(&output.stderr).into_owned();
+                if !output.status.success() && stderr.trim().is_empty() {
+                    stderr = output.status.to_string();
+                }
+                TmuxOutput {
+                    success: output.status.success(),
+                    stdout: String::from_utf8_lossy(&output.stdout)
+                        .into_owned(),
+                    stderr,
+                }
+            }
+            Err(err) => TmuxOutput {
+                success: false,
+                stderr: err.to_string(),
+                ..Default::default()
+            },
+        })
+    }
+}
+
+/// [`TmuxClient`] that spawns the local `tmux` binary.
+#[derive(Debug, Default)]
+pub struct SystemTmux {
+    server: Option<String>,
+    attach_client: Option<String>,
+}
+
+impl SystemTmux {
+    /// Client for the server on socket `server` (`tmux -L <server>`).
+    pub fn with_server(server: impl Into<String>) -> Self {
+        Self {
+            server: Some(server.into()),
+            attach_client: None,
+        }
+    }
+
+    /// Makes attaching switch the tmux client `client` to the session; see
+    /// [`TmuxClient::attach_client`].
+    pub fn attaching_client(mut self, client: Option<String>) -> Self {
+        self.attach_client = client;
+        self
+    }
+
+    /// `tmux` with `commands` as its arguments, separated by `;`.
+    fn command(&self, commands: &[&[&str]]) -> Command {
+        let mut cmd = Command::new("tmux");
+        if let Some(server) = &self.server {
+            cmd.args(["-L", server]);
+        }
+        for (i, args) in commands.iter().enumerate() {
+            if i > 0 {
+                cmd.arg(";");
+            }
+            cmd.args(args.iter().map(|arg| escape_separator(arg)));
+        }
+        cmd
+    }
+
+    /// Runs `tmux <commands>` under the [`CommandPolicy`], retrying
+    /// transient failures.
+    fn output(&self, commands: &[&[&str]]) -> io::Result<Output> {
+        let policy = command_policy();
+        let mut attempt = 0;
+
+        loop {
+            let output =
+                output_with_timeout(self.command(commands), policy.timeout)?;
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let transient = TRANSIENT_ERRORS.iter().any(|e| stderr.contains(e));
+
+            if output.status.success()
+                || !transient
+                || attempt == policy.retries
+            {
+                return Ok(output);
+            }
+
+            attempt += 1;
+            log::debug!(
+                "tmux {} failed ({}), retry {}/{}",
+                describe(commands),
+                stderr.trim(),
+                attempt,
+                policy.retries
+            );
+            thread::sleep(RETRY_DELAY * attempt);
+        }
+    }
+}
+
+/// `arg` as tmux reads it from its command line, where an argument ending
+/// in `;` ends the command unless the `;` is escaped as `\;` - which tmux
+/// then unescapes.
+pub fn escape_separator(arg: &str) -> String {
+    match arg.strip_suffix(';') {
+        Some(rest) => format!("{rest}\\;"),
+        None => arg.to_string(),
+    }
+}
+
+/// `commands` as one line for logs and errors.
+fn describe(commands: &[&[&str]]) -> String {
+    commands
+        .iter()
+        .map(|args| args.join(" "))
+        .collect::<Vec<_>>()
+        .join(" ; ")
+}
+
+/// Like [`Command::output`], but kills the process if it hasn't finished
+/// after `timeout`.
+fn output_with_timeout(
+    mut cmd: Command,
+    timeout: Duration,
+) -> io::Result<Output> {
+    let mut child = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    let (Some(mut stdout), Some(mut stderr)) =
+        (child.stdout.take(), child.stderr.take())
+    else {
+        return Err(io::Error::other("tmux output isn't piped"));
+    };
+
+    // The pipes close once tmux exits, so finishing both reads means the
+    // command is done.
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let stderr_reader = thread::spawn(move || {
+            let mut buf = Vec::new();
+            let _ = stderr.read_to_end(&mut buf);
+            buf
+        });
+        let mut buf = Vec::new();
+        let _ = stdout.read_to_end(&mut buf);
+        let _ = tx.send((buf, stderr_reader.join().unwrap_or_default()));
+    });
+
+    match rx.recv_timeout(timeout) {
+        Ok((stdout, stderr)) => Ok(Output {
+            status: child.wait()?,
+            stdout,
+            stderr,
+        }),
+        Err(_) => {
+            let _ = child.kill();
+            let _ = child.wait();
+            Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("no response within {}ms", timeout.as_millis()),
+            ))
+        }
+    }
+}
+
+impl TmuxClient for SystemTmux {
+    fn run(&self, args: &[&str]) -> Result<TmuxOutput> {
+        self.run_all(&[args])
+    }
+
+    fn run_all(&self, commands: &[&[&str]]) -> Result<TmuxOutput> {
+        log::trace!("tmux {}", describe(commands));
+        let output = self.output(commands).map_err(|source| {
+            TsmanError::TmuxUnavailable {
+                cmd: format!("tmux {}", describe(commands)),
+                source,
+            }
+        })?;
+
+        Ok(TmuxOutput {
+            success: output.status.success(),
+            stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        })
+    }
+
+    fn run_interactive(&self, args: &[&str]) -> Result<TmuxOutput> {
+        log::debug!("tmux {} (interactive)", args.join(" "));
+        let mut cmd = self.command(&[args]);
+        // `switch-client` can't cross servers, so clients of a non-default
+        // server are always started nested.
+        if self.server.is_some() {
+            cmd.env_remove("TMUX");
+        }
+        // With a config piped into tsman, the client reads the terminal
+        // from stdout instead (tmux refuses a bare /dev/tty).
+        if !io::stdin().is_terminal()
+            && io::stdout().is_terminal()
+            && let Ok(tty) = io::stdout().as_fd().try_clone_to_owned()
+        {
+            cmd.stdin(tty);
+        }
+
+        // The client talks to the terminal through stdin and stdout, so
+        // stderr is free to capture for error messages.
+        let output = cmd
+            .stderr(Stdio::piped())
+            .spawn()
+            .and_then(|child| child.wait_with_output())
+            .map_err(|source| TsmanError::TmuxUnavailable {
+                cmd: format!("tmux {}", args.join(" ")),
+                source,
+            })?;
+
+        Ok(TmuxOutput {
+            success: output.status.success(),
+            stdout: String::new(),
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        })
+    }
+
+    fn process_table(&self) -> Result<String> {
+        let output = Command::new("ps")
+            .args(["ax", "-o", "pid=,ppid=,args="])
+            .output()?;
+
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    fn server(&self) -> Option<&str> {
+        self.server.as_deref()
+    }
+
+    fn attach_client(&self) -> Option<&str> {
+        self.attach_client.as_deref()
+    }
+}
@@ -0,0 +1,362 @@
+//! Control-mode backend - [`ControlModeTmux`] keeps one `tmux -C` client
+//! open and sends commands over it instead of spawning a tmux process per
+//! query, and [`EventWatcher`] forwards the server's notifications.
+use std::borrow::Cow;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use shell_escape::escape;
+
+use crate::error::{Result, TsmanError};
+use crate::tmux::client::{SystemTmux, TmuxClient, TmuxOutput};
+
+/// [`TmuxClient`] backed by a persistent control-mode connection.
+///
+/// The connection is opened lazily on the first command and re-opened after
+/// it drops (e.g. when the session it is attached to is killed). Whenever no
+/// connection can be made - typically because the server isn't running -
+/// commands fall back to spawning `tmux` like [`SystemTmux`].
+pub struct ControlModeTmux {
+    system: SystemTmux,
+    conn: Mutex<Option<Connection>>,
+}
+
+impl ControlModeTmux {
+    pub fn new(system: SystemTmux) -> Self {
+        Self {
+            system,
+            conn: Mutex::new(None),
+        }
+    }
+}
+
+impl TmuxClient for ControlModeTmux {
+    fn run(&self, args: &[&str]) -> Result<TmuxOutput> {
+        self.run_all(&[args])
+    }
+
+    /// Writes each command as a line of its own, all at once, so tmux runs
+    /// them back to back. Unlike [`SystemTmux`], the ones after a failing
+    /// command still run.
+    fn run_all(&self, commands: &[&[&str]]) -> Result<TmuxOutput> {
+        // Control mode reads one command per line.
+        if commands
+            .iter()
+            .flat_map(|args| args.iter())
+            .any(|arg| arg.contains('\n'))
+        {
+            return self.system.run_all(commands);
+        }
+
+        let mut conn = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+        if conn.is_none() {
+            *conn = Connection::open(&self.system)
+                .inspect_err(|err| {
+                    log::debug!("No control-mode connection: {err:#}");
+                })
+                .ok();
+        }
+
+        if let Some(c) = conn.as_mut() {
+            for args in commands {
+                log::trace!("tmux -C {}", args.join(" "));
+            }
+            match c.send(commands) {
+                Ok(output) => return Ok(output),
+                Err(err) => {
+                    log::debug!("Control-mode connection lost: {err:#}");
+                    *conn = None;
+                }
+            }
+        }
+
+        self.system.run_all(commands)
+    }
+
+    fn run_interactive(&self, args: &[&str]) -> Result<TmuxOutput> {
+        self.system.run_interactive(args)
+    }
+
+    fn process_table(&self) -> Result<String> {
+        self.system.process_table()
+    }
+
+    fn server(&self) -> Option<&str> {
+        self.system.server()
+    }
+
+    fn attach_client(&self) -> Option<&str> {
+        self.system.attach_client()
+    }
+}
+
+/// Change on a tmux server reported by an [`EventWatcher`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TmuxEvent {
+    /// A session was created, closed or renamed.
+    SessionsChanged { server: Option<String> },
+    /// A window was added, closed or renamed.
+    WindowsChanged { server: Option<String> },
+}
+
+/// How long the watcher waits before reconnecting when the server has no
+/// session to attach to.
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Background control client that turns tmux notifications into
+/// [`TmuxEvent`]s. Stops when dropped.
+pub struct EventWatcher {
+    stop: Arc<AtomicBool>,
+    stdin: Arc<Mutex<Option<ChildStdin>>>,
+}
+
+impl EventWatcher {
+    /// Starts watching the server of `system`, sending events to `events`.
+    pub fn spawn(system: SystemTmux, events: Sender<TmuxEvent>) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let stdin = Arc::new(Mutex::new(None));
+
+        let watcher = Self {
+            stop: Arc::clone(&stop),
+            stdin: Arc::clone(&stdin),
+        };
+
+        thread::spawn(move || {
+            let server = system.server().map(str::to_string);
+
+            while !stop.load(Ordering::Relaxed) {
+                let Ok(mut conn) = Connection::open(&system) else {
+                    thread::sleep(RECONNECT_INTERVAL);
+                    continue;
+                };
+                // Hand stdin over so dropping the watcher can close it,
+                // which makes tmux end the client and unblocks the read.
+                *stdin.lock().unwrap_or_else(|e| e.into_inner()) =
+                    conn.stdin.take();
+                if stop.load(Ordering::Relaxed) {
+                    return;
+                }
+
+                while let Ok(Some(line)) = conn.read_line() {
+                    let Some(event) = parse_event(&line, &server) else {
+                        continue;
+                    };
+                    log::debug!("tmux event {event:?}");
+                    if events.send(event).is_err() {
+                        return;
+                    }
+                }
+
+                // The client ends when the session it was attached to is
+                // closed, so treat that as a change too.
+                let changed = TmuxEvent::SessionsChanged {
+                    server: server.clone(),
+                };
+                if events.send(changed).is_err() {
+                    return;
+                }
+            }
+        });
+
+        watcher
+    }
+}
+
+impl Drop for EventWatcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        self.stdin.lock().unwrap_or_else(|e| e.into_inner()).take();
+    }
+}
+
+fn parse_event(line: &str, server: &Option<String>) -> Option<TmuxEvent> {
+    let server = server.clone();
+    let name = line.split_whitespace().next()?;
+
+    match name {
+        "%sessions-changed" | "%session-renamed" => {
+            Some(TmuxEvent::SessionsChanged { server })
+        }
+        "%window-add"
+        | "%window-close"
+        | "%window-renamed"
+        | "%unlinked-window-add"
+        | "%unlinked-window-close"
+        | "%unlinked-window-renamed" => {
+            Some(TmuxEvent::WindowsChanged { server })
+        }
+        _ => None,
+    }
+}
+
+/// A running `tmux -C attach-session` client.
+struct Connection {
+    child: Child,
+    stdin: Option<ChildStdin>,
+    stdout: BufReader<ChildStdout>,
+    /// Writes sent so far, numbering their markers.
+    writes: u64,
+}
+
+impl Connection {
+    /// Attaches a control client to the first session of the server.
+    fn open(system: &SystemTmux) -> Result<Self> {
+        let sessions = system.run(&["list-sessions", "-F", "#{session_id}"])?;
+        let session = sessions
+            .stdout
+            .lines()
+            .next()
+            .filter(|_| sessions.success)
+            .ok_or_else(|| {
+                TsmanError::ControlClient("no session to attach to".into())
+            })?;
+
+        let mut cmd = Command::new("tmux");
+        if let Some(server) = system.server() {
+            cmd.args(["-L", server]);
+        }
+        let mut child = cmd
+            .args(["-C", "attach-session", "-t", session])
+            .args(["-f", "ignore-size,no-output"])
+            .env_remove("TMUX")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|source| TsmanError::TmuxUnavailable {
+                cmd: format!("tmux -C attach-session -t {session}"),
+                source,
+            })?;
+
+        let stdout = child.stdout.take().ok_or_else(|| {
+            TsmanError::ControlClient("stdout isn't piped".into())
+        })?;
+        let mut conn = Self {
+            stdin: child.stdin.take(),
+            stdout: BufReader::new(stdout),
+            child,
+            writes: 0,
+        };
+
+        // The attach itself is answered with a block of its own.
+        let attached = conn.read_reply()?;
+        if !attached.success {
+            return Err(TsmanError::ControlClient(format!(
+                "failed to attach: {}",
+                attached.stderr.trim_end()
+            )));
+        }
+
+        Ok(conn)
+    }
+
+    /// Sends `commands` in one write and reads their replies, merged.
+    ///
+    /// Some commands are answered with more than one block - `if-shell`
+    /// with another for the command it runs - so the write ends with a
+    /// marker printed by `display-message`, and every block before the
+    /// marker's is part of the reply.
+    fn send(&mut self, commands: &[&[&str]]) -> Result<TmuxOutput> {
+        self.writes += 1;
+        let marker = format!("tsman-sync-{}", self.writes);
+        let mut lines = String::new();
+        for args in commands {
+            let line = args
+                .iter()
+                .map(|arg| escape(Cow::from(*arg)))
+                .collect::<Vec<_>>()
+                .join(" ");
+            lines += &format!("{line}\n");
+        }
+        lines += &format!("display-message -p {marker}\n");
+
+        let stdin = self.stdin.as_mut().ok_or_else(|| {
+            TsmanError::ControlClient("client is closed".into())
+        })?;
+        stdin.write_all(lines.as_bytes())?;
+        stdin.flush()?;
+
+        let mut all = TmuxOutput {
+            success: true,
+            ..Default::default()
+        };
+        loop {
+            let reply = self.read_reply()?;
+            if reply.success && reply.stdout.trim_end() == marker {
+                return Ok(all);
+            }
+            all.success &= reply.success;
+            all.stdout += &reply.stdout;
+            all.stderr += &reply.stderr;
+        }
+    }
+
+    /// Reads the next `%begin` ... `%end`/`%error` block, skipping any
+    /// notifications in between. The block is the command's output, or its
+    /// error message for `%error`.
+    fn read_reply(&mut self) -> Result<TmuxOutput> {
+        let begin = loop {
+            let line = self.next_line()?;
+            if let Some(rest) = line.strip_prefix("%begin ") {
+                break rest.to_string();
+            }
+        };
+        // `%begin <time> <number> <flags>`; the closing guard repeats it.
+        let tag = begin.rsplit_once(' ').map_or(&*begin, |(tag, _)| tag);
+
+        let mut block = String::new();
+        loop {
+            let line = self.next_line()?;
+
+            let closes = |guard: &str| {
+                line.strip_prefix(guard)
+                    .is_some_and(|rest| rest.starts_with(tag))
+            };
+            if closes("%end ") {
+                return Ok(TmuxOutput {
+                    success: true,
+                    stdout: block,
+                    stderr: String::new(),
+                });
+            }
+            if closes("%error ") {
+                return Ok(TmuxOutput {
+                    success: false,
+                    stdout: String::new(),
+                    stderr: block,
+                });
+            }
+
+            block.push_str(&line);
+            block.push('\n');
+        }
+    }
+
+    fn next_line(&mut self) -> Result<String> {
+        self.read_line()?.ok_or_else(|| {
+            TsmanError::ControlClient("tmux control client exited".into())
+        })
+    }
+
+    /// Reads one line without its terminator, `None` once the client exits.
+    fn read_line(&mut self) -> Result<Option<String>> {
+        let mut line = String::new();
+        if self.stdout.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        line.truncate(line.trim_end_matches(['\r', '\n']).len());
+        Ok(Some(line))
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
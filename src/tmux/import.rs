@@ -0,0 +1,432 @@
+//! Converters from other session managers' files - tmuxinator and tmuxp
+//! projects, tmux-resurrect save files - to [`Session`]s, and a sniffer
+//! telling them apart.
+use std::collections::BTreeMap;
+use std::env;
+use std::path::Path;
+
+use serde_yaml::{Mapping, Value};
+
+use crate::error::{Result, TsmanError};
+
+use super::session::{Pane, Session, Window};
+
+/// Formats [`convert`] reads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// A tsman session config, or a list of them (as written by
+    /// `save --to`).
+    Tsman,
+    Tmuxinator,
+    Tmuxp,
+    /// A tmux-resurrect save file, every session in it.
+    Resurrect,
+}
+
+impl Format {
+    pub fn name(self) -> &'static str {
+        match self {
+            Format::Tsman => "tsman",
+            Format::Tmuxinator => "tmuxinator",
+            Format::Tmuxp => "tmuxp",
+            Format::Resurrect => "tmux-resurrect",
+        }
+    }
+}
+
+/// Window keys of tmuxp and tsman, which tmuxinator's `- name: command`
+/// windows don't have.
+const WINDOW_KEYS: &[&str] = &[
+    "window_name",
+    "panes",
+    "layout",
+    "index",
+    "name",
+    "start_directory",
+];
+
+/// The formats `text` could be in, most specific first. Empty if it is none
+/// of them, several if it can't be told apart (e.g. a bare `name` and
+/// `windows: []`).
+pub fn detect(text: &str) -> Vec<Format> {
+    let first_line = text.lines().find(|line| !line.trim().is_empty());
+    if first_line.is_some_and(|line| {
+        ["pane\t", "window\t", "state\t"]
+            .iter()
+            .any(|kind| line.starts_with(kind))
+    }) {
+        return vec![Format::Resurrect];
+    }
+
+    let Ok(value) = serde_yaml::from_str::<Value>(text) else {
+        return Vec::new();
+    };
+    if let Value::Sequence(sessions) = &value {
+        let all_sessions = !sessions.is_empty()
+            && sessions
+                .iter()
+                .all(|session| session.get("windows").is_some());
+        return if all_sessions {
+            vec![Format::Tsman]
+        } else {
+            Vec::new()
+        };
+    }
+    let Some(project) = value.as_mapping() else {
+        return Vec::new();
+    };
+
+    let has = |key: &str| project.contains_key(key);
+    let windows: Vec<&Value> = project
+        .get("windows")
+        .or_else(|| project.get("tabs"))
+        .and_then(Value::as_sequence)
+        .map(|windows| windows.iter().collect())
+        .unwrap_or_default();
+    let window_has = |key: &str| windows.iter().any(|w| w.get(key).is_some());
+    let shorthand_windows = windows.iter().any(|window| {
+        window.as_mapping().is_some_and(|window| {
+            window.len() == 1
+                && window.keys().all(|key| {
+                    key.as_str().is_some_and(|key| !WINDOW_KEYS.contains(&key))
+                })
+        })
+    });
+
+    let mut formats = Vec::new();
+    if has("session_name") || window_has("window_name") {
+        formats.push(Format::Tmuxp);
+    }
+    if has("work_dir") || window_has("index") {
+        formats.push(Format::Tsman);
+    }
+    if has("root") || has("project_root") || has("tabs") || shorthand_windows {
+        formats.push(Format::Tmuxinator);
+    }
+    if formats.is_empty() && has("name") && has("windows") {
+        formats = vec![Format::Tsman, Format::Tmuxinator];
+    }
+    formats
+}
+
+/// Converts `text`, read from `path`, from `format` to the sessions it
+/// describes.
+pub fn convert(
+    path: &Path,
+    text: &str,
+    format: Format,
+) -> Result<Vec<Session>> {
+    let invalid = |source: Box<dyn std::error::Error + Send + Sync>| {
+        TsmanError::InvalidConfig {
+            path: path.to_path_buf(),
+            source,
+        }
+    };
+    let yaml = || {
+        serde_yaml::from_str::<Value>(text).map_err(|err| invalid(err.into()))
+    };
+    match format {
+        Format::Resurrect => Ok(from_resurrect(text)),
+        Format::Tsman => match yaml()? {
+            value @ Value::Sequence(_) => serde_yaml::from_value(value),
+            value => serde_yaml::from_value(value).map(|session| vec![session]),
+        }
+        .map_err(|err| invalid(err.into())),
+        Format::Tmuxinator => from_tmuxinator(&yaml()?)
+            .map(|session| vec![session])
+            .map_err(invalid),
+        Format::Tmuxp => from_tmuxp(&yaml()?)
+            .map(|session| vec![session])
+            .map_err(invalid),
+    }
+}
+
+type ConvertResult<T> =
+    std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+fn from_tmuxinator(project: &Value) -> ConvertResult<Session> {
+    let name = string(project, "name").ok_or("the project has no name")?;
+    let root = string(project, "root")
+        .or_else(|| string(project, "project_root"))
+        .map(|root| expand_home(&root))
+        .unwrap_or_else(home);
+    let windows = project
+        .get("windows")
+        .or_else(|| project.get("tabs"))
+        .and_then(Value::as_sequence)
+        .ok_or("the project has no windows")?;
+
+    let mut session = empty_session(name, root.clone());
+    session.pre = ["on_project_start", "pre"]
+        .iter()
+        .filter_map(|key| commands(project.get(*key)?))
+        .collect();
+    session.pre_window = project.get("pre_window").and_then(commands);
+
+    for (i, window) in windows.iter().enumerate() {
+        // `- name: command` or `- name: {root, layout, panes}`
+        let (name, body) = match window.as_mapping().map(Mapping::iter) {
+            Some(mut entries) => entries
+                .next()
+                .ok_or("a window is empty")
+                .map(|(name, body)| (scalar(name), body))?,
+            None => (scalar(window), &Value::Null),
+        };
+        let work_dir = string(body, "root")
+            .map(|dir| relative_to(&root, &dir))
+            .unwrap_or_else(|| root.clone());
+        let commands: Vec<Option<String>> =
+            match body.get("panes").and_then(Value::as_sequence) {
+                Some(panes) => panes.iter().map(pane_commands).collect(),
+                None if body.is_mapping() => vec![None],
+                None => vec![commands(body)],
+            };
+        session.windows.push(window_with(
+            i,
+            name,
+            string(body, "layout").unwrap_or_default(),
+            &work_dir,
+            commands,
+        ));
+    }
+    Ok(session)
+}
+
+fn from_tmuxp(project: &Value) -> ConvertResult<Session> {
+    let name = string(project, "session_name")
+        .ok_or("the project has no session_name")?;
+    let root = string(project, "start_directory")
+        .map(|dir| expand_home(&dir))
+        .unwrap_or_else(home);
+    let windows = project
+        .get("windows")
+        .and_then(Value::as_sequence)
+        .ok_or("the project has no windows")?;
+
+    let mut session = empty_session(name, root.clone());
+    session.pre = project
+        .get("before_script")
+        .and_then(commands)
+        .into_iter()
+        .collect();
+    session.pre_window = project.get("shell_command_before").and_then(commands);
+
+    for (i, window) in windows.iter().enumerate() {
+        let work_dir = string(window, "start_directory")
+            .map(|dir| relative_to(&root, &dir))
+            .unwrap_or_else(|| root.clone());
+        let before = window.get("shell_command_before").and_then(commands);
+        let commands = window
+            .get("panes")
+            .and_then(Value::as_sequence)
+            .map(|panes| {
+                panes
+                    .iter()
+                    .map(|pane| {
+                        let command = match pane.get("shell_command") {
+                            Some(command) => commands(command),
+                            None if pane.is_mapping() => None,
+                            None => commands(pane),
+                        };
+                        match (&before, command) {
+                            (Some(before), Some(command)) => {
+                                Some(format!("{before}; {command}"))
+                            }
+                            (before, command) => command.or(before.clone()),
+                        }
+                    })
+                    .collect()
+            })
+            .unwrap_or_else(|| vec![before.clone()]);
+        session.windows.push(window_with(
+            i,
+            string(window, "window_name").unwrap_or_else(|| i.to_string()),
+            string(window, "layout").unwrap_or_default(),
+            &work_dir,
+            commands,
+        ));
+    }
+    Ok(session)
+}
+
+/// Reads the `window` and `pane` lines of a tmux-resurrect save file, with
+/// or without the `pane_title` field newer versions write.
+fn from_resurrect(text: &str) -> Vec<Session> {
+    let mut sessions: Vec<Session> = Vec::new();
+    let mut layouts = BTreeMap::new();
+    let mut panes = Vec::new();
+
+    for line in text.lines() {
+        let fields: Vec<&str> = line.split('\t').collect();
+        match fields.as_slice() {
+            ["window", session, index, name, _, _, layout, ..] => {
+                session_index(&mut sessions, session);
+                layouts.insert(
+                    (session.to_string(), index.to_string()),
+                    (strip_colon(name), layout.to_string()),
+                );
+            }
+            ["pane", session, window, _, _, index, rest @ ..]
+                if rest.len() >= 4 =>
+            {
+                session_index(&mut sessions, session);
+                // [title,] :dir, active, command, :full_command
+                let rest = &rest[rest.len() - 4..];
+                let command = strip_colon(rest[3]);
+                panes.push((
+                    session.to_string(),
+                    window.to_string(),
+                    Pane {
+                        index: index.to_string(),
+                        current_command: (!command.is_empty())
+                            .then_some(command),
+                        work_dir: strip_colon(rest[0]),
+                        ..Default::default()
+                    },
+                ));
+            }
+            _ => {}
+        }
+    }
+
+    for (session_name, window_index, pane) in panes {
+        let i = session_index(&mut sessions, &session_name);
+        let session = &mut sessions[i];
+        if session.work_dir.is_empty() {
+            session.work_dir = pane.work_dir.clone();
+        }
+        let window = match session
+            .windows
+            .iter()
+            .position(|window| window.index == window_index)
+        {
+            Some(i) => &mut session.windows[i],
+            None => {
+                let (name, layout) = layouts
+                    .get(&(session_name.clone(), window_index.clone()))
+                    .cloned()
+                    .unwrap_or_default();
+                session.windows.push(Window {
+                    index: window_index,
+                    name,
+                    layout,
+                    ..Default::default()
+                });
+                session.windows.last_mut().unwrap()
+            }
+        };
+        window.panes.push(pane);
+    }
+    sessions
+}
+
+/// Position of the session `name` in `sessions`, added if it isn't yet.
+fn session_index(sessions: &mut Vec<Session>, name: &str) -> usize {
+    match sessions.iter().position(|session| session.name == name) {
+        Some(i) => i,
+        None => {
+            sessions.push(empty_session(name.to_string(), String::new()));
+            sessions.len() - 1
+        }
+    }
+}
+
+fn empty_session(name: String, work_dir: String) -> Session {
+    Session {
+        name,
+        work_dir,
+        ..Default::default()
+    }
+}
+
+/// A window of panes in `work_dir` running `commands`, at least one.
+fn window_with(
+    position: usize,
+    name: String,
+    layout: String,
+    work_dir: &str,
+    mut commands: Vec<Option<String>>,
+) -> Window {
+    if commands.is_empty() {
+        commands.push(None);
+    }
+    Window {
+        index: (position + 1).to_string(),
+        name,
+        layout,
+        panes: commands
+            .into_iter()
+            .enumerate()
+            .map(|(i, command)| Pane {
+                index: i.to_string(),
+                current_command: command,
+                work_dir: work_dir.to_string(),
+                ..Default::default()
+            })
+            .collect(),
+        ..Default::default()
+    }
+}
+
+/// A tmuxinator pane: a command, a list of them, or `name: commands`.
+fn pane_commands(pane: &Value) -> Option<String> {
+    match pane.as_mapping() {
+        Some(named) => named.values().next().and_then(commands),
+        None => commands(pane),
+    }
+}
+
+/// A command, or a list of them run one after another, as one shell line.
+fn commands(value: &Value) -> Option<String> {
+    let line = match value {
+        Value::Sequence(commands) => commands
+            .iter()
+            .map(scalar)
+            .filter(|command| !command.is_empty())
+            .collect::<Vec<_>>()
+            .join("; "),
+        Value::Null => String::new(),
+        value => scalar(value),
+    };
+    (!line.is_empty()).then_some(line)
+}
+
+fn string(value: &Value, key: &str) -> Option<String> {
+    value.get(key).filter(|v| !v.is_null()).map(scalar)
+}
+
+fn scalar(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Bool(b) => b.to_string(),
+        Value::Number(n) => n.to_string(),
+        _ => String::new(),
+    }
+}
+
+fn strip_colon(field: &str) -> String {
+    field.strip_prefix(':').unwrap_or(field).to_string()
+}
+
+fn home() -> String {
+    env::var("HOME").unwrap_or_else(|_| "/".to_string())
+}
+
+fn expand_home(path: &str) -> String {
+    match path.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => {
+            format!("{}{rest}", home())
+        }
+        _ => path.to_string(),
+    }
+}
+
+/// `dir` as an absolute path, relative ones taken from `root`.
+fn relative_to(root: &str, dir: &str) -> String {
+    let dir = expand_home(dir);
+    if dir.starts_with('/') {
+        return dir;
+    }
+    let dir = dir.strip_prefix("./").unwrap_or(&dir);
+    format!("{}/{dir}", root.trim_end_matches('/'))
+}
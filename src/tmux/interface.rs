@@ -1,44 +1,316 @@
-//! Tmux interface - all tmux interaction goes through [`std::process::Command`].
-use std::borrow::Cow;
+//! Tmux interface - all tmux interaction goes through a [`TmuxClient`].
+use std::collections::HashMap;
 use std::env;
-use std::fs::write;
-use std::process::Command;
+use std::sync::OnceLock;
 
-use anyhow::{Context, Result};
-use shell_escape::escape;
-use tempfile::NamedTempFile;
+use regex::Regex;
+use serde::Deserialize;
 
+use crate::error::{Result, TsmanError};
+use crate::tmux::client::{TmuxClient, TmuxOutput};
+use crate::tmux::plan::{
+    PlanStep, Progress, TmuxCommand, WindowTargets, append_steps, respawn_plan,
+    restore_steps, type_line, window_placeholder,
+};
 use crate::tmux::session::*;
 
-const TMUX_FIELD_SEPARATOR: &str = " ";
+/// Printable, since control mode replaces control characters like tabs, and
+/// unlikely to appear in window names or paths.
+const TMUX_FIELD_SEPARATOR: &str = "|:|";
 const TMUX_LINE_SEPARATOR: &str = "\n";
 
+const TEMP_SESSION_PREFIX: &str = "tsman-temp-";
+
+/// What a restore does when a step after creating the session fails.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OnStepFailure {
+    /// Kill the half-built session and fail the restore.
+    #[default]
+    Rollback,
+    /// Keep going and list the failed steps in the [`RestoreReport`].
+    Continue,
+}
+
+static ON_STEP_FAILURE: OnceLock<OnStepFailure> = OnceLock::new();
+
+/// Sets what every restore does when one of its steps fails. Only the first
+/// call has an effect; without one, restores roll back.
+pub fn set_on_step_failure(policy: OnStepFailure) {
+    let _ = ON_STEP_FAILURE.set(policy);
+}
+
+fn on_step_failure() -> OnStepFailure {
+    ON_STEP_FAILURE.get().copied().unwrap_or_default()
+}
+
+/// Pane commands a restore treats as risky to replay.
+#[derive(Debug, Default)]
+pub struct RestoreLint {
+    /// Commands matching any of these are risky.
+    pub patterns: Vec<Regex>,
+    /// Refuse to restore a session with risky commands instead of warning.
+    pub fail: bool,
+}
+
+/// Pane commands that do damage when a shared or hand-edited config replays
+/// them: recursive deletes, shutdowns, force-pushes and the like. The `rm`
+/// flags may come in one cluster or apart, and in any order, up to the end
+/// of the command.
+pub const RISKY_COMMANDS: &[&str] = &[
+    r"\brm\s([^;&|\n]*\s)?-[a-zA-Z]*([rR][a-zA-Z]*f|f[a-zA-Z]*[rR])",
+    concat!(
+        r"\brm\s([^;&|\n]*\s)?(-[a-zA-Z]*[rR][a-zA-Z]*|--recursive)\s",
+        r"([^;&|\n]*\s)?(-[a-zA-Z]*f|--force\b)",
+    ),
+    concat!(
+        r"\brm\s([^;&|\n]*\s)?(-[a-zA-Z]*f[a-zA-Z]*|--force)\s",
+        r"([^;&|\n]*\s)?(-[a-zA-Z]*[rR]|--recursive\b)",
+    ),
+    r"\b(shutdown|reboot|halt|poweroff)\b",
+    r"\bgit\s+push\b.*\s(-f|--force)\b",
+    r"\bmkfs(\.\w+)?\b",
+    r"\bdd\b.*\bof=/dev/",
+    r">\s*/dev/[sh]d[a-z]",
+];
+
+static RESTORE_LINT: OnceLock<RestoreLint> = OnceLock::new();
+
+/// Sets the risky commands every restore warns about, or refuses to replay.
+/// Only the first call has an effect; without one, nothing is risky.
+pub fn set_restore_lint(lint: RestoreLint) {
+    let _ = RESTORE_LINT.set(lint);
+}
+
+/// A warning listing the risky commands restoring `session` would replay,
+/// or with [`RestoreLint::fail`] an error instead. Run before anything is
+/// built, so a refused restore leaves tmux untouched.
+fn lint_session(session: &Session) -> Result<Option<String>> {
+    let Some(lint) = RESTORE_LINT.get() else {
+        return Ok(None);
+    };
+    let commands = session.commands_matching(&lint.patterns);
+    if commands.is_empty() {
+        return Ok(None);
+    }
+    if lint.fail {
+        return Err(TsmanError::RiskyCommands {
+            session: session.name.clone(),
+            commands,
+        });
+    }
+    Ok(Some(format!(
+        "'{}' replays risky commands:\n  - {}",
+        session.name,
+        commands.join("\n  - ")
+    )))
+}
+
+/// Programs whose panes count as sitting at a shell prompt.
+const SHELLS: &[&str] = &[
+    "sh", "bash", "zsh", "fish", "dash", "ksh", "mksh", "tcsh", "csh", "nu",
+    "xonsh", "elvish",
+];
+
+/// Window and pane fields captured for every pane of a session. The path
+/// goes last since it is the field most likely to contain spaces.
+const PANE_FORMAT: &str = concat!(
+    "#{window_index}|:|#{window_name}|:|#{window_layout}|:|",
+    "#{monitor-activity}|:|#{monitor-bell}|:|#{monitor-silence}|:|",
+    "#{pane_index}|:|#{pane_pid}|:|#{pane_current_path}",
+);
+
 /// Captures a [`Session`] by name, or the currently attached session if `None`.
-pub fn get_session(session_name: Option<&str>) -> Result<Session> {
+pub fn get_session(
+    tmux: &dyn TmuxClient,
+    session_name: Option<&str>,
+) -> Result<Session> {
     let name = if let Some(name) = session_name {
         name.to_string()
     } else {
-        get_session_name()?
+        get_session_name(tmux)?
     };
 
-    let path = get_session_path(&name)?;
+    let path = get_session_path(tmux, &name)?;
 
-    let windows = get_windows(&name).context("Failed to get windows")?;
+    let windows = get_windows(tmux, &name)?;
 
     Ok(Session {
         name,
         work_dir: path,
         windows,
+        ..Default::default()
     })
 }
 
-/// Restores a [`Session`] by generating a shell script that creates a temp
-/// session, configures windows/panes, then renames it to avoid conflicts.
-pub fn restore_session(session: &Session) -> Result<()> {
-    let temp_name = format!("tsman-temp-{}", std::process::id());
-    create_session_from_config(session, &temp_name)?;
-    rename_session(&temp_name, &session.name)?;
-    attach_to_session(&session.name)
+/// Name of the session a restore is built under before being renamed to
+/// its real name. Unique per tsman process.
+pub fn temp_session_name() -> String {
+    format!("{TEMP_SESSION_PREFIX}{}", std::process::id())
+}
+
+/// The pid of the tsman process that built the temp session `session_name`,
+/// if it is one.
+pub fn temp_session_pid(session_name: &str) -> Option<u32> {
+    session_name.strip_prefix(TEMP_SESSION_PREFIX)?.parse().ok()
+}
+
+/// A session being built under a temp name. Unless it is committed, it is
+/// killed on drop, so a restore that fails midway leaves nothing behind.
+struct TempSession<'a> {
+    tmux: &'a dyn TmuxClient,
+    name: String,
+    committed: bool,
+}
+
+impl<'a> TempSession<'a> {
+    fn new(tmux: &'a dyn TmuxClient) -> Self {
+        Self {
+            tmux,
+            name: temp_session_name(),
+            committed: false,
+        }
+    }
+
+    /// Keeps the session past the end of the restore.
+    fn commit(mut self) {
+        self.committed = true;
+    }
+}
+
+impl Drop for TempSession<'_> {
+    fn drop(&mut self) {
+        if self.committed {
+            return;
+        }
+        log::warn!("Rolling back partially restored '{}'", self.name);
+        // The session may never have been created; there's nothing else to
+        // do if killing it fails. `=` keeps tmux from matching another
+        // process's temp session by prefix when this one is missing.
+        let target = format!("={}", self.name);
+        let _ = self.tmux.run(&["kill-session", "-t", &target]);
+    }
+}
+
+/// Restores a [`Session`] by running its restore plan under a temp session
+/// name, then renaming it to avoid conflicts. `on_progress` is called after
+/// each step of the plan.
+///
+/// A failed step is an error unless [`OnStepFailure::Continue`] is set, in
+/// which case it is listed in the returned report along with anything else
+/// that didn't come back as saved. On an error, the partially built temp
+/// session is killed.
+pub fn restore_session(
+    tmux: &dyn TmuxClient,
+    session: &Session,
+    on_progress: &mut dyn FnMut(Progress),
+) -> Result<RestoreReport> {
+    let report = restore_session_detached(tmux, session, on_progress)?;
+    attach_to_session(tmux, &session.name)?;
+    Ok(report)
+}
+
+/// Like [`restore_session`], but leaves the restored session detached.
+pub fn restore_session_detached(
+    tmux: &dyn TmuxClient,
+    session: &Session,
+    on_progress: &mut dyn FnMut(Progress),
+) -> Result<RestoreReport> {
+    let risky = lint_session(session)?;
+    let temp = TempSession::new(tmux);
+    log::info!("Restoring session '{}' as '{}'", session.name, temp.name);
+    let mut report =
+        create_session_from_config(tmux, session, &temp.name, on_progress)?;
+    report.warnings.extend(risky);
+    rename_session(tmux, &temp.name, &session.name)?;
+    temp.commit();
+    Ok(report)
+}
+
+/// Adds `windows` of the saved `session` to the running session
+/// `session_name`, after its last window. Returns the index of the first
+/// window added and the steps tmux rejected, like [`run_plan`].
+///
+/// The windows are built in the background: the session's current window
+/// and pane are selected again at the end, so its clients keep their focus.
+pub fn append_windows(
+    tmux: &dyn TmuxClient,
+    session: &Session,
+    session_name: &str,
+    windows: &[&Window],
+    on_progress: &mut dyn FnMut(Progress),
+) -> Result<(usize, Vec<String>)> {
+    let appended = Session {
+        windows: windows.iter().map(|window| (*window).clone()).collect(),
+        ..session.clone()
+    };
+    if let Some(risky) = lint_session(&appended)? {
+        log::warn!("{risky}");
+    }
+
+    let args = [
+        "list-windows",
+        "-t",
+        &format!("={session_name}"),
+        "-F",
+        "#{window_index}",
+    ];
+    let output = tmux.run(&args)?;
+    if !output.success {
+        return Err(TsmanError::SessionNotFound(session_name.to_string()));
+    }
+    let next = output
+        .stdout
+        .lines()
+        .filter_map(|index| index.parse::<usize>().ok())
+        .max()
+        .map_or(0, |last| last + 1);
+
+    let windows: Vec<Window> = windows
+        .iter()
+        .enumerate()
+        .map(|(i, window)| Window {
+            index: (next + i).to_string(),
+            ..(*window).clone()
+        })
+        .collect();
+    log::info!(
+        "Adding {} windows of '{}' to '{session_name}'",
+        windows.len(),
+        session.name
+    );
+
+    let target = format!("={session_name}:");
+    let args = [
+        "display-message",
+        "-p",
+        "-t",
+        &target,
+        "-F",
+        "#{window_id} #{pane_id}",
+    ];
+    let focus = tmux.run(&args)?.check(&args)?.stdout;
+    let failed = run_plan(
+        tmux,
+        &append_steps(session, session_name, &windows),
+        on_progress,
+    )?;
+
+    if let Some((window_id, pane_id)) = focus.trim().split_once(' ') {
+        for args in [
+            ["select-window", "-t", window_id],
+            ["select-pane", "-t", pane_id],
+        ] {
+            // The windows are there already, so losing the focus isn't
+            // worth failing for.
+            if let Err(err) = tmux.run(&args)?.check(&args) {
+                log::warn!(
+                    "Couldn't restore the focus of '{session_name}': {err}"
+                );
+            }
+        }
+    }
+    Ok((next, failed))
 }
 
 /// Kills a running session and recreates it from the saved config.
@@ -47,104 +319,433 @@ pub fn restore_session(session: &Session) -> Result<()> {
 /// session before killing the old one to avoid tmux closing the client.
 /// When false, the kill is safe without a prior switch and the function
 /// attaches to the reloaded session at the end.
+///
+/// Failing before the old session is killed leaves it running and kills the
+/// new one; after that, the new one is kept even under its temp name.
 pub fn reload_session(
+    tmux: &dyn TmuxClient,
     session: &Session,
     currently_attached: bool,
-) -> Result<()> {
-    let temp_name = format!("tsman-temp-{}", std::process::id());
-    create_session_from_config(session, &temp_name)?;
+    on_progress: &mut dyn FnMut(Progress),
+) -> Result<RestoreReport> {
+    let risky = lint_session(session)?;
+    let temp = TempSession::new(tmux);
+    let temp_name = temp.name.clone();
+    log::info!("Reloading session '{}' via '{}'", session.name, temp_name);
+    let mut report =
+        create_session_from_config(tmux, session, &temp_name, on_progress)?;
+    report.warnings.extend(risky);
     if currently_attached {
-        attach_to_session(&temp_name)?;
+        attach_to_session(tmux, &temp_name)?;
+    }
+    if let Err(err) = close_session(tmux, &session.name) {
+        if currently_attached {
+            // Back to the old session before the new one is killed.
+            let _ = attach_to_session(tmux, &session.name);
+        }
+        return Err(err);
     }
-    close_session(&session.name)?;
-    rename_session(&temp_name, &session.name)?;
+    temp.commit();
+    rename_session(tmux, &temp_name, &session.name)?;
     if !currently_attached {
-        attach_to_session(&session.name)?;
+        attach_to_session(tmux, &session.name)?;
+    }
+    Ok(report)
+}
+
+/// What didn't come back as saved after a restore. Empty when the session
+/// was restored exactly.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct RestoreReport {
+    /// Plan steps tmux rejected.
+    pub failed_steps: Vec<String>,
+    /// Differences between the saved session and the restored one.
+    pub mismatches: Vec<String>,
+    /// tmux settings that may still take the session down, e.g. the server
+    /// exiting with no client attached.
+    pub warnings: Vec<String>,
+}
+
+impl RestoreReport {
+    pub fn is_clean(&self) -> bool {
+        self.failed_steps.is_empty() && self.mismatches.is_empty()
+    }
+
+    /// Every problem found, failed steps first.
+    pub fn problems(&self) -> impl Iterator<Item = &str> {
+        self.failed_steps
+            .iter()
+            .chain(&self.mismatches)
+            .map(String::as_str)
     }
-    Ok(())
 }
 
 /// Creates a tmux session from config under the given name, without
-/// attaching or renaming.
+/// attaching or renaming, and checks the result against the config.
 fn create_session_from_config(
+    tmux: &dyn TmuxClient,
     session: &Session,
     session_name: &str,
-) -> Result<()> {
-    let mut script_str = String::new();
+    on_progress: &mut dyn FnMut(Progress),
+) -> Result<RestoreReport> {
+    // Window indexes shift under the plan if tmux renumbers windows.
+    let targets = if renumbers_windows(tmux)? {
+        log::debug!("renumber-windows is on, targeting windows by id");
+        WindowTargets::Id
+    } else {
+        WindowTargets::Index
+    };
+    let mut steps = restore_steps(session, session_name, targets);
+    if destroys_unattached(tmux)? {
+        log::debug!("destroy-unattached is on, guarding '{session_name}'");
+        let new_session = steps[0].command.clone();
+        steps[0].command = guard_until_attached(new_session, session_name);
+    }
+    let mut failed_steps = run_pre_commands(tmux, session)?;
+    let rollback = on_step_failure() == OnStepFailure::Rollback;
+    if failed_steps.is_empty() || !rollback {
+        failed_steps.extend(run_plan(tmux, &steps, on_progress)?);
+    }
+    if !failed_steps.is_empty() && rollback {
+        return Err(TsmanError::RestoreFailed {
+            session: session.name.clone(),
+            failed_steps,
+        });
+    }
+    let mismatches = verify_session(tmux, session, session_name)?;
+
+    let mut warnings = Vec::new();
+    if exits_unattached(tmux)? {
+        warnings.push(format!(
+            "exit-unattached is on and no client is attached, so the tmux \
+             server may exit and take '{}' with it",
+            session.name
+        ));
+    }
 
-    script_str += &format!(
-        "tmux new-session -d -s {} -c {}\n",
-        session_name,
-        escape(Cow::from(&session.work_dir))
-    );
+    let report = RestoreReport {
+        failed_steps,
+        mismatches,
+        warnings,
+    };
+    for problem in report
+        .problems()
+        .chain(report.warnings.iter().map(String::as_str))
+    {
+        log::warn!("Restoring '{}': {}", session.name, problem);
+    }
+    Ok(report)
+}
 
-    let first_window = &session.windows[0];
+/// Runs the `pre` commands of `session` one after another in its
+/// directory, and returns those that failed. They run through
+/// [`TmuxClient::run_shell`] rather than tmux's `run-shell`, which the tmux
+/// command timeout would cut short for a slow one like `docker compose up
+/// -d`.
+fn run_pre_commands(
+    tmux: &dyn TmuxClient,
+    session: &Session,
+) -> Result<Vec<String>> {
+    let mut failures = Vec::new();
+    for pre in &session.pre {
+        log::debug!("Running pre command '{pre}'");
+        let output = tmux.run_shell(pre, &session.work_dir)?;
+        if !output.success {
+            failures.push(format!(
+                "Pre command failed: {pre} ({})",
+                output.stderr.trim()
+            ));
+        }
+    }
+    Ok(failures)
+}
+
+/// Executes a restore plan one command at a time. `on_progress` is called
+/// after each step. The `@id` a step creating a window prints replaces the
+/// window's [`window_placeholder`] in the steps after it.
+///
+/// A rejected first step means there is no session to build on, so it
+/// fails the whole plan. Later steps only break part of the session, so
+/// the rest of the plan still runs and the failures are returned.
+pub fn run_plan(
+    tmux: &dyn TmuxClient,
+    plan: &[PlanStep],
+    on_progress: &mut dyn FnMut(Progress),
+) -> Result<Vec<String>> {
+    log::debug!("Running restore plan of {} steps", plan.len());
+    let windows = plan.iter().map(|step| step.window).max().unwrap_or(0);
+    let mut failures = Vec::new();
+    let mut window_ids: HashMap<usize, String> = HashMap::new();
+
+    for (i, step) in plan.iter().enumerate() {
+        let cmd = &match window_ids.get(&step.window) {
+            Some(id) => {
+                let placeholder = window_placeholder(step.window);
+                let substitute = |args: &[String]| -> Vec<String> {
+                    args.iter()
+                        .map(|a| match a.strip_prefix(&placeholder) {
+                            Some(pane) => format!("{id}{pane}"),
+                            None => a.clone(),
+                        })
+                        .collect()
+                };
+                TmuxCommand {
+                    args: substitute(&step.command.args),
+                    then: step
+                        .command
+                        .then
+                        .iter()
+                        .map(|args| substitute(args))
+                        .collect(),
+                }
+            }
+            None => step.command.clone(),
+        };
+        log::debug!("Step {}/{}: {}", i + 1, plan.len(), cmd);
+        let output = run_command(tmux, cmd)?;
+        let id = output.stdout.trim();
+        if output.success && step.creates_window && id.starts_with('@') {
+            window_ids.insert(step.window, id.to_string());
+        }
 
-    script_str += &get_window_config_cmd(session_name, session, first_window)?;
+        let progress = Progress {
+            step: i + 1,
+            steps: plan.len(),
+            window: step.window,
+            windows,
+            pane: step.pane,
+        };
 
-    for window in session.windows.iter().skip(1) {
-        script_str += &format!(
-            "tmux new-window -d -t {} -c {}\n",
-            session_name,
-            escape(Cow::from(&session.work_dir))
-        );
+        if !output.success {
+            if i == 0 {
+                return Err(TsmanError::TmuxFailed {
+                    cmd: cmd.to_string(),
+                    stderr: output.stderr,
+                });
+            }
+            let mut failure = format!(
+                "Step {}/{} ({}) failed: {}",
+                i + 1,
+                plan.len(),
+                progress,
+                cmd
+            );
+            let stderr = output.stderr.trim();
+            if !stderr.is_empty() {
+                failure += &format!(" ({stderr})");
+            }
+            failures.push(failure);
+        }
 
-        script_str += &get_window_config_cmd(session_name, session, window)?;
+        on_progress(progress);
     }
 
-    let script = NamedTempFile::new()?;
+    Ok(failures)
+}
 
-    write(script.path(), script_str)?;
+/// Compares a restored session with its config: window names and pane
+/// counts, and whether the directories panes should start in exist (tmux
+/// silently falls back to another directory when they don't).
+pub fn verify_session(
+    tmux: &dyn TmuxClient,
+    session: &Session,
+    session_name: &str,
+) -> Result<Vec<String>> {
+    let format =
+        format!("#{{window_name}}{TMUX_FIELD_SEPARATOR}#{{window_panes}}");
+    let args = ["list-windows", "-t", session_name, "-F", &format];
+    let output = tmux.run(&args)?.check(&args)?;
+
+    let restored: Vec<(&str, usize)> = output
+        .stdout
+        .lines()
+        .filter_map(|line| {
+            let (name, panes) = line.rsplit_once(TMUX_FIELD_SEPARATOR)?;
+            Some((name, panes.parse().ok()?))
+        })
+        .collect();
 
-    Command::new("sh")
-        .arg(script.path())
-        .status()
-        .context("Failed to reconstruct session")?;
+    let mut mismatches = Vec::new();
+    if restored.len() != session.windows.len() {
+        mismatches.push(format!(
+            "Expected {} windows, found {}",
+            session.windows.len(),
+            restored.len()
+        ));
+    }
 
-    Ok(())
+    for (i, (window, (name, panes))) in
+        session.windows.iter().zip(&restored).enumerate()
+    {
+        if window.name != *name {
+            mismatches.push(format!(
+                "Window {} is named '{}' instead of '{}'",
+                i + 1,
+                name,
+                window.name
+            ));
+        }
+        if window.panes.len() != *panes {
+            mismatches.push(format!(
+                "Window {} '{}' has {} panes instead of {}",
+                i + 1,
+                window.name,
+                panes,
+                window.panes.len()
+            ));
+        }
+    }
+
+    for dir in session.missing_dirs() {
+        mismatches.push(format!("Working directory {dir} doesn't exist"));
+    }
+
+    Ok(mismatches)
 }
 
 /// Returns whether a tmux session with the given name exists.
-pub fn is_active_session(session_name: &str) -> Result<bool> {
-    let output = Command::new("tmux")
-        .arg("list-session")
-        .args(["-F", "#{session_name}"])
-        .output()
-        .context("Failed to get sessions")?;
+pub fn is_active_session(
+    tmux: &dyn TmuxClient,
+    session_name: &str,
+) -> Result<bool> {
+    let output = tmux.run(&["list-session", "-F", "#{session_name}"])?;
 
-    let output_str = String::from_utf8(output.stdout)?;
-    let session_names =
-        output_str.split(TMUX_LINE_SEPARATOR).collect::<Vec<&str>>();
+    let session_names = output
+        .stdout
+        .split(TMUX_LINE_SEPARATOR)
+        .collect::<Vec<&str>>();
 
     Ok(session_names.contains(&session_name))
 }
 
 /// Attaches to a session. Uses `switch-client` if inside tmux, `attach-session` otherwise.
-pub fn attach_to_session(session_name: &str) -> Result<()> {
-    let is_attached = env::var("TMUX").is_ok();
+///
+/// `switch-client` can't cross servers, so a session on a non-default server
+/// is attached with a nested client when already inside tmux.
+///
+/// With a [`TmuxClient::attach_client`], that client is switched to the
+/// session instead, from inside tmux or not.
+pub fn attach_to_session(
+    tmux: &dyn TmuxClient,
+    session_name: &str,
+) -> Result<()> {
+    if let Some(client) = tmux.attach_client() {
+        let target = format!("={session_name}");
+        let args = ["switch-client", "-c", client, "-t", &target];
+        tmux.run(&args)?.check(&args)?;
+        return Ok(());
+    }
+
+    let is_attached = env::var("TMUX").is_ok() && tmux.server().is_none();
     let attach_cmd = if is_attached {
         "switch-client"
     } else {
         "attach-session"
     };
 
-    Command::new("tmux")
-        .arg(attach_cmd)
-        .args(["-t", session_name])
-        .status()
-        .context("Failed to attach session")?;
+    let args = [attach_cmd, "-t", session_name];
+    tmux.run_interactive(&args)?.check(&args)?;
 
     Ok(())
 }
 
+/// Runs `command` in a new window of a running session, or in a new pane
+/// split off its current window if `split` is set. Returns the id of the
+/// new pane (e.g. `%12`).
+///
+/// The command is typed into the pane's shell rather than run in place of
+/// it, so the pane stays open after the command exits.
+pub fn exec_in_session(
+    tmux: &dyn TmuxClient,
+    session_name: &str,
+    command: &str,
+    split: bool,
+) -> Result<String> {
+    let target = format!("{session_name}:");
+    let create = if split { "split-window" } else { "new-window" };
+    let args = [create, "-d", "-t", &target, "-P", "-F", "#{pane_id}"];
+    let output = tmux.run(&args)?.check(&args)?;
+    let pane_id = output.stdout.trim().to_string();
+
+    run_checked(tmux, &type_line(&pane_id, command))?;
+
+    Ok(pane_id)
+}
+
+/// Types `command` into every pane of a running session, or only into the
+/// panes whose foreground program is a shell if `shell_only` is set.
+/// Returns the ids of the panes it was sent to.
+pub fn broadcast_to_session(
+    tmux: &dyn TmuxClient,
+    session_name: &str,
+    command: &str,
+    shell_only: bool,
+) -> Result<Vec<String>> {
+    let format =
+        format!("#{{pane_id}}{TMUX_FIELD_SEPARATOR}#{{pane_current_command}}");
+    let output =
+        tmux.run(&["list-panes", "-s", "-t", session_name, "-F", &format])?;
+    if !output.success {
+        return Err(TsmanError::SessionNotFound(session_name.to_string()));
+    }
+
+    let mut panes = Vec::new();
+    for line in output.stdout.lines().filter(|l| !l.is_empty()) {
+        let Some((pane_id, current)) = line.split_once(TMUX_FIELD_SEPARATOR)
+        else {
+            return Err(TsmanError::UnexpectedOutput {
+                cmd: "tmux list-panes".to_string(),
+                output: line.to_string(),
+            });
+        };
+        if shell_only && !SHELLS.contains(&current) {
+            log::debug!("Skipping pane {pane_id} running {current}");
+            continue;
+        }
+
+        run_checked(tmux, &type_line(pane_id, command))?;
+        panes.push(pane_id.to_string());
+    }
+
+    Ok(panes)
+}
+
 /// Renames an active tmux session.
-pub fn rename_session(session_name: &str, new_name: &str) -> Result<()> {
-    Command::new("tmux")
-        .arg("rename-session")
-        .args(["-t", session_name])
-        .arg(new_name)
-        .status()
-        .context("Failed to rename session")?;
+pub fn rename_session(
+    tmux: &dyn TmuxClient,
+    session_name: &str,
+    new_name: &str,
+) -> Result<()> {
+    let args = ["rename-session", "-t", session_name, new_name];
+    tmux.run(&args)?.check(&args)?;
+
+    Ok(())
+}
+
+/// The last `lines` lines of scrollback and the visible contents of the pane
+/// `target`, with wrapped lines joined and trailing blank lines dropped.
+pub fn capture_pane(
+    tmux: &dyn TmuxClient,
+    target: &str,
+    lines: usize,
+) -> Result<String> {
+    let start = format!("-{lines}");
+    let args = ["capture-pane", "-p", "-J", "-t", target, "-S", &start];
+    let output = tmux.run(&args)?.check(&args)?;
+    Ok(format!("{}\n", output.stdout.trim_end()))
+}
+
+/// Makes `window` - an index, or a name with `=` in front for an exact
+/// match - the current window of a running session, so attaching to the
+/// session shows it.
+pub fn select_window(
+    tmux: &dyn TmuxClient,
+    session_name: &str,
+    window: &str,
+) -> Result<()> {
+    let target = format!("{session_name}:{window}");
+    let args = ["select-window", "-t", &target];
+    tmux.run(&args)?.check(&args)?;
 
     Ok(())
 }
@@ -154,28 +755,136 @@ pub fn rename_session(session_name: &str, new_name: &str) -> Result<()> {
 /// If the session being killed is the one we are currently attached to,
 /// switches to the next active session first so tmux doesn't close the
 /// client. If there is no other session, the kill proceeds normally
-/// (tmux will detach).
-pub fn close_session(session_name: &str) -> Result<()> {
-    if let Ok(current) = get_session_name()
+/// (tmux will detach). This only applies to the default server, since
+/// that's the one we can be attached to.
+pub fn close_session(tmux: &dyn TmuxClient, session_name: &str) -> Result<()> {
+    if tmux.server().is_none()
+        && let Ok(current) = get_session_name(tmux)
         && current == session_name
-        && let Some(next) = get_next_session(session_name)?
+        && let Some(next) = get_next_session(tmux, session_name)?
     {
-        attach_to_session(&next)?;
+        attach_to_session(tmux, &next)?;
     }
 
-    Command::new("tmux")
-        .arg("kill-session")
-        .args(["-t", session_name])
-        .status()
-        .context("Failed to kill session")?;
+    let args = ["kill-session", "-t", session_name];
+    tmux.run(&args)?.check(&args)?;
 
     Ok(())
 }
 
+/// Detaches every client from a running session, leaving the session
+/// itself running.
+pub fn detach_session(tmux: &dyn TmuxClient, session_name: &str) -> Result<()> {
+    // `detach-client -s` fails when nobody is attached, so detach the
+    // clients one by one instead.
+    let args = ["list-clients", "-t", session_name, "-F", "#{client_name}"];
+    let output = tmux.run(&args)?.check(&args)?;
+
+    for client in output.stdout.lines().filter(|c| !c.is_empty()) {
+        let args = ["detach-client", "-t", client];
+        tmux.run(&args)?.check(&args)?;
+    }
+
+    Ok(())
+}
+
+/// A tmux client - a terminal attached to the server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientInfo {
+    /// Name tmux knows the client by, its tty (e.g. `/dev/pts/3`).
+    pub name: String,
+    /// Session the client shows.
+    pub session: String,
+    pub width: u16,
+    pub height: u16,
+}
+
+/// Lists the clients attached to the server, or none if it isn't running.
+pub fn list_clients(tmux: &dyn TmuxClient) -> Result<Vec<ClientInfo>> {
+    let format = [
+        "#{client_name}",
+        "#{client_session}",
+        "#{client_width}",
+        "#{client_height}",
+    ]
+    .join(TMUX_FIELD_SEPARATOR);
+    let output = tmux.run(&["list-clients", "-F", &format])?;
+    if !output.success {
+        return Ok(Vec::new()); // server not running
+    }
+
+    Ok(output
+        .stdout
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split(TMUX_FIELD_SEPARATOR);
+            Some(ClientInfo {
+                name: fields.next()?.to_string(),
+                session: fields.next()?.to_string(),
+                width: fields.next()?.parse().ok()?,
+                height: fields.next()?.parse().ok()?,
+            })
+        })
+        .collect())
+}
+
+/// Name of the client tsman is running in, or `None` outside tmux.
+pub fn current_client(tmux: &dyn TmuxClient) -> Result<Option<String>> {
+    let args = ["display-message", "-p", "#{client_name}"];
+    let output = tmux.run(&args)?;
+    let name = output.stdout.trim();
+    Ok((output.success && !name.is_empty()).then(|| name.to_string()))
+}
+
+/// Names of the running sessions that have no client attached.
+pub fn list_detached_sessions(tmux: &dyn TmuxClient) -> Result<Vec<String>> {
+    if !tmux.run(&["has-session"])?.success {
+        return Ok(Vec::new()); // server not running
+    }
+
+    let format =
+        format!("#{{session_attached}}{TMUX_FIELD_SEPARATOR}#{{session_name}}");
+    let args = ["list-sessions", "-F", &format];
+    let output = tmux.run(&args)?.check(&args)?;
+
+    Ok(output
+        .stdout
+        .lines()
+        .filter_map(|line| line.split_once(TMUX_FIELD_SEPARATOR))
+        .filter(|(attached, _)| *attached == "0")
+        .map(|(_, name)| name.to_string())
+        .collect())
+}
+
+/// The working directory of every running session, keyed by session name.
+/// Empty if the server is not running.
+pub fn list_session_paths(
+    tmux: &dyn TmuxClient,
+) -> Result<HashMap<String, String>> {
+    if !tmux.run(&["has-session"])?.success {
+        return Ok(HashMap::new()); // server not running
+    }
+
+    let format =
+        format!("#{{session_name}}{TMUX_FIELD_SEPARATOR}#{{session_path}}");
+    let args = ["list-sessions", "-F", &format];
+    let output = tmux.run(&args)?.check(&args)?;
+
+    Ok(output
+        .stdout
+        .lines()
+        .filter_map(|line| line.split_once(TMUX_FIELD_SEPARATOR))
+        .map(|(name, path)| (name.to_string(), path.to_string()))
+        .collect())
+}
+
 /// Returns the next active session after `session_name` in the session list,
 /// or `None` if there are no other sessions.
-fn get_next_session(session_name: &str) -> Result<Option<String>> {
-    let sessions = list_active_sessions()?;
+fn get_next_session(
+    tmux: &dyn TmuxClient,
+    session_name: &str,
+) -> Result<Option<String>> {
+    let sessions = list_active_sessions(tmux)?;
     let pos = sessions.iter().position(|s| s == session_name).unwrap_or(0);
 
     // Walk forward from the current position, wrapping around.
@@ -190,46 +899,195 @@ fn get_next_session(session_name: &str) -> Result<Option<String>> {
 }
 
 /// Returns the name of the currently attached tmux session.
-pub fn get_session_name() -> Result<String> {
-    if std::env::var("TMUX").is_err() {
-        anyhow::bail!("Not inside a tmux session");
+pub fn get_session_name(tmux: &dyn TmuxClient) -> Result<String> {
+    if env::var("TMUX").is_err() {
+        return Err(TsmanError::NotInTmux);
+    }
+
+    // Target our own pane explicitly: without `-t` tmux resolves the
+    // session of whichever client sent the command.
+    let mut args = vec!["display-message", "-p"];
+    let pane = env::var("TMUX_PANE").unwrap_or_default();
+    if !pane.is_empty() {
+        args.extend(["-t", &pane]);
+    }
+    args.extend(["-F", "#{session_name}"]);
+
+    let output = tmux.run(&args)?.check(&args)?;
+
+    Ok(output.stdout.trim().to_string())
+}
+
+/// Names and pane counts of the windows of every running session, in
+/// order, keyed by session name. Empty if the server is not running.
+pub fn list_window_shapes(
+    tmux: &dyn TmuxClient,
+) -> Result<HashMap<String, Vec<(String, usize)>>> {
+    if !tmux.run(&["has-session"])?.success {
+        return Ok(HashMap::new());
     }
 
-    let output = Command::new("tmux")
-        .arg("display-message")
-        .arg("-p")
-        .args(["-F", "#{session_name}"])
-        .output()
-        .context("Failed to execute 'tmux display-message'")?;
+    let format = format!(
+        "#{{session_name}}{TMUX_FIELD_SEPARATOR}#{{window_panes}}\
+         {TMUX_FIELD_SEPARATOR}#{{window_name}}"
+    );
+    let args = ["list-windows", "-a", "-F", &format];
+    let output = tmux.run(&args)?.check(&args)?;
+
+    let mut shapes: HashMap<String, Vec<(String, usize)>> = HashMap::new();
+    for line in output.stdout.lines() {
+        let mut fields = line.splitn(3, TMUX_FIELD_SEPARATOR);
+        if let (Some(session), Some(Ok(panes)), Some(window)) =
+            (fields.next(), fields.next().map(str::parse), fields.next())
+        {
+            shapes
+                .entry(session.to_string())
+                .or_default()
+                .push((window.to_string(), panes));
+        }
+    }
+
+    Ok(shapes)
+}
+
+/// How many dead panes (kept open by `remain-on-exit`) each running session
+/// has, keyed by session name. Sessions without any are left out, and the
+/// map is empty if the server is not running.
+pub fn count_dead_panes(
+    tmux: &dyn TmuxClient,
+) -> Result<HashMap<String, usize>> {
+    if !tmux.run(&["has-session"])?.success {
+        return Ok(HashMap::new());
+    }
+
+    let format =
+        format!("#{{pane_dead}}{TMUX_FIELD_SEPARATOR}#{{session_name}}");
+    let args = ["list-panes", "-a", "-F", &format];
+    let output = tmux.run(&args)?.check(&args)?;
+
+    let mut dead: HashMap<String, usize> = HashMap::new();
+    for line in output.stdout.lines() {
+        if let Some(session) = line
+            .strip_prefix("1")
+            .and_then(|rest| rest.strip_prefix(TMUX_FIELD_SEPARATOR))
+        {
+            *dead.entry(session.to_string()).or_default() += 1;
+        }
+    }
+    Ok(dead)
+}
+
+/// The arrangement of the running session `session_name` - its windows'
+/// indexes, names and layouts and its panes' directories - as one string,
+/// for telling when it changes. Cheaper than [`get_session`], which also
+/// looks up the command running in every pane.
+pub fn session_shape(
+    tmux: &dyn TmuxClient,
+    session_name: &str,
+) -> Result<String> {
+    let format = [
+        "#{window_index}",
+        "#{window_name}",
+        "#{window_layout}",
+        "#{pane_index}",
+        "#{pane_current_path}",
+    ]
+    .join(TMUX_FIELD_SEPARATOR);
+    let target = format!("={session_name}");
+    let args = ["list-panes", "-s", "-t", &target, "-F", &format];
+    Ok(tmux.run(&args)?.check(&args)?.stdout)
+}
+
+/// A pane whose process has exited, kept open by `remain-on-exit`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeadPane {
+    pub id: String,
+    pub window_index: String,
+    pub window_name: String,
+    pub index: String,
+}
 
-    let string_output = String::from_utf8(output.stdout)
-        .context("Failed to convert tmux output to UTF-8 string")?;
+/// The dead panes of the running session `session_name`.
+pub fn list_dead_panes(
+    tmux: &dyn TmuxClient,
+    session_name: &str,
+) -> Result<Vec<DeadPane>> {
+    let format = [
+        "#{pane_dead}",
+        "#{pane_id}",
+        "#{window_index}",
+        "#{pane_index}",
+        "#{window_name}",
+    ]
+    .join(TMUX_FIELD_SEPARATOR);
+    let target = format!("={session_name}");
+    let args = ["list-panes", "-s", "-t", &target, "-F", &format];
+    let output = tmux.run(&args)?.check(&args)?;
+
+    Ok(output
+        .stdout
+        .lines()
+        .filter_map(|line| {
+            match line.splitn(5, TMUX_FIELD_SEPARATOR).collect::<Vec<_>>()[..] {
+                ["1", id, window_index, index, window_name] => Some(DeadPane {
+                    id: id.to_string(),
+                    window_index: window_index.to_string(),
+                    window_name: window_name.to_string(),
+                    index: index.to_string(),
+                }),
+                _ => None,
+            }
+        })
+        .collect())
+}
 
-    Ok(string_output.trim().to_string())
+/// Restarts `pane` as the pane it was restored from in `session`'s config -
+/// found by window name, or window index if the window was renamed, and
+/// pane index - or with its original command if the config has no such
+/// pane.
+pub fn respawn_pane(
+    tmux: &dyn TmuxClient,
+    session: &Session,
+    pane: &DeadPane,
+) -> Result<()> {
+    let window = session
+        .windows
+        .iter()
+        .find(|w| w.name == pane.window_name)
+        .or_else(|| {
+            session
+                .windows
+                .iter()
+                .find(|w| w.index == pane.window_index)
+        });
+    let saved = window.and_then(|window| {
+        let saved = window.panes.iter().find(|p| p.index == pane.index)?;
+        Some((window, saved))
+    });
+
+    let plan = match saved {
+        Some((window, saved)) => respawn_plan(&pane.id, session, window, saved),
+        None => vec![TmuxCommand::new(["respawn-pane", "-t", &pane.id])],
+    };
+    for command in plan {
+        run_checked(tmux, &command)?;
+    }
+    Ok(())
 }
 
 /// Lists all active tmux session names. Returns an empty vec if the server is not running.
-pub fn list_active_sessions() -> Result<Vec<String>> {
-    let status = Command::new("tmux")
-        .arg("has-session")
-        .stderr(std::process::Stdio::null())
-        .status()
-        .context("Failed to check tmux server status")?;
-
-    if !status.success() {
+pub fn list_active_sessions(tmux: &dyn TmuxClient) -> Result<Vec<String>> {
+    let status = tmux.run(&["has-session"])?;
+
+    if !status.success {
         return Ok(Vec::new()); // server not running
     }
 
-    let output = Command::new("tmux")
-        .arg("list-sessions")
-        .args(["-F", "#{session_name}"])
-        .output()
-        .context("Failed to get active sessions")?;
-
-    let string_output = String::from_utf8(output.stdout)
-        .context("Failed to convert tmux output to UTF-8 string")?;
+    let args = ["list-sessions", "-F", "#{session_name}"];
+    let output = tmux.run(&args)?.check(&args)?;
 
-    let parts: Vec<String> = string_output
+    let parts: Vec<String> = output
+        .stdout
         .trim()
         .split(TMUX_LINE_SEPARATOR)
         .map(|s| s.to_string())
@@ -238,89 +1096,212 @@ pub fn list_active_sessions() -> Result<Vec<String>> {
     Ok(parts)
 }
 
-fn get_session_path(session_name: &str) -> Result<String> {
-    let output = Command::new("tmux")
-        .arg("display-message")
-        .arg("-p")
-        .args(["-t", session_name])
-        .args(["-F", "#{session_path}"])
-        .output()
-        .context("Failed to execute 'tmux display-message'")?;
-
-    let string_output = String::from_utf8(output.stdout)
-        .context("Failed to convert tmux output to UTF-8 string")?;
+fn get_session_path(
+    tmux: &dyn TmuxClient,
+    session_name: &str,
+) -> Result<String> {
+    let args = [
+        "display-message",
+        "-p",
+        "-t",
+        session_name,
+        "-F",
+        "#{session_path}",
+    ];
+    let output = tmux.run(&args)?;
+    if !output.success {
+        return Err(TsmanError::SessionNotFound(session_name.to_string()));
+    }
 
-    Ok(string_output.trim().to_string())
+    Ok(output.stdout.trim().to_string())
 }
 
-fn get_windows(session_name: &str) -> Result<Vec<Window>> {
-    let output = Command::new("tmux")
-        .arg("list-windows")
-        .args(["-t", session_name])
-        .args(["-F", "#{window_index} #{window_name} #{window_layout}"])
-        .output()
-        .context("Failed to execute 'tmux list-windows'")?;
+fn get_windows(
+    tmux: &dyn TmuxClient,
+    session_name: &str,
+) -> Result<Vec<Window>> {
+    let output =
+        tmux.run(&["list-panes", "-s", "-t", session_name, "-F", PANE_FORMAT])?;
+    if !output.success {
+        return Err(TsmanError::SessionNotFound(session_name.to_string()));
+    }
 
-    let string_output = String::from_utf8(output.stdout)
-        .context("Failed to convert tmux output to UTF-8 string")?;
+    let processes = ProcessTable::load(tmux)?;
+    let global_monitor = get_global_monitor(tmux)?;
+
+    // Panes are listed window by window, so each window's panes are
+    // consecutive.
+    let mut windows: Vec<Window> = Vec::new();
+    for line in output.stdout.lines().filter(|line| !line.is_empty()) {
+        let (window, pane) =
+            parse_pane_string(&processes, global_monitor, line)?;
+
+        match windows.last_mut() {
+            Some(last) if last.index == window.index => last.panes.push(pane),
+            _ => windows.push(Window {
+                panes: vec![pane],
+                ..window
+            }),
+        }
+    }
 
-    string_output
-        .trim()
-        .split(TMUX_LINE_SEPARATOR)
-        .map(|window| parse_window_string(window, session_name))
-        .collect()
+    Ok(windows)
 }
 
-fn parse_window_string(window: &str, session_name: &str) -> Result<Window> {
-    let mut parts = window.split(" ");
+/// Whether tmux's global `destroy-unattached` option would kill a session
+/// nobody is attached to - on, or `keep-last`/`keep-group` since tmux 3.4.
+/// Off when no server is running yet, as in a fresh one.
+fn destroys_unattached(tmux: &dyn TmuxClient) -> Result<bool> {
+    let output = tmux.run(&["show-options", "-gv", "destroy-unattached"])?;
+    if !output.success {
+        return Ok(false); // server not running
+    }
+    Ok(!matches!(output.stdout.trim(), "off" | ""))
+}
 
-    match (parts.next(), parts.next(), parts.next()) {
-        (Some(index), Some(name), Some(layout)) => {
-            let index = index.to_string();
-            let window_target = format!("{session_name}:{index}");
-            let panes = get_panes(&window_target)?;
+/// `new_session` followed - in the same tmux invocation, before tmux
+/// can destroy it - turning `destroy-unattached` off for the new session,
+/// and hooks handing it back to the global setting once a client attaches,
+/// or switches to it from another session.
+fn guard_until_attached(
+    new_session: TmuxCommand,
+    session_name: &str,
+) -> TmuxCommand {
+    // Without -t, so they apply to the session after it is renamed.
+    let unguard = [
+        "set-option -u destroy-unattached",
+        "set-hook -u client-attached",
+        "set-hook -u client-session-changed",
+    ]
+    .join(" ; ");
+    new_session
+        .then([
+            "set-option",
+            "-t",
+            session_name,
+            "destroy-unattached",
+            "off",
+        ])
+        .then(["set-hook", "-t", session_name, "client-attached", &unguard])
+        .then([
+            "set-hook",
+            "-t",
+            session_name,
+            "client-session-changed",
+            &unguard,
+        ])
+}
 
-            Ok(Window {
-                index,
-                name: name.to_string(),
-                layout: layout.to_string(),
-                panes,
-            })
-        }
-        _ => {
-            anyhow::bail!(format!("Failed to parse window string: {}", window))
-        }
+/// Runs `command` and the commands chained onto it in one call to tmux.
+fn run_command(
+    tmux: &dyn TmuxClient,
+    command: &TmuxCommand,
+) -> Result<TmuxOutput> {
+    let commands: Vec<Vec<&str>> = command
+        .commands()
+        .map(|args| args.iter().map(String::as_str).collect())
+        .collect();
+    let commands: Vec<&[&str]> = commands.iter().map(Vec::as_slice).collect();
+    tmux.run_all(&commands)
+}
+
+/// Like [`run_command`], but failing if tmux rejects any of the commands.
+fn run_checked(
+    tmux: &dyn TmuxClient,
+    command: &TmuxCommand,
+) -> Result<TmuxOutput> {
+    let output = run_command(tmux, command)?;
+    if output.success {
+        return Ok(output);
     }
+    Err(TsmanError::TmuxFailed {
+        cmd: command.to_string(),
+        stderr: output.stderr,
+    })
 }
 
-fn get_panes(window_target: &str) -> Result<Vec<Pane>> {
-    let output = Command::new("tmux")
-        .arg("list-panes")
-        .args(["-t", window_target])
-        .args(["-F", "#{pane_index} #{pane_pid} #{pane_current_path}"])
-        .output()
-        .with_context(|| {
-            format!(
-                "Failed to execute 'tmux list-panes' for window {window_target}",
-            )
-        })?;
+/// Whether the server exits now that no client is attached, because of the
+/// global `exit-unattached` option.
+fn exits_unattached(tmux: &dyn TmuxClient) -> Result<bool> {
+    let args = ["show-options", "-gv", "exit-unattached"];
+    if tmux.run(&args)?.check(&args)?.stdout.trim() != "on" {
+        return Ok(false);
+    }
+    let args = ["list-clients", "-F", "#{client_name}"];
+    Ok(tmux.run(&args)?.check(&args)?.stdout.trim().is_empty())
+}
 
-    let string_output = String::from_utf8(output.stdout)
-        .context("Failed to convert tmux output to UTF-8 string")?;
+/// Whether tmux's global `renumber-windows` option is on. Off when no
+/// server is running yet, as in a fresh one.
+pub fn renumbers_windows(tmux: &dyn TmuxClient) -> Result<bool> {
+    let output = tmux.run(&["show-options", "-gv", "renumber-windows"])?;
+    if !output.success {
+        return Ok(false); // server not running
+    }
+    Ok(output.stdout.trim() == "on")
+}
 
-    string_output
-        .trim()
-        .split(TMUX_LINE_SEPARATOR)
-        .map(parse_pane_string)
-        .collect()
+/// tmux's global `base-index`, the index a new session's first window gets.
+/// 0 when no server is running yet, as in a fresh one.
+pub fn base_index(tmux: &dyn TmuxClient) -> Result<usize> {
+    let output = tmux.run(&["show-options", "-gv", "base-index"])?;
+    if !output.success {
+        return Ok(0); // server not running
+    }
+    Ok(output.stdout.trim().parse().unwrap_or(0))
 }
 
-fn parse_pane_string(pane: &str) -> Result<Pane> {
-    let mut parts = pane.split(TMUX_FIELD_SEPARATOR);
+/// The global `monitor-*` window options, which windows inherit unless they
+/// set their own.
+fn get_global_monitor(tmux: &dyn TmuxClient) -> Result<Monitor> {
+    let args = ["show-options", "-gw"];
+    let output = tmux.run(&args)?.check(&args)?;
+
+    let mut monitor = Monitor::default();
+    for line in output.stdout.lines() {
+        match line.split_once(' ') {
+            Some(("monitor-activity", value)) => {
+                monitor.activity = value == "on";
+            }
+            Some(("monitor-bell", value)) => monitor.bell = value == "on",
+            Some(("monitor-silence", value)) => {
+                monitor.silence = value.parse().unwrap_or(0);
+            }
+            _ => {}
+        }
+    }
+    Ok(monitor)
+}
 
-    match (parts.next(), parts.next(), parts.next()) {
-        (Some(index), Some(pid), Some(work_dir_str)) => {
-            let process = get_foreground_process(pid)?;
+/// Parses a [`PANE_FORMAT`] line into its pane and the (pane-less) window
+/// it belongs to. The window's alert settings are kept only if they differ
+/// from `global_monitor`.
+fn parse_pane_string(
+    processes: &ProcessTable,
+    global_monitor: Monitor,
+    pane: &str,
+) -> Result<(Window, Pane)> {
+    let parts: Vec<&str> = pane.splitn(9, TMUX_FIELD_SEPARATOR).collect();
+
+    match parts[..] {
+        [
+            window_index,
+            window_name,
+            layout,
+            activity,
+            bell,
+            silence,
+            index,
+            pid,
+            work_dir_str,
+        ] => {
+            let pid = pid.trim().parse::<u32>().map_err(|_| {
+                TsmanError::UnexpectedOutput {
+                    cmd: "tmux list-panes".to_string(),
+                    output: pid.to_string(),
+                }
+            })?;
+            let process = processes.foreground(pid);
 
             let current_command = match process {
                 Some((cmd_pid, cmdline)) if std::process::id() != cmd_pid => {
@@ -329,114 +1310,100 @@ fn parse_pane_string(pane: &str) -> Result<Pane> {
                 _ => None,
             };
 
-            Ok(Pane {
+            let monitor = Monitor {
+                activity: activity == "1",
+                bell: bell == "1",
+                silence: silence.parse().unwrap_or(0),
+            };
+
+            let window = Window {
+                index: window_index.to_string(),
+                name: window_name.to_string(),
+                layout: layout.to_string(),
+                monitor: (monitor != global_monitor).then_some(monitor),
+                ..Default::default()
+            };
+
+            let pane = Pane {
                 index: index.to_string(),
                 current_command,
                 work_dir: work_dir_str.to_string(),
-            })
+                shell: processes.shell(pid),
+                ..Default::default()
+            };
+
+            Ok((window, pane))
         }
-        _ => anyhow::bail!("Failed to parse pane string: {}", pane),
+        _ => Err(TsmanError::UnexpectedOutput {
+            cmd: "tmux list-panes".to_string(),
+            output: pane.to_string(),
+        }),
     }
 }
 
-fn get_foreground_process(shell_pid: &str) -> Result<Option<(u32, String)>> {
-    Ok(get_process_children(shell_pid)?.into_iter().next())
+/// Every process on the host, by PID and as children of their parent.
+struct ProcessTable {
+    commands: HashMap<u32, String>,
+    children: HashMap<u32, Vec<(u32, String)>>,
 }
 
-fn get_process_children(shell_pid: &str) -> Result<Vec<(u32, String)>> {
-    let target_ppid = shell_pid
-        .trim()
-        .parse::<u32>()
-        .with_context(|| format!("Invalid shell PID: {shell_pid}"))?;
+impl ProcessTable {
+    /// Reads the process table once, so every pane can be resolved without
+    /// another `ps` call.
+    fn load(tmux: &dyn TmuxClient) -> Result<Self> {
+        let output_str = tmux.process_table()?;
 
-    let output = Command::new("ps")
-        .args(["ax", "-o", "pid=,ppid=,args="])
-        .output()
-        .with_context(|| {
-            format!("Failed to get children of process #{shell_pid}")
-        })?;
+        let mut commands = HashMap::new();
+        let mut children: HashMap<u32, Vec<(u32, String)>> = HashMap::new();
 
-    let output_str = String::from_utf8(output.stdout)?;
+        for line in output_str.lines() {
+            let trimmed = line.trim();
+            if trimmed.is_empty() {
+                continue;
+            }
 
-    let mut children = Vec::new();
+            let mut iter = trimmed.splitn(2, |c: char| c.is_ascii_whitespace());
+            let pid_str = iter.next().unwrap_or("").trim();
+            let remainder = iter.next().unwrap_or("").trim_start();
 
-    for line in output_str.lines() {
-        let trimmed = line.trim();
-        if trimmed.is_empty() {
-            continue;
-        }
-
-        let mut iter = trimmed.splitn(2, |c: char| c.is_ascii_whitespace());
-        let pid_str = iter.next().unwrap_or("").trim();
-        let remainder = iter.next().unwrap_or("").trim_start();
+            let mut iter2 =
+                remainder.splitn(2, |c: char| c.is_ascii_whitespace());
+            let ppid_str = iter2.next().unwrap_or("").trim();
+            let cmdline = iter2.next().unwrap_or("").trim_start();
 
-        let mut iter2 = remainder.splitn(2, |c: char| c.is_ascii_whitespace());
-        let ppid_str = iter2.next().unwrap_or("").trim();
-        let cmdline = iter2.next().unwrap_or("").trim_start();
-
-        let Ok(pid) = pid_str.parse::<u32>() else {
-            continue;
-        };
-        let Ok(ppid) = ppid_str.parse::<u32>() else {
-            continue;
-        };
+            let Ok(pid) = pid_str.parse::<u32>() else {
+                continue;
+            };
+            let Ok(ppid) = ppid_str.parse::<u32>() else {
+                continue;
+            };
 
-        if ppid == target_ppid && !cmdline.is_empty() {
-            children.push((pid, cmdline.to_string()));
+            if !cmdline.is_empty() {
+                commands.insert(pid, cmdline.to_string());
+                children
+                    .entry(ppid)
+                    .or_default()
+                    .push((pid, cmdline.to_string()));
+            }
         }
-    }
-
-    Ok(children)
-}
-
-fn get_window_config_cmd(
-    temp_session_name: &str,
-    session: &Session,
-    window: &Window,
-) -> Result<String> {
-    let window_target = format!("{}:{}", temp_session_name, window.index);
-
-    let mut cmd = String::new();
-
-    cmd +=
-        &format!("tmux rename-window -t {} {}\n", window_target, window.name);
 
-    for _ in window.panes.iter().skip(1) {
-        cmd += &format!(
-            "tmux split-window -d -t {} -c {}\n",
-            window_target,
-            escape(Cow::from(&session.work_dir))
-        );
+        Ok(Self { commands, children })
     }
 
-    cmd += &format!(
-        "tmux select-layout -t {} {}\n",
-        window_target,
-        escape(Cow::from(&window.layout))
-    );
-
-    for pane in &window.panes {
-        let pane_target = format!("{}.{}", window_target, pane.index);
-
-        if pane.work_dir != session.work_dir {
-            cmd += &format!(
-                "tmux send-keys -t {} {} C-m\n",
-                pane_target,
-                escape(
-                    format!("cd {}; clear", escape(Cow::from(&pane.work_dir)))
-                        .into()
-                ),
-            );
-        }
-
-        if let Some(pane_cmd) = &pane.current_command {
-            cmd += &format!(
-                "tmux send-keys -t {} {} C-m\n",
-                pane_target,
-                escape(pane_cmd.into())
-            );
-        }
+    /// Returns the first child of the pane's shell, i.e. the command
+    /// running in the foreground.
+    fn foreground(&self, shell_pid: u32) -> Option<(u32, String)> {
+        self.children
+            .get(&shell_pid)
+            .and_then(|children| children.first())
+            .cloned()
     }
 
-    Ok(cmd)
+    /// Name of the shell the pane process `pid` is running, if it is one of
+    /// [`SHELLS`]. Login shells show up as e.g. `-zsh`.
+    fn shell(&self, pid: u32) -> Option<String> {
+        let program = self.commands.get(&pid)?.split_whitespace().next()?;
+        let name = program.trim_start_matches('-').rsplit('/').next()?;
+        SHELLS.contains(&name).then(|| name.to_string())
+    }
 }
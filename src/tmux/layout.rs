@@ -1,6 +1,7 @@
 //! Tmux layout model - [`Layout`] -> [`LayoutWindow`], capturing structure without work dirs.
 use serde::{Deserialize, Serialize};
 
+use super::charset::Charset;
 use super::layout_parser;
 use super::layout_renderer;
 use super::session::{Session, Window};
@@ -47,10 +48,16 @@ impl LayoutWindow {
     /// Returns a visual box-drawing preview of the window's pane layout.
     ///
     /// Falls back to a text description if parsing fails or space is too small.
-    pub fn get_preview(&self, width: usize, height: usize) -> Vec<String> {
+    pub fn get_preview(
+        &self,
+        width: usize,
+        height: usize,
+        charset: Charset,
+    ) -> Vec<String> {
         if let Ok(node) = layout_parser::parse(&self.layout)
-            && let Some(lines) =
-                layout_renderer::render(&node, &self.name, width, height)
+            && let Some(lines) = layout_renderer::render(
+                &node, &self.name, width, height, charset,
+            )
         {
             return lines;
         }
@@ -66,14 +73,14 @@ impl LayoutWindow {
 
 impl Layout {
     /// Returns a visual preview of all windows in the layout.
-    pub fn get_preview(&self, width: usize) -> String {
+    pub fn get_preview(&self, width: usize, charset: Charset) -> String {
         let mut lines: Vec<String> = Vec::new();
 
         for (i, window) in self.windows.iter().enumerate() {
             if i > 0 {
                 lines.push(String::new());
             }
-            lines.extend(window.get_preview(width, WINDOW_BOX_HEIGHT));
+            lines.extend(window.get_preview(width, WINDOW_BOX_HEIGHT, charset));
         }
 
         lines.join("\n")
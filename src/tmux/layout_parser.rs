@@ -4,7 +4,11 @@
 //! `<checksum>,<WxH,X,Y><body>` where body is either a leaf pane ID,
 //! `{children}` for horizontal splits, or `[children]` for vertical splits.
 
-use anyhow::{Context, Result, bail};
+use crate::error::{Result, TsmanError};
+
+/// Parse steps report a reason, wrapped into [`TsmanError::InvalidLayout`]
+/// once parsing stops.
+type ParseResult<T> = std::result::Result<T, String>;
 
 /// A node in the parsed layout tree.
 #[derive(Debug, Clone, PartialEq)]
@@ -30,38 +34,47 @@ pub enum LayoutBody {
 /// The input format is: `<4-char-hex-checksum>,<layout-tree>`
 /// e.g. `"b]cd,190x47,0,0{95x47,0,0,1,94x47,96,0,2}"`
 pub fn parse(layout_str: &str) -> Result<LayoutNode> {
+    parse_layout(layout_str).map_err(|reason| TsmanError::InvalidLayout {
+        layout: layout_str.to_string(),
+        reason,
+    })
+}
+
+fn parse_layout(layout_str: &str) -> ParseResult<LayoutNode> {
     // Skip the checksum: find the first comma, then parse from after it
     let rest = skip_checksum(layout_str)?;
     let (node, remaining) = parse_node(rest)?;
     if !remaining.is_empty() {
-        bail!("unexpected trailing content: {remaining:?}");
+        return Err(format!("unexpected trailing content: {remaining:?}"));
     }
     Ok(node)
 }
 
 /// Skip the 4-char checksum and comma prefix.
-fn skip_checksum(input: &str) -> Result<&str> {
-    let comma_pos = input.find(',').context("missing comma after checksum")?;
+fn skip_checksum(input: &str) -> ParseResult<&str> {
+    let comma_pos = input.find(',').ok_or("missing comma after checksum")?;
     Ok(&input[comma_pos + 1..])
 }
 
 /// Parse a single node: `WxH,X,Y` followed by body.
-fn parse_node(input: &str) -> Result<(LayoutNode, &str)> {
-    let (width, rest) = parse_u32_until(input, 'x').context("parsing width")?;
-    let (height, rest) =
-        parse_u32_until(rest, ',').context("parsing height")?;
+fn parse_node(input: &str) -> ParseResult<(LayoutNode, &str)> {
+    let (width, rest) = parse_u32_until(input, 'x')
+        .map_err(|e| format!("parsing width: {e}"))?;
+    let (height, rest) = parse_u32_until(rest, ',')
+        .map_err(|e| format!("parsing height: {e}"))?;
     // Skip X position
-    let (_, rest) = parse_u32_until(rest, ',').context("parsing x position")?;
+    let (_, rest) = parse_u32_until(rest, ',')
+        .map_err(|e| format!("parsing x position: {e}"))?;
     // Skip Y position
-    let (_, rest) =
-        parse_y_and_detect_body(rest).context("parsing y position")?;
+    let (_, rest) = parse_y_and_detect_body(rest)
+        .map_err(|e| format!("parsing y position: {e}"))?;
 
     parse_body(rest, width, height)
 }
 
 /// Parse Y coordinate, which is followed by either a bracket, comma+pane_id, or end of input.
 /// Returns (y_value, remaining_input_starting_at_body_indicator).
-fn parse_y_and_detect_body(input: &str) -> Result<(u32, &str)> {
+fn parse_y_and_detect_body(input: &str) -> ParseResult<(u32, &str)> {
     let mut end = 0;
     for (i, c) in input.char_indices() {
         if c == '{' || c == '[' || c == ',' || c == '}' || c == ']' {
@@ -69,11 +82,13 @@ fn parse_y_and_detect_body(input: &str) -> Result<(u32, &str)> {
             break;
         }
         if !c.is_ascii_digit() {
-            bail!("unexpected char {c:?} in Y coordinate");
+            return Err(format!("unexpected char {c:?} in Y coordinate"));
         }
         end = i + 1;
     }
-    let y: u32 = input[..end].parse().context("invalid Y coordinate")?;
+    let y: u32 = input[..end]
+        .parse()
+        .map_err(|_| "invalid Y coordinate".to_string())?;
     Ok((y, &input[end..]))
 }
 
@@ -82,7 +97,7 @@ fn parse_body(
     input: &str,
     width: u32,
     height: u32,
-) -> Result<(LayoutNode, &str)> {
+) -> ParseResult<(LayoutNode, &str)> {
     let node_of = |body, rest| {
         (
             LayoutNode {
@@ -120,7 +135,10 @@ fn parse_body(
             // We've reached a parent's closing bracket - treat as leaf with no pane_id
             Ok(node_of(LayoutBody::Leaf, input))
         }
-        _ => bail!("unexpected char {:?} after coordinates", &input[..1]),
+        _ => Err(format!(
+            "unexpected char {:?} after coordinates",
+            &input[..1]
+        )),
     }
 }
 
@@ -128,13 +146,15 @@ fn parse_body(
 fn parse_children(
     input: &str,
     close_bracket: u8,
-) -> Result<(Vec<LayoutNode>, &str)> {
+) -> ParseResult<(Vec<LayoutNode>, &str)> {
     let mut children = Vec::new();
     let mut rest = input;
 
     loop {
         if rest.is_empty() {
-            bail!("unexpected end of input, expected closing bracket");
+            return Err(
+                "unexpected end of input, expected closing bracket".into()
+            );
         }
         if rest.as_bytes()[0] == close_bracket {
             rest = &rest[1..];
@@ -143,7 +163,10 @@ fn parse_children(
         if !children.is_empty() {
             // Expect comma separator between children
             if rest.as_bytes()[0] != b',' {
-                bail!("expected ',' between children, got {:?}", &rest[..1]);
+                return Err(format!(
+                    "expected ',' between children, got {:?}",
+                    &rest[..1]
+                ));
             }
             rest = &rest[1..];
         }
@@ -156,12 +179,12 @@ fn parse_children(
 }
 
 /// Parse digits as u32 until the given delimiter, consuming the delimiter.
-fn parse_u32_until(input: &str, delim: char) -> Result<(u32, &str)> {
+fn parse_u32_until(input: &str, delim: char) -> ParseResult<(u32, &str)> {
     let pos = input
         .find(delim)
-        .with_context(|| format!("expected {delim:?} delimiter"))?;
+        .ok_or_else(|| format!("expected {delim:?} delimiter"))?;
     let value: u32 = input[..pos]
         .parse()
-        .with_context(|| format!("invalid number: {:?}", &input[..pos]))?;
+        .map_err(|_| format!("invalid number: {:?}", &input[..pos]))?;
     Ok((value, &input[pos + 1..]))
 }
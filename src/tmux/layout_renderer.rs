@@ -1,8 +1,9 @@
 //! Renders a parsed [`LayoutNode`] tree as a box-drawing diagram.
 //!
 //! Produces a 2D grid of characters showing pane arrangements with
-//! proportional sizing and Unicode box-drawing borders.
+//! proportional sizing and Unicode box-drawing borders, or ASCII ones.
 
+use super::charset::Charset;
 use super::layout_parser::{LayoutBody, LayoutNode};
 
 const MIN_PANE_WIDTH: usize = 3;
@@ -18,6 +19,7 @@ pub fn render(
     name: &str,
     width: usize,
     height: usize,
+    charset: Charset,
 ) -> Option<Vec<String>> {
     if width < MIN_PANE_WIDTH || height < MIN_PANE_HEIGHT {
         return None;
@@ -26,6 +28,11 @@ pub fn render(
     let mut grid = Grid::new(width, height);
     grid.draw_box(0, 0, width, height);
     draw_splits(&mut grid, node, 0, 0, width, height);
+    // Junctions are resolved on the Unicode characters, so translate only
+    // once the diagram is complete - and before the name is written.
+    for cell in grid.cells.iter_mut().flatten() {
+        *cell = charset.box_char(*cell);
+    }
     grid.overlay_name(name);
     Some(grid.to_lines())
 }
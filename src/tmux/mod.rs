@@ -1,5 +1,10 @@
+pub mod charset;
+pub mod client;
+pub mod control;
+pub mod import;
 pub mod interface;
 pub mod layout;
 pub mod layout_parser;
 pub mod layout_renderer;
+pub mod plan;
 pub mod session;
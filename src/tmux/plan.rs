@@ -0,0 +1,546 @@
+//! Restore plan - the tmux commands that rebuild a [`Session`], generated
+//! without touching tmux so it can be inspected, printed or tested.
+use std::borrow::Cow;
+use std::fmt;
+
+use shell_escape::escape;
+
+use crate::tmux::client::escape_separator;
+use crate::tmux::session::{Pane, Session, Split, Window};
+
+/// A single tmux invocation, stored as its argument list (without the
+/// leading `tmux`). Arguments are literal - clients escape them the way
+/// they send commands to tmux.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TmuxCommand {
+    pub args: Vec<String>,
+    /// Commands run right after this one in the same invocation, each as
+    /// its own argument list - see [`TmuxClient::run_all`].
+    ///
+    /// [`TmuxClient::run_all`]: crate::tmux::client::TmuxClient::run_all
+    pub then: Vec<Vec<String>>,
+}
+
+impl TmuxCommand {
+    pub fn new<I, S>(args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            args: args.into_iter().map(Into::into).collect(),
+            then: Vec::new(),
+        }
+    }
+
+    /// Chains the command `args` onto this one.
+    pub fn then<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.then.push(args.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// The argument lists of this command and those chained onto it.
+    pub fn commands(&self) -> impl Iterator<Item = &[String]> {
+        std::iter::once(self.args.as_slice())
+            .chain(self.then.iter().map(Vec::as_slice))
+    }
+}
+
+/// Formats the command as a shell-escaped `tmux ...` line, with chained
+/// commands separated by `\;`.
+impl fmt::Display for TmuxCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "tmux")?;
+        for (i, args) in self.commands().enumerate() {
+            if i > 0 {
+                write!(f, " \\;")?;
+            }
+            for arg in args {
+                let arg = escape_separator(arg);
+                write!(f, " {}", escape(Cow::from(arg.as_str())))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A plan command together with the part of the session it builds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlanStep {
+    /// 1-based position of the window the command builds.
+    pub window: usize,
+    /// 1-based position of the pane within that window, for commands that
+    /// act on a single pane.
+    pub pane: Option<usize>,
+    /// Whether the command creates the window, printing its `@id` with
+    /// [`WindowTargets::Id`].
+    pub creates_window: bool,
+    pub command: TmuxCommand,
+}
+
+/// How the commands of a plan refer to the windows they build.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WindowTargets {
+    /// `session:index`, readable but shifted by tmux when it renumbers
+    /// windows.
+    #[default]
+    Index,
+    /// The window's `@id`, known only once tmux has created the window, so
+    /// the commands use a [`window_placeholder`] in its place.
+    Id,
+}
+
+/// How far a restore has got, reported after each step that succeeds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    pub step: usize,
+    pub steps: usize,
+    pub window: usize,
+    pub windows: usize,
+    pub pane: Option<usize>,
+}
+
+impl Progress {
+    pub fn is_done(&self) -> bool {
+        self.step == self.steps
+    }
+}
+
+/// Formats the progress as `window N/M` plus `, pane K` for pane steps.
+impl fmt::Display for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "window {}/{}", self.window, self.windows)?;
+        if let Some(pane) = self.pane {
+            write!(f, ", pane {pane}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Builds the commands that recreate `session` as a detached tmux session
+/// named `session_name`.
+pub fn restore_plan(session: &Session, session_name: &str) -> Vec<TmuxCommand> {
+    restore_steps(session, session_name, WindowTargets::Index)
+        .into_iter()
+        .map(|step| step.command)
+        .collect()
+}
+
+/// Like [`restore_plan`], but records which window and pane each command
+/// builds so the restore can report its progress, and refers to the
+/// windows as `targets` says.
+///
+/// Every window is created at its saved index, gaps included. The first one
+/// comes with the session at tmux's `base-index`, so it is moved to its
+/// index unless it is already there - except with [`WindowTargets::Id`],
+/// where tmux would renumber it right back.
+pub fn restore_steps(
+    session: &Session,
+    session_name: &str,
+    targets: WindowTargets,
+) -> Vec<PlanStep> {
+    let print_id: &[&str] = match targets {
+        WindowTargets::Index => &[],
+        WindowTargets::Id => &["-P", "-F", "#{window_id}"],
+    };
+    let window_target = |position: usize, window: &Window| match targets {
+        WindowTargets::Index => format!("{session_name}:{}", window.index),
+        WindowTargets::Id => window_placeholder(position),
+    };
+
+    let first_pane = session.windows.first().and_then(|w| w.panes.first());
+    let mut new_session = new_pane(
+        ["new-session", "-d", "-s", session_name],
+        session,
+        session.windows.first(),
+        first_pane,
+    );
+    new_session
+        .args
+        .splice(2..2, print_id.iter().map(|arg| arg.to_string()));
+    let mut plan = vec![PlanStep {
+        window: 1,
+        pane: None,
+        creates_window: true,
+        command: new_session,
+    }];
+
+    if let Some(first) = session.windows.first()
+        && targets == WindowTargets::Index
+    {
+        let only_window = format!("{session_name}:^");
+        let move_window = TmuxCommand::new([
+            "move-window",
+            "-d",
+            "-s",
+            &only_window,
+            "-t",
+            &format!("{session_name}:{}", first.index),
+        ]);
+        plan.push(PlanStep {
+            window: 1,
+            pane: None,
+            creates_window: false,
+            command: TmuxCommand::new([
+                "if-shell",
+                "-F",
+                "-t",
+                &only_window,
+                &format!("#{{==:#{{window_index}},{}}}", first.index),
+                "",
+                &move_window.args.join(" "),
+            ]),
+        });
+    }
+
+    for (i, window) in session.windows.iter().enumerate() {
+        if i > 0 {
+            let target = format!("{session_name}:{}", window.index);
+            let mut new_window = new_pane(
+                ["new-window", "-d", "-t", &target],
+                session,
+                Some(window),
+                window.panes.first(),
+            );
+            new_window
+                .args
+                .splice(2..2, print_id.iter().map(|arg| arg.to_string()));
+            plan.push(PlanStep {
+                window: i + 1,
+                pane: None,
+                creates_window: true,
+                command: new_window,
+            });
+        }
+
+        plan.extend(
+            window_plan(&window_target(i + 1, window), session, window)
+                .into_iter()
+                .map(|(pane, command)| PlanStep {
+                    window: i + 1,
+                    pane,
+                    creates_window: false,
+                    command,
+                }),
+        );
+    }
+
+    plan
+}
+
+/// Placeholder for the `@id` of the window at 1-based `position` of a plan
+/// with [`WindowTargets::Id`], replaced once tmux has created the window.
+pub fn window_placeholder(position: usize) -> String {
+    format!("{{window {position}}}")
+}
+
+/// Like [`restore_steps`], but adds `windows` of `session` to the running
+/// session `session_name` instead of creating it. Each window is created at
+/// its own index, which must be free.
+pub fn append_steps(
+    session: &Session,
+    session_name: &str,
+    windows: &[Window],
+) -> Vec<PlanStep> {
+    let mut plan = Vec::new();
+    for (i, window) in windows.iter().enumerate() {
+        let target = format!("{session_name}:{}", window.index);
+        plan.push(PlanStep {
+            window: i + 1,
+            pane: None,
+            creates_window: true,
+            command: new_pane(
+                ["new-window", "-d", "-t", &target],
+                session,
+                Some(window),
+                window.panes.first(),
+            ),
+        });
+        plan.extend(window_plan(&target, session, window).into_iter().map(
+            |(pane, command)| PlanStep {
+                window: i + 1,
+                pane,
+                creates_window: false,
+                command,
+            },
+        ));
+    }
+    plan
+}
+
+/// Types `text` into the pane `target` and presses Enter.
+///
+/// The text is sent with `-l`, so words like `Up` or `C-c` in it are typed
+/// rather than pressed as keys, and newlines in it are typed as Enter too.
+pub fn type_line(target: &str, text: &str) -> TmuxCommand {
+    TmuxCommand::new(["send-keys", "-t", target, "-l", "--", text]).then([
+        "send-keys",
+        "-t",
+        target,
+        "C-m",
+    ])
+}
+
+/// Renders a plan as a shell script, one `tmux` command per line.
+pub fn to_script(plan: &[TmuxCommand]) -> String {
+    plan.iter().map(|cmd| format!("{cmd}\n")).collect()
+}
+
+/// Renders the restore of `session` as a shell script: its `pre` commands,
+/// which tsman runs itself in the session's directory, then its
+/// [`restore_plan`].
+pub fn restore_script(session: &Session, session_name: &str) -> String {
+    let cd = format!("cd {}", escape(Cow::from(&session.work_dir)));
+    let mut script: String = session
+        .pre
+        .iter()
+        .map(|pre| format!("({cd} && {pre})\n"))
+        .collect();
+    script += &to_script(&restore_plan(session, session_name));
+    script
+}
+
+/// The command `args` creating `pane` of `window`, followed by its
+/// environment and the directory it starts in. A pane with saved history
+/// starts in its own directory and prints the history before starting the
+/// shell, since changing directories later would clear it; the others start
+/// in the session's. A pane with a saved shell starts that shell rather
+/// than tmux's `default-shell`.
+fn new_pane<I, S>(
+    args: I,
+    session: &Session,
+    window: Option<&Window>,
+    pane: Option<&Pane>,
+) -> TmuxCommand
+where
+    I: IntoIterator<Item = S>,
+    S: Into<String>,
+{
+    let mut command = TmuxCommand::new(args);
+
+    let mut env = window.map(|w| w.env.clone()).unwrap_or_default();
+    env.extend(pane.iter().flat_map(|p| p.env.clone()));
+    for (name, value) in env {
+        command
+            .args
+            .extend(["-e".to_string(), format!("{name}={value}")]);
+    }
+
+    let shell = pane.and_then(|pane| pane.shell.as_deref());
+    match pane.and_then(|pane| Some((pane, pane.history.as_deref()?))) {
+        Some((pane, history)) => {
+            let shell = match shell {
+                Some(shell) => escape(Cow::from(shell)),
+                None => Cow::from("\"${SHELL:-sh}\""),
+            };
+            let replay = format!(
+                "gzip -dc {} 2>/dev/null; exec {shell} -l",
+                escape(Cow::from(history))
+            );
+            command.args.extend([
+                "-c".to_string(),
+                pane.work_dir.clone(),
+                format!("sh -c {}", escape(Cow::from(replay))),
+            ]);
+        }
+        None => {
+            command
+                .args
+                .extend(["-c".to_string(), session.work_dir.clone()]);
+            if let Some(shell) = shell {
+                command
+                    .args
+                    .push(format!("exec {} -l", escape(Cow::from(shell))));
+            }
+        }
+    }
+    command
+}
+
+/// Commands that build one window, targeted as `window_target`, each with
+/// the 1-based pane it acts on.
+fn window_plan(
+    window_target: &str,
+    session: &Session,
+    window: &Window,
+) -> Vec<(Option<usize>, TmuxCommand)> {
+    let mut plan = vec![(
+        None,
+        TmuxCommand::new(["rename-window", "-t", window_target, &window.name]),
+    )];
+
+    if let Some(monitor) = window.monitor {
+        let on_off = |on: bool| if on { "on" } else { "off" };
+        for (option, value) in [
+            ("monitor-activity", on_off(monitor.activity).to_string()),
+            ("monitor-bell", on_off(monitor.bell).to_string()),
+            ("monitor-silence", monitor.silence.to_string()),
+        ] {
+            plan.push((
+                None,
+                TmuxCommand::new([
+                    "set-option",
+                    "-w",
+                    "-t",
+                    window_target,
+                    option,
+                    &value,
+                ]),
+            ));
+        }
+    }
+
+    // Splitting the newest pane keeps the panes in config order, so each
+    // size and direction applies to the pane it was written for.
+    let by_panes = window.laid_out_by_panes();
+    let split_target = if by_panes {
+        format!("{window_target}.{{bottom-right}}")
+    } else {
+        window_target.to_string()
+    };
+    for (i, pane) in window.panes.iter().enumerate().skip(1) {
+        let mut args = vec!["split-window", "-d", "-t", &split_target];
+        match window.splits.get(i - 1) {
+            Some(Split::Horizontal) => args.push("-h"),
+            Some(Split::Vertical) => args.push("-v"),
+            None => {}
+        }
+        let size = pane.size.map(|size| size.to_string());
+        if let Some(size) = &size {
+            args.extend(["-l", size]);
+        }
+        plan.push((
+            Some(i + 1),
+            new_pane(args, session, Some(window), Some(pane)),
+        ));
+    }
+
+    if !by_panes && !window.layout.is_empty() {
+        plan.push((
+            None,
+            TmuxCommand::new([
+                "select-layout",
+                "-t",
+                window_target,
+                &window.layout,
+            ]),
+        ));
+    }
+
+    for (i, pane) in window.panes.iter().enumerate() {
+        let pane_target = format!("{}.{}", window_target, pane.index);
+        plan.extend(
+            start_pane(&pane_target, session, pane)
+                .into_iter()
+                .map(|command| (Some(i + 1), command)),
+        );
+    }
+
+    plan
+}
+
+/// Commands that restart the dead pane `pane_target` as `pane` of `window`
+/// in the config, the way a restore would have started it.
+pub fn respawn_plan(
+    pane_target: &str,
+    session: &Session,
+    window: &Window,
+    pane: &Pane,
+) -> Vec<TmuxCommand> {
+    let mut plan = vec![new_pane(
+        ["respawn-pane", "-t", pane_target],
+        session,
+        Some(window),
+        Some(pane),
+    )];
+    plan.extend(start_pane(pane_target, session, pane));
+    plan
+}
+
+/// The lines typed into a freshly started pane: changing to its directory,
+/// the session's `pre_window`, loading the project environment and running
+/// its command.
+fn start_pane(
+    pane_target: &str,
+    session: &Session,
+    pane: &Pane,
+) -> Vec<TmuxCommand> {
+    let mut lines = Vec::new();
+
+    if pane.work_dir != session.work_dir && pane.history.is_none() {
+        let cd = format!("cd {}; clear", escape(Cow::from(&pane.work_dir)));
+        lines.push(type_line(pane_target, &cd));
+    }
+
+    if let Some(pre_window) = &session.pre_window {
+        lines.push(type_line(pane_target, pre_window));
+    }
+
+    if let Some(pane_cmd) = &pane.current_command {
+        if let Some(load) = load_env(session, pane) {
+            lines.push(type_line(pane_target, &load));
+        }
+        lines.push(type_line(pane_target, pane_cmd));
+    }
+
+    lines
+}
+
+/// The shell line loading the session's project environment in a pane
+/// before its command runs - direnv's for the pane's directory, then the
+/// env file - or `None` if it has neither. It is written for the pane's
+/// saved shell: fish and nushell get their own syntax, anything else
+/// (including tmux's `default-shell` when none was saved) POSIX syntax.
+fn load_env(session: &Session, pane: &Pane) -> Option<String> {
+    let shell = pane
+        .shell
+        .as_deref()
+        .and_then(|shell| shell.rsplit('/').next())
+        .unwrap_or("sh");
+    let mut steps = Vec::new();
+    if session.direnv {
+        steps.push(match shell {
+            "fish" => "direnv export fish 2>/dev/null | source".to_string(),
+            "nu" => "direnv export json err> /dev/null | from json \
+                     | default {} | load-env"
+                .to_string(),
+            _ => r#"eval "$(direnv export bash 2>/dev/null)""#.to_string(),
+        });
+    }
+    if let Some(file) = &session.env_file {
+        // `.` looks a bare file name up in PATH first.
+        let file = if file.contains('/') {
+            file.clone()
+        } else {
+            format!("./{file}")
+        };
+        // Shells that can't source the file read the variables it exports
+        // back from `sh`.
+        const DUMP: &str = r#"sh -c 'set -a; . "$1" >/dev/null; exec env' sh"#;
+        steps.push(match shell {
+            "fish" => format!(
+                "for kv in ({DUMP} {}); set kv (string split -m 1 = -- $kv); \
+                 contains -- $kv[1] PWD OLDPWD SHLVL _; \
+                 or set -gx $kv[1] $kv[2]; end",
+                escape(Cow::from(file))
+            ),
+            "nu" => format!(
+                "{DUMP} {} | lines | split column -n 2 = k v \
+                 | where k not-in [PWD OLDPWD SHLVL _] | transpose -r -d \
+                 | load-env",
+                nu_quote(&file)
+            ),
+            _ => format!("set -a; . {}; set +a", escape(Cow::from(file))),
+        });
+    }
+    (!steps.is_empty()).then(|| steps.join("; "))
+}
+
+/// Quotes `text` as a nushell double-quoted string.
+fn nu_quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
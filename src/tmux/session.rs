@@ -1,41 +1,262 @@
 //! Tmux session model - [`Session`] -> [`Window`] -> [`Pane`] hierarchy.
-use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use regex::Regex;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::charset::Charset;
+use crate::error::{Result, TsmanError};
 
 /// A single tmux pane.
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct Pane {
     pub index: String,
     pub current_command: Option<String>,
     pub work_dir: String,
+    /// Shell the pane was running (e.g. `fish`), started explicitly on
+    /// restore instead of tmux's `default-shell`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shell: Option<String>,
+    /// Gzipped scrollback printed in the pane when it is restored.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub history: Option<String>,
+    /// Environment variables the pane is started with, on top of its
+    /// window's.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    /// Size the pane is split off with, instead of its window's layout.
+    /// Ignored for a window's first pane, which gets what is left.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<PaneSize>,
 }
 
 /// A tmux window containing one or more [`Pane`]s.
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct Window {
     pub index: String,
     pub name: String,
-    /// Tmux layout string (e.g. `"bb62,80x24,0,0,0"`).
+    /// Tmux layout string (e.g. `"bb62,80x24,0,0,0"`). Not applied when
+    /// empty or when the window is [laid out by its
+    /// panes](Window::laid_out_by_panes).
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub layout: String,
+    /// Direction each pane after the first is split off the one before it
+    /// in, for hand-written configs without a layout string.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub splits: Vec<Split>,
+    /// Alert settings, saved only when they differ from the global ones.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub monitor: Option<Monitor>,
+    /// Environment variables every pane of the window is started with.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
     pub panes: Vec<Pane>,
 }
 
+/// A window's `monitor-activity`, `monitor-bell` and `monitor-silence`
+/// options.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(default)]
+pub struct Monitor {
+    pub activity: bool,
+    pub bell: bool,
+    /// Seconds of silence before an alert, 0 for none.
+    pub silence: u32,
+}
+
+/// tmux's defaults.
+impl Default for Monitor {
+    fn default() -> Self {
+        Self {
+            activity: false,
+            bell: true,
+            silence: 0,
+        }
+    }
+}
+
+/// Direction a pane is split in, as tmux names them: `horizontal` puts the
+/// new pane to the right, `vertical` below.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Split {
+    #[serde(alias = "h")]
+    Horizontal,
+    #[serde(alias = "v")]
+    Vertical,
+}
+
+/// A pane's share of the pane it is split from (`30%`) or its width or
+/// height in cells (`12`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PaneSize {
+    Percent(u8),
+    Cells(u16),
+}
+
+impl fmt::Display for PaneSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PaneSize::Percent(percent) => write!(f, "{percent}%"),
+            PaneSize::Cells(cells) => write!(f, "{cells}"),
+        }
+    }
+}
+
+impl FromStr for PaneSize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let size = match s.strip_suffix('%') {
+            Some(percent) => percent
+                .trim()
+                .parse()
+                .ok()
+                .filter(|percent| (1..100).contains(percent))
+                .map(PaneSize::Percent),
+            None => s
+                .parse()
+                .ok()
+                .filter(|&cells| cells > 0)
+                .map(PaneSize::Cells),
+        };
+        size.ok_or_else(|| {
+            format!(
+                "invalid pane size '{s}', expected a percentage (1%-99%) \
+                 or a number of cells"
+            )
+        })
+    }
+}
+
+impl Serialize for PaneSize {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for PaneSize {
+    fn deserialize<D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Cells(u16),
+            Text(String),
+        }
+
+        match Raw::deserialize(deserializer)? {
+            Raw::Cells(cells) => cells.to_string(),
+            Raw::Text(text) => text,
+        }
+        .parse()
+        .map_err(serde::de::Error::custom)
+    }
+}
+
 /// A full tmux session snapshot with one or more [`Window`]s.
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct Session {
     pub name: String,
     pub work_dir: String,
+    /// Loads each pane's direnv environment before running its command.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub direnv: bool,
+    /// File of `KEY=value` lines sourced in each pane before running its
+    /// command, relative to the pane's directory.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub env_file: Option<String>,
+    /// Shell commands run once, in the session's directory, before the
+    /// session is created - e.g. `docker compose up -d`. tsman runs them
+    /// itself rather than through tmux, so they can take as long as they
+    /// need.
+    #[serde(
+        default,
+        deserialize_with = "one_or_many",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub pre: Vec<String>,
+    /// Command typed into every pane before its own command - e.g.
+    /// `source .venv/bin/activate`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pre_window: Option<String>,
+    /// Files of shared windows ([`Fragment`]s) added after the session's
+    /// own when it is loaded, relative to the sessions directory.
+    #[serde(
+        default,
+        alias = "windows_from",
+        deserialize_with = "one_or_many",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub include: Vec<String>,
+    pub windows: Vec<Window>,
+}
+
+/// Appended to the running parts of a [`Session::get_live_preview`].
+const LIVE_MARK: &str = " *";
+
+/// Nests session `previews` under a `root` line, as one tree.
+pub fn combine_previews(
+    root: &str,
+    previews: &[String],
+    charset: Charset,
+) -> String {
+    let glyphs = charset.tree();
+    let mut tree = format!("{root}\n");
+
+    for (idx, preview) in previews.iter().enumerate() {
+        let last = idx == previews.len() - 1;
+        for (line_idx, line) in preview.lines().enumerate() {
+            let prefix = match (line_idx, last) {
+                (0, false) => {
+                    format!(" {}{} ", glyphs.branch, glyphs.horizontal)
+                }
+                (0, true) => format!(" {}{} ", glyphs.last, glyphs.horizontal),
+                (_, false) => format!(" {}   ", glyphs.vertical),
+                (_, true) => "     ".to_string(),
+            };
+            tree += &prefix;
+            tree += line;
+            tree.push('\n');
+        }
+    }
+
+    tree
+}
+
+/// A file of windows shared between sessions, pulled into them by their
+/// `include` lists.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Fragment {
     pub windows: Vec<Window>,
+    /// File the fragment was read from, for errors about it.
+    #[serde(skip)]
+    pub path: PathBuf,
 }
 
 impl Pane {
-    /// Returns a textual preview, optionally prefixed with the pane index.
-    pub fn get_preview(&self, show_index: bool) -> String {
+    /// Returns a textual preview, optionally prefixed with the pane index
+    /// and a Nerd Font icon for its command.
+    pub fn get_preview(&self, show_index: bool, icons: bool) -> String {
         let mut preview = String::new();
 
         if show_index {
             preview += &format!("({}) ", self.index);
         }
 
+        if icons {
+            preview += command_icon(self.current_command.as_deref());
+            preview.push(' ');
+        }
+
         preview += match self.current_command.as_ref() {
             Some(cmd) => cmd,
             None => "_",
@@ -46,34 +267,60 @@ impl Pane {
 }
 
 impl Window {
-    /// Returns a tree-like preview of the window and its panes.
-    pub fn get_preview(&self, add_connector: bool) -> String {
+    /// Whether the window's panes are split by its `splits` and their
+    /// sizes rather than by its layout string.
+    pub fn laid_out_by_panes(&self) -> bool {
+        !self.splits.is_empty()
+            || self.panes.iter().any(|pane| pane.size.is_some())
+    }
+
+    /// Returns a tree-like preview of the window and its panes. If the
+    /// window is running with `live_panes` panes, it and the panes it still
+    /// has are marked with `*`.
+    pub fn get_preview(
+        &self,
+        add_connector: bool,
+        charset: Charset,
+        icons: bool,
+        live_panes: Option<usize>,
+    ) -> String {
+        let mark = if live_panes.is_some() { LIVE_MARK } else { "" };
         if self.panes.len() == 1 {
             return format!(
-                "{}: {}\n",
+                "{}: {}{mark}\n",
                 self.name,
-                self.panes[0].get_preview(false)
+                self.panes[0].get_preview(false, icons)
             );
         }
 
-        let mut preview = format!("{}:\n", self.name);
+        let pane_preview = |idx: usize| {
+            let preview = self.panes[idx].get_preview(true, icons);
+            match live_panes {
+                Some(live) if idx < live => preview + LIVE_MARK,
+                _ => preview,
+            }
+        };
+        let mut preview = format!("{}:{mark}\n", self.name);
 
-        let connector = if add_connector { "║" } else { " " };
+        let glyphs = charset.tree();
+        let connector = if add_connector { glyphs.vertical } else { " " };
 
         let mut pane_idx = 0;
         while pane_idx < self.panes.len() - 1 {
             preview += &format!(
-                " {}  ╠═ {}\n",
+                " {}  {} {}\n",
                 connector,
-                self.panes[pane_idx].get_preview(true)
+                glyphs.branch,
+                pane_preview(pane_idx)
             );
             pane_idx += 1;
         }
 
         preview += &format!(
-            " {}  ╚═ {}\n",
+            " {}  {} {}\n",
             connector,
-            self.panes[pane_idx].get_preview(true)
+            glyphs.last,
+            pane_preview(pane_idx)
         );
 
         preview
@@ -81,34 +328,398 @@ impl Window {
 }
 
 impl Session {
-    /// Returns a tree-like preview of the full session hierarchy.
-    pub fn get_preview(&self) -> String {
-        let mut preview = format!("{}:\n", self.name);
+    /// A session of one window, named like the session, at window index
+    /// `index`, with a single pane in `work_dir` - what a project without
+    /// a config is opened as.
+    pub fn single_window(name: &str, work_dir: &str, index: usize) -> Self {
+        Self {
+            name: name.to_string(),
+            work_dir: work_dir.to_string(),
+            windows: vec![Window {
+                index: index.to_string(),
+                name: name.to_string(),
+                panes: vec![Pane {
+                    index: "0".to_string(),
+                    work_dir: work_dir.to_string(),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
 
-        let mut window_idx = 0;
-        while window_idx < self.windows.len() - 1 {
-            let window = &self.windows[window_idx];
-            let end_connector =
-                if window.panes.len() > 1 { "╦═" } else { "" };
+    /// Returns a tree-like preview of the full session hierarchy, with
+    /// Nerd Font icons for the pane commands if `icons` is set.
+    pub fn get_preview(&self, charset: Charset, icons: bool) -> String {
+        self.preview(charset, icons, None)
+    }
 
-            preview +=
-                &format!(" ╠══{} {}", end_connector, window.get_preview(true));
-            window_idx += 1;
-        }
+    /// [`get_preview`](Self::get_preview) with the session, and the windows
+    /// and panes it still has, marked with `*` if it is running. `live` are
+    /// the running session's window names and pane counts, as returned by
+    /// [`list_window_shapes`].
+    ///
+    /// [`list_window_shapes`]: super::interface::list_window_shapes
+    pub fn get_live_preview(
+        &self,
+        charset: Charset,
+        icons: bool,
+        live: Option<&[(String, usize)]>,
+    ) -> String {
+        self.preview(charset, icons, live)
+    }
 
-        let last_window = &self.windows[window_idx];
-        let end_connector = if last_window.panes.len() > 1 {
-            "╦═"
-        } else {
-            ""
+    fn preview(
+        &self,
+        charset: Charset,
+        icons: bool,
+        live: Option<&[(String, usize)]>,
+    ) -> String {
+        let glyphs = charset.tree();
+        let mut preview = match live {
+            Some(_) => format!("{}:{LIVE_MARK}\n", self.name),
+            None => format!("{}:\n", self.name),
+        };
+        let live_panes = |window: &Window| {
+            live?
+                .iter()
+                .find(|(name, _)| *name == window.name)
+                .map(|(_, panes)| *panes)
         };
 
-        preview += &format!(
-            " ╚══{} {}",
-            end_connector,
-            last_window.get_preview(false) // no need to add connector on last window
-        );
+        for (window_idx, window) in self.windows.iter().enumerate() {
+            // No need to add a connector on the last window.
+            let last = window_idx == self.windows.len() - 1;
+            let end_connector = if window.panes.len() > 1 {
+                glyphs.fork
+            } else {
+                ""
+            };
+
+            preview += &format!(
+                " {}{}{} {}",
+                if last { glyphs.last } else { glyphs.branch },
+                glyphs.horizontal,
+                end_connector,
+                window.get_preview(!last, charset, icons, live_panes(window))
+            );
+        }
 
         preview
     }
+
+    /// Returns whether the running session's windows - their names and pane
+    /// counts, in order, as returned by [`list_window_shapes`] - no longer
+    /// match this config.
+    ///
+    /// [`list_window_shapes`]: super::interface::list_window_shapes
+    pub fn differs_from(&self, windows: &[(String, usize)]) -> bool {
+        self.windows.len() != windows.len()
+            || self.windows.iter().zip(windows).any(
+                |(window, (name, panes))| {
+                    window.name != *name || window.panes.len() != *panes
+                },
+            )
+    }
+
+    /// Moves the window at `from` to position `to`, clamped to the window
+    /// list, and returns where it ended up.
+    ///
+    /// With `renumber` - tmux's `renumber-windows` - the windows are given
+    /// consecutive indexes from the lowest. Otherwise the indexes stay the
+    /// ones saved, gaps included, handed out in the new order.
+    pub fn move_window(
+        &mut self,
+        from: usize,
+        to: usize,
+        renumber: bool,
+    ) -> usize {
+        let to = to.min(self.windows.len().saturating_sub(1));
+        if from < self.windows.len() && from != to {
+            let base = self.base_window_index();
+            let indexes: Vec<String> =
+                self.windows.iter().map(|w| w.index.clone()).collect();
+            let window = self.windows.remove(from);
+            self.windows.insert(to, window);
+            if renumber {
+                self.renumber_windows(base);
+            } else {
+                for (window, index) in self.windows.iter_mut().zip(indexes) {
+                    window.index = index;
+                }
+            }
+        }
+        to
+    }
+
+    /// Removes the window at `index`. A session keeps at least one window,
+    /// so the last one is never removed. With `renumber`, the windows after
+    /// it close the gap, as in [`Self::move_window`].
+    pub fn remove_window(
+        &mut self,
+        index: usize,
+        renumber: bool,
+    ) -> Option<Window> {
+        if self.windows.len() < 2 || index >= self.windows.len() {
+            return None;
+        }
+        let base = self.base_window_index();
+        let window = self.windows.remove(index);
+        if renumber {
+            self.renumber_windows(base);
+        }
+        Some(window)
+    }
+
+    /// The lowest window index in use - tmux's `base-index` when the config
+    /// was saved.
+    fn base_window_index(&self) -> usize {
+        self.windows
+            .iter()
+            .filter_map(|window| window.index.parse::<usize>().ok())
+            .min()
+            .unwrap_or(0)
+    }
+
+    /// Gives the windows consecutive indexes in list order from `base`,
+    /// matching the indexes tmux assigns when the session is restored window
+    /// by window.
+    fn renumber_windows(&mut self, base: usize) {
+        for (i, window) in self.windows.iter_mut().enumerate() {
+            window.index = (base + i).to_string();
+        }
+    }
+
+    /// Commands restoring the session would run - `pre`, `pre_window` and
+    /// each pane's command - matching any of `patterns`, each described
+    /// with where it runs.
+    pub fn commands_matching(&self, patterns: &[Regex]) -> Vec<String> {
+        let pre = self.pre.iter().map(|command| ("pre".to_string(), command));
+        let pre_window = self
+            .pre_window
+            .iter()
+            .map(|command| ("pre_window, in every pane".to_string(), command));
+        let panes = self
+            .windows
+            .iter()
+            .flat_map(|window| window.panes.iter().map(move |p| (window, p)))
+            .filter_map(|(window, pane)| {
+                let label = format!(
+                    "Window {} '{}', pane {}",
+                    window.index, window.name, pane.index
+                );
+                Some((label, pane.current_command.as_ref()?))
+            });
+
+        pre.chain(pre_window)
+            .chain(panes)
+            .filter(|(_, command)| {
+                patterns.iter().any(|re| re.is_match(command))
+            })
+            .map(|(label, command)| format!("{label}: {command}"))
+            .collect()
+    }
+
+    /// Copies the environment settings of `saved` - which tmux doesn't
+    /// report, so a freshly captured session has none - over: `direnv`,
+    /// `env_file`, `pre` and `pre_window`, and the `env` maps of the windows
+    /// with the same name and their panes with the same index.
+    pub fn keep_env_from(&mut self, saved: &Session) {
+        self.direnv = saved.direnv;
+        self.env_file.clone_from(&saved.env_file);
+        self.pre.clone_from(&saved.pre);
+        self.pre_window.clone_from(&saved.pre_window);
+        for window in &mut self.windows {
+            let Some(old) =
+                saved.windows.iter().find(|w| w.name == window.name)
+            else {
+                continue;
+            };
+            window.env.clone_from(&old.env);
+            for pane in &mut window.panes {
+                if let Some(old) =
+                    old.panes.iter().find(|p| p.index == pane.index)
+                {
+                    pane.env.clone_from(&old.env);
+                }
+            }
+        }
+    }
+
+    /// Adds the windows of `fragment` after the session's own, numbered on
+    /// from its last window so their indexes don't clash. Fails if one has
+    /// the name of a window the session already has, from its own config or
+    /// an earlier fragment: saving over the config tells included windows
+    /// apart by name.
+    pub fn add_fragment(&mut self, fragment: Fragment) -> Result<()> {
+        if let Some(window) = fragment.windows.iter().find(|included| {
+            self.windows.iter().any(|own| own.name == included.name)
+        }) {
+            return Err(TsmanError::InvalidConfig {
+                path: fragment.path,
+                source: format!(
+                    "window '{}' is already in session '{}'",
+                    window.name, self.name
+                )
+                .into(),
+            });
+        }
+        let next = self
+            .windows
+            .iter()
+            .filter_map(|window| window.index.parse::<usize>().ok())
+            .max()
+            .map_or(0, |last| last + 1);
+        for (index, mut window) in (next..).zip(fragment.windows) {
+            window.index = index.to_string();
+            self.windows.push(window);
+        }
+        Ok(())
+    }
+
+    /// Takes over `saved`'s `include` list and drops the windows its
+    /// `fragments` provide, matched by name, so saving a running session
+    /// over its config leaves the included windows in their files.
+    pub fn keep_includes_from(
+        &mut self,
+        saved: &Session,
+        fragments: &[Fragment],
+    ) {
+        self.include.clone_from(&saved.include);
+        self.windows.retain(|window| {
+            !fragments
+                .iter()
+                .flat_map(|fragment| &fragment.windows)
+                .any(|included| included.name == window.name)
+        });
+    }
+
+    /// Working directories of the session and its panes that don't exist on
+    /// this machine, each listed once. tmux starts such panes in the home
+    /// directory instead.
+    pub fn missing_dirs(&self) -> Vec<&str> {
+        let mut missing: Vec<&str> = Vec::new();
+        let dirs = std::iter::once(self.work_dir.as_str()).chain(
+            self.windows
+                .iter()
+                .flat_map(|w| &w.panes)
+                .map(|p| p.work_dir.as_str()),
+        );
+        for dir in dirs {
+            if !missing.contains(&dir) && !Path::new(dir).is_dir() {
+                missing.push(dir);
+            }
+        }
+        missing
+    }
+
+    /// Rewrites every path under `from` (the directory itself or anything
+    /// below it) to the same path under `to`, in the session's and panes'
+    /// work_dirs and in pane commands. Returns how many fields changed.
+    pub fn remap_paths(&mut self, from: &str, to: &str) -> usize {
+        let from = from.trim_end_matches('/');
+        let to = to.trim_end_matches('/');
+        let mut changed = 0;
+
+        let mut update =
+            |field: &mut String,
+             remap: fn(&str, &str, &str) -> Option<String>| {
+                if let Some(new) = remap(field, from, to)
+                    && new != *field
+                {
+                    *field = new;
+                    changed += 1;
+                }
+            };
+
+        update(&mut self.work_dir, remap_path);
+        for pane in self.windows.iter_mut().flat_map(|w| &mut w.panes) {
+            update(&mut pane.work_dir, remap_path);
+            if let Some(cmd) = &mut pane.current_command {
+                update(cmd, remap_command);
+            }
+        }
+
+        changed
+    }
+}
+
+/// Reads a single string as a list of one.
+fn one_or_many<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(item) => vec![item],
+        OneOrMany::Many(items) => items,
+    })
+}
+
+/// Nerd Font icon for a pane running `command`.
+fn command_icon(command: Option<&str>) -> &'static str {
+    let program = command
+        .and_then(|command| command.split_whitespace().next())
+        .map(|program| program.rsplit('/').next().unwrap_or(program));
+
+    match program {
+        Some("vim" | "nvim" | "vi") => "\u{e62b}",
+        Some("git" | "lazygit" | "tig") => "\u{e702}",
+        Some("python" | "python3" | "ipython") => "\u{e73c}",
+        Some("node" | "npm" | "yarn" | "pnpm") => "\u{e718}",
+        Some("cargo" | "rustc") => "\u{e7a8}",
+        Some("docker" | "podman") => "\u{f308}",
+        Some("ssh" | "mosh") => "\u{f817}",
+        Some("htop" | "top" | "btop") => "\u{f080}",
+        _ => "\u{f120}",
+    }
+}
+
+/// `path` moved from under `from` to under `to`, if it is under `from`.
+fn remap_path(path: &str, from: &str, to: &str) -> Option<String> {
+    let rest = path.strip_prefix(from)?;
+    (rest.is_empty() || rest.starts_with('/')).then(|| format!("{to}{rest}"))
+}
+
+/// `command` with every path under `from` in it moved to under `to`, if
+/// there are any. A match must start and end on a path boundary, so
+/// `/old` doesn't match inside `/older` or `/x/old`.
+fn remap_command(command: &str, from: &str, to: &str) -> Option<String> {
+    let is_path_char =
+        |c: char| c.is_alphanumeric() || matches!(c, '/' | '.' | '_' | '-');
+
+    let mut result = String::with_capacity(command.len());
+    let mut rest = command;
+    let mut found = false;
+
+    while let Some(pos) = rest.find(from) {
+        let (before, after) = (&rest[..pos], &rest[pos + from.len()..]);
+        let starts_path = !before
+            .chars()
+            .next_back()
+            .or_else(|| result.chars().next_back())
+            .is_some_and(is_path_char);
+        let ends_path = after
+            .chars()
+            .next()
+            .is_none_or(|c| c == '/' || !is_path_char(c));
+
+        result += before;
+        if starts_path && ends_path {
+            result += to;
+            found = true;
+        } else {
+            result += from;
+        }
+        rest = after;
+    }
+    result += rest;
+
+    found.then_some(result)
 }
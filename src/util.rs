@@ -1,5 +1,6 @@
 use std::fmt;
 
+use dirs::home_dir;
 use regex::Regex;
 
 /// Invalid session name error - used as clap's `value_parser` error type.
@@ -26,3 +27,33 @@ pub fn validate_session_name(name: &str) -> Result<String, SessionNameError> {
         Ok(name.to_string())
     }
 }
+
+/// Whether `name` is a file of several sessions (`*.yaml` or `*.yml`)
+/// rather than a session name.
+pub fn is_session_file(name: &str) -> bool {
+    name.ends_with(".yaml") || name.ends_with(".yml")
+}
+
+/// Accepts a session name, or a file of several sessions.
+pub fn validate_session_or_file(
+    name: &str,
+) -> Result<String, SessionNameError> {
+    if is_session_file(name) {
+        Ok(name.to_string())
+    } else {
+        validate_session_name(name)
+    }
+}
+
+/// Expands a leading `~` to the home directory, for paths the shell didn't
+/// expand (e.g. `--from=~/src`).
+pub fn expand_home(path: &str) -> String {
+    match (path.strip_prefix('~'), home_dir()) {
+        (Some(rest), Some(home))
+            if rest.is_empty() || rest.starts_with('/') =>
+        {
+            format!("{}{rest}", home.display())
+        }
+        _ => path.to_string(),
+    }
+}
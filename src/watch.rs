@@ -0,0 +1,58 @@
+//! Watch - keeps one running session's config in sync with it while it is
+//! being rearranged, without running the daemon.
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{Result, bail};
+
+use crate::actions;
+use crate::persistence::Persistence;
+use crate::tmux::client::TmuxClient;
+use crate::tmux::interface::{is_active_session, session_shape};
+
+/// How often the session is checked for changes.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Saves `session_name` over its config whenever its windows, panes or
+/// layout change, once they have stayed the same for `debounce`. Runs until
+/// the session is closed.
+pub fn watch(
+    session_name: &str,
+    debounce: Duration,
+    tmux: &dyn TmuxClient,
+    persistence: &Persistence,
+) -> Result<()> {
+    if !is_active_session(tmux, session_name)? {
+        bail!("Session '{session_name}' is not running");
+    }
+
+    let mut saved = session_shape(tmux, session_name)?;
+    // The latest unsaved shape and when it was first seen.
+    let mut pending: Option<(String, Instant)> = None;
+    println!("Watching '{session_name}' for changes, press Ctrl-C to stop.");
+
+    loop {
+        thread::sleep(POLL_INTERVAL);
+        if !is_active_session(tmux, session_name)? {
+            println!("'{session_name}' was closed.");
+            return Ok(());
+        }
+
+        let shape = session_shape(tmux, session_name)?;
+        if shape == saved {
+            pending = None;
+            continue;
+        }
+        match &pending {
+            Some((last, since)) if *last == shape => {
+                if since.elapsed() >= debounce {
+                    actions::save_target(session_name, tmux, persistence)?;
+                    println!("Saved '{session_name}'.");
+                    saved = shape;
+                    pending = None;
+                }
+            }
+            _ => pending = Some((shape, Instant::now())),
+        }
+    }
+}
@@ -0,0 +1,35 @@
+//! Session fixtures shared by the integration tests.
+#![allow(dead_code)]
+
+use tsman::tmux::session::{Pane, Session, Window};
+
+/// Pane `index` in `work_dir`, running `command` or sitting at a prompt.
+pub fn pane(index: &str, work_dir: &str, command: Option<&str>) -> Pane {
+    Pane {
+        index: index.to_string(),
+        current_command: command.map(str::to_string),
+        work_dir: work_dir.to_string(),
+        ..Default::default()
+    }
+}
+
+/// Window `index` named `name`, with a single-pane layout string.
+pub fn window(index: &str, name: &str, panes: Vec<Pane>) -> Window {
+    Window {
+        index: index.to_string(),
+        name: name.to_string(),
+        layout: "abcd,80x24,0,0,0".to_string(),
+        panes,
+        ..Default::default()
+    }
+}
+
+/// Session `dev` in `work_dir`.
+pub fn session(work_dir: &str, windows: Vec<Window>) -> Session {
+    Session {
+        name: "dev".to_string(),
+        work_dir: work_dir.to_string(),
+        windows,
+        ..Default::default()
+    }
+}
@@ -0,0 +1,106 @@
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::Duration;
+
+use serde_json::{Value, json};
+use tempfile::TempDir;
+
+const INVALID_PARAMS: i64 = -32602;
+
+/// A `tsman daemon` storing its configs under a throwaway directory, killed
+/// on drop.
+struct Daemon {
+    child: Child,
+    stream: BufReader<UnixStream>,
+    dir: TempDir,
+}
+
+impl Daemon {
+    fn start() -> Self {
+        let dir = TempDir::new().unwrap();
+        for sub in ["home", "sessions", "layouts", "data"] {
+            fs::create_dir(dir.path().join(sub)).unwrap();
+        }
+        let socket = dir.path().join("tsman.sock");
+        let child = Command::new(env!("CARGO_BIN_EXE_tsman"))
+            .args(["daemon", "--socket"])
+            .arg(&socket)
+            .env("HOME", dir.path().join("home"))
+            .env("TSMAN_CONFIG_STORAGE_DIR", dir.path().join("sessions"))
+            .env("TSMAN_LAYOUT_STORAGE_DIR", dir.path().join("layouts"))
+            .env("TSMAN_DATA_DIR", dir.path().join("data"))
+            .stdout(Stdio::null())
+            .spawn()
+            .unwrap();
+
+        let mut attempts = 0;
+        let stream = loop {
+            match UnixStream::connect(&socket) {
+                Ok(stream) => break stream,
+                Err(_) if attempts < 100 => {
+                    attempts += 1;
+                    thread::sleep(Duration::from_millis(50));
+                }
+                Err(err) => panic!("daemon didn't start: {err}"),
+            }
+        };
+
+        Self {
+            child,
+            stream: BufReader::new(stream),
+            dir,
+        }
+    }
+
+    fn path(&self) -> &Path {
+        self.dir.path()
+    }
+
+    fn call(&mut self, method: &str, params: Value) -> Value {
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": method,
+            "params": params,
+        });
+        writeln!(self.stream.get_mut(), "{request}").unwrap();
+        let mut line = String::new();
+        self.stream.read_line(&mut line).unwrap();
+        serde_json::from_str(&line).unwrap()
+    }
+}
+
+impl Drop for Daemon {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[test]
+fn names_outside_the_storage_dir_are_refused() {
+    let mut daemon = Daemon::start();
+    let outside = daemon.path().join("outside.yaml");
+    fs::write(&outside, "name: outside\nwork_dir: /\nwindows: []\n").unwrap();
+
+    for (method, params) in [
+        ("show", json!({ "name": "../outside" })),
+        ("open", json!({ "name": "../outside" })),
+        ("save", json!({ "name": "../outside" })),
+        ("rename", json!({ "name": "../outside", "new_name": "dev" })),
+        ("rename", json!({ "name": "dev", "new_name": "../../x" })),
+    ] {
+        let response = daemon.call(method, params);
+        assert_eq!(
+            response["error"]["code"], INVALID_PARAMS,
+            "{method}: {response}"
+        );
+    }
+
+    assert!(outside.exists());
+    assert!(!daemon.path().join("sessions/dev.yaml").exists());
+}
@@ -0,0 +1,53 @@
+use tsman::diff::{self, Line};
+
+#[test]
+fn lines_are_diffed_by_common_subsequence() {
+    let lines = diff::diff_lines("a\nb\nc\nd\n", "a\nx\nc\nd\ne\n");
+
+    assert_eq!(
+        lines,
+        [
+            Line::Same("a"),
+            Line::Removed("b"),
+            Line::Added("x"),
+            Line::Same("c"),
+            Line::Same("d"),
+            Line::Added("e"),
+        ]
+    );
+    assert!(!diff::has_changes(&diff::diff_lines("a\n", "a\n")));
+}
+
+#[test]
+fn unified_output_has_hunks_with_context() {
+    let old = (1..=20).map(|n| format!("{n}\n")).collect::<String>();
+    let new = old.replace("\n2\n", "\ntwo\n").replace("19\n", "");
+    let lines = diff::diff_lines(&old, &new);
+
+    assert_eq!(
+        diff::unified(&lines, "old", "new", false),
+        "--- old\n+++ new\n\
+         @@ -1,5 +1,5 @@\n 1\n-2\n+two\n 3\n 4\n 5\n\
+         @@ -16,5 +16,4 @@\n 16\n 17\n 18\n-19\n 20\n"
+    );
+    assert!(
+        diff::unified(&lines, "old", "new", true)
+            .contains("\x1b[31m-2\x1b[0m\n")
+    );
+    assert_eq!(
+        diff::unified(&diff::diff_lines("a", "a"), "o", "n", false),
+        ""
+    );
+}
+
+#[test]
+fn side_by_side_output_pairs_changed_lines() {
+    let lines = diff::diff_lines("name: dev\nold\ngone\n", "name: dev\nnew\n");
+
+    assert_eq!(
+        diff::side_by_side(&lines, 23, false),
+        "name: dev    name: dev\n\
+         old        | new\n\
+         gone       <\n"
+    );
+}
@@ -0,0 +1,63 @@
+use std::io;
+
+use anyhow::Context;
+use tsman::error::{self, TsmanError};
+
+#[test]
+fn exit_code_of_wrapped_error() {
+    let err = Err::<(), _>(TsmanError::ConfigNotFound {
+        kind: "session",
+        name: "dev".to_string(),
+    })
+    .context("Failed to read session from config file")
+    .context("Failed to execute command")
+    .unwrap_err();
+
+    assert_eq!(error::exit_code(&err), 2);
+}
+
+#[test]
+fn exit_codes_by_kind() {
+    let unavailable = TsmanError::TmuxUnavailable {
+        cmd: "tmux list-sessions".to_string(),
+        source: io::Error::from(io::ErrorKind::NotFound),
+    };
+    let invalid = TsmanError::InvalidLayout {
+        layout: "bogus".to_string(),
+        reason: "missing comma after checksum".to_string(),
+    };
+
+    assert_eq!(unavailable.exit_code(), 3);
+    assert_eq!(invalid.exit_code(), 4);
+    assert_eq!(TsmanError::Aborted.exit_code(), 5);
+    assert_eq!(TsmanError::NotInTmux.exit_code(), 1);
+}
+
+#[test]
+fn unclassified_error() {
+    let err = anyhow::anyhow!("Not inside a tmux session").context("Failed");
+    assert_eq!(error::exit_code(&err), 1);
+}
+
+#[test]
+fn tmux_failure_includes_stderr() {
+    let err = TsmanError::TmuxFailed {
+        cmd: "tmux kill-session -t dev".to_string(),
+        stderr: "can't find session: dev\n".to_string(),
+    };
+    assert_eq!(
+        err.to_string(),
+        "'tmux kill-session -t dev' failed: can't find session: dev"
+    );
+}
+
+#[test]
+fn differing_session_exits_silently() {
+    let err = Err::<(), _>(TsmanError::SessionDiffers("dev".to_string()))
+        .context("Failed to execute command")
+        .unwrap_err();
+
+    assert_eq!(error::exit_code(&err), 6);
+    assert!(error::is_silent(&err));
+    assert!(!error::is_silent(&anyhow::anyhow!("Failed")));
+}
@@ -0,0 +1,94 @@
+use tsman::glob;
+
+fn matches(glob: &str, subject: &str) -> bool {
+    glob::to_regex(glob).unwrap().is_match(subject)
+}
+
+fn path_matches(glob: &str, subject: &str) -> bool {
+    glob::path_to_regex(glob).unwrap().is_match(subject)
+}
+
+#[test]
+fn wildcards_match_names() {
+    for (glob, subject, expected) in [
+        ("*", "", true),
+        ("*", "dev", true),
+        ("dev-*", "dev-api", true),
+        ("dev-*", "dev-", true),
+        ("dev-*", "prod-api", false),
+        ("*-api", "dev-api", true),
+        ("?ev", "dev", true),
+        ("?ev", "ev", false),
+        ("d?v-*", "dav-x", true),
+        ("dev", "devx", false),
+        ("dev", "xdev", false),
+        ("a/*", "a/b/c", true),
+    ] {
+        assert_eq!(matches(glob, subject), expected, "{glob} ~ {subject}");
+    }
+}
+
+#[test]
+fn bracket_expressions_match_sets() {
+    for (glob, subject, expected) in [
+        ("dev[12]", "dev1", true),
+        ("dev[12]", "dev3", false),
+        ("dev[0-9]", "dev7", true),
+        ("dev[!0-9]", "dev7", false),
+        ("dev[!0-9]", "devx", true),
+        ("[]x]", "]", true),
+        ("[^]", "^", true),
+        ("[^]", "a", false),
+        ("[a&&b]", "&", true),
+        ("[\\]", "\\", true),
+        ("dev[1", "dev[1", true),
+        ("dev[1", "dev1", false),
+    ] {
+        assert_eq!(matches(glob, subject), expected, "{glob} ~ {subject}");
+    }
+}
+
+#[test]
+fn regex_metacharacters_match_themselves() {
+    for (glob, subject, expected) in [
+        ("a.b", "a.b", true),
+        ("a.b", "axb", false),
+        ("a+", "a+", true),
+        ("a+", "aa", false),
+        ("(dev)", "(dev)", true),
+        ("dev|prod", "dev", false),
+        ("^dev$", "^dev$", true),
+        ("a{2}", "a{2}", true),
+        ("a{2}", "aa", false),
+        ("a\\d", "a\\d", true),
+        ("a\\d", "a1", false),
+        ("$*", "$HOME", true),
+    ] {
+        assert_eq!(matches(glob, subject), expected, "{glob} ~ {subject}");
+    }
+}
+
+#[test]
+fn path_wildcards_stop_at_slashes() {
+    for (glob, subject, expected) in [
+        ("work/*", "work/api", true),
+        ("work/*", "work/api/web", false),
+        ("work/**", "work/api/web", true),
+        ("**/api", "work/team/api", true),
+        ("work/?pi", "work/api", true),
+        ("work?api", "work/api", false),
+        ("work/[ab]pi", "work/api", true),
+        ("work/a.i", "work/api", false),
+    ] {
+        assert_eq!(path_matches(glob, subject), expected, "{glob} ~ {subject}");
+    }
+}
+
+#[test]
+fn detects_wildcards() {
+    assert!(glob::is_glob("dev-*"));
+    assert!(glob::is_glob("dev?"));
+    assert!(glob::is_glob("dev[12]"));
+    assert!(!glob::is_glob("dev-api"));
+    assert!(!glob::is_glob("a.b+c"));
+}
@@ -0,0 +1,188 @@
+use std::fs;
+use std::process::Command;
+
+use tempfile::TempDir;
+use tsman::test_support::TestServer;
+use tsman::tmux::client::{SystemTmux, TmuxClient};
+use tsman::tmux::control::ControlModeTmux;
+use tsman::tmux::interface;
+use tsman::tmux::session::{Fragment, Session};
+
+#[test]
+fn servers_are_isolated() {
+    let first = TestServer::start().unwrap();
+    let second = TestServer::start().unwrap();
+    first.new_session("dev", "/tmp").unwrap();
+
+    assert_ne!(first.socket(), second.socket());
+    assert_eq!(first.sessions().unwrap(), ["dev"]);
+    assert!(second.sessions().unwrap().is_empty());
+}
+
+#[test]
+fn captured_session_restores_with_the_same_shape() {
+    let server = TestServer::start().unwrap();
+    server.new_session("dev", "/tmp").unwrap();
+    server
+        .run(&["split-window", "-t", "dev", "-c", "/"])
+        .unwrap();
+    server
+        .run(&["new-window", "-t", "dev", "-n", "logs"])
+        .unwrap();
+    let captured = server.capture("dev").unwrap();
+
+    server.kill_session("dev").unwrap();
+    let report = server.restore(&captured).unwrap();
+    let restored = server.capture("dev").unwrap();
+
+    assert!(report.is_clean(), "{report:?}");
+    let shape = |session: &Session| {
+        session
+            .windows
+            .iter()
+            .map(|window| (window.name.clone(), window.panes.len()))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(shape(&restored), shape(&captured));
+}
+
+#[test]
+fn dropping_the_server_kills_it() {
+    let server = TestServer::start().unwrap();
+    server.new_session("dev", "/tmp").unwrap();
+    let tmux = SystemTmux::with_server(server.socket());
+    drop(server);
+
+    assert!(!tmux.run(&["has-session", "-t", "dev"]).unwrap().success);
+}
+
+#[test]
+fn detached_restores_survive_destroy_unattached() {
+    let server = TestServer::start().unwrap();
+    server.new_session("dev", "/tmp").unwrap();
+    let captured = server.capture("dev").unwrap();
+    server.kill_session("dev").unwrap();
+    server
+        .run(&["set-option", "-g", "destroy-unattached", "on"])
+        .unwrap();
+
+    let report = server.restore(&captured).unwrap();
+
+    assert!(report.is_clean(), "{report:?}");
+    assert_eq!(server.sessions().unwrap(), ["dev"]);
+    let hooks = server.run(&["show-hooks", "-t", "dev"]).unwrap();
+    assert!(hooks.stdout.contains("client-attached"), "{}", hooks.stdout);
+}
+
+#[test]
+fn detached_restores_survive_destroy_unattached_under_control_mode() {
+    let server = TestServer::start().unwrap();
+    server.new_session("dev", "/tmp").unwrap();
+    let captured = server.capture("dev").unwrap();
+    server.kill_session("dev").unwrap();
+    // Somewhere for the control client to attach, kept alive by it.
+    server.new_session("anchor", "/tmp").unwrap();
+    let tmux = ControlModeTmux::new(SystemTmux::with_server(server.socket()));
+    // Set through the control client, attaching it before the option
+    // could destroy the anchor.
+    let args = ["set-option", "-g", "destroy-unattached", "on"];
+    tmux.run(&args).unwrap().check(&args).unwrap();
+
+    let report =
+        interface::restore_session_detached(&tmux, &captured, &mut |_| {})
+            .unwrap();
+
+    assert!(report.is_clean(), "{report:?}");
+    assert_eq!(server.sessions().unwrap(), ["anchor", "dev"]);
+    let hooks = server.run(&["show-hooks", "-t", "dev"]).unwrap();
+    assert!(hooks.stdout.contains("client-attached"), "{}", hooks.stdout);
+}
+
+#[test]
+fn switching_to_a_guarded_restore_hands_back_destroy_unattached() {
+    let server = TestServer::start().unwrap();
+    server.new_session("dev", "/tmp").unwrap();
+    let captured = server.capture("dev").unwrap();
+    server.kill_session("dev").unwrap();
+    server.new_session("anchor", "/tmp").unwrap();
+    let tmux = ControlModeTmux::new(SystemTmux::with_server(server.socket()));
+    let args = ["set-option", "-g", "destroy-unattached", "on"];
+    tmux.run(&args).unwrap().check(&args).unwrap();
+    interface::restore_session_detached(&tmux, &captured, &mut |_| {}).unwrap();
+
+    // As attaching from inside tmux does.
+    let args = ["switch-client", "-t", "=dev"];
+    tmux.run(&args).unwrap().check(&args).unwrap();
+
+    let options = server.run(&["show-options", "-t", "dev"]).unwrap();
+    assert!(
+        !options.stdout.contains("destroy-unattached"),
+        "{}",
+        options.stdout
+    );
+    let hooks = server.run(&["show-hooks", "-t", "dev"]).unwrap();
+    assert!(!hooks.stdout.contains("client-"), "{}", hooks.stdout);
+}
+
+#[test]
+fn single_window_sessions_restore_cleanly() {
+    let server = TestServer::start().unwrap();
+    server
+        .run(&["set-option", "-g", "base-index", "1"])
+        .unwrap();
+    let index = interface::base_index(server.tmux()).unwrap();
+    let session = Session::single_window("dev", "/tmp", index);
+
+    let report = server.restore(&session).unwrap();
+
+    assert!(report.is_clean(), "{report:?}");
+    let restored = server.capture("dev").unwrap();
+    assert_eq!(restored.windows.len(), 1);
+    assert_eq!(restored.windows[0].index, "1");
+    assert_eq!(restored.windows[0].name, "dev");
+}
+
+#[test]
+fn diff_leaves_included_windows_out() {
+    let server = TestServer::start().unwrap();
+    // A shell's startup could change the pane commands between captures.
+    server
+        .run(&["set-option", "-g", "default-command", "sleep 600"])
+        .unwrap();
+    server.new_session("dev", "/tmp").unwrap();
+    server
+        .run(&["new-window", "-t", "dev", "-n", "logs", "-c", "/tmp"])
+        .unwrap();
+    let mut saved = server.capture("dev").unwrap();
+    let fragment = Fragment {
+        windows: vec![saved.windows.pop().unwrap()],
+        ..Default::default()
+    };
+    saved.include = vec!["shared".to_string()];
+
+    let dir = TempDir::new().unwrap();
+    let sessions = dir.path().join("sessions");
+    fs::create_dir(&sessions).unwrap();
+    let write = |name: &str, yaml: String| {
+        fs::write(sessions.join(format!("{name}.yaml")), yaml).unwrap();
+    };
+    write("dev", serde_yaml::to_string(&saved).unwrap());
+    write("shared", serde_yaml::to_string(&fragment).unwrap());
+
+    let socket_path = server
+        .run(&["display-message", "-p", "#{socket_path}"])
+        .unwrap()
+        .stdout;
+    let output = Command::new(env!("CARGO_BIN_EXE_tsman"))
+        .args(["diff", "dev", "--quiet"])
+        .env("TMUX", format!("{},0,0", socket_path.trim()))
+        .env("HOME", dir.path())
+        .env("TSMAN_CONFIG_STORAGE_DIR", &sessions)
+        .env("TSMAN_LAYOUT_STORAGE_DIR", dir.path().join("layouts"))
+        .env("TSMAN_DATA_DIR", dir.path().join("data"))
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{output:?}");
+    assert!(output.status.success(), "{output:?}");
+}
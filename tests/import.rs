@@ -0,0 +1,169 @@
+use std::path::Path;
+
+use tsman::tmux::import::{self, Format};
+use tsman::tmux::session::Session;
+
+const TMUXINATOR: &str = "\
+name: blog
+root: /home/user/blog
+pre_window: nvm use
+windows:
+  - editor:
+      layout: main-vertical
+      panes:
+        - vim
+        - guard
+  - server: bundle exec rails s
+  - logs:
+      root: log
+      panes:
+        - tail:
+          - cd production
+          - tail -f app.log
+  - shell:
+";
+
+const TMUXP: &str = "\
+session_name: api
+start_directory: /srv/api
+shell_command_before: source .venv/bin/activate
+windows:
+- window_name: code
+  layout: tiled
+  panes:
+    - shell_command:
+        - git pull
+        - nvim
+    - echo ready
+    - null
+- window_name: db
+  start_directory: ./db
+  panes:
+    - shell_command: psql
+";
+
+const RESURRECT: &str = "\
+pane\tdev\t1\t1\t:*\t0\ttitle\t:/home/user/dev\t1\tnvim\t:nvim .
+pane\tdev\t1\t1\t:*\t1\ttitle\t:/tmp\t0\tbash\t:
+pane\tops\t0\t1\t:*\t0\t:/var/log\t1\ttail\t:tail -f syslog
+window\tdev\t1\t:editor\t1\t:*\tabcd,80x24,0,0,0\t:
+window\tops\t0\t:logs\t1\t:*\tef01,80x24,0,0,1\t:
+state\tdev\tops
+";
+
+fn convert(text: &str, format: Format) -> Vec<Session> {
+    import::convert(Path::new("project.yml"), text, format).unwrap()
+}
+
+fn panes(session: &Session) -> Vec<(&str, &str, Option<&str>)> {
+    session
+        .windows
+        .iter()
+        .flat_map(|window| {
+            window.panes.iter().map(|pane| {
+                (
+                    window.name.as_str(),
+                    pane.work_dir.as_str(),
+                    pane.current_command.as_deref(),
+                )
+            })
+        })
+        .collect()
+}
+
+#[test]
+fn formats_are_detected_from_the_contents() {
+    let tsman = "name: dev\nwork_dir: /\nwindows: []\n";
+    let bundle = "- name: dev\n  work_dir: /\n  windows: []\n";
+
+    assert_eq!(import::detect(TMUXINATOR), [Format::Tmuxinator]);
+    assert_eq!(import::detect(TMUXP), [Format::Tmuxp]);
+    assert_eq!(import::detect(RESURRECT), [Format::Resurrect]);
+    assert_eq!(import::detect(tsman), [Format::Tsman]);
+    assert_eq!(import::detect(bundle), [Format::Tsman]);
+}
+
+#[test]
+fn ambiguous_and_unknown_files_are_reported() {
+    assert_eq!(
+        import::detect("name: dev\nwindows: []\n"),
+        [Format::Tsman, Format::Tmuxinator]
+    );
+    assert!(import::detect("just: text\n").is_empty());
+    assert!(import::detect("{{ not yaml").is_empty());
+}
+
+#[test]
+fn tmuxinator_projects_are_converted() {
+    let [session] = convert(TMUXINATOR, Format::Tmuxinator).try_into().unwrap();
+
+    assert_eq!(session.name, "blog");
+    assert_eq!(session.work_dir, "/home/user/blog");
+    assert_eq!(session.pre_window.as_deref(), Some("nvm use"));
+    assert_eq!(session.windows[0].layout, "main-vertical");
+    assert_eq!(
+        panes(&session),
+        [
+            ("editor", "/home/user/blog", Some("vim")),
+            ("editor", "/home/user/blog", Some("guard")),
+            ("server", "/home/user/blog", Some("bundle exec rails s")),
+            (
+                "logs",
+                "/home/user/blog/log",
+                Some("cd production; tail -f app.log")
+            ),
+            ("shell", "/home/user/blog", None),
+        ]
+    );
+}
+
+#[test]
+fn tmuxp_projects_are_converted() {
+    let [session] = convert(TMUXP, Format::Tmuxp).try_into().unwrap();
+
+    assert_eq!(session.name, "api");
+    assert_eq!(
+        session.pre_window.as_deref(),
+        Some("source .venv/bin/activate")
+    );
+    assert_eq!(
+        panes(&session),
+        [
+            ("code", "/srv/api", Some("git pull; nvim")),
+            ("code", "/srv/api", Some("echo ready")),
+            ("code", "/srv/api", None),
+            ("db", "/srv/api/db", Some("psql")),
+        ]
+    );
+}
+
+#[test]
+fn resurrect_files_give_every_session() {
+    let sessions = convert(RESURRECT, Format::Resurrect);
+
+    assert_eq!(sessions.len(), 2);
+    assert_eq!(sessions[0].name, "dev");
+    assert_eq!(sessions[0].work_dir, "/home/user/dev");
+    assert_eq!(sessions[0].windows[0].layout, "abcd,80x24,0,0,0");
+    assert_eq!(
+        panes(&sessions[0]),
+        [
+            ("editor", "/home/user/dev", Some("nvim .")),
+            ("editor", "/tmp", None),
+        ]
+    );
+    // Older versions don't save pane titles.
+    assert_eq!(
+        panes(&sessions[1]),
+        [("logs", "/var/log", Some("tail -f syslog"))]
+    );
+}
+
+#[test]
+fn invalid_projects_are_config_errors() {
+    let err =
+        import::convert(Path::new("p.yml"), "windows: []\n", Format::Tmuxp)
+            .unwrap_err();
+
+    assert!(err.to_string().contains("p.yml"), "{err}");
+}
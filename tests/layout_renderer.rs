@@ -1,9 +1,11 @@
+use tsman::tmux::charset::Charset;
 use tsman::tmux::{layout_parser, layout_renderer};
 
 #[test]
 fn render_single_pane() {
     let node = layout_parser::parse("1f76,80x24,0,0,0").unwrap();
-    let lines = layout_renderer::render(&node, "main", 20, 5).unwrap();
+    let lines = layout_renderer::render(&node, "main", 20, 5, Charset::Unicode)
+        .unwrap();
     assert_eq!(lines.len(), 5);
     // Top border has name
     assert!(lines[0].contains("main"));
@@ -22,7 +24,9 @@ fn render_horizontal_split() {
     let node =
         layout_parser::parse("b1cd,190x47,0,0{95x47,0,0,1,94x47,96,0,2}")
             .unwrap();
-    let lines = layout_renderer::render(&node, "editor", 21, 5).unwrap();
+    let lines =
+        layout_renderer::render(&node, "editor", 21, 5, Charset::Unicode)
+            .unwrap();
     // Should have a vertical divider roughly in the middle
     // Check that some interior row has a │ character not at the edges
     let mid_row = &lines[2];
@@ -38,7 +42,9 @@ fn render_horizontal_split() {
 fn render_vertical_split() {
     let node = layout_parser::parse("a1b2,80x24,0,0[80x12,0,0,1,80x11,0,13,2]")
         .unwrap();
-    let lines = layout_renderer::render(&node, "shell", 20, 7).unwrap();
+    let lines =
+        layout_renderer::render(&node, "shell", 20, 7, Charset::Unicode)
+            .unwrap();
     // Should have a horizontal divider somewhere in the middle rows
     let has_hdiv = lines[1..6].iter().any(|line| {
         let chars: Vec<char> = line.chars().skip(1).collect();
@@ -54,7 +60,8 @@ fn render_nested_splits() {
         "xxxx,200x50,0,0{100x50,0,0,1,99x50,101,0[99x25,101,0,2,99x24,101,26,3]}",
     )
     .unwrap();
-    let lines = layout_renderer::render(&node, "dev", 30, 9).unwrap();
+    let lines =
+        layout_renderer::render(&node, "dev", 30, 9, Charset::Unicode).unwrap();
     // Should have both vertical and horizontal dividers
     let all_text: String = lines.join("\n");
     assert!(all_text.contains('┬'), "expected ┬ junction");
@@ -64,7 +71,9 @@ fn render_nested_splits() {
 #[test]
 fn render_too_small_returns_none() {
     let node = layout_parser::parse("1f76,80x24,0,0,0").unwrap();
-    assert!(layout_renderer::render(&node, "x", 2, 2).is_none());
+    assert!(
+        layout_renderer::render(&node, "x", 2, 2, Charset::Unicode).is_none()
+    );
 }
 
 #[test]
@@ -76,7 +85,8 @@ fn render_hsplit_with_inner_vsplit_connects_dividers() {
         "840e,147x36,0,0{66x36,0,0[66x18,0,0,158,66x17,0,19,159],80x36,67,0,157}",
     )
     .unwrap();
-    let lines = layout_renderer::render(&node, "zsh", 40, 8).unwrap();
+    let lines =
+        layout_renderer::render(&node, "zsh", 40, 8, Charset::Unicode).unwrap();
 
     // Find the vertical divider column from a plain interior row
     let interior_row = &lines[2];
@@ -107,8 +117,29 @@ fn render_hsplit_with_inner_vsplit_connects_dividers() {
 #[test]
 fn render_name_truncation() {
     let node = layout_parser::parse("1f76,80x24,0,0,0").unwrap();
-    let lines =
-        layout_renderer::render(&node, "very-long-window-name", 10, 4).unwrap();
+    let lines = layout_renderer::render(
+        &node,
+        "very-long-window-name",
+        10,
+        4,
+        Charset::Unicode,
+    )
+    .unwrap();
     // Name should be truncated to fit
     assert_eq!(lines[0].chars().count(), 10);
 }
+
+#[test]
+fn render_ascii() {
+    let node =
+        layout_parser::parse("b1cd,190x47,0,0{95x47,0,0,1,94x47,96,0,2}")
+            .unwrap();
+    let lines = layout_renderer::render(&node, "editor", 21, 5, Charset::Ascii)
+        .unwrap();
+    assert!(lines.iter().all(|line| line.is_ascii()));
+    assert!(lines[0].starts_with("+ editor -"));
+    assert!(lines[0].ends_with('+'));
+    assert!(lines[2].starts_with('|'));
+    // The divider meets the bottom border in a junction
+    assert_eq!(lines[4].matches('+').count(), 3);
+}
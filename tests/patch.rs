@@ -0,0 +1,97 @@
+use serde_yaml::Value;
+use tsman::patch;
+
+const CONFIG: &str = "\
+name: dev
+work_dir: /tmp
+pre_window: nvm use
+windows:
+- index: '1'
+  name: editor
+  panes:
+  - index: '0'
+    work_dir: /tmp
+  - index: '1'
+    work_dir: /tmp
+";
+
+fn config() -> Value {
+    serde_yaml::from_str(CONFIG).unwrap()
+}
+
+#[test]
+fn assignments_set_nested_fields() {
+    let mut doc = config();
+    let (path, value) = patch::parse_assignment(
+        "windows.0.panes.1.current_command=npm run dev",
+    )
+    .unwrap();
+    patch::set(&mut doc, &path, value).unwrap();
+    let (path, value) =
+        patch::parse_assignment("windows.0.env.PORT=3000").unwrap();
+    patch::set(&mut doc, &path, value).unwrap();
+
+    let panes = &doc["windows"][0]["panes"];
+    assert_eq!(panes[1]["current_command"], "npm run dev");
+    assert_eq!(doc["windows"][0]["env"]["PORT"], 3000);
+}
+
+#[test]
+fn assignment_values_are_yaml() {
+    assert_eq!(
+        patch::parse_assignment("monitor='3'").unwrap(),
+        ("monitor".to_string(), Value::from("3"))
+    );
+    assert_eq!(
+        patch::parse_assignment("pre_window=null").unwrap().1,
+        Value::Null
+    );
+    assert!(patch::parse_assignment("pre_window").is_err());
+}
+
+#[test]
+fn assigning_null_removes_the_key() {
+    let mut doc = config();
+
+    patch::set(&mut doc, "pre_window", Value::Null).unwrap();
+    patch::set(&mut doc, "windows.0.panes.1.current_command", Value::Null)
+        .unwrap();
+
+    assert!(doc.get("pre_window").is_none());
+    assert!(
+        doc["windows"][0]["panes"][1]
+            .get("current_command")
+            .is_none()
+    );
+    assert_eq!(doc["windows"][0]["panes"][1]["index"], "1");
+}
+
+#[test]
+fn indices_append_but_dont_skip() {
+    let mut doc = config();
+
+    patch::set(&mut doc, "windows.1.name", "shell".into()).unwrap();
+    assert_eq!(doc["windows"][1]["name"], "shell");
+
+    let err = patch::set(&mut doc, "windows.5.name", "x".into()).unwrap_err();
+    assert!(err.to_string().contains("past the 2 items"), "{err}");
+    assert!(patch::set(&mut doc, "name.first", "x".into()).is_err());
+    assert!(patch::set(&mut doc, "windows.first", "x".into()).is_err());
+}
+
+#[test]
+fn patches_merge_mappings_and_replace_the_rest() {
+    let mut doc = config();
+    let patch: Value = serde_yaml::from_str(
+        "work_dir: /srv\npre_window: null\nwindows: []\nenv:\n  A: b\n",
+    )
+    .unwrap();
+
+    patch::merge(&mut doc, patch);
+
+    assert_eq!(doc["name"], "dev");
+    assert_eq!(doc["work_dir"], "/srv");
+    assert!(doc.get("pre_window").is_none());
+    assert_eq!(doc["windows"], Value::Sequence(Vec::new()));
+    assert_eq!(doc["env"]["A"], "b");
+}
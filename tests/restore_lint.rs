@@ -0,0 +1,113 @@
+// Restore lint is process-wide, so it gets a test binary of its own.
+mod common;
+
+use std::cell::RefCell;
+
+use regex::Regex;
+use tsman::error::{Result, TsmanError};
+use tsman::tmux::client::{TmuxClient, TmuxOutput};
+use tsman::tmux::interface::{self, RestoreLint};
+
+use common::{pane, session, window};
+
+/// Fake tmux server accepting every command and recording it.
+#[derive(Default)]
+struct FakeTmux {
+    commands: RefCell<Vec<String>>,
+}
+
+impl TmuxClient for FakeTmux {
+    fn run(&self, args: &[&str]) -> Result<TmuxOutput> {
+        self.commands.borrow_mut().push(args.join(" "));
+        Ok(TmuxOutput {
+            success: true,
+            ..Default::default()
+        })
+    }
+
+    fn run_interactive(&self, args: &[&str]) -> Result<TmuxOutput> {
+        self.run(args)
+    }
+
+    fn process_table(&self) -> Result<String> {
+        Ok(String::new())
+    }
+
+    fn server(&self) -> Option<&str> {
+        None
+    }
+}
+
+/// The lint of every test here: it can only be set once per process.
+fn refuse_rm_rf() {
+    interface::set_restore_lint(RestoreLint {
+        patterns: vec![Regex::new(r"\brm\s+-rf\b").unwrap()],
+        fail: true,
+    });
+}
+
+#[test]
+fn risky_sessions_are_refused_before_touching_tmux() {
+    refuse_rm_rf();
+    let session = session(
+        "/tmp",
+        vec![window(
+            "1",
+            "shell",
+            vec![pane("0", "/tmp", Some("rm -rf ~"))],
+        )],
+    );
+    let tmux = FakeTmux::default();
+
+    let err = interface::restore_session_detached(&tmux, &session, &mut |_| {})
+        .unwrap_err();
+    assert!(
+        matches!(&err, TsmanError::RiskyCommands { session, commands }
+            if session == "dev" && commands.len() == 1),
+        "{err}"
+    );
+    let err = interface::reload_session(&tmux, &session, false, &mut |_| {})
+        .unwrap_err();
+    assert!(matches!(err, TsmanError::RiskyCommands { .. }), "{err}");
+    let err = interface::append_windows(
+        &tmux,
+        &session,
+        "work",
+        &[&session.windows[0]],
+        &mut |_| {},
+    )
+    .unwrap_err();
+    assert!(matches!(err, TsmanError::RiskyCommands { .. }), "{err}");
+
+    assert!(tmux.commands.borrow().is_empty());
+}
+
+#[test]
+fn risky_pre_commands_are_refused_before_running() {
+    refuse_rm_rf();
+    let dir = tempfile::TempDir::new().unwrap();
+    let marker = dir.path().join("ran");
+    let mut session = session(
+        dir.path().to_str().unwrap(),
+        vec![window("1", "shell", vec![pane("0", "/tmp", None)])],
+    );
+    session.pre = vec![format!("touch {} && rm -rf ~", marker.display())];
+    session.pre_window = Some("rm -rf /tmp/cache".to_string());
+    let tmux = FakeTmux::default();
+
+    let err = interface::restore_session_detached(&tmux, &session, &mut |_| {})
+        .unwrap_err();
+    let TsmanError::RiskyCommands { commands, .. } = &err else {
+        panic!("{err}");
+    };
+    assert_eq!(
+        commands,
+        &[
+            format!("pre: touch {} && rm -rf ~", marker.display()),
+            "pre_window, in every pane: rm -rf /tmp/cache".to_string(),
+        ]
+    );
+
+    assert!(!marker.exists());
+    assert!(tmux.commands.borrow().is_empty());
+}
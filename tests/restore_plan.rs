@@ -0,0 +1,598 @@
+mod common;
+
+use tsman::tmux::client::escape_separator;
+use tsman::tmux::plan::{self, TmuxCommand, WindowTargets};
+use tsman::tmux::session::{Monitor, PaneSize, Split};
+
+use common::{pane, session, window};
+
+fn lines(plan: &[TmuxCommand]) -> Vec<String> {
+    plan.iter().map(ToString::to_string).collect()
+}
+
+#[test]
+fn single_pane_session() {
+    let session = session(
+        "/home/user/dev",
+        vec![window(
+            "1",
+            "editor",
+            vec![pane("0", "/home/user/dev", None)],
+        )],
+    );
+
+    assert_eq!(
+        lines(&plan::restore_plan(&session, "dev")),
+        [
+            "tmux new-session -d -s dev -c /home/user/dev",
+            "tmux if-shell -F -t 'dev:^' '#{==:#{window_index},1}' '' \
+             'move-window -d -s dev:^ -t dev:1'",
+            "tmux rename-window -t 'dev:1' editor",
+            "tmux select-layout -t 'dev:1' abcd,80x24,0,0,0",
+        ]
+    );
+}
+
+#[test]
+fn window_alert_settings_are_applied() {
+    let mut editor =
+        window("1", "editor", vec![pane("0", "/home/user/dev", None)]);
+    editor.monitor = Some(Monitor {
+        activity: true,
+        bell: false,
+        silence: 15,
+    });
+    let session = session("/home/user/dev", vec![editor]);
+
+    assert_eq!(
+        lines(&plan::restore_plan(&session, "dev")),
+        [
+            "tmux new-session -d -s dev -c /home/user/dev",
+            "tmux if-shell -F -t 'dev:^' '#{==:#{window_index},1}' '' \
+             'move-window -d -s dev:^ -t dev:1'",
+            "tmux rename-window -t 'dev:1' editor",
+            "tmux set-option -w -t 'dev:1' monitor-activity on",
+            "tmux set-option -w -t 'dev:1' monitor-bell off",
+            "tmux set-option -w -t 'dev:1' monitor-silence 15",
+            "tmux select-layout -t 'dev:1' abcd,80x24,0,0,0",
+        ]
+    );
+}
+
+#[test]
+fn splits_panes_and_sends_commands() {
+    let session = session(
+        "/home/user/dev",
+        vec![window(
+            "1",
+            "editor",
+            vec![
+                pane("0", "/home/user/dev", Some("nvim .")),
+                pane("1", "/tmp", None),
+            ],
+        )],
+    );
+
+    assert_eq!(
+        lines(&plan::restore_plan(&session, "dev")),
+        [
+            "tmux new-session -d -s dev -c /home/user/dev",
+            "tmux if-shell -F -t 'dev:^' '#{==:#{window_index},1}' '' \
+             'move-window -d -s dev:^ -t dev:1'",
+            "tmux rename-window -t 'dev:1' editor",
+            "tmux split-window -d -t 'dev:1' -c /home/user/dev",
+            "tmux select-layout -t 'dev:1' abcd,80x24,0,0,0",
+            "tmux send-keys -t 'dev:1.0' -l -- 'nvim .' \\; send-keys \
+             -t 'dev:1.0' C-m",
+            "tmux send-keys -t 'dev:1.1' -l -- 'cd /tmp; clear' \\; send-keys \
+             -t 'dev:1.1' C-m",
+        ]
+    );
+}
+
+#[test]
+fn panes_with_history_print_it_before_the_shell() {
+    let mut shell = pane("1", "/tmp", None);
+    shell.history = Some("/h/1.1.gz".to_string());
+    let session = session(
+        "/home/user/dev",
+        vec![window(
+            "1",
+            "editor",
+            vec![pane("0", "/home/user/dev", None), shell],
+        )],
+    );
+
+    let plan = plan::restore_plan(&session, "dev");
+
+    assert_eq!(
+        plan[3],
+        TmuxCommand::new([
+            "split-window",
+            "-d",
+            "-t",
+            "dev:1",
+            "-c",
+            "/tmp",
+            "sh -c 'gzip -dc /h/1.1.gz 2>/dev/null; exec \"${SHELL:-sh}\" -l'",
+        ])
+    );
+    // Changing directory would clear the history again.
+    assert!(!lines(&plan).iter().any(|line| line.contains("cd /tmp")));
+}
+
+#[test]
+fn panes_start_their_saved_shell() {
+    let mut fish = pane("0", "/home/user/dev", None);
+    fish.shell = Some("fish".to_string());
+    let mut zsh = pane("1", "/tmp", None);
+    zsh.shell = Some("zsh".to_string());
+    zsh.history = Some("/h/1.1.gz".to_string());
+    let session = session(
+        "/home/user/dev",
+        vec![window("1", "editor", vec![fish, zsh])],
+    );
+
+    let plan = plan::restore_plan(&session, "dev");
+
+    assert_eq!(
+        plan[0],
+        TmuxCommand::new([
+            "new-session",
+            "-d",
+            "-s",
+            "dev",
+            "-c",
+            "/home/user/dev",
+            "exec fish -l",
+        ])
+    );
+    assert_eq!(
+        plan[3].args.last().unwrap(),
+        "sh -c 'gzip -dc /h/1.1.gz 2>/dev/null; exec zsh -l'"
+    );
+}
+
+#[test]
+fn sized_panes_are_split_to_size_without_the_layout() {
+    let mut logs = pane("1", "/home/user/dev", None);
+    logs.size = Some(PaneSize::Percent(30));
+    let mut shell = pane("2", "/home/user/dev", None);
+    shell.size = Some(PaneSize::Cells(8));
+    let session = session(
+        "/home/user/dev",
+        vec![window(
+            "1",
+            "editor",
+            vec![pane("0", "/home/user/dev", None), logs, shell],
+        )],
+    );
+
+    assert_eq!(
+        lines(&plan::restore_plan(&session, "dev"))[3..],
+        [
+            "tmux split-window -d -t 'dev:1.{bottom-right}' -l '30%' -c \
+             /home/user/dev",
+            "tmux split-window -d -t 'dev:1.{bottom-right}' -l 8 -c \
+             /home/user/dev",
+        ]
+    );
+}
+
+#[test]
+fn splits_set_each_panes_direction() {
+    let mut editor = window(
+        "1",
+        "editor",
+        vec![
+            pane("0", "/home/user/dev", None),
+            pane("1", "/home/user/dev", None),
+            pane("2", "/home/user/dev", None),
+        ],
+    );
+    editor.splits = vec![Split::Horizontal, Split::Vertical];
+    editor.panes[2].size = Some(PaneSize::Percent(40));
+    let session = session("/home/user/dev", vec![editor]);
+
+    assert_eq!(
+        lines(&plan::restore_plan(&session, "dev"))[3..],
+        [
+            "tmux split-window -d -t 'dev:1.{bottom-right}' -h -c \
+             /home/user/dev",
+            "tmux split-window -d -t 'dev:1.{bottom-right}' -v -l '40%' -c \
+             /home/user/dev",
+        ]
+    );
+}
+
+#[test]
+fn extra_windows_are_created() {
+    let session = session(
+        "/home/user/dev",
+        vec![
+            window("1", "editor", vec![pane("0", "/home/user/dev", None)]),
+            window("2", "shell", vec![pane("0", "/home/user/dev", None)]),
+        ],
+    );
+
+    let plan = plan::restore_plan(&session, "tsman-temp-1");
+
+    assert_eq!(
+        plan[4],
+        TmuxCommand::new([
+            "new-window",
+            "-d",
+            "-t",
+            "tsman-temp-1:2",
+            "-c",
+            "/home/user/dev"
+        ])
+    );
+    assert_eq!(
+        plan[5].to_string(),
+        "tmux rename-window -t 'tsman-temp-1:2' shell"
+    );
+}
+
+#[test]
+fn windows_keep_their_saved_indexes() {
+    let session = session(
+        "/home/user/dev",
+        vec![
+            window("1", "editor", vec![pane("0", "/home/user/dev", None)]),
+            window("2", "shell", vec![pane("0", "/home/user/dev", None)]),
+            window("5", "logs", vec![pane("0", "/home/user/dev", None)]),
+        ],
+    );
+
+    let new_windows: Vec<_> = plan::restore_plan(&session, "dev")
+        .into_iter()
+        .filter(|command| command.args[0] == "new-window")
+        .map(|command| command.args[3].clone())
+        .collect();
+
+    assert_eq!(new_windows, ["dev:2", "dev:5"]);
+}
+
+#[test]
+fn session_without_windows_only_creates_session() {
+    let plan =
+        plan::restore_plan(&session("/home/user/dev", Vec::new()), "dev");
+    assert_eq!(plan.len(), 1);
+    assert_eq!(plan[0].args[0], "new-session");
+}
+
+#[test]
+fn script_escapes_arguments() {
+    let session = session(
+        "/home/user/my project",
+        vec![window(
+            "1",
+            "editor",
+            vec![pane("0", "/home/user/it's", None)],
+        )],
+    );
+
+    let script = plan::to_script(&plan::restore_plan(&session, "dev"));
+    let mut script_lines = script.lines();
+
+    assert_eq!(
+        script_lines.next(),
+        Some("tmux new-session -d -s dev -c '/home/user/my project'")
+    );
+    assert_eq!(
+        script_lines.last(),
+        Some(
+            r#"tmux send-keys -t 'dev:1.0' -l -- 'cd '\''/home/user/it'\''\'\'''\''s'\''; clear' \; send-keys -t 'dev:1.0' C-m"#
+        )
+    );
+    assert!(script.ends_with('\n'));
+}
+
+#[test]
+fn steps_record_window_and_pane() {
+    let session = session(
+        "/home/user/dev",
+        vec![
+            window("1", "editor", vec![pane("0", "/home/user/dev", None)]),
+            window(
+                "2",
+                "shell",
+                vec![
+                    pane("0", "/home/user/dev", None),
+                    pane("1", "/tmp", Some("htop")),
+                ],
+            ),
+        ],
+    );
+
+    let steps = plan::restore_steps(&session, "dev", WindowTargets::Index);
+    let positions: Vec<_> = steps
+        .iter()
+        .map(|step| (step.command.args[0].as_str(), step.window, step.pane))
+        .collect();
+
+    assert_eq!(
+        positions,
+        [
+            ("new-session", 1, None),
+            ("if-shell", 1, None),
+            ("rename-window", 1, None),
+            ("select-layout", 1, None),
+            ("new-window", 2, None),
+            ("rename-window", 2, None),
+            ("split-window", 2, Some(2)),
+            ("select-layout", 2, None),
+            ("send-keys", 2, Some(2)),
+            ("send-keys", 2, Some(2)),
+        ]
+    );
+}
+
+#[test]
+fn panes_start_with_their_window_and_own_env() {
+    let mut editor = window(
+        "1",
+        "editor",
+        vec![pane("0", "/tmp", None), pane("1", "/tmp", None)],
+    );
+    editor.env.insert("PORT".to_string(), "3000".to_string());
+    editor.env.insert("MODE".to_string(), "dev".to_string());
+    editor.panes[1]
+        .env
+        .insert("PORT".to_string(), "4000".to_string());
+    let session = session("/home/user/dev", vec![editor]);
+
+    let plan = plan::restore_plan(&session, "dev");
+
+    assert_eq!(
+        plan[0],
+        TmuxCommand::new([
+            "new-session",
+            "-d",
+            "-s",
+            "dev",
+            "-e",
+            "MODE=dev",
+            "-e",
+            "PORT=3000",
+            "-c",
+            "/home/user/dev",
+        ])
+    );
+    assert_eq!(
+        plan[3],
+        TmuxCommand::new([
+            "split-window",
+            "-d",
+            "-t",
+            "dev:1",
+            "-e",
+            "MODE=dev",
+            "-e",
+            "PORT=4000",
+            "-c",
+            "/home/user/dev",
+        ])
+    );
+}
+
+#[test]
+fn project_env_is_loaded_before_pane_commands() {
+    let mut session = session(
+        "/home/user/dev",
+        vec![window(
+            "1",
+            "editor",
+            vec![pane("0", "/home/user/dev", Some("cargo run"))],
+        )],
+    );
+    session.direnv = true;
+    session.env_file = Some(".env".to_string());
+
+    assert_eq!(
+        lines(&plan::restore_plan(&session, "dev"))[4..],
+        [
+            "tmux send-keys -t 'dev:1.0' -l -- 'eval \"$(direnv export \
+             bash 2>/dev/null)\"; set -a; . ./.env; set +a' \\; send-keys \
+             -t 'dev:1.0' C-m",
+            "tmux send-keys -t 'dev:1.0' -l -- 'cargo run' \\; send-keys \
+             -t 'dev:1.0' C-m",
+        ]
+    );
+}
+
+#[test]
+fn pre_runs_once_and_pre_window_in_every_pane() {
+    let mut session = session(
+        "/home/user/dev",
+        vec![window(
+            "1",
+            "editor",
+            vec![
+                pane("0", "/home/user/dev", Some("cargo run")),
+                pane("1", "/home/user/dev", None),
+            ],
+        )],
+    );
+    session.pre = vec!["docker compose up -d # db".to_string()];
+    session.pre_window = Some("source .venv/bin/activate".to_string());
+
+    let lines = lines(&plan::restore_plan(&session, "dev"));
+
+    // tsman runs `pre` itself, so only the script shows it.
+    assert!(!lines.iter().any(|line| line.contains("docker")));
+    assert!(plan::restore_script(&session, "dev").starts_with(
+        "(cd /home/user/dev && docker compose up -d # db)\ntmux new-session"
+    ));
+    assert_eq!(
+        lines[5..],
+        [
+            "tmux send-keys -t 'dev:1.0' -l -- 'source .venv/bin/activate' \
+             \\; send-keys -t 'dev:1.0' C-m",
+            "tmux send-keys -t 'dev:1.0' -l -- 'cargo run' \\; send-keys \
+             -t 'dev:1.0' C-m",
+            "tmux send-keys -t 'dev:1.1' -l -- 'source .venv/bin/activate' \
+             \\; send-keys -t 'dev:1.1' C-m",
+        ]
+    );
+}
+
+#[test]
+fn id_targets_wait_for_the_window_ids() {
+    let session = session(
+        "/home/user/dev",
+        vec![
+            window("1", "editor", vec![pane("0", "/home/user/dev", None)]),
+            window("5", "shell", vec![pane("0", "/tmp", Some("htop"))]),
+        ],
+    );
+
+    let steps = plan::restore_steps(&session, "dev", WindowTargets::Id);
+    let commands: Vec<_> =
+        steps.iter().map(|step| step.command.to_string()).collect();
+
+    assert_eq!(
+        commands,
+        [
+            "tmux new-session -d -P -F '#{window_id}' -s dev -c /home/user/dev",
+            "tmux rename-window -t '{window 1}' editor",
+            "tmux select-layout -t '{window 1}' abcd,80x24,0,0,0",
+            "tmux new-window -d -P -F '#{window_id}' -t 'dev:5' -c /home/user/dev",
+            "tmux rename-window -t '{window 2}' shell",
+            "tmux select-layout -t '{window 2}' abcd,80x24,0,0,0",
+            "tmux send-keys -t '{window 2}.0' -l -- 'cd /tmp; clear' \\; send-keys \
+             -t '{window 2}.0' C-m",
+            "tmux send-keys -t '{window 2}.0' -l -- htop \\; send-keys \
+             -t '{window 2}.0' C-m",
+        ]
+    );
+    assert!(
+        steps
+            .iter()
+            .map(|step| step.creates_window)
+            .eq([true, false, false, true, false, false, false, false])
+    );
+}
+
+#[test]
+fn hostile_commands_are_typed_literally() {
+    let typed = |text: &str| plan::type_line("dev:1.0", text).args[5].clone();
+
+    // `-l` and `--` keep key names and leading dashes from being read as
+    // keys or flags, so the text is typed as is.
+    for text in [
+        "find . -exec rm {} ;",
+        "echo a; echo b # done",
+        "Up",
+        "-v",
+        "printf 'a\nb'\necho",
+    ] {
+        assert_eq!(typed(text), text);
+    }
+    // Enter is pressed by a command of its own, not a `;` argument.
+    assert_eq!(
+        plan::type_line("dev:1.0", "Up").then,
+        [["send-keys", "-t", "dev:1.0", "C-m"]]
+    );
+    // A trailing `;` would end the tmux command, so it is escaped on the
+    // command line.
+    assert_eq!(
+        escape_separator("find . -exec rm {} ;"),
+        "find . -exec rm {} \\;"
+    );
+    assert_eq!(
+        escape_separator("find . -exec rm {} \\;"),
+        "find . -exec rm {} \\\\;"
+    );
+    assert_eq!(
+        plan::type_line("dev:1.0", "ls ;").to_string(),
+        "tmux send-keys -t 'dev:1.0' -l -- 'ls \\;' \\; send-keys -t 'dev:1.0' C-m"
+    );
+}
+
+#[cfg(feature = "test-harness")]
+#[test]
+fn plans_run_the_same_under_control_mode() {
+    use std::time::{Duration, Instant};
+
+    use tsman::test_support::TestServer;
+    use tsman::tmux::client::{SystemTmux, TmuxClient};
+    use tsman::tmux::control::ControlModeTmux;
+    use tsman::tmux::interface;
+
+    let server = TestServer::start().unwrap();
+    // Somewhere for the control client to attach.
+    server.new_session("anchor", "/tmp").unwrap();
+    let tmux = ControlModeTmux::new(SystemTmux::with_server(server.socket()));
+    let typed = "echo tsman-typed ;";
+    let mut session = session(
+        "/home/user/dev",
+        vec![window("1", "build;", vec![pane("0", "/tmp", None)])],
+    );
+    session.work_dir = "/tmp".to_string();
+    session.windows[0].layout.clear();
+    session.windows[0].panes[0].current_command = Some(typed.to_string());
+
+    let report =
+        interface::restore_session_detached(&tmux, &session, &mut |_| {})
+            .unwrap();
+
+    assert!(report.is_clean(), "{report:?}");
+    let names = server
+        .run(&["list-windows", "-t", "dev", "-F", "#{window_name}"])
+        .unwrap();
+    assert_eq!(names.stdout.trim(), "build;");
+    // The command is typed as is, without the Enter that follows it.
+    let deadline = Instant::now() + Duration::from_secs(5);
+    let screen = loop {
+        let screen = tmux
+            .run(&["capture-pane", "-p", "-t", "dev:1.0"])
+            .unwrap()
+            .stdout;
+        if screen.contains(typed) || Instant::now() > deadline {
+            break screen;
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    };
+    assert!(screen.lines().any(|line| line.ends_with(typed)), "{screen}");
+    assert!(!screen.contains("C-m"), "{screen}");
+}
+
+#[test]
+fn project_env_is_loaded_in_the_panes_shell_syntax() {
+    let mut fish = pane("0", "/home/user/dev", Some("cargo run"));
+    fish.shell = Some("/usr/bin/fish".to_string());
+    let mut nu = pane("1", "/home/user/dev", Some("cargo test"));
+    nu.shell = Some("nu".to_string());
+    let mut session = session(
+        "/home/user/dev",
+        vec![window("1", "editor", vec![fish, nu])],
+    );
+    session.direnv = true;
+    session.env_file = Some(".env".to_string());
+
+    let lines = lines(&plan::restore_plan(&session, "dev"));
+    let typed = |target: &str| {
+        lines
+            .iter()
+            .find(|line| {
+                line.contains(&format!("send-keys -t '{target}' -l"))
+                    && line.contains("direnv")
+            })
+            .cloned()
+            .unwrap()
+    };
+
+    let fish = typed("dev:1.0");
+    assert!(
+        fish.contains("direnv export fish 2>/dev/null | source"),
+        "{fish}"
+    );
+    assert!(fish.contains("set -gx $kv[1] $kv[2]"), "{fish}");
+    assert!(!fish.contains("set -a; . ./.env; set +a"), "{fish}");
+    let nu = typed("dev:1.1");
+    assert!(nu.contains("direnv export json"), "{nu}");
+    assert!(nu.contains(r#"sh "./.env" | lines"#), "{nu}");
+    assert!(nu.contains("load-env"), "{nu}");
+}
@@ -0,0 +1,71 @@
+use regex::Regex;
+use tsman::tmux::interface::RISKY_COMMANDS;
+
+fn is_risky(command: &str) -> bool {
+    RISKY_COMMANDS
+        .iter()
+        .any(|pattern| Regex::new(pattern).unwrap().is_match(command))
+}
+
+#[test]
+fn recursive_forced_deletes_are_risky() {
+    for command in [
+        "rm -rf ~",
+        "rm -fr ~",
+        "rm -Rf build",
+        "rm -vrf build",
+        "rm -r -f build",
+        "rm -f -r build",
+        "rm -R -f build",
+        "rm -r -v -f build",
+        "rm -r build -f",
+        "rm build -rf",
+        "rm --recursive --force build",
+        "rm --force --recursive build",
+        "rm -r --force build",
+        "rm --recursive -f build",
+        "rm -rf --no-preserve-root /",
+        "sudo rm -rf /var/lib",
+        "/bin/rm -rf /",
+        "cd /tmp && rm -r -f *",
+    ] {
+        assert!(is_risky(command), "{command}");
+    }
+}
+
+#[test]
+fn other_deletes_are_not_risky() {
+    for command in [
+        "rm build.log",
+        "rm -f build.log",
+        "rm -r build",
+        "rm -i -r build",
+        "rm --preserve-root -f build.log",
+        "rm -r build; ls -f",
+        "rm -r build && git commit -f",
+        "rm -f a | grep -r x",
+        "perm -rf",
+        "npm run -- -r -f",
+    ] {
+        assert!(!is_risky(command), "{command}");
+    }
+}
+
+#[test]
+fn other_damaging_commands_are_risky() {
+    for (command, expected) in [
+        ("sudo shutdown now", true),
+        ("reboot", true),
+        ("git push --force", true),
+        ("git push -f origin main", true),
+        ("git push origin main", false),
+        ("mkfs.ext4 /dev/sdb1", true),
+        ("dd if=image.iso of=/dev/sdb", true),
+        ("dd if=/dev/zero of=disk.img", false),
+        ("cat image > /dev/sda", true),
+        ("npm run dev", false),
+        ("nvim .", false),
+    ] {
+        assert_eq!(is_risky(command), expected, "{command}");
+    }
+}
@@ -0,0 +1,365 @@
+mod common;
+
+use regex::Regex;
+use tsman::error::TsmanError;
+use tsman::tmux::charset::Charset;
+use tsman::tmux::session::{
+    Fragment, Pane, PaneSize, Session, Window, combine_previews,
+};
+
+use common::{pane, session, window};
+
+/// Session `dev` in `work_dir` with one `editor` window of `panes`.
+fn editor_session(work_dir: &str, panes: Vec<Pane>) -> Session {
+    session(work_dir, vec![window("1", "editor", panes)])
+}
+
+#[test]
+fn remaps_work_dirs_under_prefix() {
+    let mut session = editor_session(
+        "/home/old",
+        vec![pane("0", "/home/old/api", None), pane("0", "/tmp", None)],
+    );
+
+    assert_eq!(session.remap_paths("/home/old/", "/srv/new"), 2);
+    assert_eq!(session.work_dir, "/srv/new");
+    let panes = &session.windows[0].panes;
+    assert_eq!(panes[0].work_dir, "/srv/new/api");
+    assert_eq!(panes[1].work_dir, "/tmp");
+}
+
+#[test]
+fn only_whole_components_match() {
+    let mut session = editor_session(
+        "/home/older",
+        vec![pane("0", "/x/home/old", Some("ls /home/old2 /x/home/old"))],
+    );
+
+    assert_eq!(session.remap_paths("/home/old", "/srv/new"), 0);
+    assert_eq!(session.work_dir, "/home/older");
+}
+
+#[test]
+fn remaps_paths_inside_commands() {
+    let mut session = editor_session(
+        "/tmp",
+        vec![pane(
+            "0",
+            "/tmp",
+            Some("nvim /home/old/src 'x=/home/old' && cd /home/old"),
+        )],
+    );
+
+    assert_eq!(session.remap_paths("/home/old", "/srv/new"), 1);
+    assert_eq!(
+        session.windows[0].panes[0].current_command.as_deref(),
+        Some("nvim /srv/new/src 'x=/srv/new' && cd /srv/new")
+    );
+}
+
+#[test]
+fn missing_dirs_lists_each_once() {
+    let session = editor_session(
+        "/nonexistent/dev",
+        vec![
+            pane("0", "/tmp", None),
+            pane("0", "/nonexistent/dev", None),
+            pane("0", "/nonexistent/other", None),
+        ],
+    );
+
+    assert_eq!(
+        session.missing_dirs(),
+        ["/nonexistent/dev", "/nonexistent/other"]
+    );
+}
+
+#[test]
+fn ascii_preview_has_no_box_drawing() {
+    let mut session = editor_session(
+        "/tmp",
+        vec![pane("0", "/tmp", Some("nvim")), pane("1", "/tmp", None)],
+    );
+    session.windows.push(idle_window("2", "shell"));
+
+    assert_eq!(
+        session.get_preview(Charset::Ascii, false),
+        "dev:\n |--+- editor:\n |  |- (0) nvim\n |  `- (1) _\n `-- shell: _\n"
+    );
+    assert!(
+        session
+            .get_preview(Charset::Unicode, false)
+            .contains("╠══╦═ editor")
+    );
+}
+
+#[test]
+fn preview_icons_follow_pane_commands() {
+    let session =
+        editor_session("/tmp", vec![pane("0", "/tmp", Some("nvim ."))]);
+    let plain = session.get_preview(Charset::Unicode, false);
+    let icons = session.get_preview(Charset::Unicode, true);
+
+    assert!(plain.contains("editor: nvim ."));
+    assert!(icons.contains("editor: \u{e62b} nvim ."));
+}
+
+#[test]
+fn live_preview_marks_the_running_parts() {
+    let session = editor_session(
+        "/tmp",
+        vec![pane("0", "/tmp", Some("nvim")), pane("1", "/tmp", None)],
+    );
+    let live = [("editor".to_string(), 1), ("logs".to_string(), 1)];
+
+    assert_eq!(
+        session.get_live_preview(Charset::Ascii, false, Some(&live)),
+        "dev: *\n `--+- editor: *\n    |- (0) nvim *\n    `- (1) _\n"
+    );
+    assert_eq!(
+        session.get_live_preview(Charset::Ascii, false, None),
+        session.get_preview(Charset::Ascii, false)
+    );
+}
+
+#[test]
+fn previews_are_combined_under_a_root() {
+    let previews =
+        ["dev:\n `-- editor: _\n", "ops:\n `-- logs: _\n"].map(str::to_string);
+
+    assert_eq!(
+        combine_previews("sessions", &previews, Charset::Ascii),
+        "sessions\n |-- dev:\n |    `-- editor: _\n `-- ops:\n      `-- logs: _\n"
+    );
+    assert_eq!(
+        combine_previews("sessions", &[], Charset::Ascii),
+        "sessions\n"
+    );
+}
+
+#[test]
+fn differs_from_compares_window_names_and_pane_counts() {
+    let session = editor_session(
+        "/tmp",
+        vec![pane("0", "/tmp", None), pane("0", "/tmp", None)],
+    );
+    let live = |name: &str, panes| vec![(name.to_string(), panes)];
+
+    assert!(!session.differs_from(&live("editor", 2)));
+    assert!(session.differs_from(&live("editor", 1)));
+    assert!(session.differs_from(&live("shell", 2)));
+    assert!(session.differs_from(&[]));
+}
+
+/// A window with one idle pane in `/tmp`.
+fn idle_window(index: &str, name: &str) -> Window {
+    window(index, name, vec![pane("0", "/tmp", None)])
+}
+
+fn window_list(session: &Session) -> Vec<(&str, &str)> {
+    session
+        .windows
+        .iter()
+        .map(|w| (w.index.as_str(), w.name.as_str()))
+        .collect()
+}
+
+#[test]
+fn move_window_renumbers_from_the_lowest_index() {
+    let mut session = editor_session("/tmp", vec![]);
+    session.windows = vec![
+        idle_window("1", "editor"),
+        idle_window("2", "shell"),
+        idle_window("3", "logs"),
+    ];
+
+    assert_eq!(session.move_window(2, 0, true), 0);
+    assert_eq!(
+        window_list(&session),
+        [("1", "logs"), ("2", "editor"), ("3", "shell")]
+    );
+
+    // Moving past the end stops at the last position.
+    assert_eq!(session.move_window(0, 10, true), 2);
+    assert_eq!(
+        window_list(&session),
+        [("1", "editor"), ("2", "shell"), ("3", "logs")]
+    );
+}
+
+#[test]
+fn move_window_keeps_index_gaps_without_renumbering() {
+    let mut session = editor_session("/tmp", vec![]);
+    session.windows = vec![
+        idle_window("1", "editor"),
+        idle_window("3", "shell"),
+        idle_window("7", "logs"),
+    ];
+
+    assert_eq!(session.move_window(2, 0, false), 0);
+    assert_eq!(
+        window_list(&session),
+        [("1", "logs"), ("3", "editor"), ("7", "shell")]
+    );
+}
+
+#[test]
+fn remove_window_keeps_the_last_one() {
+    let mut session = editor_session("/tmp", vec![]);
+    session.windows =
+        vec![idle_window("0", "editor"), idle_window("1", "shell")];
+
+    assert_eq!(session.remove_window(0, true).unwrap().name, "editor");
+    assert_eq!(window_list(&session), [("0", "shell")]);
+    assert!(session.remove_window(0, true).is_none());
+    assert_eq!(session.windows.len(), 1);
+}
+
+#[test]
+fn remove_window_leaves_a_gap_without_renumbering() {
+    let mut session = editor_session("/tmp", vec![]);
+    session.windows = vec![
+        idle_window("1", "editor"),
+        idle_window("2", "shell"),
+        idle_window("3", "logs"),
+    ];
+
+    session.remove_window(1, false);
+    assert_eq!(window_list(&session), [("1", "editor"), ("3", "logs")]);
+}
+
+#[test]
+fn commands_matching_lists_risky_pane_commands() {
+    let session = editor_session(
+        "/",
+        vec![
+            pane("0", "/", Some("rm -rf build")),
+            pane("0", "/", Some("cargo watch")),
+        ],
+    );
+    let patterns = [Regex::new(r"\brm\s+-rf\b").unwrap()];
+
+    assert_eq!(
+        session.commands_matching(&patterns),
+        ["Window 1 'editor', pane 0: rm -rf build"]
+    );
+}
+
+#[test]
+fn keep_env_from_carries_env_over_to_matching_windows() {
+    let mut saved = editor_session("/tmp", vec![pane("0", "/tmp", None)]);
+    saved.env_file = Some(".env".to_string());
+    saved.windows[0]
+        .env
+        .insert("RUST_LOG".to_string(), "debug".to_string());
+    saved.windows[0].panes[0]
+        .env
+        .insert("PORT".to_string(), "8080".to_string());
+    let mut captured =
+        editor_session("/tmp", vec![pane("0", "/tmp", Some("cargo run"))]);
+    captured.windows.push(Window {
+        name: "logs".to_string(),
+        ..captured.windows[0].clone()
+    });
+
+    captured.keep_env_from(&saved);
+
+    assert_eq!(captured.env_file.as_deref(), Some(".env"));
+    assert_eq!(captured.windows[0].env["RUST_LOG"], "debug");
+    assert_eq!(captured.windows[0].panes[0].env["PORT"], "8080");
+    assert!(captured.windows[1].env.is_empty());
+}
+
+#[test]
+fn include_accepts_one_file_or_a_list() {
+    let one: Session = serde_yaml::from_str(
+        "name: dev\nwork_dir: /\ninclude: monitoring.yaml\nwindows: []\n",
+    )
+    .unwrap();
+    assert_eq!(one.include, ["monitoring.yaml"]);
+
+    let many: Session = serde_yaml::from_str(
+        "name: dev\nwork_dir: /\nwindows_from: [a, b]\nwindows: []\n",
+    )
+    .unwrap();
+    assert_eq!(many.include, ["a", "b"]);
+}
+
+#[test]
+fn pane_sizes_are_percentages_or_cells() {
+    let session: Session = serde_yaml::from_str(
+        "name: dev\nwork_dir: /\nwindows:\n- index: '1'\n  name: editor\n  \
+         panes:\n  - {index: '0', current_command: null, work_dir: /}\n  \
+         - {index: '1', current_command: null, work_dir: /, size: 30%}\n  \
+         - {index: '2', current_command: null, work_dir: /, size: 12}\n",
+    )
+    .unwrap();
+    let sizes: Vec<_> = session.windows[0]
+        .panes
+        .iter()
+        .map(|pane| pane.size)
+        .collect();
+    assert_eq!(
+        sizes,
+        [None, Some(PaneSize::Percent(30)), Some(PaneSize::Cells(12))]
+    );
+    assert!(session.windows[0].layout.is_empty());
+
+    for invalid in ["0%", "100%", "0", "wide"] {
+        assert!(invalid.parse::<PaneSize>().is_err(), "{invalid}");
+    }
+}
+
+#[test]
+fn included_windows_follow_the_sessions_own() {
+    let mut session = editor_session("/", vec![]);
+    session.windows.push(idle_window("4", "logs"));
+
+    session
+        .add_fragment(Fragment {
+            windows: vec![idle_window("1", "htop"), idle_window("2", "tail")],
+            ..Default::default()
+        })
+        .unwrap();
+
+    assert_eq!(
+        window_list(&session),
+        [("1", "editor"), ("4", "logs"), ("5", "htop"), ("6", "tail")]
+    );
+}
+
+#[test]
+fn included_window_named_like_the_sessions_own_is_refused() {
+    let mut session = editor_session("/", vec![]);
+    let err = session
+        .add_fragment(Fragment {
+            windows: vec![idle_window("1", "htop"), idle_window("2", "editor")],
+            path: "/sessions/fragments/tools.yaml".into(),
+        })
+        .unwrap_err();
+
+    assert!(
+        matches!(&err, TsmanError::InvalidConfig { path, source }
+            if path.ends_with("tools.yaml")
+                && source.to_string().contains("'editor'")),
+        "{err:?}"
+    );
+    assert_eq!(window_list(&session), [("1", "editor")]);
+}
+
+#[test]
+fn keep_includes_from_leaves_included_windows_out() {
+    let mut saved = editor_session("/", vec![]);
+    saved.include = vec!["monitoring.yaml".to_string()];
+    let fragments = [Fragment {
+        windows: vec![idle_window("1", "htop")],
+        ..Default::default()
+    }];
+    let mut live = editor_session("/", vec![]);
+    live.windows.push(idle_window("2", "htop"));
+
+    live.keep_includes_from(&saved, &fragments);
+
+    assert_eq!(live.include, ["monitoring.yaml"]);
+    assert_eq!(window_list(&live), [("1", "editor")]);
+}
@@ -0,0 +1,28 @@
+// The tmux command timeout is process-wide, so it gets a test binary of its
+// own.
+use std::time::Duration;
+
+use tempfile::TempDir;
+use tsman::test_support::TestServer;
+use tsman::tmux::client::{self, CommandPolicy};
+
+#[test]
+fn pre_commands_outlast_the_tmux_timeout() {
+    client::set_command_policy(CommandPolicy {
+        timeout: Duration::from_secs(1),
+        retries: 0,
+    });
+    let dir = TempDir::new().unwrap();
+    let work_dir = dir.path().to_str().unwrap();
+    let server = TestServer::start().unwrap();
+    server.new_session("dev", work_dir).unwrap();
+    let mut captured = server.capture("dev").unwrap();
+    server.kill_session("dev").unwrap();
+    captured.pre = vec!["sleep 2 && touch started".to_string()];
+
+    let report = server.restore(&captured).unwrap();
+
+    assert!(report.is_clean(), "{report:?}");
+    assert!(dir.path().join("started").exists());
+    assert_eq!(server.sessions().unwrap(), ["dev"]);
+}
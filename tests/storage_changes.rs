@@ -0,0 +1,106 @@
+use std::fs::{self, File};
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::{Duration, SystemTime};
+
+use tempfile::TempDir;
+
+/// A throwaway storage and data directory for `tsman` runs.
+struct Storage {
+    dir: TempDir,
+}
+
+impl Storage {
+    fn new() -> Self {
+        let dir = TempDir::new().unwrap();
+        for sub in ["home", "tmux", "sessions", "layouts", "data"] {
+            fs::create_dir(dir.path().join(sub)).unwrap();
+        }
+        Self { dir }
+    }
+
+    fn config(&self, name: &str) -> PathBuf {
+        self.dir
+            .path()
+            .join("sessions")
+            .join(format!("{name}.yaml"))
+    }
+
+    fn write(&self, name: &str, work_dir: &str) {
+        let yaml = format!("name: {name}\nwork_dir: {work_dir}\nwindows: []\n");
+        fs::write(self.config(name), yaml).unwrap();
+    }
+
+    /// The lines `tsman doctor` prints.
+    fn doctor(&self) -> Vec<String> {
+        let output = Command::new(env!("CARGO_BIN_EXE_tsman"))
+            .arg("doctor")
+            .env_remove("TMUX")
+            .env("HOME", self.dir.path().join("home"))
+            .env("TMUX_TMPDIR", self.dir.path().join("tmux"))
+            .env("TSMAN_CONFIG_STORAGE_DIR", self.dir.path().join("sessions"))
+            .env("TSMAN_LAYOUT_STORAGE_DIR", self.dir.path().join("layouts"))
+            .env("TSMAN_DATA_DIR", self.dir.path().join("data"))
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{output:?}");
+        String::from_utf8(output.stdout)
+            .unwrap()
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
+}
+
+#[test]
+fn existing_configs_are_recorded_on_the_first_run() {
+    let storage = Storage::new();
+    storage.write("api", "/srv/api");
+    storage.write("web", "/srv/web");
+
+    assert_eq!(storage.doctor(), ["No problems found."]);
+    assert_eq!(storage.doctor(), ["No problems found."]);
+}
+
+#[test]
+fn renames_copies_and_additions_are_reported() {
+    let storage = Storage::new();
+    storage.write("api", "/srv/api");
+    storage.write("web", "/srv/web");
+    storage.doctor();
+
+    fs::rename(storage.config("api"), storage.config("backend")).unwrap();
+    fs::copy(storage.config("web"), storage.config("web-2")).unwrap();
+    storage.write("docs", "/srv/docs");
+
+    assert_eq!(
+        storage.doctor(),
+        [
+            "'api' was renamed to 'backend'",
+            "'web-2' is a copy of 'web'",
+            "'docs' is new",
+        ]
+    );
+    assert_eq!(storage.doctor(), ["No problems found."]);
+}
+
+#[test]
+fn configs_saved_before_hashing_are_not_new() {
+    let storage = Storage::new();
+    storage.write("api", "/srv/api");
+    storage.doctor();
+
+    // Left by a tsman that didn't record hashes yet.
+    storage.write("legacy", "/srv/legacy");
+    let last_week = SystemTime::now() - Duration::from_secs(7 * 24 * 60 * 60);
+    File::options()
+        .write(true)
+        .open(storage.config("legacy"))
+        .unwrap()
+        .set_modified(last_week)
+        .unwrap();
+    storage.write("docs", "/srv/docs");
+
+    assert_eq!(storage.doctor(), ["'docs' is new"]);
+    assert_eq!(storage.doctor(), ["No problems found."]);
+}
@@ -0,0 +1,768 @@
+mod common;
+
+use std::cell::{Cell, RefCell};
+
+use tsman::error::{Result, TsmanError};
+use tsman::tmux::client::{TmuxClient, TmuxOutput};
+use tsman::tmux::interface;
+use tsman::tmux::plan::{PlanStep, Progress, TmuxCommand};
+use tsman::tmux::session::{Monitor, Pane, Session, Window};
+
+use common::{pane, session, window};
+
+/// Fake tmux server answering from canned output and recording every
+/// command it receives.
+#[derive(Default)]
+struct FakeTmux {
+    /// Whether the server is up. `new-session` starts it, as in tmux.
+    running: Cell<bool>,
+    server: Option<String>,
+    attach_client: Option<String>,
+    /// Subcommand that exits with an error.
+    fail_on: Option<&'static str>,
+    commands: RefCell<Vec<String>>,
+}
+
+impl FakeTmux {
+    fn running() -> Self {
+        Self {
+            running: Cell::new(true),
+            ..Default::default()
+        }
+    }
+
+    fn commands(&self) -> Vec<String> {
+        self.commands.borrow().clone()
+    }
+}
+
+impl TmuxClient for FakeTmux {
+    fn run(&self, args: &[&str]) -> Result<TmuxOutput> {
+        self.commands.borrow_mut().push(args.join(" "));
+
+        let stdout = match args {
+            ["list-sessions", "-F", format]
+                if format.starts_with("#{session_attached}") =>
+            {
+                "1|:|dev\n0|:|notes\n"
+            }
+            ["list-sessions", "-F", format]
+                if format.ends_with("#{session_path}") =>
+            {
+                "dev|:|/home/user/dev\nnotes|:|/tmp/my notes\n"
+            }
+            ["list-sessions", ..] | ["list-session", ..] => "dev\nnotes\n",
+            ["display-message", "-p", "-t", _, "-F", "#{session_path}"] => {
+                "/home/user/dev\n"
+            }
+            [
+                "display-message",
+                "-p",
+                "-t",
+                _,
+                "-F",
+                "#{window_id} #{pane_id}",
+            ] => "@1 %1\n",
+            ["list-panes", "-s", "-t", "=dev", "-F", format]
+                if format.starts_with("#{pane_dead}") =>
+            {
+                concat!(
+                    "0|:|%1|:|1|:|0|:|editor\n",
+                    "1|:|%2|:|2|:|0|:|my shell\n",
+                    "1|:|%3|:|3|:|0|:|gone\n",
+                )
+            }
+            ["list-panes", "-s", "-t", "dev", "-F", format]
+                if format.starts_with("#{pane_id}") =>
+            {
+                "%1|:|zsh\n%2|:|nvim\n"
+            }
+            ["list-panes", "-s", "-t", "dev", ..] => concat!(
+                "1|:|editor|:|abcd,80x24,0,0,0|:|0|:|1|:|0|:|",
+                "0|:|100|:|/home/user/dev\n",
+                "2|:|my shell|:|ef01,80x24,0,0,1|:|1|:|1|:|30|:|",
+                "0|:|200|:|/tmp/my dir\n",
+                "2|:|my shell|:|ef01,80x24,0,0,1|:|1|:|1|:|30|:|",
+                "1|:|300|:|/tmp\n",
+            ),
+            ["list-panes", "-a", "-F", _] => {
+                "0|:|dev\n1|:|dev\n1|:|notes\n1|:|dev\n"
+            }
+            ["list-clients", "-F", _] => concat!(
+                "/dev/pts/1|:|dev|:|200|:|50\n",
+                "/dev/pts/4|:|notes|:|80|:|24\n",
+            ),
+            ["show-options", "-gw"] => {
+                "monitor-activity off\nmonitor-bell on\nmonitor-silence 0\n"
+            }
+            ["list-windows", "-t", "dev", ..] => "editor|:|1\nmy shell|:|1\n",
+            ["list-windows", "-t", "=dev", "-F", "#{window_index}"] => "1\n2\n",
+            ["list-windows", "-a", ..] => {
+                "dev|:|1|:|editor\ndev|:|2|:|my shell\nnotes|:|1|:|zsh\n"
+            }
+            ["new-window" | "split-window", .., "#{pane_id}"] => "%7\n",
+            ["new-session", "-d", "-P", "-F", "#{window_id}", ..] => "@3\n",
+            ["new-window", "-d", "-P", "-F", "#{window_id}", ..] => "@4\n",
+            _ => "",
+        };
+
+        if args[0] == "new-session" && self.fail_on != Some("new-session") {
+            self.running.set(true);
+        }
+        let success = self.running.get() && self.fail_on != Some(args[0]);
+        Ok(TmuxOutput {
+            success,
+            stdout: stdout.to_string(),
+            stderr: if success {
+                String::new()
+            } else {
+                format!("{} rejected\n", args[0])
+            },
+        })
+    }
+
+    fn run_interactive(&self, args: &[&str]) -> Result<TmuxOutput> {
+        self.commands.borrow_mut().push(args.join(" "));
+        Ok(TmuxOutput {
+            success: self.running.get(),
+            stderr: if self.running.get() {
+                String::new()
+            } else {
+                "no server running\n".to_string()
+            },
+            ..Default::default()
+        })
+    }
+
+    fn process_table(&self) -> Result<String> {
+        self.commands.borrow_mut().push("ps".to_string());
+        Ok("  100     1 -zsh\n  101   100 nvim .\n  200     1 -bash\n"
+            .to_string())
+    }
+
+    fn server(&self) -> Option<&str> {
+        self.server.as_deref()
+    }
+
+    fn attach_client(&self) -> Option<&str> {
+        self.attach_client.as_deref()
+    }
+
+    fn run_shell(&self, command: &str, dir: &str) -> Result<TmuxOutput> {
+        self.commands
+            .borrow_mut()
+            .push(format!("sh {dir}: {command}"));
+        let success = self.fail_on != Some("sh");
+        Ok(TmuxOutput {
+            success,
+            stderr: if success {
+                String::new()
+            } else {
+                "exit status: 1".to_string()
+            },
+            ..Default::default()
+        })
+    }
+}
+
+#[test]
+fn get_session_captures_windows_and_panes() {
+    let tmux = FakeTmux::running();
+    let session = interface::get_session(&tmux, Some("dev")).unwrap();
+
+    assert_eq!(
+        tmux.commands()
+            .iter()
+            .filter(|cmd| cmd.starts_with("list-"))
+            .count(),
+        1
+    );
+    assert_eq!(tmux.commands().iter().filter(|cmd| *cmd == "ps").count(), 1);
+
+    assert_eq!(session.name, "dev");
+    assert_eq!(session.work_dir, "/home/user/dev");
+    assert_eq!(session.windows.len(), 2);
+
+    let editor = &session.windows[0];
+    assert_eq!(editor.index, "1");
+    assert_eq!(editor.name, "editor");
+    assert_eq!(editor.layout, "abcd,80x24,0,0,0");
+    assert_eq!(editor.monitor, None);
+    assert_eq!(editor.panes.len(), 1);
+    assert_eq!(editor.panes[0].current_command.as_deref(), Some("nvim ."));
+    assert_eq!(editor.panes[0].shell.as_deref(), Some("zsh"));
+
+    let shell = &session.windows[1];
+    assert_eq!(shell.name, "my shell");
+    assert_eq!(
+        shell.monitor,
+        Some(Monitor {
+            activity: true,
+            bell: true,
+            silence: 30,
+        })
+    );
+    assert_eq!(shell.panes.len(), 2);
+    assert_eq!(shell.panes[0].work_dir, "/tmp/my dir");
+    assert_eq!(shell.panes[0].current_command, None);
+    assert_eq!(shell.panes[0].shell.as_deref(), Some("bash"));
+    assert_eq!(shell.panes[1].shell, None);
+    assert_eq!(shell.panes[1].index, "1");
+}
+
+#[test]
+fn get_session_of_missing_session() {
+    let tmux = FakeTmux::default();
+    let err = interface::get_session(&tmux, Some("nope")).unwrap_err();
+    assert!(matches!(err, TsmanError::SessionNotFound(name) if name == "nope"));
+}
+
+#[test]
+fn list_active_sessions_without_server() {
+    let tmux = FakeTmux::default();
+    assert!(interface::list_active_sessions(&tmux).unwrap().is_empty());
+    assert_eq!(tmux.commands(), ["has-session"]);
+}
+
+#[test]
+fn list_active_sessions_with_server() {
+    let tmux = FakeTmux::running();
+    assert_eq!(
+        interface::list_active_sessions(&tmux).unwrap(),
+        ["dev", "notes"]
+    );
+}
+
+#[test]
+fn list_window_shapes_groups_windows_by_session() {
+    let tmux = FakeTmux::running();
+    let shapes = interface::list_window_shapes(&tmux).unwrap();
+    assert_eq!(shapes.len(), 2);
+    assert_eq!(
+        shapes["dev"],
+        [("editor".to_string(), 1), ("my shell".to_string(), 2)]
+    );
+    assert_eq!(shapes["notes"], [("zsh".to_string(), 1)]);
+
+    let stopped = FakeTmux::default();
+    assert!(interface::list_window_shapes(&stopped).unwrap().is_empty());
+}
+
+#[test]
+fn count_dead_panes_by_session() {
+    let tmux = FakeTmux::running();
+    let dead = interface::count_dead_panes(&tmux).unwrap();
+    assert_eq!(dead.len(), 2);
+    assert_eq!(dead["dev"], 2);
+    assert_eq!(dead["notes"], 1);
+
+    let stopped = FakeTmux::default();
+    assert!(interface::count_dead_panes(&stopped).unwrap().is_empty());
+}
+
+#[test]
+fn list_detached_sessions_skips_attached() {
+    let tmux = FakeTmux::running();
+    assert_eq!(interface::list_detached_sessions(&tmux).unwrap(), ["notes"]);
+    assert!(
+        interface::list_detached_sessions(&FakeTmux::default())
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[test]
+fn list_session_paths_keys_paths_by_session() {
+    let tmux = FakeTmux::running();
+    let paths = interface::list_session_paths(&tmux).unwrap();
+    assert_eq!(paths.len(), 2);
+    assert_eq!(paths["dev"], "/home/user/dev");
+    assert_eq!(paths["notes"], "/tmp/my notes");
+
+    let stopped = FakeTmux::default();
+    assert!(interface::list_session_paths(&stopped).unwrap().is_empty());
+}
+
+#[test]
+fn is_active_session_matches_exact_name() {
+    let tmux = FakeTmux::running();
+    assert!(interface::is_active_session(&tmux, "dev").unwrap());
+    assert!(!interface::is_active_session(&tmux, "de").unwrap());
+}
+
+#[test]
+fn rename_session_issues_rename() {
+    let tmux = FakeTmux::running();
+    interface::rename_session(&tmux, "dev", "work").unwrap();
+    assert_eq!(tmux.commands(), ["rename-session -t dev work"]);
+}
+
+#[test]
+fn select_window_targets_the_session_window() {
+    let tmux = FakeTmux::running();
+    interface::select_window(&tmux, "dev", "2").unwrap();
+    assert_eq!(tmux.commands(), ["select-window -t dev:2"]);
+}
+
+#[test]
+fn rename_session_surfaces_tmux_stderr() {
+    let tmux = FakeTmux {
+        fail_on: Some("rename-session"),
+        ..FakeTmux::running()
+    };
+    let err = interface::rename_session(&tmux, "dev", "work").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "'tmux rename-session -t dev work' failed: rename-session rejected"
+    );
+}
+
+#[test]
+fn attach_surfaces_tmux_stderr() {
+    let tmux = FakeTmux::default();
+    let err = interface::attach_to_session(&tmux, "dev").unwrap_err();
+    assert!(matches!(
+        err,
+        TsmanError::TmuxFailed { stderr, .. } if stderr == "no server running\n"
+    ));
+}
+
+#[test]
+fn list_clients_parses_each_client() {
+    let clients = interface::list_clients(&FakeTmux::running()).unwrap();
+    assert_eq!(
+        clients,
+        [
+            interface::ClientInfo {
+                name: "/dev/pts/1".to_string(),
+                session: "dev".to_string(),
+                width: 200,
+                height: 50,
+            },
+            interface::ClientInfo {
+                name: "/dev/pts/4".to_string(),
+                session: "notes".to_string(),
+                width: 80,
+                height: 24,
+            },
+        ]
+    );
+    assert!(
+        interface::list_clients(&FakeTmux::default())
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[test]
+fn attach_switches_the_chosen_client() {
+    let tmux = FakeTmux {
+        attach_client: Some("/dev/pts/4".to_string()),
+        ..FakeTmux::running()
+    };
+    interface::attach_to_session(&tmux, "dev").unwrap();
+    assert_eq!(tmux.commands(), ["switch-client -c /dev/pts/4 -t =dev"]);
+}
+
+#[test]
+fn exec_in_session_types_command_into_new_pane() {
+    let tmux = FakeTmux::running();
+    let pane = interface::exec_in_session(&tmux, "dev", "make", true).unwrap();
+    assert_eq!(pane, "%7");
+    assert_eq!(
+        tmux.commands(),
+        [
+            "split-window -d -t dev: -P -F #{pane_id}",
+            "send-keys -t %7 -l -- make",
+            "send-keys -t %7 C-m",
+        ]
+    );
+}
+
+#[test]
+fn broadcast_skips_busy_panes_when_shell_only() {
+    let tmux = FakeTmux::running();
+    let panes =
+        interface::broadcast_to_session(&tmux, "dev", "source .env", true)
+            .unwrap();
+    assert_eq!(panes, ["%1"]);
+    assert!(tmux.commands().windows(2).any(|sent| sent
+        == ["send-keys -t %1 -l -- source .env", "send-keys -t %1 C-m"]));
+
+    let panes =
+        interface::broadcast_to_session(&tmux, "dev", "ls", false).unwrap();
+    assert_eq!(panes, ["%1", "%2"]);
+}
+
+#[test]
+fn close_session_on_other_server_only_kills() {
+    let tmux = FakeTmux {
+        running: Cell::new(true),
+        server: Some("other".to_string()),
+        ..Default::default()
+    };
+    interface::close_session(&tmux, "dev").unwrap();
+    assert_eq!(tmux.commands(), ["kill-session -t dev"]);
+}
+
+fn step<const N: usize>(
+    window: usize,
+    pane: Option<usize>,
+    args: [&str; N],
+) -> PlanStep {
+    PlanStep {
+        window,
+        pane,
+        creates_window: matches!(args[0], "new-session" | "new-window"),
+        command: TmuxCommand::new(args),
+    }
+}
+
+#[test]
+fn run_plan_runs_every_step() {
+    let tmux = FakeTmux::running();
+    let plan = [
+        step(1, None, ["new-session", "-d", "-s", "dev"]),
+        step(
+            1,
+            Some(1),
+            ["send-keys", "-t", "dev", "echo 'hi'; ls", "C-m"],
+        ),
+    ];
+
+    let mut progress = Vec::new();
+    let failures =
+        interface::run_plan(&tmux, &plan, &mut |p| progress.push(p)).unwrap();
+    assert!(failures.is_empty());
+    assert_eq!(
+        tmux.commands(),
+        [
+            "new-session -d -s dev",
+            "send-keys -t dev echo 'hi'; ls C-m"
+        ]
+    );
+    assert_eq!(progress.len(), 2);
+    assert_eq!(
+        progress[1],
+        Progress {
+            step: 2,
+            steps: 2,
+            window: 1,
+            windows: 1,
+            pane: Some(1),
+        }
+    );
+    assert!(progress[1].is_done());
+}
+
+#[test]
+fn run_plan_continues_past_failing_step() {
+    let tmux = FakeTmux {
+        fail_on: Some("select-layout"),
+        ..FakeTmux::running()
+    };
+    let plan = [
+        step(1, None, ["new-session", "-d", "-s", "dev"]),
+        step(1, None, ["select-layout", "-t", "dev:1", "bogus"]),
+        step(1, None, ["rename-window", "-t", "dev:1", "editor"]),
+    ];
+
+    let mut reported = 0;
+    let failures =
+        interface::run_plan(&tmux, &plan, &mut |_| reported += 1).unwrap();
+    assert_eq!(
+        failures,
+        [
+            "Step 2/3 (window 1/1) failed: tmux select-layout -t 'dev:1' bogus \
+          (select-layout rejected)"
+        ]
+    );
+    assert_eq!(tmux.commands().len(), 3);
+    assert_eq!(reported, 3);
+}
+
+#[test]
+fn run_plan_targets_windows_by_the_ids_tmux_printed() {
+    let tmux = FakeTmux::running();
+    let plan = [
+        step(1, None, ["new-session", "-d", "-P", "-F", "#{window_id}"]),
+        step(1, None, ["rename-window", "-t", "{window 1}", "editor"]),
+        step(2, None, ["new-window", "-d", "-P", "-F", "#{window_id}"]),
+        step(
+            2,
+            Some(1),
+            ["send-keys", "-t", "{window 2}.0", "htop", "C-m"],
+        ),
+    ];
+
+    interface::run_plan(&tmux, &plan, &mut |_| {}).unwrap();
+    assert_eq!(tmux.commands()[1], "rename-window -t @3 editor");
+    assert_eq!(tmux.commands()[3], "send-keys -t @4.0 htop C-m");
+}
+
+#[test]
+fn run_plan_fails_without_session() {
+    let tmux = FakeTmux {
+        fail_on: Some("new-session"),
+        ..FakeTmux::running()
+    };
+    let plan = [
+        step(1, None, ["new-session", "-d", "-s", "dev"]),
+        step(1, None, ["rename-window", "-t", "dev:1", "editor"]),
+    ];
+
+    let err = interface::run_plan(&tmux, &plan, &mut |_| {}).unwrap_err();
+    assert!(matches!(
+        err,
+        TsmanError::TmuxFailed { cmd, .. } if cmd == "tmux new-session -d -s dev"
+    ));
+    assert_eq!(tmux.commands().len(), 1);
+}
+
+#[test]
+fn verify_session_reports_differences() {
+    let tmux = FakeTmux::running();
+    let session = session(
+        "/",
+        vec![
+            window("1", "editor", vec![pane("0", "/", None)]),
+            window(
+                "1",
+                "shell",
+                vec![
+                    pane("0", "/", None),
+                    pane("0", "/tsman-missing-dir", None),
+                ],
+            ),
+            window("1", "logs", vec![pane("0", "/", None)]),
+        ],
+    );
+
+    assert_eq!(
+        interface::verify_session(&tmux, &session, "dev").unwrap(),
+        [
+            "Expected 3 windows, found 2",
+            "Window 2 is named 'my shell' instead of 'shell'",
+            "Window 2 'shell' has 1 panes instead of 2",
+            "Working directory /tsman-missing-dir doesn't exist",
+        ]
+    );
+}
+
+#[test]
+fn failed_restore_kills_the_temp_session() {
+    let tmux = FakeTmux {
+        fail_on: Some("rename-session"),
+        ..FakeTmux::running()
+    };
+    let session = session("/", Vec::new());
+
+    assert!(
+        interface::restore_session_detached(&tmux, &session, &mut |_| {})
+            .is_err()
+    );
+    let temp_name = interface::temp_session_name();
+    assert_eq!(
+        tmux.commands().last().unwrap(),
+        &format!("kill-session -t ={temp_name}")
+    );
+}
+
+#[test]
+fn windows_are_not_renumbered_before_the_server_starts() {
+    let tmux = FakeTmux::default();
+    assert!(!interface::renumbers_windows(&tmux).unwrap());
+    assert_eq!(tmux.commands(), ["show-options -gv renumber-windows"]);
+}
+
+#[test]
+fn restore_starts_the_server_when_none_is_running() {
+    let tmux = FakeTmux::default();
+    let report = interface::restore_session_detached(
+        &tmux,
+        &session("/", Vec::new()),
+        &mut |_| {},
+    )
+    .unwrap();
+    assert!(report.is_clean());
+    let commands = tmux.commands();
+    assert_eq!(
+        commands[..2],
+        [
+            "show-options -gv renumber-windows",
+            "show-options -gv destroy-unattached",
+        ]
+    );
+    assert!(commands[2].starts_with("new-session"));
+}
+
+#[test]
+fn temp_session_pid_parses_temp_names_only() {
+    assert_eq!(interface::temp_session_pid("tsman-temp-4242"), Some(4242));
+    assert_eq!(interface::temp_session_pid("tsman-temp-x"), None);
+    assert_eq!(interface::temp_session_pid("dev"), None);
+}
+
+#[test]
+fn failed_step_rolls_the_restore_back() {
+    let tmux = FakeTmux {
+        fail_on: Some("select-layout"),
+        ..FakeTmux::running()
+    };
+    let session = session(
+        "/",
+        vec![Window {
+            layout: "bogus".to_string(),
+            ..window("1", "editor", vec![pane("0", "/", None)])
+        }],
+    );
+
+    let err = interface::restore_session_detached(&tmux, &session, &mut |_| {})
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        TsmanError::RestoreFailed { session, failed_steps }
+            if session == "dev" && failed_steps.len() == 1
+    ));
+    assert!(
+        !tmux
+            .commands()
+            .iter()
+            .any(|c| c.starts_with("rename-session"))
+    );
+    assert!(tmux.commands().last().unwrap().starts_with("kill-session"));
+}
+
+#[test]
+fn capture_pane_reads_scrollback_of_the_pane() {
+    let tmux = FakeTmux::running();
+    interface::capture_pane(&tmux, "dev:1.0", 500).unwrap();
+    assert_eq!(tmux.commands(), ["capture-pane -p -J -t dev:1.0 -S -500"]);
+}
+
+#[test]
+fn append_windows_adds_them_after_the_last_window() {
+    let tmux = FakeTmux::running();
+    let logs = window("1", "logs", vec![pane("0", "/", None)]);
+    let session = Session {
+        name: "tools".to_string(),
+        ..session("/", vec![logs.clone()])
+    };
+
+    let (first, failed) = interface::append_windows(
+        &tmux,
+        &session,
+        "dev",
+        &[&logs],
+        &mut |_| {},
+    )
+    .unwrap();
+    assert_eq!(first, 3);
+    assert!(failed.is_empty());
+    let commands = tmux.commands();
+    assert_eq!(
+        commands[2..4],
+        ["new-window -d -t dev:3 -c /", "rename-window -t dev:3 logs"]
+    );
+    // The window and pane the session showed before are selected again.
+    assert_eq!(
+        commands[commands.len() - 2..],
+        ["select-window -t @1", "select-pane -t %1"]
+    );
+}
+
+#[test]
+fn dead_panes_respawn_from_the_config() {
+    let tmux = FakeTmux::running();
+    let dead = interface::list_dead_panes(&tmux, "dev").unwrap();
+    assert_eq!(
+        dead.iter().map(|pane| pane.id.as_str()).collect::<Vec<_>>(),
+        ["%2", "%3"]
+    );
+    assert_eq!(dead[0].window_name, "my shell");
+
+    let session = session(
+        "/home/user/dev",
+        vec![window(
+            "2",
+            "my shell",
+            vec![Pane {
+                shell: Some("fish".to_string()),
+                ..pane("0", "/tmp", Some("htop"))
+            }],
+        )],
+    );
+    for pane in &dead {
+        interface::respawn_pane(&tmux, &session, pane).unwrap();
+    }
+
+    assert_eq!(
+        tmux.commands()[1..],
+        [
+            "respawn-pane -t %2 -c /home/user/dev exec fish -l",
+            "send-keys -t %2 -l -- cd /tmp; clear",
+            "send-keys -t %2 C-m",
+            "send-keys -t %2 -l -- htop",
+            "send-keys -t %2 C-m",
+            // Not in the config, so it gets its original command back.
+            "respawn-pane -t %3",
+        ]
+    );
+}
+
+#[test]
+fn pre_commands_run_through_the_client_before_the_session() {
+    let tmux = FakeTmux::default();
+    let mut session = session(
+        "/srv/dev",
+        vec![window("1", "shell", vec![pane("0", "/srv/dev", None)])],
+    );
+    session.pre = vec!["docker compose up -d".to_string(), "make".to_string()];
+
+    interface::restore_session_detached(&tmux, &session, &mut |_| {}).unwrap();
+
+    let commands = tmux.commands();
+    let position = |prefix: &str| {
+        commands
+            .iter()
+            .position(|cmd| cmd.starts_with(prefix))
+            .unwrap()
+    };
+    let pre = position("sh ");
+    assert_eq!(
+        commands[pre..pre + 2],
+        ["sh /srv/dev: docker compose up -d", "sh /srv/dev: make"]
+    );
+    assert!(pre < position("new-session"));
+}
+
+#[test]
+fn failed_pre_commands_roll_back_before_touching_tmux() {
+    let tmux = FakeTmux {
+        fail_on: Some("sh"),
+        ..Default::default()
+    };
+    let mut session = session(
+        "/srv/dev",
+        vec![window("1", "shell", vec![pane("0", "/srv/dev", None)])],
+    );
+    session.pre = vec!["docker compose up -d".to_string()];
+
+    let err = interface::restore_session_detached(&tmux, &session, &mut |_| {})
+        .unwrap_err();
+
+    assert!(
+        err.to_string().contains(
+            "Pre command failed: docker compose up -d (exit status: 1)"
+        ),
+        "{err}"
+    );
+    assert!(
+        !tmux
+            .commands()
+            .iter()
+            .any(|cmd| cmd.starts_with("new-session")),
+        "{:?}",
+        tmux.commands()
+    );
+}